@@ -13,9 +13,10 @@ use secp256k1::SecretKey;
 
 
 
-pub async fn deploy(chainUrl:&str,compiledDirectoryPath:&str) -> String {
-    let eth_key = "b75dc70f894ef8bbd8cbb6d9f70c146b87f53cdb959f0ab6ac272a8b33e767f2";
-    let key_bytes = hex::decode(eth_key).unwrap();
+/// Deploys `<compiledDirectoryPath>/Verifier.bin` with the transaction signed by `account`,
+/// a private key in hex, and returns the address of the contract.
+pub async fn deploy(chainUrl:&str,compiledDirectoryPath:&str,account:&str) -> String {
+    let key_bytes = hex::decode(account.trim_start_matches("0x")).expect("Invalid account key");
     let key = SecretKey::from_slice(key_bytes.as_slice()).unwrap();
     let http = web3::transports::Http::new(chainUrl).unwrap();
     let web3 = web3::Web3::new(http);
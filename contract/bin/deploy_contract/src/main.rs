@@ -1,12 +1,17 @@
 
+use std::env;
 use deploy_contract::deploy;
 
 
 #[tokio::main]
 async fn main() {
 
-    let address = deploy("https://goerli.infura.io/v3/e4903a8305824888b3d9ea0e7760b31e","/Users/yuqi/project/mips_circuit/contract/verifier/g16/verifier").await;
-    // let address = deploy("http://127.0.0.1:8545","/Users/yuqi/project/mips_circuit/contract/verifier/g16/verifier").await;
+    // Same variables as the verifier generator, see setenv.bash.
+    let chain_url = env::var("VERIFIER_CHAIN_URL").expect("VERIFIER_CHAIN_URL is not set");
+    let abi_path = env::var("VERIFIER_ABI_PATH").expect("VERIFIER_ABI_PATH is not set");
+    let account = env::var("VERIFIER_ACCOUNT").expect("VERIFIER_ACCOUNT is not set");
+
+    let address = deploy(&chain_url,&abi_path,&account).await;
 
     println!("{:?}",address);
 }
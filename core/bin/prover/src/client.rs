@@ -0,0 +1,215 @@
+//! Sources of prover jobs: the database directly or the prover API of the server.
+
+// Built-in deps
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+// External deps
+use jsonwebtoken::{encode, EncodingKey, Header};
+use serde::{de::DeserializeOwned, Serialize};
+// Workspace deps
+use config::configs::prover::Retry;
+use witness_generator::database_interface::DatabaseInterface;
+use witness_generator::prover_api::{
+    self, PayloadAuthToken, ProverFailure, ProverInputRequest, ProverJob, PublishProof, WorkingOn,
+    JOB_HEADER,
+};
+
+/// Lifetime of the tokens signed by `ApiClient`, in seconds.
+const AUTH_TOKEN_LIFETIME: u64 = 3600;
+
+/// Operations the prover needs to lease jobs and publish their proofs.
+#[async_trait::async_trait]
+pub trait JobClient: Clone + Send + Sync + 'static {
+    /// Leases to `prover_name` the next idle job of the circuit compiled to the program with the hash `program_hash`.
+    async fn get_job(
+        &self,
+        prover_name: &str,
+        program_hash: &str,
+    ) -> anyhow::Result<Option<ProverJob>>;
+
+    /// Extends the lease on the job, returns `false` if the job belongs to another prover.
+    async fn working_on(&self, job_id: i64, prover_name: &str) -> anyhow::Result<bool>;
+
+    /// Stores the proof of the job, returns `false` if the job belongs to another prover.
+    async fn publish(&self, proof: PublishProof) -> anyhow::Result<bool>;
+
+    /// Returns the job to the queue after the prover failed it, or dead-letters it
+    /// if it failed too many times. Returns `false` if the job belongs to another prover.
+    async fn report_failure(&self, failure: ProverFailure) -> anyhow::Result<bool>;
+}
+
+/// Client for the provers with access to the database.
+#[derive(Debug, Clone)]
+pub struct DatabaseClient<DB: DatabaseInterface> {
+    database: DB,
+    /// Retry policy for the failed jobs, the server applies its own to `ApiClient` failures.
+    retry: Retry,
+}
+
+impl<DB: DatabaseInterface> DatabaseClient<DB> {
+    pub fn new(database: DB, retry: Retry) -> Self {
+        Self { database, retry }
+    }
+}
+
+#[async_trait::async_trait]
+impl<DB: DatabaseInterface> JobClient for DatabaseClient<DB> {
+    async fn get_job(
+        &self,
+        prover_name: &str,
+        program_hash: &str,
+    ) -> anyhow::Result<Option<ProverJob>> {
+        let request = ProverInputRequest {
+            prover_name: prover_name.to_string(),
+            program_hash: program_hash.to_string(),
+        };
+        prover_api::get_job(&self.database, &self.retry, &request).await
+    }
+
+    async fn working_on(&self, job_id: i64, prover_name: &str) -> anyhow::Result<bool> {
+        let request = WorkingOn {
+            job_id,
+            prover_name: prover_name.to_string(),
+        };
+        prover_api::working_on(&self.database, &request).await
+    }
+
+    async fn publish(&self, proof: PublishProof) -> anyhow::Result<bool> {
+        prover_api::publish(&self.database, proof).await
+    }
+
+    async fn report_failure(&self, failure: ProverFailure) -> anyhow::Result<bool> {
+        prover_api::report_failure(&self.database, &self.retry, &failure).await
+    }
+}
+
+/// Client of the prover API served by the witness generator, for the provers
+/// without access to the database.
+#[derive(Debug, Clone)]
+pub struct ApiClient {
+    http_client: reqwest::Client,
+    api_url: String,
+    secret_auth: String,
+}
+
+impl ApiClient {
+    pub fn new(api_url: &str, secret_auth: String, request_timeout: Duration) -> Self {
+        let http_client = reqwest::Client::builder()
+            .timeout(request_timeout)
+            .build()
+            .expect("Failed to create the HTTP client");
+
+        Self {
+            http_client,
+            api_url: api_url.trim_end_matches('/').to_string(),
+            secret_auth,
+        }
+    }
+
+    fn auth_token(&self) -> anyhow::Result<String> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        let claims = PayloadAuthToken {
+            sub: "prover".to_string(),
+            exp: (now + AUTH_TOKEN_LIFETIME) as usize,
+        };
+        let token = encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret(self.secret_auth.as_ref()),
+        )?;
+
+        Ok(token)
+    }
+
+    async fn post<T: Serialize + ?Sized, R: DeserializeOwned>(
+        &self,
+        method: &str,
+        body: &T,
+    ) -> anyhow::Result<R> {
+        let response = self.post_request(method, body).await?;
+        Ok(response.json().await?)
+    }
+
+    async fn post_request<T: Serialize + ?Sized>(
+        &self,
+        method: &str,
+        body: &T,
+    ) -> anyhow::Result<reqwest::Response> {
+        let url = format!("{}/api/internal/prover/{}", self.api_url, method);
+        let response = self
+            .http_client
+            .post(&url)
+            .bearer_auth(self.auth_token()?)
+            .json(body)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(response)
+    }
+}
+
+#[async_trait::async_trait]
+impl JobClient for ApiClient {
+    async fn get_job(
+        &self,
+        prover_name: &str,
+        program_hash: &str,
+    ) -> anyhow::Result<Option<ProverJob>> {
+        let request = ProverInputRequest {
+            prover_name: prover_name.to_string(),
+            program_hash: program_hash.to_string(),
+        };
+        let response = self.post_request("get_job", &request).await?;
+        if response.status() == reqwest::StatusCode::NO_CONTENT {
+            return Ok(None);
+        }
+
+        // The job comes in a header, the body being its gzipped witness.
+        let header = response
+            .headers()
+            .get(JOB_HEADER)
+            .ok_or_else(|| anyhow::format_err!("The response has no {} header", JOB_HEADER))?;
+        let mut job: ProverJob = serde_json::from_slice(header.as_bytes())?;
+        job.witness = response.bytes().await?.to_vec();
+
+        Ok(Some(job))
+    }
+
+    async fn working_on(&self, job_id: i64, prover_name: &str) -> anyhow::Result<bool> {
+        let request = WorkingOn {
+            job_id,
+            prover_name: prover_name.to_string(),
+        };
+        self.post("working_on", &request).await
+    }
+
+    async fn publish(&self, proof: PublishProof) -> anyhow::Result<bool> {
+        match self.post_request("publish", &proof).await {
+            Ok(_) => Ok(true),
+            // The server refuses the proofs of the jobs leased to another prover.
+            Err(e)
+                if e.downcast_ref::<reqwest::Error>()
+                    .and_then(reqwest::Error::status)
+                    == Some(reqwest::StatusCode::CONFLICT) =>
+            {
+                Ok(false)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn report_failure(&self, failure: ProverFailure) -> anyhow::Result<bool> {
+        match self.post_request("failure", &failure).await {
+            Ok(_) => Ok(true),
+            // The server refuses the failures of the jobs leased to another prover.
+            Err(e)
+                if e.downcast_ref::<reqwest::Error>()
+                    .and_then(reqwest::Error::status)
+                    == Some(reqwest::StatusCode::CONFLICT) =>
+            {
+                Ok(false)
+            }
+            Err(e) => Err(e),
+        }
+    }
+}
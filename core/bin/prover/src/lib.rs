@@ -1,16 +1,24 @@
-use std::collections::HashMap;
 // Built-in deps
+use std::path::Path;
 use std::sync::{
     atomic::{AtomicBool, AtomicI32, Ordering},
     Arc,
 };
+use std::time::Duration;
 use tokio::sync::oneshot;
 // Workspace deps
+use circuit::artifacts::program_hash;
+use circuit::proof::ProofOptions;
+use circuit::witness::Witness;
+use config::configs::prover::{Circuit, Retry};
 use config::ProverConfig as EnvProverConfig;
-use storage::ConnectionPool;
-use types::BlockNumber;
-use witness_generator::database_interface::DatabaseInterface;
-use zokrates_common::constants;
+use witness_generator::prover_api::{ProverFailure, ProverJob, PublishProof};
+use zokrates_common::helpers::{BackendParameter, SchemeParameter};
+// Local deps
+use self::client::JobClient;
+
+pub mod client;
+pub mod nova;
 
 const ABSENT_PROVER_ID: i32 = -1;
 
@@ -53,15 +61,107 @@ impl ShutdownRequest {
     }
 }
 
-pub async fn prover_work_cycle(
-    connection_pool: ConnectionPool,
+/// Name under which the prover leases jobs, unique among the running provers.
+fn prover_name() -> String {
+    let host = std::env::var("HOSTNAME").unwrap_or_else(|_| "localhost".to_string());
+    format!("{}-{}", host, std::process::id())
+}
+
+/// Extends the lease on the job every `heartbeat_interval` until `stop` fires.
+/// Fires `lost` and returns once the job has been given to another prover.
+async fn heartbeat<C: JobClient>(
+    client: C,
+    job_id: i64,
+    prover_name: String,
+    heartbeat_interval: Duration,
+    mut stop: oneshot::Receiver<()>,
+    lost: oneshot::Sender<()>,
+) {
+    let mut timer = tokio::time::interval(heartbeat_interval);
+    loop {
+        tokio::select! {
+            _ = timer.tick() => {},
+            _ = &mut stop => return,
+        }
+
+        match client.working_on(job_id, &prover_name).await {
+            Ok(true) => {}
+            Ok(false) => {
+                vlog::warn!("Job {} has been given to another prover", job_id);
+                let _ = lost.send(());
+                return;
+            }
+            Err(e) => vlog::warn!("Failed to send heartbeat for job {}: {}", job_id, e),
+        }
+    }
+}
+
+/// Proves the witness of `job` with the circuit of `config`, stopping before the next step of
+/// the proof once `abort` is set.
+async fn prove_job(
+    job: &ProverJob,
+    config: &Circuit,
+    abort: Arc<AtomicBool>,
+) -> Result<String, String> {
+    let witness = job
+        .decompress_witness()
+        .map(Witness::from_bytes)
+        .map_err(|e| e.to_string())?;
+    let options = ProofOptions::new(&config.file_path, witness, &config.proving_key_path)
+        .backend(BackendParameter::Ark)
+        .scheme(SchemeParameter::G16)
+        .abort(abort);
+    tokio::task::spawn_blocking(move || circuit::proof::generate_proof(&options))
+        .await
+        .unwrap_or_else(|e| Err(format!("Proof generation panicked: {}", e)))
+}
+
+/// Publishes the proof of the job, retrying with a backoff up to `retry.max_attempts` times.
+/// Returns `false` if the job was leased to another prover in the meantime.
+async fn publish_proof<C: JobClient>(
+    client: &C,
+    retry: &Retry,
+    job_id: i64,
+    prover_name: &str,
+    proof: String,
+) -> Result<bool, String> {
+    let mut attempts = 0;
+    loop {
+        let request = PublishProof {
+            job_id,
+            prover_name: prover_name.to_string(),
+            proof: proof.clone(),
+        };
+        match client.publish(request).await {
+            Ok(stored) => return Ok(stored),
+            Err(e) => {
+                attempts += 1;
+                vlog::warn!(
+                    "Failed to publish the proof of job {} (attempt {}): {}",
+                    job_id,
+                    attempts,
+                    e
+                );
+                if attempts >= retry.max_attempts {
+                    return Err(format!("Failed to publish the proof: {}", e));
+                }
+                tokio::time::sleep(retry.backoff(attempts)).await;
+            }
+        }
+    }
+}
+
+pub async fn prover_work_cycle<C: JobClient>(
+    client: C,
     shutdown: ShutdownRequest,
     prover_options: EnvProverConfig,
 ) {
     vlog::info!("Running worker cycle");
-    let database = witness_generator::database::Database::new(connection_pool);
+    let prover_name = prover_name();
+    // Only the jobs of the circuit the prover proves with are leased to it.
+    let program_hash = program_hash(Path::new(&prover_options.circuit.file_path))
+        .expect("Failed to read the circuit");
     let mut new_job_poll_timer = tokio::time::interval(prover_options.prover.cycle_wait());
-    let mut current_block = BlockNumber(1);
     loop {
         new_job_poll_timer.tick().await;
 
@@ -69,43 +169,82 @@ pub async fn prover_work_cycle(
             break;
         }
 
-        let mut block_args = HashMap::new();
-        let circuit_path = std::env::var("CIRCUIT_FILE_PATH").unwrap();
-        let proving_key_path = std::env::var("CIRCUIT_PROVING_KEY_PATH").unwrap();
-        block_args.insert("input".to_string(), circuit_path);
-        let mut storage = database.acquire_connection().await.unwrap();
-        current_block = BlockNumber(database.load_last_proof_block_number(&mut storage).await.unwrap() as u32);
-        let next_block = BlockNumber(*current_block + 1);
-        let witness_str = database
-            .load_witness(&mut storage, current_block)
-            .await
-            .unwrap();
-        if witness_str.is_none() {
-            continue;
+        let job = match client.get_job(&prover_name, &program_hash).await {
+            Ok(Some(job)) => job,
+            Ok(None) => continue,
+            Err(e) => {
+                // The job goes back to the queue once its lease expires.
+                vlog::warn!("Failed to get a prover job: {}", e);
+                continue;
+            }
+        };
+        vlog::info!(
+            "Proving blocks {}..={} (job {})",
+            *job.first_block,
+            *job.last_block,
+            job.job_id
+        );
+
+        let (stop_heartbeat, stop) = oneshot::channel();
+        let (lease_lost, lost) = oneshot::channel();
+        tokio::spawn(heartbeat(
+            client.clone(),
+            job.job_id,
+            prover_name.clone(),
+            prover_options.prover.heartbeat_interval(),
+            stop,
+            lease_lost,
+        ));
+
+        let abort = Arc::new(AtomicBool::new(false));
+        let proof = tokio::select! {
+            proof = prove_job(&job, &prover_options.circuit, abort.clone()) => Some(proof),
+            Ok(()) = lost => None,
+        };
+        let _ = stop_heartbeat.send(());
+
+        let result = match proof {
+            Some(Ok(proof)) => {
+                let retry = &prover_options.retry;
+                publish_proof(&client, retry, job.job_id, &prover_name, proof).await
+            }
+            Some(Err(error)) => Err(error),
+            // The proof running in the background stops before its next step, and the job is
+            // neither published nor failed: it belongs to the other prover.
+            None => {
+                abort.store(true, Ordering::SeqCst);
+                Ok(false)
+            }
+        };
+        match result {
+            Ok(true) => {}
+            // The other prover publishes the proof, the job isn't failed.
+            Ok(false) => vlog::warn!(
+                "Job {} was leased to another prover, dropping its proof",
+                job.job_id
+            ),
+            Err(error) => {
+                vlog::error!("Failed to prove job {}: {}", job.job_id, error);
+                let failure = ProverFailure {
+                    job_id: job.job_id,
+                    prover_name: prover_name.clone(),
+                    error,
+                };
+                match client.report_failure(failure).await {
+                    Ok(true) => {}
+                    Ok(false) => vlog::warn!(
+                        "Job {} was leased to another prover, dropping its failure",
+                        job.job_id
+                    ),
+                    Err(e) => {
+                        vlog::warn!("Failed to report the failure of job {}: {}", job.job_id, e)
+                    }
+                }
+            }
         }
 
-        block_args.insert("backend".to_string(), constants::ARK.to_string());
-        block_args.insert("proving-scheme".to_string(), constants::G16.to_string());
-        block_args.insert("witness".to_string(), witness_str.unwrap());
-        block_args.insert("proving-key-path".to_string(), proving_key_path);
-        let proof_str = circuit::proof::generate_proof(&block_args).unwrap();
-        let mut transaction = storage.start_transaction().await.unwrap();
-        database
-            .store_proof(&mut transaction,
-                         1,
-                         current_block,
-                         proof_str)
-            .await
-            .unwrap();
-        database
-            .update_last_proof_block_number(
-                &mut transaction,
-                next_block
-            )
-            .await.unwrap();
-        transaction.commit().await.unwrap();
-
-        // Update current block.
-        current_block = next_block;
+        if prover_options.prover.die_after_proof {
+            break;
+        }
     }
-}
\ No newline at end of file
+}
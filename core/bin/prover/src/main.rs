@@ -1,7 +1,15 @@
 // Workspace deps
+use config::configs::api::ProverApiConfig;
+use config::configs::blob_store::BlobStoreConfig;
+use config::configs::prover::{NovaCircuit, ProverMode};
 use config::configs::ProverConfig as EnvProverConfig;
+use witness_generator::database::Database;
+use witness_generator::witness_store::WitnessStore;
 // Local deps
+use prover::client::{ApiClient, DatabaseClient};
+use prover::nova::nova_work_cycle;
 use prover::{prover_work_cycle, ShutdownRequest};
+use prometheus_exporter::run_prometheus_exporter;
 use storage::ConnectionPool;
 
 #[tokio::main]
@@ -10,6 +18,10 @@ async fn main() {
     // used env
     let prover_options = EnvProverConfig::from_env();
 
+    if let Some(port) = prover_options.prover.prometheus_port {
+        run_prometheus_exporter(port);
+    }
+
     let shutdown_request = ShutdownRequest::new();
 
     // Handle termination requests.
@@ -30,6 +42,32 @@ async fn main() {
             .expect("Failed to register ctrlc handler");
     }
 
-    let connection_pool = ConnectionPool::new(None);
-    prover_work_cycle(connection_pool.clone(),shutdown_request, prover_options).await;
+    if prover_options.prover.mode == ProverMode::Nova {
+        let connection_pool = ConnectionPool::new(None);
+        let circuit = NovaCircuit::from_env();
+        nova_work_cycle(
+            Database::new(connection_pool),
+            shutdown_request,
+            prover_options,
+            circuit,
+        )
+        .await;
+    } else if prover_options.prover.use_api {
+        let api_options = ProverApiConfig::from_env();
+        let client = ApiClient::new(
+            &api_options.url,
+            api_options.secret_auth,
+            prover_options.prover.request_timeout(),
+        );
+        prover_work_cycle(client, shutdown_request, prover_options).await;
+    } else {
+        let connection_pool = ConnectionPool::new(None);
+        let witness_store = WitnessStore::from_config(&BlobStoreConfig::from_env())
+            .expect("Failed to open the blob store of the witnesses");
+        let client = DatabaseClient::new(
+            Database::new(connection_pool).with_witness_store(witness_store),
+            prover_options.retry.clone(),
+        );
+        prover_work_cycle(client, shutdown_request, prover_options).await;
+    }
 }
@@ -0,0 +1,173 @@
+//! Nova mode of the prover: instead of leasing Groth16 jobs, the prover folds every trace record
+//! of a program run that has exited into one compressed proof, see `circuit::nova`.
+//! It reads the traces from the database, so it can't get its work through the prover API.
+
+// Built-in deps
+use std::time::Duration;
+use tokio::sync::oneshot;
+// Workspace deps
+use circuit::nova::NovaOptions;
+use config::configs::prover::NovaCircuit;
+use config::ProverConfig as EnvProverConfig;
+use types::BlockNumber;
+use witness_generator::database_interface::DatabaseInterface;
+// Local deps
+use crate::ShutdownRequest;
+
+/// Leases the first run that has exited and has no Nova proof yet, and proves it.
+/// A failed run is returned to the queue after a backoff, and skipped once it failed
+/// `prover_options.retry.max_attempts` times. Returns `false` if there is no run to prove.
+pub async fn prove_next_run<DB: DatabaseInterface>(
+    database: &DB,
+    options: &NovaOptions,
+    prover_name: &str,
+    prover_options: &EnvProverConfig,
+) -> anyhow::Result<bool> {
+    let run = match database
+        .lease_next_unproven_run(prover_name, prover_options.core.gone_timeout())
+        .await?
+    {
+        Some(run) => run,
+        None => return Ok(false),
+    };
+
+    let (stop_heartbeat, stop) = oneshot::channel();
+    tokio::spawn(heartbeat(
+        database.clone(),
+        run.f_program_id.clone(),
+        prover_name.to_string(),
+        prover_options.prover.heartbeat_interval(),
+        stop,
+    ));
+    let result = prove_run(database, options, &run.f_program_id).await;
+    let _ = stop_heartbeat.send(());
+
+    if let Err(e) = result {
+        let retry = &prover_options.retry;
+        let failed = database
+            .fail_nova_run(
+                &run.f_program_id,
+                prover_name,
+                &e.to_string(),
+                retry.max_attempts,
+                retry.backoff(run.f_attempts as u32 + 1),
+            )
+            .await?;
+        if let Some(failed) = failed.filter(|failed| failed.f_attempts >= retry.max_attempts as i32)
+        {
+            vlog::error!(
+                "The run of {} failed {} times, skipping it",
+                run.f_program_id,
+                failed.f_attempts
+            );
+        }
+        return Err(e);
+    }
+
+    Ok(true)
+}
+
+/// Extends the lease on the run every `heartbeat_interval` until `stop` fires.
+async fn heartbeat<DB: DatabaseInterface>(
+    database: DB,
+    program_id: String,
+    prover_name: String,
+    heartbeat_interval: Duration,
+    mut stop: oneshot::Receiver<()>,
+) {
+    let mut timer = tokio::time::interval(heartbeat_interval);
+    loop {
+        tokio::select! {
+            _ = timer.tick() => {},
+            _ = &mut stop => return,
+        }
+
+        match database
+            .record_nova_run_is_working(&program_id, &prover_name)
+            .await
+        {
+            Ok(true) => {}
+            Ok(false) => vlog::warn!("The run of {} has been given to another prover", program_id),
+            Err(e) => vlog::warn!(
+                "Failed to send heartbeat for the run of {}: {}",
+                program_id,
+                e
+            ),
+        }
+    }
+}
+
+/// Folds the traces of the run of `program_id` and stores the proof.
+async fn prove_run<DB: DatabaseInterface>(
+    database: &DB,
+    options: &NovaOptions,
+    program_id: &str,
+) -> anyhow::Result<()> {
+    let traces = database.load_program_traces(program_id).await?;
+    let (first_block, last_block) = match (traces.first(), traces.last()) {
+        (Some(first), Some(last)) => (
+            BlockNumber(first.f_block as u32),
+            BlockNumber(last.f_block as u32),
+        ),
+        _ => anyhow::bail!("No traces of {}", program_id),
+    };
+    vlog::info!(
+        "Proving the run of {} with Nova, blocks {}..={}",
+        program_id,
+        *first_block,
+        *last_block
+    );
+
+    let records: Vec<_> = traces.into_iter().map(|trace| trace.f_trace).collect();
+    let options = options.clone();
+    let proof = tokio::task::spawn_blocking(move || circuit::nova::prove_run(&options, &records))
+        .await
+        .unwrap_or_else(|e| Err(format!("Nova proving panicked: {}", e)))
+        .map_err(|e| anyhow::format_err!("Failed to prove the run of {}: {}", program_id, e))?;
+
+    let steps = proof.steps as i64;
+    let stored = database
+        .store_nova_proof(
+            program_id,
+            first_block,
+            last_block,
+            steps,
+            serde_json::to_value(&proof)?,
+        )
+        .await?;
+    if !stored {
+        vlog::warn!(
+            "The run of {} has been proven by another prover",
+            program_id
+        );
+    }
+
+    Ok(())
+}
+
+/// Proves the program runs one after the other until `shutdown` is requested.
+pub async fn nova_work_cycle<DB: DatabaseInterface>(
+    database: DB,
+    shutdown: ShutdownRequest,
+    prover_options: EnvProverConfig,
+    circuit: NovaCircuit,
+) {
+    vlog::info!("Running Nova worker cycle");
+    let options = NovaOptions::new(&circuit.file_path, &circuit.abi_file_path)
+        .params(circuit.params_path.as_ref());
+    let prover_name = crate::prover_name();
+    let mut new_run_poll_timer = tokio::time::interval(prover_options.prover.cycle_wait());
+    loop {
+        new_run_poll_timer.tick().await;
+
+        if shutdown.get() {
+            break;
+        }
+
+        match prove_next_run(&database, &options, &prover_name, &prover_options).await {
+            Ok(true) if prover_options.prover.die_after_proof => break,
+            Ok(_) => {}
+            Err(e) => vlog::error!("{}", e),
+        }
+    }
+}
@@ -0,0 +1,241 @@
+//! Fixture shared by the integration tests: the circuit of `fixtures/pipeline.zok` set up in a
+//! temporary directory, and an in-memory database holding traces and the registered circuit.
+
+// Each test file uses a part of the fixture only.
+#![allow(dead_code)]
+
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::Path;
+use std::time::Duration;
+
+use circuit::batch::BatchInput;
+use config::configs::prover::{
+    Circuit, Core, Prover, ProverMode, Retry, WitnessGenerator as WitnessGeneratorConfig,
+};
+use config::ProverConfig;
+use mips_vm::{Executor, Memory, NoPreimages, State};
+use prover::client::DatabaseClient;
+use prover::{prover_work_cycle, ShutdownRequest};
+use rand_0_8::rngs::StdRng;
+use rand_0_8::SeedableRng;
+use storage::prover::records::StorageCircuit;
+use types::BlockNumber;
+use witness_generator::memory_database::InMemoryDatabase;
+use witness_generator::registry;
+use witness_generator::settlement::Settlement;
+use witness_generator::verifier_generator::VerifierGenerator;
+use witness_generator::witness_generator::WitnessGenerator;
+use zokrates_ark::Ark;
+use zokrates_common::helpers::BackendParameter;
+use zokrates_common::CompileConfig;
+use zokrates_core::compile::compile;
+use zokrates_field::Bn128Field;
+use zokrates_fs_resolver::FileSystemResolver;
+use zokrates_proof_systems::{NonUniversalBackend, G16};
+
+/// Builds a [`Pipeline`], by default with the setup of the seed 42 and a single trace.
+pub struct PipelineBuilder {
+    seed: u64,
+    traces: usize,
+}
+
+impl PipelineBuilder {
+    pub fn new() -> Self {
+        Self {
+            seed: 42,
+            traces: 1,
+        }
+    }
+
+    /// Randomness of the setup of the circuit.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Amount of copies of [`fixture_trace`] stored in the database.
+    pub fn traces(mut self, traces: usize) -> Self {
+        self.traces = traces;
+        self
+    }
+
+    pub async fn build(self) -> Pipeline {
+        let dir = tempdir::TempDir::new("pipeline").unwrap();
+        let (circuit, vk) = setup_circuit(dir.path(), self.seed);
+
+        let database = InMemoryDatabase::new();
+        for _ in 0..self.traces {
+            database.add_trace(fixture_trace());
+        }
+        let registered = registry::register_circuit(&database, &circuit, None)
+            .await
+            .unwrap();
+
+        Pipeline {
+            dir,
+            circuit,
+            vk,
+            database,
+            registered,
+        }
+    }
+}
+
+/// The circuit of `fixtures/pipeline.zok` and the database the pipeline runs on.
+pub struct Pipeline {
+    /// Holds the artifacts of the circuit.
+    pub dir: tempdir::TempDir,
+    pub circuit: Circuit,
+    pub vk: serde_json::Value,
+    pub database: InMemoryDatabase,
+    /// The circuit as registered in `database`.
+    pub registered: StorageCircuit,
+}
+
+impl Pipeline {
+    /// A witness generator starting from the first trace, with a witness per trace.
+    pub fn witness_generator(&self) -> WitnessGenerator<InMemoryDatabase> {
+        let input = BatchInput::from_abi(Path::new(&self.circuit.abi_file_path)).unwrap();
+        WitnessGenerator::new(
+            self.database.clone(),
+            Duration::from_millis(10),
+            BlockNumber(1),
+            BlockNumber(1),
+            self.circuit.clone(),
+            input,
+            self.registered.f_id,
+            retry(),
+        )
+    }
+
+    /// Runs a prover on the database until its first proof.
+    pub async fn prove(&self) {
+        prover_work_cycle(
+            DatabaseClient::new(self.database.clone(), retry()),
+            ShutdownRequest::new(),
+            self.prover_config(),
+        )
+        .await;
+    }
+
+    /// A verifier generator starting after the block 0, submitting the proofs of
+    /// `aggregation_size` batches together.
+    pub fn verifier_generator<S: Settlement>(
+        &self,
+        settlement: S,
+        aggregation_size: u32,
+    ) -> VerifierGenerator<InMemoryDatabase, S> {
+        VerifierGenerator::new(
+            self.database.clone(),
+            Duration::from_millis(10),
+            BlockNumber(0),
+            BlockNumber(1),
+            aggregation_size,
+            settlement,
+            self.registered.clone(),
+            BackendParameter::Ark,
+            retry(),
+        )
+    }
+
+    /// The config of a Groth16 prover stopping after its first proof.
+    pub fn prover_config(&self) -> ProverConfig {
+        ProverConfig {
+            prover: Prover {
+                heartbeat_interval: 1000,
+                cycle_wait: 10,
+                request_timeout: 10,
+                die_after_proof: true,
+                use_api: false,
+                prometheus_port: None,
+                mode: ProverMode::Groth16,
+            },
+            core: Core {
+                gone_timeout: 60000,
+                idle_provers: 1,
+            },
+            witness_generator: WitnessGeneratorConfig {
+                prepare_data_interval: 10,
+                witness_generators: 1,
+            },
+            circuit: self.circuit.clone(),
+            retry: retry(),
+        }
+    }
+}
+
+pub fn retry() -> Retry {
+    Retry {
+        max_attempts: 3,
+        backoff: 10,
+        max_backoff: 100,
+    }
+}
+
+/// The trace record of `addiu $t0, $zero, 5`.
+pub fn fixture_trace() -> serde_json::Value {
+    let mut memory = Memory::new();
+    memory.set_word(0x0040_0000, 0x2408_0005).unwrap();
+    let state = State {
+        cycle: 0,
+        pc: 0x0040_0000,
+        next_pc: 0x0040_0004,
+        lo: 0,
+        hi: 0,
+        regs: [0; 32],
+        heap: mips_vm::state::HEAP_START,
+        exit_code: 0,
+        exited: false,
+        memory,
+    };
+
+    let record = Executor::new(state, NoPreimages).step().unwrap().unwrap();
+    serde_json::to_value(record).unwrap()
+}
+
+/// Compiles `fixtures/pipeline.zok` into `dir` and runs the setup with the randomness `seed`,
+/// returns the circuit config along with the verification key.
+fn setup_circuit(dir: &Path, seed: u64) -> (Circuit, serde_json::Value) {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let entry_point = root.join("tests/fixtures/pipeline.zok");
+    let stdlib = root.join("../../../zokrates_stdlib/stdlib");
+    let resolver = FileSystemResolver::with_stdlib_root(stdlib.to_str().unwrap());
+    let arena = typed_arena::Arena::new();
+
+    let source = fs::read_to_string(&entry_point).unwrap();
+    let artifacts = compile::<Bn128Field, _>(
+        source,
+        entry_point,
+        Some(&resolver),
+        CompileConfig::default(),
+        &arena,
+    )
+    .unwrap();
+    let (program, abi) = artifacts.into_inner();
+    let program = program.collect();
+
+    let circuit = Circuit {
+        batch_size: 1,
+        file_path: dir.join("out").to_str().unwrap().to_string(),
+        abi_file_path: dir.join("abi.json").to_str().unwrap().to_string(),
+        proving_key_path: dir.join("proving.key").to_str().unwrap().to_string(),
+        verification_key_path: dir.join("verification.key").to_str().unwrap().to_string(),
+        offline_max_leaves: None,
+    };
+    program
+        .clone()
+        .serialize(BufWriter::new(File::create(&circuit.file_path).unwrap()))
+        .unwrap();
+    serde_json::to_writer(File::create(&circuit.abi_file_path).unwrap(), &abi).unwrap();
+
+    let keypair = <Ark as NonUniversalBackend<Bn128Field, G16>>::setup(
+        program,
+        &mut StdRng::seed_from_u64(seed),
+    );
+    fs::write(&circuit.proving_key_path, keypair.pk).unwrap();
+    let vk = serde_json::to_value(keypair.vk).unwrap();
+    fs::write(&circuit.verification_key_path, vk.to_string()).unwrap();
+
+    (circuit, vk)
+}
@@ -0,0 +1,166 @@
+//! Folds the steps of a run into a Nova proof.
+
+mod common;
+
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::Path;
+use std::time::Duration;
+
+use circuit::nova::{NovaOptions, NovaProof};
+use common::PipelineBuilder;
+use config::configs::prover::{NovaCircuit, ProverMode};
+use mips_vm::memory::MemoryHash;
+use mips_vm::{Executor, Memory, NoPreimages, State};
+use prover::nova::nova_work_cycle;
+use prover::ShutdownRequest;
+use storage::prover::records::NewTrace;
+use witness_generator::database_interface::DatabaseInterface;
+use zokrates_common::CompileConfig;
+use zokrates_core::compile::compile;
+use zokrates_field::PallasField;
+use zokrates_fs_resolver::FileSystemResolver;
+
+/// Compiles the step circuit `entry_point`, relative to the crate, for Pallas into `dir`.
+fn setup_nova_circuit(entry_point: &str, dir: &Path) -> NovaCircuit {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let entry_point = root.join(entry_point);
+    let stdlib = root.join("../../../zokrates_stdlib/stdlib");
+    let resolver = FileSystemResolver::with_stdlib_root(stdlib.to_str().unwrap());
+    let arena = typed_arena::Arena::new();
+
+    let source = fs::read_to_string(&entry_point).unwrap();
+    let artifacts = compile::<PallasField, _>(
+        source,
+        entry_point,
+        Some(&resolver),
+        CompileConfig::default(),
+        &arena,
+    )
+    .unwrap();
+    let (program, abi) = artifacts.into_inner();
+
+    let circuit = NovaCircuit {
+        file_path: dir.join("nova").to_str().unwrap().to_string(),
+        abi_file_path: dir.join("nova_abi.json").to_str().unwrap().to_string(),
+        params_path: None,
+    };
+    program
+        .collect()
+        .serialize(BufWriter::new(File::create(&circuit.file_path).unwrap()))
+        .unwrap();
+    serde_json::to_writer(File::create(&circuit.abi_file_path).unwrap(), &abi).unwrap();
+
+    circuit
+}
+
+/// The trace records of `addiu $t0, $zero, 5; addiu $t1, $zero, 7` in a memory hashed with
+/// `hash`.
+fn run(hash: MemoryHash) -> Vec<serde_json::Value> {
+    let mut memory = Memory::with_hash(hash);
+    memory.set_word(0x0040_0000, 0x2408_0005).unwrap();
+    memory.set_word(0x0040_0004, 0x2409_0007).unwrap();
+    let state = State {
+        cycle: 0,
+        pc: 0x0040_0000,
+        next_pc: 0x0040_0004,
+        lo: 0,
+        hi: 0,
+        regs: [0; 32],
+        heap: mips_vm::state::HEAP_START,
+        exit_code: 0,
+        exited: false,
+        memory,
+    };
+
+    let mut executor = Executor::new(state, NoPreimages);
+    (0..2)
+        .map(|_| serde_json::to_value(executor.step().unwrap().unwrap()).unwrap())
+        .collect()
+}
+
+/// The records of [`run`], the run exits after them.
+fn fixture_run() -> Vec<serde_json::Value> {
+    let mut records = run(MemoryHash::Poseidon);
+    // Ends the run, the fixture circuit doesn't check the exit.
+    records[1]["newExited"] = serde_json::Value::Bool(true);
+    records
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn run_is_folded_into_one_nova_proof() {
+    let pipeline = PipelineBuilder::new().traces(0).build().await;
+    let database = &pipeline.database;
+    let nova_circuit = setup_nova_circuit("tests/fixtures/nova_step.zok", pipeline.dir.path());
+
+    let traces = fixture_run()
+        .into_iter()
+        .enumerate()
+        .map(|(step, trace)| NewTrace {
+            segment: 0,
+            step: step as i64,
+            trace,
+        })
+        .collect();
+    database.store_traces("run", traces).await.unwrap();
+
+    let mut config = pipeline.prover_config();
+    config.prover.mode = ProverMode::Nova;
+    nova_work_cycle(
+        database.clone(),
+        ShutdownRequest::new(),
+        config,
+        nova_circuit.clone(),
+    )
+    .await;
+
+    let stored = database.load_nova_proof("run").await.unwrap().unwrap();
+    assert_eq!(stored.f_first_block, 1);
+    assert_eq!(stored.f_last_block, 2);
+    assert_eq!(stored.f_steps, 2);
+    assert!(database
+        .lease_next_unproven_run("other", Duration::from_secs(60))
+        .await
+        .unwrap()
+        .is_none());
+
+    let proof: NovaProof = serde_json::from_value(stored.f_proof).unwrap();
+    assert_eq!(proof.initial_state["cycle"], fixture_run()[0]["cycle"]);
+    assert_eq!(proof.final_state["cycle"], fixture_run()[1]["newCycle"]);
+    let options = NovaOptions::new(&nova_circuit.file_path, &nova_circuit.abi_file_path);
+    assert!(circuit::nova::verify_run(&options, &proof).unwrap());
+
+    // The proof doesn't prove another final state.
+    let mut forged = proof;
+    forged.final_state["cycle"] = fixture_run()[0]["cycle"].clone();
+    assert!(!circuit::nova::verify_run(&options, &forged).unwrap());
+}
+
+#[test]
+#[ignore] // compiling and folding the step circuit takes several minutes
+fn run_is_folded_through_the_step_circuit() {
+    // The step circuit is compiled and run on a thread with a large stack, like the circuit
+    // tests of the batches.
+    std::thread::Builder::new()
+        .stack_size(64 * 1024 * 1024)
+        .spawn(|| {
+            let dir = tempdir::TempDir::new("nova_step").unwrap();
+            let nova_circuit = setup_nova_circuit("../../lib/circuit/nova_step.zok", dir.path());
+            let options = NovaOptions::new(&nova_circuit.file_path, &nova_circuit.abi_file_path);
+
+            // nova_step.zok hashes the memory over the Pallas field
+            let records = run(MemoryHash::PoseidonPallas);
+            let proof = circuit::nova::prove_run(&options, &records).unwrap();
+            assert_eq!(proof.steps, 2);
+            assert_eq!(proof.final_state["pc"], records[1]["newPc"]);
+            assert_eq!(proof.final_state["memRoot"], records[1]["newMemRoot"]);
+            assert!(circuit::nova::verify_run(&options, &proof).unwrap());
+
+            let mut forged = proof;
+            forged.final_state["regs"] = records[0]["regs"].clone();
+            assert!(!circuit::nova::verify_run(&options, &forged).unwrap());
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+}
@@ -0,0 +1,148 @@
+//! Runs the witness generator, a prover and the verifier generator on an in-memory database.
+
+mod common;
+
+use std::time::Duration;
+
+use blob_store::BlobStore;
+use common::{retry, PipelineBuilder};
+use prover::client::{DatabaseClient, JobClient};
+use prover::{prover_work_cycle, ShutdownRequest};
+use storage::prover::{PipelineStage, ProverJobStatus};
+use types::BlockNumber;
+use witness_generator::database_interface::DatabaseInterface;
+use witness_generator::memory_database::InMemoryDatabase;
+use witness_generator::pipeline_metrics::PipelineLag;
+use witness_generator::prover_api::{ProverFailure, ProverJob, PublishProof};
+use witness_generator::settlement::MockSettlement;
+use witness_generator::witness_store::WitnessStore;
+use zokrates_common::helpers::BackendParameter;
+
+/// Client on which the job is given to the prover `other` as soon as its prover sends a heartbeat.
+#[derive(Clone)]
+struct LostLeaseClient {
+    client: DatabaseClient<InMemoryDatabase>,
+    database: InMemoryDatabase,
+    circuit_id: i64,
+}
+
+#[async_trait::async_trait]
+impl JobClient for LostLeaseClient {
+    async fn get_job(
+        &self,
+        prover_name: &str,
+        program_hash: &str,
+    ) -> anyhow::Result<Option<ProverJob>> {
+        self.client.get_job(prover_name, program_hash).await
+    }
+
+    async fn working_on(&self, job_id: i64, prover_name: &str) -> anyhow::Result<bool> {
+        self.database
+            .release_prover_job(job_id, prover_name, Duration::from_secs(0))
+            .await?;
+        self.database
+            .get_idle_prover_job_from_job_queue("other", self.circuit_id)
+            .await?;
+        Ok(false)
+    }
+
+    async fn publish(&self, proof: PublishProof) -> anyhow::Result<bool> {
+        self.client.publish(proof).await
+    }
+
+    async fn report_failure(&self, failure: ProverFailure) -> anyhow::Result<bool> {
+        self.client.report_failure(failure).await
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn trace_is_proven_and_verified() {
+    let pipeline = PipelineBuilder::new().build().await;
+    let database = &pipeline.database;
+    assert_eq!(
+        PipelineLag::load(database).await.unwrap(),
+        PipelineLag {
+            witness: 1,
+            proof: 1,
+            verify: 1,
+        }
+    );
+
+    // Witness generation starts from the first trace.
+    let mut witness_generator = pipeline.witness_generator();
+    assert!(witness_generator.prepare_next_witness().await.unwrap());
+    assert!(!witness_generator.prepare_next_witness().await.unwrap());
+    assert_eq!(database.pending_jobs_count().await.unwrap(), 1);
+    assert_eq!(database.pending_witness_traces_count().await.unwrap(), 0);
+    // The witness is kept in the blob store, compressed.
+    let witness = database
+        .load_witness(BlockNumber(1))
+        .await
+        .unwrap()
+        .unwrap();
+    let key = WitnessStore::key(BlockNumber(1), pipeline.registered.f_id);
+    assert_eq!(database.blob_store().keys(), vec![key.clone()]);
+    let blob = database.blob_store().get(&key).await.unwrap().unwrap();
+    assert!(blob.len() < witness.len());
+
+    // The prover stops after its first proof.
+    pipeline.prove().await;
+    let jobs = database.jobs();
+    assert_eq!(jobs.len(), 1);
+    assert_eq!(jobs[0].f_job_status, ProverJobStatus::Done.to_number());
+    assert_eq!(database.pending_jobs_count().await.unwrap(), 0);
+
+    // Verification starts after the last verified block.
+    let settlement = MockSettlement::new(BackendParameter::Ark, pipeline.vk.clone());
+    let mut verifier_generator = pipeline.verifier_generator(settlement.clone(), 1);
+    assert!(verifier_generator.verify_next_proof().await.unwrap());
+    assert!(!verifier_generator.verify_next_proof().await.unwrap());
+    let submissions = settlement.submissions();
+    assert_eq!(submissions.len(), 1);
+    assert_eq!(submissions[0].block, BlockNumber(1));
+    assert!(submissions[0].accepted);
+    assert_eq!(
+        database
+            .load_last_verified_proof_block_number()
+            .await
+            .unwrap(),
+        1
+    );
+    assert_eq!(
+        PipelineLag::load(database).await.unwrap(),
+        PipelineLag {
+            witness: 0,
+            proof: 0,
+            verify: 0,
+        }
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn proof_is_aborted_once_the_lease_is_lost() {
+    let pipeline = PipelineBuilder::new().build().await;
+    let database = &pipeline.database;
+    let mut witness_generator = pipeline.witness_generator();
+    assert!(witness_generator.prepare_next_witness().await.unwrap());
+
+    let client = LostLeaseClient {
+        client: DatabaseClient::new(database.clone(), retry()),
+        database: database.clone(),
+        circuit_id: pipeline.registered.f_id,
+    };
+    prover_work_cycle(client, ShutdownRequest::new(), pipeline.prover_config()).await;
+
+    // The job stays with the other prover, without a proof or a failure.
+    let jobs = database.jobs();
+    assert_eq!(
+        jobs[0].f_job_status,
+        ProverJobStatus::InProgress.to_number()
+    );
+    assert_eq!(jobs[0].f_updated_by, "other");
+    assert!(database.load_proof(BlockNumber(1)).await.unwrap().is_none());
+    assert!(database
+        .load_block_failure(PipelineStage::Proof, BlockNumber(1))
+        .await
+        .unwrap()
+        .is_none());
+}
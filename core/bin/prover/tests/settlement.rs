@@ -0,0 +1,33 @@
+//! Submits the proof of a trace through the dry run settlement.
+
+mod common;
+
+use std::fs;
+use std::path::Path;
+
+use common::PipelineBuilder;
+use types::BlockNumber;
+use witness_generator::settlement::DryRunSettlement;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn dry_run_writes_the_calldata() {
+    let pipeline = PipelineBuilder::new().build().await;
+    let abi_path =
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("../../../contract/verifier/g16/verifier");
+
+    let mut witness_generator = pipeline.witness_generator();
+    assert!(witness_generator.prepare_next_witness().await.unwrap());
+    pipeline.prove().await;
+
+    let settlement = DryRunSettlement::new(
+        abi_path.to_str().unwrap(),
+        pipeline.dir.path().join("calldata"),
+    );
+    let mut verifier_generator = pipeline.verifier_generator(settlement.clone(), 1);
+    assert!(verifier_generator.verify_next_proof().await.unwrap());
+
+    // The selector of `verifyTx`, the 8 words of the proof and its single input.
+    let calldata = fs::read_to_string(settlement.calldata_path(BlockNumber(1))).unwrap();
+    assert!(calldata.starts_with("0x"));
+    assert_eq!(calldata.len(), 2 + 2 * (4 + 9 * 32));
+}
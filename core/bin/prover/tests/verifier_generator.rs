@@ -0,0 +1,88 @@
+//! Runs the verifier generator on the proofs of a prover.
+
+mod common;
+
+use common::PipelineBuilder;
+use storage::prover::{PipelineStage, ProverJobStatus};
+use types::BlockNumber;
+use witness_generator::database_interface::DatabaseInterface;
+use witness_generator::settlement::MockSettlement;
+use zokrates_common::helpers::BackendParameter;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn invalid_proof_is_returned_to_the_provers() {
+    let mut pipeline = PipelineBuilder::new().build().await;
+    // The proofs made with the keys of `pipeline` don't verify against the ones of another setup.
+    let other = PipelineBuilder::new().seed(7).traces(0).build().await;
+    pipeline.registered.f_verification_key = other.vk;
+    let database = &pipeline.database;
+
+    let mut witness_generator = pipeline.witness_generator();
+    assert!(witness_generator.prepare_next_witness().await.unwrap());
+    pipeline.prove().await;
+
+    let settlement = MockSettlement::new(BackendParameter::Ark, serde_json::Value::Null);
+    let mut verifier_generator = pipeline.verifier_generator(settlement.clone(), 1);
+    assert!(!verifier_generator.verify_next_proof().await.unwrap());
+    assert!(settlement.submissions().is_empty());
+
+    // The proof is set aside and the batch is proven again.
+    assert!(database.load_proof(BlockNumber(1)).await.unwrap().is_none());
+    assert_eq!(
+        database.jobs()[0].f_job_status,
+        ProverJobStatus::Idle.to_number()
+    );
+    let failure = database
+        .load_block_failure(PipelineStage::Proof, BlockNumber(1))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(failure.f_attempts, 1);
+    assert!(!failure.f_dead);
+    assert_eq!(
+        database
+            .load_last_verified_proof_block_number()
+            .await
+            .unwrap(),
+        0
+    );
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn proofs_are_submitted_together() {
+    let pipeline = PipelineBuilder::new().traces(2).build().await;
+    let database = &pipeline.database;
+
+    let mut witness_generator = pipeline.witness_generator();
+    assert!(witness_generator.prepare_next_witness().await.unwrap());
+    assert!(witness_generator.prepare_next_witness().await.unwrap());
+
+    let settlement = MockSettlement::new(BackendParameter::Ark, pipeline.vk.clone());
+    let mut verifier_generator = pipeline.verifier_generator(settlement.clone(), 2);
+
+    // The first proof waits for the second one.
+    pipeline.prove().await;
+    assert!(!verifier_generator.verify_next_proof().await.unwrap());
+    assert!(settlement.submissions().is_empty());
+
+    pipeline.prove().await;
+    assert!(verifier_generator.verify_next_proof().await.unwrap());
+
+    let submissions = settlement.submissions();
+    assert_eq!(submissions.len(), 1);
+    assert_eq!(submissions[0].block, BlockNumber(1));
+    assert_eq!(submissions[0].inputs.len(), 2);
+    assert!(submissions[0].accepted);
+    let aggregated_proofs = database.aggregated_proofs();
+    assert_eq!(aggregated_proofs.len(), 1);
+    assert_eq!(aggregated_proofs[0].f_first_block, 1);
+    assert_eq!(aggregated_proofs[0].f_last_block, 2);
+    assert_eq!(aggregated_proofs[0].f_proof_ids, vec![1, 2]);
+    assert_eq!(
+        database
+            .load_last_verified_proof_block_number()
+            .await
+            .unwrap(),
+        2
+    );
+}
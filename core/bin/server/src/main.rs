@@ -1,21 +1,44 @@
 use futures::{channel::mpsc, executor::block_on, SinkExt, StreamExt, future};
 use std::cell::RefCell;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::thread;
+use std::time::Duration;
+
+use anyhow::Context;
 
 use structopt::StructOpt;
 
 use serde::{Deserialize, Serialize};
 
 use tokio::task::JoinHandle;
-use config::configs::api::ProverApiConfig;
+use config::configs::api::{PrometheusConfig, ProverApiConfig};
+use config::configs::blob_store::BlobStoreConfig;
 use config::configs::verifier::VerifierConfig;
-use storage::ConnectionPool;
+use storage::{ConnectionPool, StorageProcessor};
 use config::ProverConfig;
+use config::configs::prover::Circuit;
+use circuit::batch::TraceRecordType;
+use circuit::generator::{CircuitCache, CircuitParams, Features, MemoryHash, MemoryModel};
+use circuit::proof::calldata::VerifyTxCalldata;
+use storage::prover::records::NewTrace;
+use types::BlockNumber;
+use witness_generator::database::Database;
+use witness_generator::database_interface::DatabaseInterface as _;
+use witness_generator::traces;
+use witness_generator::witness_store::WitnessStore;
 use storage::database_interface::DatabaseInterface;
 use witness_generator::{run_prover_server, run_verifier_server};
+use witness_generator::pipeline_metrics::report_pipeline_lag;
+use prometheus_exporter::run_prometheus_exporter;
 
 const DEFAULT_CHANNEL_CAPACITY: usize = 32_768;
+/// Interval between the updates of the pipeline lag gauges.
+const PIPELINE_LAG_INTERVAL: Duration = Duration::from_secs(10);
+/// Stack of the thread compiling a circuit, which recurses deeply.
+const COMPILER_STACK_SIZE: usize = 64 * 1024 * 1024;
 
 #[derive(Debug, Clone, Copy)]
 pub enum ServerCommand {
@@ -116,6 +139,64 @@ struct Opt {
         default_value = "rest-api,web3-api,rpc-api,rpc-websocket-api,eth-sender,witness-generator,verifier-generator,forced-exit,prometheus,core,rejected-task-cleaner,fetchers,prometheus-periodic-metrics"
     )]
     components: ComponentsToRun,
+    #[structopt(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(StructOpt)]
+enum Command {
+    /// Imports a Cannon JSONL trace file, holding one trace record per line
+    ImportTraces {
+        /// Id of the traced program
+        #[structopt(long)]
+        program_id: String,
+        /// Segment of the program run covered by the file
+        #[structopt(long, default_value = "0")]
+        segment: i64,
+        /// Step of the first record of the file
+        #[structopt(long, default_value = "0")]
+        first_step: i64,
+        /// Amount of records read and stored at once
+        #[structopt(long, default_value = "1000")]
+        chunk_size: usize,
+        /// Path to the trace file
+        #[structopt(parse(from_os_str))]
+        file: PathBuf,
+    },
+    /// Logs the `verifyTx` calldata of a stored proof, its decoded arguments and an estimate of its gas
+    ExportCalldata {
+        /// First block of the proven batch
+        #[structopt(long)]
+        block: u32,
+    },
+    /// Generates the MIPS circuit for a batch size, compiles it unless it is cached and prints its artifacts
+    CompileCircuit {
+        /// Trace records checked by one proof
+        #[structopt(long, default_value = "1")]
+        batch_size: u32,
+        /// Hash of the memory Merkle tree, poseidon or keccak
+        #[structopt(long, default_value = "poseidon")]
+        memory_hash: MemoryHash,
+        /// Leaves the preimage syscall out of the circuit, which makes it illegal
+        #[structopt(long)]
+        no_preimage: bool,
+        /// Preimage syscalls a batch may hold, each of them adding a preimage check to the circuit
+        #[structopt(long, default_value = "1")]
+        preimage_calls: u32,
+        /// Returns the commitments to the state before and after the batch, see commitment.zok
+        #[structopt(long)]
+        commitment: bool,
+        /// Checks the memory offline with at most this many distinct leaves per batch instead
+        /// of a Merkle proof per access, see offline_memory.zok. Needs --no-preimage
+        #[structopt(long)]
+        offline_memory: Option<u32>,
+        /// Directory of the compiled circuits
+        #[structopt(long, parse(from_os_str), default_value = "core/lib/circuit/generated")]
+        cache_dir: PathBuf,
+        /// Root of the ZoKrates standard library
+        #[structopt(long, parse(from_os_str), default_value = "zokrates_stdlib/stdlib")]
+        stdlib: PathBuf,
+    },
 }
 
 #[tokio::main]
@@ -134,9 +215,54 @@ async fn main() -> anyhow::Result<()> {
         vlog::info!("Performing the server genesis initialization",);
         // let config = ChainConfig::from_env();
         // genesis_init(&config).await;
+        let mut storage = StorageProcessor::establish_connection().await?;
+        storage.run_migrations().await?;
+        vlog::info!("Database schema is up to date");
         return Ok(());
     }
 
+    if let Some(Command::ImportTraces {
+        program_id,
+        segment,
+        first_step,
+        chunk_size,
+        file,
+    }) = opt.command
+    {
+        return import_traces_file(&file, program_id, segment, first_step, chunk_size).await;
+    }
+
+    if let Some(Command::ExportCalldata { block }) = opt.command {
+        return export_calldata(BlockNumber(block)).await;
+    }
+
+    if let Some(Command::CompileCircuit {
+        batch_size,
+        memory_hash,
+        no_preimage,
+        preimage_calls,
+        commitment,
+        offline_memory,
+        cache_dir,
+        stdlib,
+    }) = opt.command
+    {
+        let params = CircuitParams {
+            batch_size,
+            memory_hash,
+            memory_model: match offline_memory {
+                Some(max_leaves) => MemoryModel::Offline { max_leaves },
+                None => MemoryModel::Merkle,
+            },
+            features: Features {
+                preimage: !no_preimage,
+                commitment,
+            },
+            preimage_calls,
+        };
+        return compile_circuit(params, CircuitCache::new(&cache_dir, &stdlib));
+    }
+
     // It's a `ServerCommand::Launch`, perform the usual routine.
     vlog::info!("Running the zkm server");
     run_server(&opt.components).await;
@@ -157,6 +283,12 @@ async fn run_server(components: &ComponentsToRun) {
     if components.0.contains(&Component::VerifierGenerator) {
         tasks.push(run_verifier(connection_pool.clone()))
     }
+    if components.0.contains(&Component::Prometheus) {
+        let prometheus_config = PrometheusConfig::from_env();
+        tasks.push(run_prometheus_exporter(prometheus_config.port));
+        let database = Database::new(read_only_connection_pool.clone());
+        tasks.push(tokio::spawn(report_pipeline_lag(database, PIPELINE_LAG_INTERVAL)));
+    }
 
     {
         let stop_signal_sender = RefCell::new(stop_signal_sender.clone());
@@ -197,17 +329,145 @@ pub async fn wait_for_tasks(task_futures: Vec<JoinHandle<()>>) {
 }
 
 
+/// Reads the trace records of `file`, numbering them from `first_step`.
+fn read_traces(
+    file: &Path,
+    segment: i64,
+    first_step: i64,
+) -> anyhow::Result<impl Iterator<Item = anyhow::Result<NewTrace>>> {
+    let reader = BufReader::new(
+        File::open(file).with_context(|| format!("Could not open {}", file.display()))?,
+    );
+    let lines = reader
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !matches!(line, Ok(line) if line.trim().is_empty()));
+
+    Ok((first_step..).zip(lines).map(move |(step, (index, line))| {
+        let trace = serde_json::from_str(&line?)
+            .with_context(|| format!("Invalid JSON on line {}", index + 1))?;
+        Ok(NewTrace {
+            segment,
+            step,
+            trace,
+        })
+    }))
+}
+
+/// Streams the trace records of `file` into the database, checking each of them against the
+/// circuit ABI. The file is imported in a single transaction, so that an invalid file stores
+/// nothing and the blocks of a file are consecutive. It has to fill whole batches of the circuit.
+async fn import_traces_file(
+    file: &Path,
+    program_id: String,
+    segment: i64,
+    first_step: i64,
+    chunk_size: usize,
+) -> anyhow::Result<()> {
+    let circuit = Circuit::from_env();
+    let record_type = TraceRecordType::from_abi(Path::new(&circuit.abi_file_path))
+        .map_err(anyhow::Error::msg)?;
+
+    let pool = ConnectionPool::new(Some(1));
+    let mut storage = pool.access_storage().await?;
+    let mut transaction = storage.start_transaction().await?;
+    let mut blocks: Option<(BlockNumber, BlockNumber)> = None;
+    let mut count = 0;
+    let mut traces = read_traces(file, segment, first_step)?.peekable();
+    while traces.peek().is_some() {
+        let chunk = traces
+            .by_ref()
+            .take(chunk_size.max(1))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        for trace in &chunk {
+            traces::check_trace(&record_type, trace)
+                .map_err(anyhow::Error::msg)
+                .with_context(|| format!("Invalid trace at step {}", trace.step))?;
+        }
+        count += chunk.len();
+        let (first_block, last_block) = transaction
+            .prover_schema()
+            .store_traces(&program_id, chunk)
+            .await?;
+        vlog::info!("Stored blocks {}..={}", *first_block, *last_block);
+        blocks = Some((blocks.map_or(first_block, |(first, _)| first), last_block));
+    }
+
+    let (first_block, last_block) =
+        blocks.with_context(|| format!("No traces in {}", file.display()))?;
+    traces::check_whole_batches(count, circuit.batch_size)
+        .with_context(|| format!("Invalid trace file {}", file.display()))?;
+    transaction.commit().await?;
+    vlog::info!(
+        "Imported the traces of {} as blocks {}..={}",
+        program_id,
+        *first_block,
+        *last_block
+    );
+
+    Ok(())
+}
+
+/// Logs the `verifyTx` transaction of the proof of the batch starting at `block`, as it would
+/// be sent to the verifier contract at `VERIFIER_ABI_PATH`. Nothing is sent.
+async fn export_calldata(block: BlockNumber) -> anyhow::Result<()> {
+    let verifier_config = VerifierConfig::from_env();
+    let database = Database::new(ConnectionPool::new(Some(1)));
+    let proof = database
+        .load_proof(block)
+        .await?
+        .with_context(|| format!("No valid proof of block {}", *block))?;
+
+    let tx = VerifyTxCalldata::from_stored_proof(&proof.f_proof, &verifier_config.abi_path)
+        .map_err(anyhow::Error::msg)?;
+    vlog::info!("verifyTx calldata of block {}: {}", *block, tx);
+
+    Ok(())
+}
+
 pub fn run_witness_generator(connection_pool: ConnectionPool) -> JoinHandle<()> {
     vlog::info!("Starting the Prover server actors");
     let prover_api_config = ProverApiConfig::from_env();
     let prover_config = ProverConfig::from_env();
-    let database = witness_generator::database::Database::new(connection_pool);
+    let witness_store = WitnessStore::from_config(&BlobStoreConfig::from_env())
+        .expect("Failed to open the blob store of the witnesses");
+    let database =
+        witness_generator::database::Database::new(connection_pool).with_witness_store(witness_store);
     run_prover_server(database, prover_api_config, prover_config)
 }
 
 pub fn run_verifier(connection_pool: ConnectionPool) -> JoinHandle<()> {
     vlog::info!("Starting the Prover server actors");
     let verifier_config = VerifierConfig::from_env();
+    let prover_config = ProverConfig::from_env();
     let database = witness_generator::database::Database::new(connection_pool);
-    run_verifier_server(database, verifier_config)
-}
\ No newline at end of file
+    run_verifier_server(
+        database,
+        verifier_config,
+        prover_config.circuit,
+        prover_config.retry,
+    )
+}
+
+fn compile_circuit(params: CircuitParams, cache: CircuitCache) -> anyhow::Result<()> {
+    let circuit = thread::Builder::new()
+        .name("circuit_compiler".to_string())
+        .stack_size(COMPILER_STACK_SIZE)
+        .spawn(move || cache.get(&params))?
+        .join()
+        .map_err(|_| anyhow::anyhow!("The circuit compiler panicked"))?
+        .map_err(anyhow::Error::msg)?;
+
+    println!("CIRCUIT_BATCH_SIZE={}", params.batch_size);
+    println!("CIRCUIT_FILE_PATH={}", circuit.program_path.display());
+    println!("CIRCUIT_ABI_FILE_PATH={}", circuit.abi_path.display());
+    if let MemoryModel::Offline { max_leaves } = params.memory_model {
+        println!("CIRCUIT_OFFLINE_MAX_LEAVES={}", max_leaves);
+    }
+    println!(
+        "# generate the keys with: zokrates setup -i {}",
+        circuit.program_path.display()
+    );
+
+    Ok(())
+}
@@ -5,12 +5,18 @@
 
 // Built-in
 use std::clone::Clone;
+use std::time::Duration;
 use serde_json::Value;
 // Workspace uses
 use storage::{ConnectionPool, StorageProcessor};
-use storage::prover::records::StoredProof;
+use storage::prover::records::{
+    NewTrace, StorageBlockFailure, StorageCircuit, StorageNovaRun, StorageProverJobQueueCloud,
+    StorageTrace, StoredAggregatedProof, StoredNovaProof, StoredProof,
+};
+use storage::prover::PipelineStage;
 use types::BlockNumber;
 // Local uses
+use crate::witness_store::WitnessStore;
 use crate::DatabaseInterface;
 
 const NUMBER_OF_STORED_ACCOUNT_TREE_CACHE: u32 = 300;
@@ -21,26 +27,42 @@ const NUMBER_OF_STORED_ACCOUNT_TREE_CACHE: u32 = 300;
 pub struct Database {
     /// Connection to the database.
     db_pool: ConnectionPool,
+    /// Blob store of the witnesses, required to store or load them.
+    witness_store: Option<WitnessStore>,
 }
 
 impl Database {
     pub fn new(db_pool: ConnectionPool) -> Self {
-        Self { db_pool }
+        Self {
+            db_pool,
+            witness_store: None,
+        }
     }
-}
 
-#[async_trait::async_trait]
-impl DatabaseInterface for Database {
+    /// Stores the witnesses in `witness_store`.
+    pub fn with_witness_store(mut self, witness_store: WitnessStore) -> Self {
+        self.witness_store = Some(witness_store);
+        self
+    }
+
+    fn witness_store(&self) -> anyhow::Result<&WitnessStore> {
+        self.witness_store
+            .as_ref()
+            .ok_or_else(|| anyhow::format_err!("No blob store is configured for the witnesses"))
+    }
+
+    /// Returns connection to the database.
     async fn acquire_connection(&self) -> anyhow::Result<StorageProcessor<'_>> {
         let connection = self.db_pool.access_storage().await?;
 
         Ok(connection)
     }
+}
 
-    async fn load_last_proof_block_number(
-        &self,
-        connection: &mut StorageProcessor<'_>,
-    ) -> anyhow::Result<i64> {
+#[async_trait::async_trait]
+impl DatabaseInterface for Database {
+    async fn load_last_proof_block_number(&self) -> anyhow::Result<i64> {
+        let mut connection = self.acquire_connection().await?;
         let number = connection.prover_schema().load_last_proof_block_number().await?;
 
         Ok(number)
@@ -48,9 +70,9 @@ impl DatabaseInterface for Database {
 
     async fn update_last_proof_block_number(
         &self,
-        connection: &mut StorageProcessor<'_>,
         block_number: BlockNumber,
     ) -> anyhow::Result<()> {
+        let mut connection = self.acquire_connection().await?;
         connection
             .prover_schema()
             .update_last_proof_block_number(block_number)
@@ -59,46 +81,41 @@ impl DatabaseInterface for Database {
         Ok(())
     }
 
-    async fn load_last_witness_block_number(
-        &self,
-        connection: &mut StorageProcessor<'_>,
-    ) -> anyhow::Result<i64> {
+    async fn load_last_witness_block_number(&self) -> anyhow::Result<i64> {
+        let mut connection = self.acquire_connection().await?;
         let number = connection.prover_schema().load_last_witness_block_number().await?;
 
         Ok(number)
     }
 
-    async fn load_last_verified_proof_block_number(
-        &self,
-        connection: &mut StorageProcessor<'_>,
-    ) -> anyhow::Result<i64> {
-        let number = connection.prover_schema().load_last_verified_proof_block_number().await?;
-
-        Ok(number)
-    }
-
-    async fn update_last_verified_proof_block_number(
+    async fn update_last_witness_block_number(
         &self,
-        connection: &mut StorageProcessor<'_>,
         block_number: BlockNumber,
     ) -> anyhow::Result<()> {
+        let mut connection = self.acquire_connection().await?;
         connection
             .prover_schema()
-            .update_last_verified_proof_block_number(block_number)
+            .update_last_witness_block_number(block_number)
             .await?;
 
         Ok(())
     }
 
+    async fn load_last_verified_proof_block_number(&self) -> anyhow::Result<i64> {
+        let mut connection = self.acquire_connection().await?;
+        let number = connection.prover_schema().load_last_verified_proof_block_number().await?;
+
+        Ok(number)
+    }
 
-    async fn update_last_witness_block_number(
+    async fn update_last_verified_proof_block_number(
         &self,
-        connection: &mut StorageProcessor<'_>,
         block_number: BlockNumber,
     ) -> anyhow::Result<()> {
+        let mut connection = self.acquire_connection().await?;
         connection
             .prover_schema()
-            .update_last_witness_block_number(block_number)
+            .update_last_verified_proof_block_number(block_number)
             .await?;
 
         Ok(())
@@ -106,61 +123,413 @@ impl DatabaseInterface for Database {
 
     async fn load_trace(
         &self,
-        connection: &mut StorageProcessor<'_>,
         block_number: BlockNumber,
     ) -> anyhow::Result<Option<String>> {
+        let mut connection = self.acquire_connection().await?;
         let trace = connection.prover_schema().load_trace(block_number).await?;
 
         Ok(trace)
     }
 
+    async fn load_traces(
+        &self,
+        first_block: BlockNumber,
+        count: u32,
+    ) -> anyhow::Result<Option<Vec<Value>>> {
+        let mut connection = self.acquire_connection().await?;
+        let traces = connection
+            .prover_schema()
+            .load_traces(first_block, count)
+            .await?;
+
+        Ok(traces)
+    }
+
+    async fn store_traces(
+        &self,
+        program_id: &str,
+        traces: Vec<NewTrace>,
+    ) -> anyhow::Result<(BlockNumber, BlockNumber)> {
+        let mut connection = self.acquire_connection().await?;
+        let blocks = connection
+            .prover_schema()
+            .store_traces(program_id, traces)
+            .await?;
+
+        Ok(blocks)
+    }
+
     async fn load_witness(
         &self,
-        connection: &mut StorageProcessor<'_>,
         block_number: BlockNumber,
-    ) -> anyhow::Result<Option<String>> {
-        let witness = connection.prover_schema().get_witness(block_number).await?;
+    ) -> anyhow::Result<Option<Vec<u8>>> {
+        let mut connection = self.acquire_connection().await?;
+        let record = match connection.prover_schema().get_witness(block_number).await? {
+            Some(record) => record,
+            None => return Ok(None),
+        };
+        let witness = self.witness_store()?.load(record).await?;
 
-        Ok(witness)
+        Ok(Some(witness))
+    }
+
+    async fn load_compressed_witness(
+        &self,
+        block_number: BlockNumber,
+    ) -> anyhow::Result<Option<(Vec<u8>, String)>> {
+        let mut connection = self.acquire_connection().await?;
+        let record = match connection.prover_schema().get_witness(block_number).await? {
+            Some(record) => record,
+            None => return Ok(None),
+        };
+        let witness = self.witness_store()?.load_compressed(record).await?;
+
+        Ok(Some(witness))
     }
 
     async fn load_proof(
         &self,
-        connection: &mut StorageProcessor<'_>,
         block_number: BlockNumber,
     ) -> anyhow::Result<Option<StoredProof>> {
+        let mut connection = self.acquire_connection().await?;
         let proof = connection.prover_schema().load_proof(block_number).await?;
 
         Ok(proof)
     }
 
-    async fn store_proof(
+    async fn store_aggregated_proof(
+        &self,
+        first_block: BlockNumber,
+        last_block: BlockNumber,
+        proof_ids: Vec<i64>,
+    ) -> anyhow::Result<StoredAggregatedProof> {
+        let mut connection = self.acquire_connection().await?;
+        let aggregated_proof = connection
+            .prover_schema()
+            .store_aggregated_proof(first_block, last_block, &proof_ids)
+            .await?;
+
+        Ok(aggregated_proof)
+    }
+
+    async fn lease_next_unproven_run(
+        &self,
+        prover_name: &str,
+        gone_timeout: Duration,
+    ) -> anyhow::Result<Option<StorageNovaRun>> {
+        let mut connection = self.acquire_connection().await?;
+        let run = connection
+            .prover_schema()
+            .lease_next_unproven_run(prover_name, gone_timeout)
+            .await?;
+
+        Ok(run)
+    }
+
+    async fn record_nova_run_is_working(
+        &self,
+        program_id: &str,
+        prover_name: &str,
+    ) -> anyhow::Result<bool> {
+        let mut connection = self.acquire_connection().await?;
+        let leased = connection
+            .prover_schema()
+            .record_nova_run_is_working(program_id, prover_name)
+            .await?;
+
+        Ok(leased)
+    }
+
+    async fn fail_nova_run(
+        &self,
+        program_id: &str,
+        prover_name: &str,
+        error: &str,
+        max_attempts: u32,
+        retry_after: Duration,
+    ) -> anyhow::Result<Option<StorageNovaRun>> {
+        let mut connection = self.acquire_connection().await?;
+        let run = connection
+            .prover_schema()
+            .fail_nova_run(program_id, prover_name, error, max_attempts, retry_after)
+            .await?;
+
+        Ok(run)
+    }
+
+    async fn load_program_traces(&self, program_id: &str) -> anyhow::Result<Vec<StorageTrace>> {
+        let mut connection = self.acquire_connection().await?;
+        let traces = connection
+            .prover_schema()
+            .load_program_traces(program_id)
+            .await?;
+
+        Ok(traces)
+    }
+
+    async fn store_nova_proof(
+        &self,
+        program_id: &str,
+        first_block: BlockNumber,
+        last_block: BlockNumber,
+        steps: i64,
+        proof: Value,
+    ) -> anyhow::Result<bool> {
+        let mut connection = self.acquire_connection().await?;
+        let stored = connection
+            .prover_schema()
+            .store_nova_proof(program_id, first_block, last_block, steps, proof)
+            .await?;
+
+        Ok(stored)
+    }
+
+    async fn load_nova_proof(&self, program_id: &str) -> anyhow::Result<Option<StoredNovaProof>> {
+        let mut connection = self.acquire_connection().await?;
+        let proof = connection.prover_schema().load_nova_proof(program_id).await?;
+
+        Ok(proof)
+    }
+
+    async fn reject_proof(
         &self,
-        connection: &mut StorageProcessor<'_>,
-        job_id: i32,
         block_number: BlockNumber,
+        retry_after: Option<Duration>,
+    ) -> anyhow::Result<bool> {
+        let mut connection = self.acquire_connection().await?;
+        let rejected = connection
+            .prover_schema()
+            .reject_proof(block_number, retry_after)
+            .await?;
+
+        Ok(rejected)
+    }
+
+    async fn store_proof(
+        &self,
+        job_id: i64,
+        prover_name: &str,
         proof_str: String,
+    ) -> anyhow::Result<bool> {
+        let proof_value = serde_json::from_str(proof_str.as_str())?;
+        let mut connection = self.acquire_connection().await?;
+        let stored = connection
+            .prover_schema()
+            .store_proof(job_id, prover_name, proof_value)
+            .await?;
+
+        Ok(stored)
+    }
+
+    async fn store_witness_job(
+        &self,
+        first_block: BlockNumber,
+        last_block: BlockNumber,
+        witness: Vec<u8>,
+        object_job: String,
+        circuit_id: i64,
     ) -> anyhow::Result<()> {
-        let proof_value = serde_json::from_str(proof_str.as_str()).unwrap();
-        connection
+        // A witness written without its row is overwritten when the batch is retried.
+        let (key, hash) = self
+            .witness_store()?
+            .put(first_block, circuit_id, &witness)
+            .await?;
+
+        let mut connection = self.acquire_connection().await?;
+        let mut transaction = connection.start_transaction().await?;
+        transaction
+            .prover_schema()
+            .store_witness(first_block, &key, &hash, circuit_id)
+            .await?;
+        transaction
             .prover_schema()
-            .store_proof(job_id, block_number, proof_value)
+            .add_prover_job_to_job_queue(
+                first_block,
+                last_block,
+                first_block.to_string(),
+                object_job,
+                circuit_id,
+            )
             .await?;
+        transaction
+            .prover_schema()
+            .update_last_witness_block_number(BlockNumber(*last_block + 1))
+            .await?;
+        transaction.commit().await?;
 
         Ok(())
     }
 
-    async fn store_witness(
+    async fn get_idle_prover_job_from_job_queue(
+        &self,
+        prover_name: &str,
+        circuit_id: i64,
+    ) -> anyhow::Result<Option<StorageProverJobQueueCloud>> {
+        let mut connection = self.acquire_connection().await?;
+        let job = connection
+            .prover_schema()
+            .get_idle_prover_job_from_job_queue(prover_name, circuit_id)
+            .await?;
+
+        Ok(job)
+    }
+
+    async fn load_prover_job(
+        &self,
+        job_id: i64,
+    ) -> anyhow::Result<Option<StorageProverJobQueueCloud>> {
+        let mut connection = self.acquire_connection().await?;
+        let job = connection.prover_schema().load_prover_job(job_id).await?;
+
+        Ok(job)
+    }
+
+    async fn record_prover_is_working(
+        &self,
+        job_id: i64,
+        prover_name: &str,
+    ) -> anyhow::Result<bool> {
+        let mut connection = self.acquire_connection().await?;
+        let leased = connection
+            .prover_schema()
+            .record_prover_is_working(job_id, prover_name)
+            .await?;
+
+        Ok(leased)
+    }
+
+    async fn release_prover_job(
+        &self,
+        job_id: i64,
+        prover_name: &str,
+        delay: Duration,
+    ) -> anyhow::Result<bool> {
+        let mut connection = self.acquire_connection().await?;
+        let released = connection
+            .prover_schema()
+            .release_prover_job(job_id, prover_name, delay)
+            .await?;
+
+        Ok(released)
+    }
+
+    async fn fail_prover_job(
+        &self,
+        job_id: i64,
+        prover_name: &str,
+    ) -> anyhow::Result<bool> {
+        let mut connection = self.acquire_connection().await?;
+        let failed = connection
+            .prover_schema()
+            .fail_prover_job(job_id, prover_name)
+            .await?;
+
+        Ok(failed)
+    }
+
+    async fn record_block_failure(
         &self,
-        connection: &mut StorageProcessor<'_>,
+        stage: PipelineStage,
         block: BlockNumber,
-        witness: String,
-    ) -> anyhow::Result<()> {
-        connection
+        error: &str,
+        max_attempts: u32,
+    ) -> anyhow::Result<StorageBlockFailure> {
+        let mut connection = self.acquire_connection().await?;
+        let failure = connection
             .prover_schema()
-            .store_witness(block, witness)
+            .record_block_failure(stage, block, error, max_attempts)
             .await?;
 
-        Ok(())
+        Ok(failure)
+    }
+
+    async fn load_block_failure(
+        &self,
+        stage: PipelineStage,
+        block: BlockNumber,
+    ) -> anyhow::Result<Option<StorageBlockFailure>> {
+        let mut connection = self.acquire_connection().await?;
+        let failure = connection
+            .prover_schema()
+            .load_block_failure(stage, block)
+            .await?;
+
+        Ok(failure)
+    }
+
+    async fn mark_stale_jobs_as_idle(
+        &self,
+        gone_timeout: Duration,
+    ) -> anyhow::Result<u64> {
+        let mut connection = self.acquire_connection().await?;
+        let jobs = connection
+            .prover_schema()
+            .mark_stale_jobs_as_idle(gone_timeout)
+            .await?;
+
+        Ok(jobs)
+    }
+
+    async fn pending_jobs_count(&self) -> anyhow::Result<u32> {
+        let mut connection = self.acquire_connection().await?;
+        let count = connection.prover_schema().pending_jobs_count().await?;
+
+        Ok(count)
+    }
+
+    async fn pending_witness_traces_count(&self) -> anyhow::Result<u32> {
+        let mut connection = self.acquire_connection().await?;
+        let count = connection
+            .prover_schema()
+            .pending_witness_traces_count()
+            .await?;
+
+        Ok(count)
+    }
+
+    async fn pending_proof_blocks_count(&self) -> anyhow::Result<u32> {
+        let mut connection = self.acquire_connection().await?;
+        let count = connection
+            .prover_schema()
+            .pending_proof_blocks_count()
+            .await?;
+
+        Ok(count)
+    }
+
+    async fn load_last_trace_block_number(&self) -> anyhow::Result<i64> {
+        let mut connection = self.acquire_connection().await?;
+        let number = connection.prover_schema().load_last_trace_block_number().await?;
+
+        Ok(number)
+    }
+
+    async fn register_circuit(
+        &self,
+        program_hash: &str,
+        abi: Value,
+        verification_key: Value,
+        verifier_address: Option<&str>,
+    ) -> anyhow::Result<StorageCircuit> {
+        let mut connection = self.acquire_connection().await?;
+        let circuit = connection
+            .prover_schema()
+            .register_circuit(program_hash, abi, verification_key, verifier_address)
+            .await?;
+
+        Ok(circuit)
+    }
+
+    async fn load_circuit_by_hash(
+        &self,
+        program_hash: &str,
+    ) -> anyhow::Result<Option<StorageCircuit>> {
+        let mut connection = self.acquire_connection().await?;
+        let circuit = connection
+            .prover_schema()
+            .load_circuit_by_hash(program_hash)
+            .await?;
+
+        Ok(circuit)
     }
 }
@@ -2,86 +2,236 @@
 //! The essential part of this module is the trait that abstracts
 //! the database interaction, so no real database is needed to run
 //! the prover-server, which is required for tests.
+//!
+//! Every method is a self-contained operation: implementations manage their
+//! connections and transactions themselves, see `Database` for the Postgres one
+//! and `InMemoryDatabase` for the one used in tests.
 
 // Built-in
 use std::clone::Clone;
 use std::marker::{Send, Sync};
+use std::time::Duration;
 use serde_json::Value;
-use storage::prover::records::StoredProof;
+use storage::prover::records::{
+    NewTrace, StorageBlockFailure, StorageCircuit, StorageNovaRun, StorageProverJobQueueCloud,
+    StorageTrace, StoredAggregatedProof, StoredNovaProof, StoredProof,
+};
+use storage::prover::PipelineStage;
 // Workspace uses
-use storage::StorageProcessor;
 use types::BlockNumber;
 
 /// Abstract database access trait.
 #[async_trait::async_trait]
 pub trait DatabaseInterface: Send + Sync + Clone + 'static {
-    /// Returns connection to the database.
-    async fn acquire_connection(&self) -> anyhow::Result<StorageProcessor<'_>>;
+    async fn load_last_proof_block_number(&self) -> anyhow::Result<i64>;
 
-    async fn load_last_proof_block_number(
+    async fn update_last_proof_block_number(&self, block_number: BlockNumber)
+        -> anyhow::Result<()>;
+
+    async fn load_last_witness_block_number(&self) -> anyhow::Result<i64>;
+
+    async fn update_last_witness_block_number(
         &self,
-        connection: &mut StorageProcessor<'_>,
-    ) -> anyhow::Result<i64>;
+        block_number: BlockNumber,
+    ) -> anyhow::Result<()>;
+
+    async fn load_last_verified_proof_block_number(&self) -> anyhow::Result<i64>;
 
-    async fn update_last_proof_block_number(
+    async fn update_last_verified_proof_block_number(
         &self,
-        connection: &mut StorageProcessor<'_>,
         block_number: BlockNumber,
     ) -> anyhow::Result<()>;
 
-    async fn load_last_witness_block_number(
+    async fn load_trace(&self, block_number: BlockNumber) -> anyhow::Result<Option<String>>;
+
+    /// Returns `count` consecutive traces starting from `first_block`,
+    /// or `None` if some of them are not stored yet.
+    async fn load_traces(
         &self,
-        connection: &mut StorageProcessor<'_>,
-    ) -> anyhow::Result<i64>;
+        first_block: BlockNumber,
+        count: u32,
+    ) -> anyhow::Result<Option<Vec<Value>>>;
 
-    async fn load_last_verified_proof_block_number(
+    /// Appends the trace records of the program `program_id`,
+    /// returns the blocks assigned to the first and the last of them.
+    async fn store_traces(
         &self,
-        connection: &mut StorageProcessor<'_>,
-    ) -> anyhow::Result<i64>;
+        program_id: &str,
+        traces: Vec<NewTrace>,
+    ) -> anyhow::Result<(BlockNumber, BlockNumber)>;
 
-    async fn update_last_verified_proof_block_number(
+    /// Returns stored witness for a block, read from the blob store.
+    async fn load_witness(&self, block_number: BlockNumber) -> anyhow::Result<Option<Vec<u8>>>;
+
+    /// Returns stored witness for a block gzipped, along with the hash of its content,
+    /// see `WitnessStore::load_compressed`.
+    async fn load_compressed_witness(
         &self,
-        connection: &mut StorageProcessor<'_>,
         block_number: BlockNumber,
-    ) -> anyhow::Result<()>;
+    ) -> anyhow::Result<Option<(Vec<u8>, String)>>;
 
-    async fn update_last_witness_block_number(
+    /// Returns the valid proof for a block.
+    async fn load_proof(&self, block_number: BlockNumber) -> anyhow::Result<Option<StoredProof>>;
+
+    /// Records the proofs with the ids `proof_ids`, of the batches from `first_block` to `last_block`,
+    /// as submitted together.
+    async fn store_aggregated_proof(
         &self,
-        connection: &mut StorageProcessor<'_>,
-        block_number: BlockNumber,
-    ) -> anyhow::Result<()>;
+        first_block: BlockNumber,
+        last_block: BlockNumber,
+        proof_ids: Vec<i64>,
+    ) -> anyhow::Result<StoredAggregatedProof>;
 
-    async fn load_trace(
+    /// Leases to `prover_name` the first run that has exited and has no Nova proof yet.
+    /// The runs leased to other provers, waiting for their backoff or failed too many times
+    /// are skipped, unless their prover didn't report for `gone_timeout`.
+    async fn lease_next_unproven_run(
         &self,
-        connection: &mut StorageProcessor<'_>,
-        block_number: BlockNumber,
-    ) -> anyhow::Result<Option<String>>;
+        prover_name: &str,
+        gone_timeout: Duration,
+    ) -> anyhow::Result<Option<StorageNovaRun>>;
 
-    /// Returns stored witness for a block.
-    async fn load_witness(
+    /// Extends the lease of `prover_name` on the run of `program_id`,
+    /// returns `false` if the run belongs to another prover.
+    async fn record_nova_run_is_working(
         &self,
-        connection: &mut StorageProcessor<'_>,
-        block_number: BlockNumber,
-    ) -> anyhow::Result<Option<String>>;
+        program_id: &str,
+        prover_name: &str,
+    ) -> anyhow::Result<bool>;
+
+    /// Counts a failed attempt of `prover_name` at the run of `program_id` and returns the run
+    /// to the queue after `retry_after`, or for good once `max_attempts` attempts have failed.
+    /// Returns `None` if `prover_name` lost the lease.
+    async fn fail_nova_run(
+        &self,
+        program_id: &str,
+        prover_name: &str,
+        error: &str,
+        max_attempts: u32,
+        retry_after: Duration,
+    ) -> anyhow::Result<Option<StorageNovaRun>>;
+
+    /// Returns the trace records of the program `program_id`, in the order of its run.
+    async fn load_program_traces(&self, program_id: &str) -> anyhow::Result<Vec<StorageTrace>>;
+
+    /// Stores the Nova proof of the run of `program_id`, covering the traces from `first_block`
+    /// to `last_block`. Returns `false` if the run has a proof already.
+    async fn store_nova_proof(
+        &self,
+        program_id: &str,
+        first_block: BlockNumber,
+        last_block: BlockNumber,
+        steps: i64,
+        proof: Value,
+    ) -> anyhow::Result<bool>;
+
+    /// Returns the Nova proof of the run of `program_id`.
+    async fn load_nova_proof(&self, program_id: &str) -> anyhow::Result<Option<StoredNovaProof>>;
 
-    async fn load_proof(
+    /// Marks the proof for a block as invalid and returns its job to the queue,
+    /// to be proven again after `retry_after`, or dead-letters the job if `retry_after` is `None`.
+    async fn reject_proof(
         &self,
-        connection: &mut StorageProcessor<'_>,
         block_number: BlockNumber,
-    ) -> anyhow::Result<Option<StoredProof>>;
+        retry_after: Option<Duration>,
+    ) -> anyhow::Result<bool>;
 
+    /// Stores the proof of the job leased by `prover_name` under the first block of the job
+    /// and marks the job as done. Returns `false` if `prover_name` doesn't hold the lease.
     async fn store_proof(
         &self,
-        connection: &mut StorageProcessor<'_>,
-        job_id: i32,
-        block_number: BlockNumber,
+        job_id: i64,
+        prover_name: &str,
         proof: String,
+    ) -> anyhow::Result<bool>;
+
+    /// Stores the witness of the blocks from `first_block` to `last_block` inclusive,
+    /// adds the job proving them and moves the last witness block number past them,
+    /// all at once. The witness and the job are recorded with the circuit `circuit_id`.
+    /// The witness is written to the blob store before it is recorded.
+    async fn store_witness_job(
+        &self,
+        first_block: BlockNumber,
+        last_block: BlockNumber,
+        witness: Vec<u8>,
+        object_job: String,
+        circuit_id: i64,
     ) -> anyhow::Result<()>;
 
-    async fn store_witness(
+    /// Leases the next idle job of the circuit `circuit_id` to `prover_name`.
+    async fn get_idle_prover_job_from_job_queue(
+        &self,
+        prover_name: &str,
+        circuit_id: i64,
+    ) -> anyhow::Result<Option<StorageProverJobQueueCloud>>;
+
+    /// Returns the job `job_id` of the queue.
+    async fn load_prover_job(
+        &self,
+        job_id: i64,
+    ) -> anyhow::Result<Option<StorageProverJobQueueCloud>>;
+
+    /// Extends the lease of `prover_name` on the job, returns `false` if the lease is lost.
+    async fn record_prover_is_working(
         &self,
-        connection: &mut StorageProcessor<'_>,
+        job_id: i64,
+        prover_name: &str,
+    ) -> anyhow::Result<bool>;
+
+    /// Returns the job leased by `prover_name` to the queue, it can be leased again after `delay`.
+    async fn release_prover_job(
+        &self,
+        job_id: i64,
+        prover_name: &str,
+        delay: Duration,
+    ) -> anyhow::Result<bool>;
+
+    /// Moves the job leased by `prover_name` to the dead letter queue.
+    async fn fail_prover_job(&self, job_id: i64, prover_name: &str) -> anyhow::Result<bool>;
+
+    /// Counts a failed attempt of `stage` on the batch starting at `block`,
+    /// which is dead-lettered after `max_attempts` attempts.
+    async fn record_block_failure(
+        &self,
+        stage: PipelineStage,
         block: BlockNumber,
-        witness: String,
-    ) -> anyhow::Result<()>;
+        error: &str,
+        max_attempts: u32,
+    ) -> anyhow::Result<StorageBlockFailure>;
+
+    /// Returns the failures of `stage` on the batch starting at `block`.
+    async fn load_block_failure(
+        &self,
+        stage: PipelineStage,
+        block: BlockNumber,
+    ) -> anyhow::Result<Option<StorageBlockFailure>>;
+
+    /// Returns to the queue the jobs whose provers are gone.
+    async fn mark_stale_jobs_as_idle(&self, gone_timeout: Duration) -> anyhow::Result<u64>;
+
+    /// Returns the amount of prover jobs that are not done yet.
+    async fn pending_jobs_count(&self) -> anyhow::Result<u32>;
+
+    /// Returns the amount of traces that have no witness yet.
+    async fn pending_witness_traces_count(&self) -> anyhow::Result<u32>;
+
+    /// Returns the amount of traces covered by the prover jobs that are not done yet.
+    async fn pending_proof_blocks_count(&self) -> anyhow::Result<u32>;
+
+    /// Returns the block of the last stored trace, 0 if there is none.
+    async fn load_last_trace_block_number(&self) -> anyhow::Result<i64>;
+
+    /// Registers the circuit with the program hash `program_hash`, or returns it if it is registered already.
+    /// Fails if it is registered with another ABI, verification key or verifier address.
+    async fn register_circuit(
+        &self,
+        program_hash: &str,
+        abi: Value,
+        verification_key: Value,
+        verifier_address: Option<&str>,
+    ) -> anyhow::Result<StorageCircuit>;
+
+    /// Returns the circuit registered with the program hash `program_hash`.
+    async fn load_circuit_by_hash(&self, program_hash: &str)
+        -> anyhow::Result<Option<StorageCircuit>>;
 }
@@ -0,0 +1,65 @@
+//! Errors of the witness generator and the verifier generator, and the retry policy applied to them.
+
+// Workspace deps
+use config::configs::prover::Retry;
+use storage::prover::PipelineStage;
+use types::BlockNumber;
+// Local deps
+use crate::database_interface::DatabaseInterface;
+
+#[derive(Debug, thiserror::Error)]
+pub enum PipelineError {
+    #[error("database access failed: {0}")]
+    Database(#[from] anyhow::Error),
+    #[error("witness computation failed: {0}")]
+    Witness(String),
+    #[error("malformed proof: {0}")]
+    MalformedProof(String),
+    #[error("proof starts from state {pre}, but the previous proof ends with {post}")]
+    BrokenChain { pre: String, post: String },
+    #[error("local verification failed: {0}")]
+    LocalVerification(String),
+    #[error("proof submission failed: {0}")]
+    Submission(String),
+    #[error("proof was rejected")]
+    Rejected,
+    #[error("proof was made with circuit {found}, but the verifier checks circuit {expected}")]
+    CircuitMismatch { expected: i64, found: i64 },
+}
+
+/// Records that `stage` failed on the batch starting at `block`.
+/// Returns the delay before the batch is retried, or `None` if it has been dead-lettered.
+pub async fn handle_failure<DB: DatabaseInterface>(
+    database: &DB,
+    retry: &Retry,
+    stage: PipelineStage,
+    block: BlockNumber,
+    error: &PipelineError,
+) -> Option<std::time::Duration> {
+    vlog::warn!("{:?} of block {} failed: {}", stage, *block, error);
+    metrics::increment_counter!("pipeline.failures", "stage" => stage.to_string());
+
+    let failure = match database
+        .record_block_failure(stage, block, &error.to_string(), retry.max_attempts)
+        .await
+    {
+        Ok(failure) => failure,
+        Err(e) => {
+            vlog::warn!("Failed to record the failure of block {}: {}", *block, e);
+            return Some(retry.backoff(1));
+        }
+    };
+
+    if failure.f_dead {
+        vlog::error!(
+            "{:?} of block {} failed {} times, moving it to the dead letter queue",
+            stage,
+            *block,
+            failure.f_attempts
+        );
+        metrics::increment_counter!("pipeline.dead_letters", "stage" => stage.to_string());
+        None
+    } else {
+        Some(retry.backoff(failure.f_attempts as u32))
+    }
+}
@@ -1,4 +1,6 @@
 // Built-in
+use std::convert::TryFrom;
+use std::path::Path;
 use std::sync::Arc;
 use std::thread;
 use std::time::Duration;
@@ -16,29 +18,39 @@ use jsonwebtoken::{decode, DecodingKey, Validation};
 use serde::{Deserialize, Serialize};
 use tokio::sync::RwLock;
 // Workspace deps
+use circuit::batch::{BatchInput, TraceRecordType};
 use config::ProverConfig;
+use config::configs::prover::{Circuit, Retry};
 // Local deps
 use self::database_interface::DatabaseInterface;
+use self::prover_api::{
+    PayloadAuthToken, ProverFailure, ProverInputRequest, PublishProof, WorkingOn, JOB_HEADER,
+};
 use self::scaler::ScalerOracle;
+use self::traces::{ImportError, ImportTraces};
 use tokio::task::JoinHandle;
 use types::BlockNumber;
 use utils::panic_notify::{spawn_panic_handler, ThreadPanicNotify};
 use config::configs::api::ProverApiConfig;
 use config::configs::verifier::VerifierConfig;
+use zokrates_common::helpers::BackendParameter;
 
 pub mod database;
 pub mod database_interface;
+pub mod error;
+pub mod memory_database;
+pub mod pipeline_metrics;
+pub mod prover_api;
+pub mod registry;
+pub mod settlement;
+pub mod traces;
 pub mod verifier_generator;
+pub mod witness_generator;
+pub mod witness_store;
 mod scaler;
-mod witness_generator;
-
-#[derive(Debug, Serialize, Deserialize)]
-struct PayloadAuthToken {
-    /// Subject (whom auth token refers to).
-    sub: String,
-    /// Expiration time (as UTC timestamp).
-    exp: usize,
-}
+
+/// Maximum size of the JSON requests, large enough for the trace imports.
+const MAX_PAYLOAD_SIZE: usize = 64 * 1024 * 1024;
 
 pub struct CreateBlockProof {
     pub block_number: BlockNumber,
@@ -50,28 +62,36 @@ struct AppState<DB: DatabaseInterface> {
     secret_auth: String,
     database: DB,
     scaler_oracle: Arc<RwLock<ScalerOracle<DB>>>,
+    retry: Retry,
+    trace_record_type: TraceRecordType,
+    /// Traces proven together, the imports have to fill whole batches.
+    batch_size: u32,
 }
 
 impl<DB: DatabaseInterface> AppState<DB> {
-    pub fn new(secret_auth: String, database: DB, idle_provers: u32) -> Self {
+    pub fn new(
+        secret_auth: String,
+        database: DB,
+        idle_provers: u32,
+        batch_size: u32,
+        retry: Retry,
+        trace_record_type: TraceRecordType,
+    ) -> Self {
         let scaler_oracle = Arc::new(RwLock::new(ScalerOracle::new(
             database.clone(),
             idle_provers,
+            batch_size,
         )));
 
         Self {
             secret_auth,
             database,
             scaler_oracle,
+            retry,
+            trace_record_type,
+            batch_size,
         }
     }
-
-    async fn access_storage(&self) -> actix_web::Result<storage::StorageProcessor<'_>> {
-        self.database.acquire_connection().await.map_err(|e| {
-            vlog::warn!("Failed to access storage: {}", e);
-            actix_web::error::ErrorInternalServerError(e)
-        })
-    }
 }
 
 /// The structure that stores the secret key for checking JsonWebToken matching.
@@ -126,6 +146,128 @@ pub struct RequiredReplicasOutput {
     needed_count: u32,
 }
 
+/// Periodically returns to the queue the jobs of the provers that stopped sending heartbeats.
+async fn clean_stale_jobs<DB: DatabaseInterface>(database: DB, gone_timeout: Duration) {
+    let mut timer = tokio::time::interval(gone_timeout);
+    loop {
+        timer.tick().await;
+
+        match database.mark_stale_jobs_as_idle(gone_timeout).await {
+            Ok(0) => {}
+            Ok(jobs) => vlog::warn!("Returned {} jobs of gone provers to the queue", jobs),
+            Err(e) => vlog::warn!("Failed to clean up stale prover jobs: {}", e),
+        }
+    }
+}
+
+async fn required_replicas<DB: DatabaseInterface>(
+    data: web::Data<AppState<DB>>,
+    input: web::Json<RequiredReplicasInput>,
+) -> actix_web::Result<HttpResponse> {
+    let mut oracle = data.scaler_oracle.write().await;
+
+    let needed_count = oracle.provers_required().await.map_err(|e| {
+        vlog::warn!("Failed to compute the required amount of provers: {}", e);
+        actix_web::error::ErrorInternalServerError(e)
+    })?;
+    vlog::info!(
+        "Provers required: {} (currently running: {})",
+        needed_count,
+        input.current_count
+    );
+
+    let response = RequiredReplicasOutput { needed_count };
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+/// Converts an error of a prover API request into the 500 response.
+fn internal_error(e: anyhow::Error) -> actix_web::Error {
+    vlog::warn!("Prover API request failed: {}", e);
+    actix_web::error::ErrorInternalServerError(e)
+}
+
+async fn get_job<DB: DatabaseInterface>(
+    data: web::Data<AppState<DB>>,
+    input: web::Json<ProverInputRequest>,
+) -> actix_web::Result<HttpResponse> {
+    let mut job = match prover_api::get_job(&data.database, &data.retry, &input)
+        .await
+        .map_err(internal_error)?
+    {
+        Some(job) => job,
+        None => return Ok(HttpResponse::NoContent().finish()),
+    };
+
+    // The witness is sent gzipped as it is stored, the job along with it in a header.
+    let witness = std::mem::take(&mut job.witness);
+    let header = serde_json::to_string(&job).map_err(actix_web::error::ErrorInternalServerError)?;
+    Ok(HttpResponse::Ok()
+        .content_type("application/gzip")
+        .insert_header((JOB_HEADER, header))
+        .body(witness))
+}
+
+async fn working_on<DB: DatabaseInterface>(
+    data: web::Data<AppState<DB>>,
+    input: web::Json<WorkingOn>,
+) -> actix_web::Result<HttpResponse> {
+    let leased = prover_api::working_on(&data.database, &input)
+        .await
+        .map_err(internal_error)?;
+
+    Ok(HttpResponse::Ok().json(leased))
+}
+
+async fn publish<DB: DatabaseInterface>(
+    data: web::Data<AppState<DB>>,
+    input: web::Json<PublishProof>,
+) -> actix_web::Result<HttpResponse> {
+    let stored = prover_api::publish(&data.database, input.into_inner())
+        .await
+        .map_err(internal_error)?;
+
+    if stored {
+        Ok(HttpResponse::Ok().finish())
+    } else {
+        Ok(HttpResponse::Conflict().finish())
+    }
+}
+
+async fn failure<DB: DatabaseInterface>(
+    data: web::Data<AppState<DB>>,
+    input: web::Json<ProverFailure>,
+) -> actix_web::Result<HttpResponse> {
+    let recorded = prover_api::report_failure(&data.database, &data.retry, &input)
+        .await
+        .map_err(internal_error)?;
+
+    if recorded {
+        Ok(HttpResponse::Ok().finish())
+    } else {
+        Ok(HttpResponse::Conflict().finish())
+    }
+}
+
+async fn import_traces<DB: DatabaseInterface>(
+    data: web::Data<AppState<DB>>,
+    input: web::Json<ImportTraces>,
+) -> actix_web::Result<HttpResponse> {
+    let imported = traces::import_traces(
+        &data.database,
+        &data.trace_record_type,
+        data.batch_size,
+        input.into_inner(),
+    )
+    .await
+    .map_err(|e| match e {
+        ImportError::Database(e) => internal_error(e),
+        e => actix_web::error::ErrorBadRequest(e),
+    })?;
+
+    Ok(HttpResponse::Ok().json(imported))
+}
+
 pub fn run_prover_server<DB: DatabaseInterface>(
     database: DB,
     prover_api_opts: ProverApiConfig,
@@ -133,6 +275,8 @@ pub fn run_prover_server<DB: DatabaseInterface>(
 ) -> JoinHandle<()> {
     let witness_generator_opts = prover_opts.witness_generator;
     let core_opts = prover_opts.core;
+    let circuit_opts = prover_opts.circuit;
+    let retry = prover_opts.retry;
     let (handler, panic_sender) = spawn_panic_handler();
 
     thread::Builder::new()
@@ -143,22 +287,40 @@ pub fn run_prover_server<DB: DatabaseInterface>(
 
             actix_runtime.block_on(async move {
 
-                let last_witness_block = {
-                    let mut storage = database
-                        .acquire_connection()
-                        .await
-                        .expect("Failed to access storage");
-
-                    database
-                        .load_last_witness_block_number(&mut storage)
-                        .await
-                        .expect("Failed to get last witness block number")
-                        as usize
-                };
+                let last_witness_block = database
+                    .load_last_witness_block_number()
+                    .await
+                    .expect("Failed to get last witness block number")
+                    as usize;
+
+                let circuit_input =
+                    BatchInput::from_abi(Path::new(&circuit_opts.abi_file_path))
+                        .expect("Failed to read the circuit ABI");
+                let trace_record_type =
+                    TraceRecordType::from_abi(Path::new(&circuit_opts.abi_file_path))
+                        .expect("Failed to read the circuit ABI");
+                assert_eq!(
+                    circuit_input.batch_size(),
+                    circuit_opts.batch_size,
+                    "The circuit at {} takes {} trace records, generate it with `server compile-circuit --batch-size {}`",
+                    circuit_opts.file_path,
+                    circuit_input.batch_size(),
+                    circuit_opts.batch_size
+                );
+                assert_eq!(
+                    circuit_input.max_leaves(),
+                    circuit_opts.offline_max_leaves,
+                    "CIRCUIT_OFFLINE_MAX_LEAVES has to be the maximum number of leaves of the circuit at {} if it is an offline memory circuit, and unset otherwise",
+                    circuit_opts.file_path
+                );
+
+                let circuit = registry::register_circuit(&database, &circuit_opts, None)
+                    .await
+                    .expect("Failed to register the circuit");
 
                 // Start pool maintainer threads.
                 let start_block = last_witness_block as u32;
-                let block_step = 1;
+                let block_step = circuit_opts.batch_size;
                 vlog::info!(
                         "Starting witness generator ({},{})",
                         start_block,
@@ -169,14 +331,27 @@ pub fn run_prover_server<DB: DatabaseInterface>(
                     witness_generator_opts.prepare_data_interval(),
                     BlockNumber(start_block),
                     BlockNumber(block_step),
+                    circuit_opts,
+                    circuit_input,
+                    circuit.f_id,
+                    retry.clone(),
                 );
                 pool_maintainer.start(panic_sender.clone());
+                // Return the jobs of gone provers to the queue.
+                actix_rt::spawn(clean_stale_jobs(database.clone(), core_opts.gone_timeout()));
                 // Start HTTP server.
                 let secret_auth = prover_api_opts.secret_auth.clone();
                 let idle_provers = core_opts.idle_provers;
+                let batch_size = circuit_input.batch_size();
                 HttpServer::new(move || {
-                    let app_state =
-                        AppState::new(secret_auth.clone(), database.clone(), idle_provers);
+                    let app_state = AppState::new(
+                        secret_auth.clone(),
+                        database.clone(),
+                        idle_provers,
+                        batch_size,
+                        retry.clone(),
+                        trace_record_type.clone(),
+                    );
 
                     let auth = HttpAuthentication::bearer(move |req, credentials| async {
                         let secret_auth = req
@@ -194,7 +369,20 @@ pub fn run_prover_server<DB: DatabaseInterface>(
                     App::new()
                         .wrap(auth)
                         .app_data(web::Data::new(app_state))
+                        .app_data(web::JsonConfig::default().limit(MAX_PAYLOAD_SIZE))
                         .route("/status", web::get().to(status))
+                        .route("/scaler/replicas", web::post().to(required_replicas::<DB>))
+                        .service(
+                            web::scope("/api/internal/prover")
+                                .route("/get_job", web::post().to(get_job::<DB>))
+                                .route("/working_on", web::post().to(working_on::<DB>))
+                                .route("/publish", web::post().to(publish::<DB>))
+                                .route("/failure", web::post().to(failure::<DB>)),
+                        )
+                        .service(
+                            web::scope("/api/internal/traces")
+                                .route("/import", web::post().to(import_traces::<DB>)),
+                        )
                 })
                     .bind(&prover_api_opts.bind_addr())
                     .expect("failed to bind")
@@ -211,6 +399,8 @@ pub fn run_prover_server<DB: DatabaseInterface>(
 pub fn run_verifier_server<DB: DatabaseInterface>(
     database: DB,
     verifier_opts: VerifierConfig,
+    circuit_opts: Circuit,
+    retry: Retry,
 ) -> JoinHandle<()> {
     let (handler, panic_sender) = spawn_panic_handler();
 
@@ -222,22 +412,23 @@ pub fn run_verifier_server<DB: DatabaseInterface>(
 
             actix_runtime.block_on(async move {
 
-                let last_verified_proof_block = {
-                    let mut storage = database
-                        .acquire_connection()
-                        .await
-                        .expect("Failed to access storage");
-
-                    database
-                        .load_last_verified_proof_block_number(&mut storage)
-                        .await
-                        .expect("Failed to get last witness block number")
-                        as usize
-                };
+                let last_verified_proof_block = database
+                    .load_last_verified_proof_block_number()
+                    .await
+                    .expect("Failed to get last witness block number")
+                    as usize;
+
+                let circuit = registry::register_circuit(
+                    &database,
+                    &circuit_opts,
+                    Some(&verifier_opts.contract_address),
+                )
+                    .await
+                    .expect("Failed to register the circuit");
 
                 // Start pool maintainer threads.
                 let start_block = last_verified_proof_block as u32;
-                let block_step = 1;
+                let block_step = circuit_opts.batch_size;
                 vlog::info!(
                         "Starting witness generator ({},{})",
                         start_block,
@@ -246,16 +437,28 @@ pub fn run_verifier_server<DB: DatabaseInterface>(
 
                 println!("{:?}",verifier_opts.chain_url);
 
+                // The mock settlement checks the proofs with its own copy of the backend.
+                let parse_backend = || {
+                    BackendParameter::try_from(verifier_opts.backend.as_str())
+                        .expect("Unknown verifier backend")
+                };
+                let backend = parse_backend();
+                let settlement = settlement::from_config(
+                    &verifier_opts,
+                    circuit.f_verification_key.clone(),
+                    parse_backend(),
+                );
+
                 let pool_maintainer = verifier_generator::VerifierGenerator::new(
                     database.clone(),
                     Duration::from_millis(500),
                     BlockNumber(start_block),
                     BlockNumber(block_step),
-                    verifier_opts.chain_url,
-                    verifier_opts.contract_address,
-                    verifier_opts.abi_path,
-                    verifier_opts.account
-
+                    verifier_opts.aggregation_size.unwrap_or(1),
+                    settlement,
+                    circuit,
+                    backend,
+                    retry,
                 );
                 pool_maintainer.start(panic_sender.clone());
                 // Start HTTP server.
@@ -0,0 +1,925 @@
+//! In-memory implementation of `DatabaseInterface`.
+//!
+//! It mirrors the behaviour of the queries in `storage::prover` closely enough to run
+//! the witness generator, the provers and the verifier generator together without Postgres.
+
+// Built-in
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+// External
+use chrono::Utc;
+use serde_json::Value;
+// Workspace uses
+use blob_store::MemoryBlobStore;
+use storage::prover::records::{
+    NewTrace, StorageBlockFailure, StorageBlockWitnessCloud, StorageCircuit, StorageNovaRun,
+    StorageProverJobQueueCloud, StorageTrace, StoredAggregatedProof, StoredNovaProof, StoredProof,
+};
+use storage::prover::{PipelineStage, ProverJobStatus, ProverJobType};
+use types::BlockNumber;
+// Local uses
+use crate::witness_store::WitnessStore;
+use crate::DatabaseInterface;
+
+#[derive(Debug)]
+struct Tables {
+    /// Traces by their block, the blocks start with 1 like `f_traces.f_block`.
+    traces: BTreeMap<i64, Value>,
+    /// Blocks of the traces by their program id, segment and step.
+    trace_positions: HashMap<(String, i64, i64), i64>,
+    /// Witness records by their block, the witnesses themselves are in the blob store.
+    witnesses: HashMap<i64, StorageBlockWitnessCloud>,
+    jobs: Vec<StorageProverJobQueueCloud>,
+    proofs: Vec<StoredProof>,
+    aggregated_proofs: Vec<StoredAggregatedProof>,
+    nova_proofs: Vec<StoredNovaProof>,
+    /// Leases of the runs proven in the Nova mode, by their program id.
+    nova_runs: HashMap<String, StorageNovaRun>,
+    /// Failures by their stage and first block, like the unique key of `t_block_failures`.
+    failures: HashMap<(String, i64), StorageBlockFailure>,
+    circuits: Vec<StorageCircuit>,
+    last_proof_block: Option<i64>,
+    last_witness_block: Option<i64>,
+    last_verified_proof_block: Option<i64>,
+}
+
+/// Database wrapper keeping all the tables in memory, for tests.
+#[derive(Debug, Clone)]
+pub struct InMemoryDatabase {
+    tables: Arc<Mutex<Tables>>,
+    blobs: MemoryBlobStore,
+}
+
+impl Default for InMemoryDatabase {
+    fn default() -> Self {
+        let tables = Tables {
+            traces: BTreeMap::new(),
+            trace_positions: HashMap::new(),
+            witnesses: HashMap::new(),
+            jobs: Vec::new(),
+            proofs: Vec::new(),
+            aggregated_proofs: Vec::new(),
+            nova_proofs: Vec::new(),
+            nova_runs: HashMap::new(),
+            failures: HashMap::new(),
+            circuits: Vec::new(),
+            last_proof_block: None,
+            last_witness_block: None,
+            last_verified_proof_block: None,
+        };
+
+        Self {
+            tables: Arc::new(Mutex::new(tables)),
+            blobs: MemoryBlobStore::new(),
+        }
+    }
+}
+
+impl InMemoryDatabase {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends a trace record as a step of the legacy run, like the records inserted without
+    /// a position, returns the block it is stored under.
+    pub fn add_trace(&self, trace: Value) -> BlockNumber {
+        let mut tables = self.tables();
+        let id = tables.traces.keys().next_back().map_or(1, |id| id + 1);
+        tables.traces.insert(id, trace);
+        tables
+            .trace_positions
+            .insert(("legacy".to_string(), 0, id - 1), id);
+
+        BlockNumber(id as u32)
+    }
+
+    /// Returns a copy of the job queue.
+    pub fn jobs(&self) -> Vec<StorageProverJobQueueCloud> {
+        self.tables().jobs.clone()
+    }
+
+    /// Returns a copy of the proofs submitted together.
+    pub fn aggregated_proofs(&self) -> Vec<StoredAggregatedProof> {
+        self.tables().aggregated_proofs.clone()
+    }
+
+    /// Returns the blob store holding the witnesses.
+    pub fn blob_store(&self) -> MemoryBlobStore {
+        self.blobs.clone()
+    }
+
+    fn witness_store(&self) -> WitnessStore {
+        WitnessStore::new(Arc::new(self.blobs.clone()))
+    }
+
+    fn tables(&self) -> MutexGuard<'_, Tables> {
+        self.tables.lock().expect("in-memory database is poisoned")
+    }
+}
+
+#[async_trait::async_trait]
+impl DatabaseInterface for InMemoryDatabase {
+    async fn load_last_proof_block_number(&self) -> anyhow::Result<i64> {
+        Ok(self.tables().last_proof_block.unwrap_or(1))
+    }
+
+    async fn update_last_proof_block_number(
+        &self,
+        block_number: BlockNumber,
+    ) -> anyhow::Result<()> {
+        self.tables().last_proof_block = Some(i64::from(*block_number));
+
+        Ok(())
+    }
+
+    async fn load_last_witness_block_number(&self) -> anyhow::Result<i64> {
+        Ok(self.tables().last_witness_block.unwrap_or(1))
+    }
+
+    async fn update_last_witness_block_number(
+        &self,
+        block_number: BlockNumber,
+    ) -> anyhow::Result<()> {
+        self.tables().last_witness_block = Some(i64::from(*block_number));
+
+        Ok(())
+    }
+
+    async fn load_last_verified_proof_block_number(&self) -> anyhow::Result<i64> {
+        Ok(self.tables().last_verified_proof_block.unwrap_or(0))
+    }
+
+    async fn update_last_verified_proof_block_number(
+        &self,
+        block_number: BlockNumber,
+    ) -> anyhow::Result<()> {
+        self.tables().last_verified_proof_block = Some(i64::from(*block_number));
+
+        Ok(())
+    }
+
+    async fn load_trace(&self, block_number: BlockNumber) -> anyhow::Result<Option<String>> {
+        let tables = self.tables();
+        let trace = tables.traces.get(&i64::from(*block_number));
+
+        Ok(trace.map(Value::to_string))
+    }
+
+    async fn load_traces(
+        &self,
+        first_block: BlockNumber,
+        count: u32,
+    ) -> anyhow::Result<Option<Vec<Value>>> {
+        let tables = self.tables();
+        let first = i64::from(*first_block);
+        let traces: Vec<_> = tables
+            .traces
+            .range(first..first + i64::from(count))
+            .map(|(_, trace)| trace.clone())
+            .collect();
+
+        if traces.len() < count as usize {
+            return Ok(None);
+        }
+        Ok(Some(traces))
+    }
+
+    async fn store_traces(
+        &self,
+        program_id: &str,
+        traces: Vec<NewTrace>,
+    ) -> anyhow::Result<(BlockNumber, BlockNumber)> {
+        if traces.is_empty() {
+            anyhow::bail!("No traces to store");
+        }
+        let mut tables = self.tables();
+        let positions: Vec<_> = traces
+            .iter()
+            .map(|trace| (program_id.to_string(), trace.segment, trace.step))
+            .collect();
+        let unique: HashSet<_> = positions.iter().collect();
+        if unique.len() < positions.len()
+            || positions
+                .iter()
+                .any(|position| tables.trace_positions.contains_key(position))
+        {
+            anyhow::bail!("Traces of {} are already stored", program_id);
+        }
+
+        let first_block = tables.traces.keys().next_back().map_or(1, |block| block + 1);
+        let count = traces.len() as i64;
+        for (block, trace) in (first_block..).zip(traces) {
+            tables.traces.insert(block, trace.trace);
+        }
+        tables
+            .trace_positions
+            .extend(positions.into_iter().zip(first_block..));
+
+        Ok((
+            BlockNumber(first_block as u32),
+            BlockNumber((first_block + count - 1) as u32),
+        ))
+    }
+
+    async fn load_witness(&self, block_number: BlockNumber) -> anyhow::Result<Option<Vec<u8>>> {
+        let record = self
+            .tables()
+            .witnesses
+            .get(&i64::from(*block_number))
+            .cloned();
+        match record {
+            Some(record) => Ok(Some(self.witness_store().load(record).await?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn load_compressed_witness(
+        &self,
+        block_number: BlockNumber,
+    ) -> anyhow::Result<Option<(Vec<u8>, String)>> {
+        let record = self
+            .tables()
+            .witnesses
+            .get(&i64::from(*block_number))
+            .cloned();
+        match record {
+            Some(record) => Ok(Some(self.witness_store().load_compressed(record).await?)),
+            None => Ok(None),
+        }
+    }
+
+    async fn load_proof(&self, block_number: BlockNumber) -> anyhow::Result<Option<StoredProof>> {
+        let tables = self.tables();
+        let proof = tables
+            .proofs
+            .iter()
+            .rev()
+            .find(|proof| proof.f_block_number == i64::from(*block_number) && !proof.f_invalid)
+            .map(|proof| StoredProof {
+                f_id: proof.f_id,
+                f_block_number: proof.f_block_number,
+                f_proof: proof.f_proof.clone(),
+                f_created_at: proof.f_created_at,
+                f_circuit_id: proof.f_circuit_id,
+                f_invalid: proof.f_invalid,
+            });
+
+        Ok(proof)
+    }
+
+    async fn store_aggregated_proof(
+        &self,
+        first_block: BlockNumber,
+        last_block: BlockNumber,
+        proof_ids: Vec<i64>,
+    ) -> anyhow::Result<StoredAggregatedProof> {
+        let mut tables = self.tables();
+        let aggregated_proof = StoredAggregatedProof {
+            f_id: tables.aggregated_proofs.len() as i64 + 1,
+            f_first_block: i64::from(*first_block),
+            f_last_block: i64::from(*last_block),
+            f_proof_ids: proof_ids,
+            f_created_at: Utc::now(),
+        };
+        tables.aggregated_proofs.push(aggregated_proof.clone());
+
+        Ok(aggregated_proof)
+    }
+
+    async fn lease_next_unproven_run(
+        &self,
+        prover_name: &str,
+        gone_timeout: Duration,
+    ) -> anyhow::Result<Option<StorageNovaRun>> {
+        let now = Utc::now();
+        let deadline = now - chrono::Duration::from_std(gone_timeout)?;
+        let mut tables = self.tables();
+        let tables = &mut *tables;
+        let mut exited: Vec<_> = tables
+            .trace_positions
+            .iter()
+            .filter(|((program_id, _, _), block)| {
+                let leasable = match tables.nova_runs.get(program_id) {
+                    None => true,
+                    Some(run) if run.f_status == ProverJobStatus::Idle.to_number() => {
+                        run.f_updated_at <= now
+                    }
+                    Some(run) if run.f_status == ProverJobStatus::InProgress.to_number() => {
+                        run.f_updated_at < deadline
+                    }
+                    Some(_) => false,
+                };
+                leasable
+                    && tables.traces[block]["newExited"] == Value::Bool(true)
+                    && !tables
+                        .nova_proofs
+                        .iter()
+                        .any(|proof| &proof.f_program_id == program_id)
+            })
+            .map(|((program_id, _, _), block)| (*block, program_id.clone()))
+            .collect();
+        exited.sort();
+
+        let program_id = match exited.into_iter().next() {
+            Some((_, program_id)) => program_id,
+            None => return Ok(None),
+        };
+        let run = tables
+            .nova_runs
+            .entry(program_id.clone())
+            .or_insert_with(|| StorageNovaRun {
+                f_program_id: program_id,
+                f_status: ProverJobStatus::Idle.to_number(),
+                f_updated_by: String::new(),
+                f_updated_at: now,
+                f_attempts: 0,
+                f_last_error: None,
+            });
+        run.f_status = ProverJobStatus::InProgress.to_number();
+        run.f_updated_by = prover_name.to_string();
+        run.f_updated_at = now;
+
+        Ok(Some(run.clone()))
+    }
+
+    async fn record_nova_run_is_working(
+        &self,
+        program_id: &str,
+        prover_name: &str,
+    ) -> anyhow::Result<bool> {
+        let mut tables = self.tables();
+        let run = tables.nova_runs.get_mut(program_id).filter(|run| {
+            run.f_updated_by == prover_name
+                && run.f_status == ProverJobStatus::InProgress.to_number()
+        });
+
+        Ok(run.map(|run| run.f_updated_at = Utc::now()).is_some())
+    }
+
+    async fn fail_nova_run(
+        &self,
+        program_id: &str,
+        prover_name: &str,
+        error: &str,
+        max_attempts: u32,
+        retry_after: Duration,
+    ) -> anyhow::Result<Option<StorageNovaRun>> {
+        let available_at = Utc::now() + chrono::Duration::from_std(retry_after)?;
+        let mut tables = self.tables();
+        let run = match tables.nova_runs.get_mut(program_id).filter(|run| {
+            run.f_updated_by == prover_name
+                && run.f_status == ProverJobStatus::InProgress.to_number()
+        }) {
+            Some(run) => run,
+            None => return Ok(None),
+        };
+        run.f_attempts += 1;
+        run.f_last_error = Some(error.to_string());
+        run.f_status = if run.f_attempts >= max_attempts as i32 {
+            ProverJobStatus::Failed.to_number()
+        } else {
+            ProverJobStatus::Idle.to_number()
+        };
+        run.f_updated_by = "server_release_run".to_string();
+        run.f_updated_at = available_at;
+
+        Ok(Some(run.clone()))
+    }
+
+    async fn load_program_traces(&self, program_id: &str) -> anyhow::Result<Vec<StorageTrace>> {
+        let tables = self.tables();
+        let mut traces: Vec<_> = tables
+            .trace_positions
+            .iter()
+            .filter(|((id, _, _), _)| id == program_id)
+            .map(|((_, segment, step), block)| StorageTrace {
+                f_id: *block,
+                f_trace: tables.traces[block].clone(),
+                f_created_at: Utc::now(),
+                f_block: *block,
+                f_program_id: program_id.to_string(),
+                f_segment: *segment,
+                f_step: *step,
+            })
+            .collect();
+        traces.sort_by_key(|trace| (trace.f_segment, trace.f_step));
+
+        Ok(traces)
+    }
+
+    async fn store_nova_proof(
+        &self,
+        program_id: &str,
+        first_block: BlockNumber,
+        last_block: BlockNumber,
+        steps: i64,
+        proof: Value,
+    ) -> anyhow::Result<bool> {
+        let mut tables = self.tables();
+        if tables
+            .nova_proofs
+            .iter()
+            .any(|proof| proof.f_program_id == program_id)
+        {
+            return Ok(false);
+        }
+        let nova_proof = StoredNovaProof {
+            f_id: tables.nova_proofs.len() as i64 + 1,
+            f_program_id: program_id.to_string(),
+            f_first_block: i64::from(*first_block),
+            f_last_block: i64::from(*last_block),
+            f_steps: steps,
+            f_proof: proof,
+            f_created_at: Utc::now(),
+        };
+        tables.nova_proofs.push(nova_proof);
+
+        Ok(true)
+    }
+
+    async fn load_nova_proof(&self, program_id: &str) -> anyhow::Result<Option<StoredNovaProof>> {
+        let proof = self
+            .tables()
+            .nova_proofs
+            .iter()
+            .find(|proof| proof.f_program_id == program_id)
+            .cloned();
+
+        Ok(proof)
+    }
+
+    async fn reject_proof(
+        &self,
+        block_number: BlockNumber,
+        retry_after: Option<Duration>,
+    ) -> anyhow::Result<bool> {
+        let block = i64::from(*block_number);
+        let available_at = Utc::now() + chrono::Duration::from_std(retry_after.unwrap_or_default())?;
+        let mut tables = self.tables();
+        let mut rejected = false;
+        for proof in tables
+            .proofs
+            .iter_mut()
+            .filter(|proof| proof.f_block_number == block && !proof.f_invalid)
+        {
+            proof.f_invalid = true;
+            rejected = true;
+        }
+
+        let status = match retry_after {
+            Some(_) => ProverJobStatus::Idle,
+            None => ProverJobStatus::Failed,
+        };
+        for job in tables.jobs.iter_mut().filter(|job| {
+            job.f_first_block == block && job.f_job_status == ProverJobStatus::Done.to_number()
+        }) {
+            job.f_job_status = status.to_number();
+            job.f_updated_by = "server_reject_proof".to_string();
+            job.f_updated_at = available_at;
+        }
+
+        Ok(rejected)
+    }
+
+    async fn store_proof(
+        &self,
+        job_id: i64,
+        prover_name: &str,
+        proof: String,
+    ) -> anyhow::Result<bool> {
+        let proof = serde_json::from_str(&proof)?;
+        let mut tables = self.tables();
+        let job = match tables.jobs.iter_mut().find(|job| {
+            job.f_id == job_id
+                && job.f_job_type == ProverJobType::SingleProof.to_string()
+                && job.f_updated_by == prover_name
+                && job.f_job_status == ProverJobStatus::InProgress.to_number()
+        }) {
+            Some(job) => job,
+            None => return Ok(false),
+        };
+        job.f_job_status = ProverJobStatus::Done.to_number();
+        job.f_updated_by = "server_finish_job".to_string();
+        job.f_updated_at = Utc::now();
+        let (first_block, circuit_id) = (job.f_first_block, job.f_circuit_id);
+
+        let f_id = tables.proofs.len() as i64 + 1;
+        tables.proofs.push(StoredProof {
+            f_id,
+            f_block_number: first_block,
+            f_proof: proof,
+            f_created_at: Utc::now(),
+            f_circuit_id: circuit_id,
+            f_invalid: false,
+        });
+
+        Ok(true)
+    }
+
+    async fn store_witness_job(
+        &self,
+        first_block: BlockNumber,
+        last_block: BlockNumber,
+        witness: Vec<u8>,
+        object_job: String,
+        circuit_id: i64,
+    ) -> anyhow::Result<()> {
+        let (key, hash) = self
+            .witness_store()
+            .put(first_block, circuit_id, &witness)
+            .await?;
+
+        let mut tables = self.tables();
+        let witness_id = tables.witnesses.len() as i64 + 1;
+        tables.witnesses.insert(
+            i64::from(*first_block),
+            StorageBlockWitnessCloud {
+                f_id: witness_id,
+                f_block: i64::from(*first_block),
+                f_version: 0,
+                f_object_key: key,
+                f_object_witness: None,
+                f_circuit_id: Some(circuit_id),
+                f_content_hash: Some(hash),
+            },
+        );
+
+        let f_id = tables.jobs.len() as i64 + 1;
+        tables.jobs.push(StorageProverJobQueueCloud {
+            f_id,
+            f_job_status: ProverJobStatus::Idle.to_number(),
+            f_job_priority: 0,
+            f_job_type: ProverJobType::SingleProof.to_string(),
+            f_created_at: Utc::now(),
+            f_version: 0,
+            f_updated_by: "witness_generator".to_string(),
+            f_updated_at: Utc::now(),
+            f_first_block: i64::from(*first_block),
+            f_last_block: i64::from(*last_block),
+            f_object_key: first_block.to_string(),
+            f_object_job: object_job,
+            f_circuit_id: Some(circuit_id),
+        });
+        tables.last_witness_block = Some(i64::from(*last_block) + 1);
+
+        Ok(())
+    }
+
+    async fn get_idle_prover_job_from_job_queue(
+        &self,
+        prover_name: &str,
+        circuit_id: i64,
+    ) -> anyhow::Result<Option<StorageProverJobQueueCloud>> {
+        let now = Utc::now();
+        let mut tables = self.tables();
+        let job = tables
+            .jobs
+            .iter_mut()
+            .filter(|job| {
+                job.f_job_status == ProverJobStatus::Idle.to_number()
+                    && job.f_updated_at <= now
+                    && job.f_circuit_id == Some(circuit_id)
+            })
+            .min_by_key(|job| (-job.f_job_priority, job.f_id));
+
+        Ok(job.map(|job| {
+            job.f_job_status = ProverJobStatus::InProgress.to_number();
+            job.f_updated_by = prover_name.to_string();
+            job.f_updated_at = Utc::now();
+            job.f_version += 1;
+            job.clone()
+        }))
+    }
+
+    async fn load_prover_job(
+        &self,
+        job_id: i64,
+    ) -> anyhow::Result<Option<StorageProverJobQueueCloud>> {
+        Ok(self
+            .tables()
+            .jobs
+            .iter()
+            .find(|job| job.f_id == job_id)
+            .cloned())
+    }
+
+    async fn record_prover_is_working(
+        &self,
+        job_id: i64,
+        prover_name: &str,
+    ) -> anyhow::Result<bool> {
+        let mut tables = self.tables();
+        let job = tables.jobs.iter_mut().find(|job| {
+            job.f_id == job_id
+                && job.f_updated_by == prover_name
+                && job.f_job_status == ProverJobStatus::InProgress.to_number()
+        });
+
+        Ok(job.map(|job| job.f_updated_at = Utc::now()).is_some())
+    }
+
+    async fn release_prover_job(
+        &self,
+        job_id: i64,
+        prover_name: &str,
+        delay: Duration,
+    ) -> anyhow::Result<bool> {
+        let available_at = Utc::now() + chrono::Duration::from_std(delay)?;
+        let mut tables = self.tables();
+        let job = tables.jobs.iter_mut().find(|job| {
+            job.f_id == job_id
+                && job.f_updated_by == prover_name
+                && job.f_job_status == ProverJobStatus::InProgress.to_number()
+        });
+
+        Ok(job
+            .map(|job| {
+                job.f_job_status = ProverJobStatus::Idle.to_number();
+                job.f_updated_by = "server_release_job".to_string();
+                job.f_updated_at = available_at;
+            })
+            .is_some())
+    }
+
+    async fn fail_prover_job(&self, job_id: i64, prover_name: &str) -> anyhow::Result<bool> {
+        let mut tables = self.tables();
+        let job = tables.jobs.iter_mut().find(|job| {
+            job.f_id == job_id
+                && job.f_updated_by == prover_name
+                && job.f_job_status == ProverJobStatus::InProgress.to_number()
+        });
+
+        Ok(job
+            .map(|job| {
+                job.f_job_status = ProverJobStatus::Failed.to_number();
+                job.f_updated_by = "server_fail_job".to_string();
+                job.f_updated_at = Utc::now();
+            })
+            .is_some())
+    }
+
+    async fn record_block_failure(
+        &self,
+        stage: PipelineStage,
+        block: BlockNumber,
+        error: &str,
+        max_attempts: u32,
+    ) -> anyhow::Result<StorageBlockFailure> {
+        let mut tables = self.tables();
+        let f_id = tables.failures.len() as i64 + 1;
+        let failure = tables
+            .failures
+            .entry((stage.to_string(), i64::from(*block)))
+            .or_insert_with(|| StorageBlockFailure {
+                f_id,
+                f_stage: stage.to_string(),
+                f_block: i64::from(*block),
+                f_attempts: 0,
+                f_last_error: String::new(),
+                f_dead: false,
+                f_updated_at: Utc::now(),
+            });
+        failure.f_attempts += 1;
+        failure.f_last_error = error.to_string();
+        failure.f_dead = failure.f_attempts as i64 >= i64::from(max_attempts);
+        failure.f_updated_at = Utc::now();
+
+        Ok(failure.clone())
+    }
+
+    async fn load_block_failure(
+        &self,
+        stage: PipelineStage,
+        block: BlockNumber,
+    ) -> anyhow::Result<Option<StorageBlockFailure>> {
+        let tables = self.tables();
+        let failure = tables
+            .failures
+            .get(&(stage.to_string(), i64::from(*block)))
+            .cloned();
+
+        Ok(failure)
+    }
+
+    async fn mark_stale_jobs_as_idle(&self, gone_timeout: Duration) -> anyhow::Result<u64> {
+        let deadline = Utc::now() - chrono::Duration::from_std(gone_timeout)?;
+        let mut tables = self.tables();
+        let mut jobs = 0;
+        for job in tables.jobs.iter_mut().filter(|job| {
+            job.f_job_status == ProverJobStatus::InProgress.to_number()
+                && job.f_updated_at < deadline
+        }) {
+            job.f_job_status = ProverJobStatus::Idle.to_number();
+            job.f_updated_by = "server_clean_idle".to_string();
+            job.f_updated_at = Utc::now();
+            jobs += 1;
+        }
+
+        Ok(jobs)
+    }
+
+    async fn pending_jobs_count(&self) -> anyhow::Result<u32> {
+        let tables = self.tables();
+        let count = tables
+            .jobs
+            .iter()
+            .filter(|job| {
+                job.f_job_status == ProverJobStatus::Idle.to_number()
+                    || job.f_job_status == ProverJobStatus::InProgress.to_number()
+            })
+            .count();
+
+        Ok(count as u32)
+    }
+
+    async fn pending_witness_traces_count(&self) -> anyhow::Result<u32> {
+        let tables = self.tables();
+        let first = tables.last_witness_block.unwrap_or(1);
+
+        Ok(tables.traces.range(first..).count() as u32)
+    }
+
+    async fn pending_proof_blocks_count(&self) -> anyhow::Result<u32> {
+        let tables = self.tables();
+        let count: i64 = tables
+            .jobs
+            .iter()
+            .filter(|job| {
+                job.f_job_status == ProverJobStatus::Idle.to_number()
+                    || job.f_job_status == ProverJobStatus::InProgress.to_number()
+            })
+            .map(|job| job.f_last_block - job.f_first_block + 1)
+            .sum();
+
+        Ok(count as u32)
+    }
+
+    async fn load_last_trace_block_number(&self) -> anyhow::Result<i64> {
+        let tables = self.tables();
+
+        Ok(tables.traces.keys().next_back().copied().unwrap_or(0))
+    }
+
+    async fn register_circuit(
+        &self,
+        program_hash: &str,
+        abi: Value,
+        verification_key: Value,
+        verifier_address: Option<&str>,
+    ) -> anyhow::Result<StorageCircuit> {
+        let mut tables = self.tables();
+        let f_id = tables.circuits.len() as i64 + 1;
+        let index = match tables
+            .circuits
+            .iter()
+            .position(|circuit| circuit.f_program_hash == program_hash)
+        {
+            Some(index) => index,
+            None => {
+                tables.circuits.push(StorageCircuit {
+                    f_id,
+                    f_program_hash: program_hash.to_string(),
+                    f_abi: abi.clone(),
+                    f_verification_key: verification_key.clone(),
+                    f_verifier_address: verifier_address.map(str::to_string),
+                    f_created_at: Utc::now(),
+                });
+                tables.circuits.len() - 1
+            }
+        };
+        let circuit = &mut tables.circuits[index];
+
+        if circuit.f_abi != abi {
+            anyhow::bail!("Circuit {} is registered with another ABI", program_hash);
+        }
+        if circuit.f_verification_key != verification_key {
+            anyhow::bail!(
+                "Circuit {} is registered with another verification key",
+                program_hash
+            );
+        }
+        match (&circuit.f_verifier_address, verifier_address) {
+            (Some(registered), Some(address)) if !registered.eq_ignore_ascii_case(address) => {
+                anyhow::bail!(
+                    "Circuit {} is registered with the verifier {}, not {}",
+                    program_hash,
+                    registered,
+                    address
+                );
+            }
+            (None, Some(address)) => circuit.f_verifier_address = Some(address.to_string()),
+            _ => {}
+        }
+
+        Ok(circuit.clone())
+    }
+
+    async fn load_circuit_by_hash(
+        &self,
+        program_hash: &str,
+    ) -> anyhow::Result<Option<StorageCircuit>> {
+        let tables = self.tables();
+        let circuit = tables
+            .circuits
+            .iter()
+            .find(|circuit| circuit.f_program_hash == program_hash)
+            .cloned();
+
+        Ok(circuit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn failing_nova_run_is_retried_then_skipped() {
+        let database = InMemoryDatabase::new();
+        let traces = [false, true]
+            .iter()
+            .enumerate()
+            .map(|(step, exited)| NewTrace {
+                segment: 0,
+                step: step as i64,
+                trace: serde_json::json!({ "newExited": exited }),
+            })
+            .collect();
+        database.store_traces("run", traces).await.unwrap();
+        let gone_timeout = Duration::from_secs(60);
+
+        let run = database
+            .lease_next_unproven_run("a", gone_timeout)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(run.f_program_id, "run");
+        // The run is proven by one prover at a time.
+        assert!(database
+            .lease_next_unproven_run("b", gone_timeout)
+            .await
+            .unwrap()
+            .is_none());
+        assert!(database
+            .fail_nova_run("run", "b", "not leased", 2, Duration::ZERO)
+            .await
+            .unwrap()
+            .is_none());
+
+        let failed = database
+            .fail_nova_run("run", "a", "attempt 1", 2, Duration::ZERO)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(failed.f_attempts, 1);
+        assert_eq!(failed.f_status, ProverJobStatus::Idle.to_number());
+
+        database
+            .lease_next_unproven_run("b", gone_timeout)
+            .await
+            .unwrap()
+            .unwrap();
+        let failed = database
+            .fail_nova_run("run", "b", "attempt 2", 2, Duration::ZERO)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(failed.f_status, ProverJobStatus::Failed.to_number());
+        assert_eq!(failed.f_last_error.as_deref(), Some("attempt 2"));
+
+        // The run isn't leased anymore once it failed `max_attempts` times.
+        assert!(database
+            .lease_next_unproven_run("a", gone_timeout)
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn job_without_circuit_is_not_leased() {
+        let database = InMemoryDatabase::new();
+        let circuit = database
+            .register_circuit("hash", serde_json::json!({}), serde_json::json!({}), None)
+            .await
+            .unwrap();
+        database
+            .store_witness_job(
+                BlockNumber(1),
+                BlockNumber(1),
+                b"witness".to_vec(),
+                "out".into(),
+                circuit.f_id,
+            )
+            .await
+            .unwrap();
+        // Queued before the registry was introduced.
+        database.tables().jobs[0].f_circuit_id = None;
+
+        assert!(database
+            .get_idle_prover_job_from_job_queue("prover", circuit.f_id)
+            .await
+            .unwrap()
+            .is_none());
+    }
+}
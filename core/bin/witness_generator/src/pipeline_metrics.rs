@@ -0,0 +1,51 @@
+//! Gauges of the progress of the pipeline, exported by the `prometheus` component of the server.
+//!
+//! The lag of a stage is the amount of stored traces it hasn't gone through yet.
+
+// Built-in
+use std::time::Duration;
+// Local deps
+use crate::database_interface::DatabaseInterface;
+
+/// Lag of every stage of the pipeline, in blocks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PipelineLag {
+    /// Traces without a witness.
+    pub witness: u64,
+    /// Traces without a proof, dead-lettered ones excepted.
+    pub proof: u64,
+    /// Traces after the last verified one.
+    pub verify: u64,
+}
+
+impl PipelineLag {
+    pub async fn load<DB: DatabaseInterface>(database: &DB) -> anyhow::Result<Self> {
+        let witness = u64::from(database.pending_witness_traces_count().await?);
+        let proof = witness + u64::from(database.pending_proof_blocks_count().await?);
+        let last_trace = database.load_last_trace_block_number().await?;
+        let last_verified = database.load_last_verified_proof_block_number().await?;
+
+        Ok(Self {
+            witness,
+            proof,
+            verify: last_trace.saturating_sub(last_verified).max(0) as u64,
+        })
+    }
+}
+
+/// Updates the `pipeline.lag` gauges every `interval`.
+pub async fn report_pipeline_lag<DB: DatabaseInterface>(database: DB, interval: Duration) {
+    let mut timer = tokio::time::interval(interval);
+    loop {
+        timer.tick().await;
+
+        match PipelineLag::load(&database).await {
+            Ok(lag) => {
+                metrics::gauge!("pipeline.lag", lag.witness as f64, "stage" => "witness");
+                metrics::gauge!("pipeline.lag", lag.proof as f64, "stage" => "proof");
+                metrics::gauge!("pipeline.lag", lag.verify as f64, "stage" => "verify");
+            }
+            Err(e) => vlog::warn!("Failed to load the lag of the pipeline: {}", e),
+        }
+    }
+}
@@ -0,0 +1,455 @@
+//! Prover job API served by `run_prover_server`.
+//!
+//! Remote provers get jobs and submit proofs through these requests instead of
+//! accessing the database. The same operations back the HTTP handlers and the
+//! provers that do have database access.
+
+// External
+use serde::{Deserialize, Serialize};
+// Workspace deps
+use config::configs::prover::Retry;
+use storage::prover::{PipelineStage, ProverJobStatus};
+use types::BlockNumber;
+// Local deps
+use crate::database_interface::DatabaseInterface;
+use crate::registry;
+
+/// Claims of the JWT the provers authenticate with.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PayloadAuthToken {
+    /// Subject (whom auth token refers to).
+    pub sub: String,
+    /// Expiration time (as UTC timestamp).
+    pub exp: usize,
+}
+
+/// Input of the `/api/internal/prover/get_job` endpoint.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProverInputRequest {
+    pub prover_name: String,
+    /// Hash of the program the prover proves with, see `circuit::artifacts::program_hash`.
+    pub program_hash: String,
+}
+
+/// Header of the `/api/internal/prover/get_job` response holding the leased job as JSON,
+/// the body of the response being the gzipped witness.
+pub const JOB_HEADER: &str = "x-prover-job";
+
+/// Job leased to a prover, output of the `/api/internal/prover/get_job` endpoint.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProverJob {
+    pub job_id: i64,
+    /// First trace proven by the job, the witness and the proof are stored under it.
+    pub first_block: BlockNumber,
+    /// Last trace proven by the job.
+    pub last_block: BlockNumber,
+    /// Hash of the content of the witness, see `blob_store::content_hash`.
+    pub witness_hash: String,
+    /// Witness gzipped as it is kept in the blob store, sent as the body of the response.
+    #[serde(skip)]
+    pub witness: Vec<u8>,
+}
+
+impl ProverJob {
+    /// Decompresses the witness and checks its hash.
+    pub fn decompress_witness(&self) -> anyhow::Result<Vec<u8>> {
+        blob_store::decompress(&self.witness, &self.witness_hash)
+            .map_err(|e| anyhow::format_err!("Invalid witness of job {}: {}", self.job_id, e))
+    }
+}
+
+/// Input of the `/api/internal/prover/working_on` endpoint.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkingOn {
+    pub job_id: i64,
+    pub prover_name: String,
+}
+
+/// Input of the `/api/internal/prover/publish` endpoint.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PublishProof {
+    pub job_id: i64,
+    /// Prover holding the lease on the job, the proof is refused otherwise.
+    pub prover_name: String,
+    pub proof: String,
+}
+
+/// Input of the `/api/internal/prover/failure` endpoint.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProverFailure {
+    pub job_id: i64,
+    /// Prover holding the lease on the job, the failure is refused otherwise.
+    pub prover_name: String,
+    pub error: String,
+}
+
+/// Leases the next job of the prover's circuit to the prover and loads its witness.
+/// Fails if the circuit of the prover isn't registered.
+/// A job whose witness can't be loaded is reported as failed with `retry` right away,
+/// instead of waiting for its lease to expire.
+pub async fn get_job<DB: DatabaseInterface>(
+    database: &DB,
+    retry: &Retry,
+    request: &ProverInputRequest,
+) -> anyhow::Result<Option<ProverJob>> {
+    let circuit_id = registry::circuit_id(database, &request.program_hash).await?;
+    let job = match database
+        .get_idle_prover_job_from_job_queue(&request.prover_name, circuit_id)
+        .await?
+    {
+        Some(job) => job,
+        None => return Ok(None),
+    };
+
+    let first_block = BlockNumber(job.f_first_block as u32);
+    let (witness, witness_hash) = match database.load_compressed_witness(first_block).await {
+        Ok(Some(witness)) => witness,
+        result => {
+            let error = match result {
+                Err(e) => format!("Failed to load the witness of job {}: {}", job.f_id, e),
+                _ => format!("No witness for job {}", job.f_id),
+            };
+            let failure = ProverFailure {
+                job_id: job.f_id,
+                prover_name: request.prover_name.clone(),
+                error: error.clone(),
+            };
+            report_failure(database, retry, &failure).await?;
+            anyhow::bail!(error);
+        }
+    };
+
+    Ok(Some(ProverJob {
+        job_id: job.f_id,
+        first_block,
+        last_block: BlockNumber(job.f_last_block as u32),
+        witness_hash,
+        witness,
+    }))
+}
+
+/// Extends the lease of the prover on the job, returns `false` if the lease is lost.
+pub async fn working_on<DB: DatabaseInterface>(
+    database: &DB,
+    request: &WorkingOn,
+) -> anyhow::Result<bool> {
+    database
+        .record_prover_is_working(request.job_id, &request.prover_name)
+        .await
+}
+
+/// Stores the proof under the first block of the job and marks the job as done.
+/// Returns `false` if the prover doesn't hold the lease on the job anymore.
+pub async fn publish<DB: DatabaseInterface>(
+    database: &DB,
+    request: PublishProof,
+) -> anyhow::Result<bool> {
+    database
+        .store_proof(request.job_id, &request.prover_name, request.proof)
+        .await
+}
+
+/// Returns the job of a failed prover to the queue, to be retried after a backoff.
+/// The job is moved to the dead letter queue once it failed `retry.max_attempts` times.
+/// Returns `false` without counting the failure if the prover doesn't hold the lease on the job,
+/// the failures are counted under the first block of the job.
+pub async fn report_failure<DB: DatabaseInterface>(
+    database: &DB,
+    retry: &Retry,
+    request: &ProverFailure,
+) -> anyhow::Result<bool> {
+    let job = match database.load_prover_job(request.job_id).await? {
+        Some(job)
+            if job.f_updated_by == request.prover_name
+                && job.f_job_status == ProverJobStatus::InProgress.to_number() =>
+        {
+            job
+        }
+        _ => {
+            vlog::warn!(
+                "Prover {} reported a failure of job {}, which it doesn't hold",
+                request.prover_name,
+                request.job_id
+            );
+            return Ok(false);
+        }
+    };
+
+    vlog::warn!(
+        "Prover {} failed job {}: {}",
+        request.prover_name,
+        request.job_id,
+        request.error
+    );
+    metrics::increment_counter!("pipeline.failures", "stage" => PipelineStage::Proof.to_string());
+
+    let failure = database
+        .record_block_failure(
+            PipelineStage::Proof,
+            BlockNumber(job.f_first_block as u32),
+            &request.error,
+            retry.max_attempts,
+        )
+        .await?;
+
+    if failure.f_dead {
+        vlog::error!(
+            "Job {} failed {} times, moving it to the dead letter queue",
+            request.job_id,
+            failure.f_attempts
+        );
+        metrics::increment_counter!("pipeline.dead_letters", "stage" => PipelineStage::Proof.to_string());
+        database
+            .fail_prover_job(request.job_id, &request.prover_name)
+            .await
+    } else {
+        database
+            .release_prover_job(
+                request.job_id,
+                &request.prover_name,
+                retry.backoff(failure.f_attempts as u32),
+            )
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory_database::InMemoryDatabase;
+    use crate::settlement::MockSettlement;
+    use crate::verifier_generator::VerifierGenerator;
+    use crate::witness_store::WitnessStore;
+    use blob_store::BlobStore;
+    use zokrates_common::helpers::BackendParameter;
+
+    fn retry() -> Retry {
+        Retry {
+            max_attempts: 3,
+            backoff: 0,
+            max_backoff: 0,
+        }
+    }
+
+    fn job_request(prover_name: &str) -> ProverInputRequest {
+        ProverInputRequest {
+            prover_name: prover_name.into(),
+            program_hash: "hash".into(),
+        }
+    }
+
+    fn proof_of(job_id: i64, prover_name: &str) -> PublishProof {
+        PublishProof {
+            job_id,
+            prover_name: prover_name.into(),
+            proof: "{}".into(),
+        }
+    }
+
+    #[tokio::test]
+    async fn proof_of_a_job_leased_to_another_prover_is_refused() {
+        let database = InMemoryDatabase::new();
+        let circuit = database
+            .register_circuit("hash", serde_json::json!({}), serde_json::json!({}), None)
+            .await
+            .unwrap();
+        database
+            .store_witness_job(
+                BlockNumber(3),
+                BlockNumber(4),
+                b"witness".to_vec(),
+                "out".into(),
+                circuit.f_id,
+            )
+            .await
+            .unwrap();
+        let job = get_job(&database, &retry(), &job_request("b"))
+            .await
+            .unwrap()
+            .unwrap();
+        // The witness is given as it is stored, gzipped.
+        let key = WitnessStore::key(BlockNumber(3), circuit.f_id);
+        assert_eq!(
+            Some(job.witness.clone()),
+            database.blob_store().get(&key).await.unwrap()
+        );
+        assert_eq!(job.decompress_witness().unwrap(), b"witness");
+
+        assert!(!publish(&database, proof_of(job.job_id, "a")).await.unwrap());
+        assert!(database.load_proof(BlockNumber(3)).await.unwrap().is_none());
+        assert_eq!(
+            database.jobs()[0].f_job_status,
+            ProverJobStatus::InProgress.to_number()
+        );
+
+        // The proof of the prover holding the lease is stored under the first block of the job.
+        assert!(publish(&database, proof_of(job.job_id, "b")).await.unwrap());
+        assert!(database.load_proof(BlockNumber(3)).await.unwrap().is_some());
+        assert!(!publish(&database, proof_of(job.job_id, "b")).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn failure_of_a_job_leased_to_another_prover_is_refused() {
+        let database = InMemoryDatabase::new();
+        let circuit = database
+            .register_circuit("hash", serde_json::json!({}), serde_json::json!({}), None)
+            .await
+            .unwrap();
+        database
+            .store_witness_job(
+                BlockNumber(3),
+                BlockNumber(4),
+                b"witness".to_vec(),
+                "out".into(),
+                circuit.f_id,
+            )
+            .await
+            .unwrap();
+        let job = get_job(&database, &retry(), &job_request("a"))
+            .await
+            .unwrap()
+            .unwrap();
+
+        // Neither counted nor released, the job stays with the prover holding the lease.
+        let failure = ProverFailure {
+            job_id: job.job_id,
+            prover_name: "b".into(),
+            error: "failed".into(),
+        };
+        assert!(!report_failure(&database, &retry(), &failure).await.unwrap());
+        assert!(database
+            .load_block_failure(PipelineStage::Proof, BlockNumber(3))
+            .await
+            .unwrap()
+            .is_none());
+        assert_eq!(
+            database.jobs()[0].f_job_status,
+            ProverJobStatus::InProgress.to_number()
+        );
+        assert_eq!(database.jobs()[0].f_updated_by, "a");
+
+        // The failure of the prover holding the lease is counted under the first block of the job.
+        let failure = ProverFailure {
+            prover_name: "a".into(),
+            ..failure
+        };
+        assert!(report_failure(&database, &retry(), &failure).await.unwrap());
+        let failure = database
+            .load_block_failure(PipelineStage::Proof, BlockNumber(3))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(failure.f_attempts, 1);
+        assert_eq!(
+            database.jobs()[0].f_job_status,
+            ProverJobStatus::Idle.to_number()
+        );
+    }
+
+    #[tokio::test]
+    async fn job_without_witness_is_released() {
+        let database = InMemoryDatabase::new();
+        let circuit = database
+            .register_circuit("hash", serde_json::json!({}), serde_json::json!({}), None)
+            .await
+            .unwrap();
+        database
+            .store_witness_job(
+                BlockNumber(1),
+                BlockNumber(1),
+                b"witness".to_vec(),
+                "out".into(),
+                circuit.f_id,
+            )
+            .await
+            .unwrap();
+        database
+            .blob_store()
+            .remove(&WitnessStore::key(BlockNumber(1), circuit.f_id));
+
+        assert!(get_job(&database, &retry(), &job_request("a"))
+            .await
+            .is_err());
+        // The job is back in the queue without waiting for the lease of the prover to expire.
+        let job = &database.jobs()[0];
+        assert_eq!(job.f_job_status, ProverJobStatus::Idle.to_number());
+        assert_eq!(job.f_updated_by, "server_release_job");
+    }
+
+    #[tokio::test]
+    async fn failing_job_is_dead_lettered_and_skipped() {
+        let database = InMemoryDatabase::new();
+        let circuit = database
+            .register_circuit("hash", serde_json::json!({}), serde_json::json!({}), None)
+            .await
+            .unwrap();
+        database
+            .store_witness_job(
+                BlockNumber(1),
+                BlockNumber(1),
+                b"witness".to_vec(),
+                "out".into(),
+                circuit.f_id,
+            )
+            .await
+            .unwrap();
+
+        let retry = Retry {
+            max_attempts: 2,
+            ..retry()
+        };
+        for attempt in 1..=retry.max_attempts {
+            let job = get_job(&database, &retry, &job_request("prover"))
+                .await
+                .unwrap()
+                .unwrap();
+            let failure = ProverFailure {
+                job_id: job.job_id,
+                prover_name: "prover".into(),
+                error: format!("attempt {}", attempt),
+            };
+            assert!(report_failure(&database, &retry, &failure).await.unwrap());
+        }
+
+        // The job is not given to the provers anymore.
+        assert!(get_job(&database, &retry, &job_request("prover"))
+            .await
+            .unwrap()
+            .is_none());
+        assert_eq!(
+            database.jobs()[0].f_job_status,
+            ProverJobStatus::Failed.to_number()
+        );
+        assert_eq!(database.pending_jobs_count().await.unwrap(), 0);
+        let failure = database
+            .load_block_failure(PipelineStage::Proof, BlockNumber(1))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(failure.f_attempts, 2);
+        assert_eq!(failure.f_last_error, "attempt 2");
+        assert!(failure.f_dead);
+
+        // The verifier moves past the batch without a proof.
+        let mut verifier_generator = VerifierGenerator::new(
+            database.clone(),
+            std::time::Duration::from_millis(10),
+            BlockNumber(0),
+            BlockNumber(1),
+            1,
+            MockSettlement::new(BackendParameter::Ark, serde_json::Value::Null),
+            circuit,
+            BackendParameter::Ark,
+            retry,
+        );
+        assert!(verifier_generator.verify_next_proof().await.unwrap());
+        assert!(!verifier_generator.verify_next_proof().await.unwrap());
+        assert_eq!(
+            database
+                .load_last_verified_proof_block_number()
+                .await
+                .unwrap(),
+            1
+        );
+    }
+}
@@ -0,0 +1,177 @@
+//! Circuit registry shared by the witness generator, the provers and the verifier generator.
+//!
+//! Every witness, prover job and proof is stored with the id of the circuit it was made with,
+//! the circuit being identified by the hash of its compiled program. A circuit is registered
+//! once with its ABI, verification key and verifier contract, and the registration of the same
+//! program with other ones is refused.
+
+// Built-in
+use std::path::Path;
+// Workspace deps
+use circuit::artifacts::CircuitArtifacts;
+use config::configs::prover::Circuit;
+use storage::prover::records::StorageCircuit;
+// Local deps
+use crate::database_interface::DatabaseInterface;
+
+/// Registers the circuit configured by `circuit`, or checks that it is registered with the same artifacts.
+/// The verifier generator passes the address of the contract it submits the proofs to.
+pub async fn register_circuit<DB: DatabaseInterface>(
+    database: &DB,
+    circuit: &Circuit,
+    verifier_address: Option<&str>,
+) -> anyhow::Result<StorageCircuit> {
+    let artifacts = CircuitArtifacts::load(
+        Path::new(&circuit.file_path),
+        Path::new(&circuit.abi_file_path),
+        Path::new(&circuit.verification_key_path),
+    )
+    .map_err(anyhow::Error::msg)?;
+
+    let registered = database
+        .register_circuit(
+            &artifacts.program_hash,
+            artifacts.abi,
+            artifacts.verification_key,
+            verifier_address,
+        )
+        .await?;
+    vlog::info!(
+        "Running circuit {} ({})",
+        registered.f_id,
+        registered.f_program_hash
+    );
+
+    Ok(registered)
+}
+
+/// Returns the id of the circuit registered with `program_hash`, failing if there is none.
+pub async fn circuit_id<DB: DatabaseInterface>(
+    database: &DB,
+    program_hash: &str,
+) -> anyhow::Result<i64> {
+    let circuit = database
+        .load_circuit_by_hash(program_hash)
+        .await?
+        .ok_or_else(|| anyhow::format_err!("Circuit {} is not registered", program_hash))?;
+
+    Ok(circuit.f_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::PipelineError;
+    use crate::memory_database::InMemoryDatabase;
+    use crate::prover_api::{self, ProverInputRequest, PublishProof};
+    use crate::settlement::MockSettlement;
+    use crate::verifier_generator::VerifierGenerator;
+    use config::configs::prover::Retry;
+    use types::BlockNumber;
+    use zokrates_common::helpers::BackendParameter;
+
+    fn retry() -> Retry {
+        Retry {
+            max_attempts: 3,
+            backoff: 0,
+            max_backoff: 0,
+        }
+    }
+
+    fn job_request(program_hash: &str) -> ProverInputRequest {
+        ProverInputRequest {
+            prover_name: "prover".into(),
+            program_hash: program_hash.into(),
+        }
+    }
+
+    #[tokio::test]
+    async fn mismatched_circuits_are_refused() {
+        let database = InMemoryDatabase::new();
+        let abi = serde_json::json!({"inputs": []});
+        let vk = serde_json::json!({"alpha": "1"});
+        let circuit = database
+            .register_circuit("hash", abi.clone(), vk.clone(), None)
+            .await
+            .unwrap();
+
+        // The same program can't be registered with other artifacts.
+        assert!(database
+            .register_circuit("hash", serde_json::json!({}), vk.clone(), None)
+            .await
+            .is_err());
+        assert!(database
+            .register_circuit("hash", abi.clone(), serde_json::json!({}), None)
+            .await
+            .is_err());
+        let with_verifier = database
+            .register_circuit("hash", abi.clone(), vk.clone(), Some("0xabc"))
+            .await
+            .unwrap();
+        assert_eq!(with_verifier.f_id, circuit.f_id);
+        assert_eq!(with_verifier.f_verifier_address.as_deref(), Some("0xabc"));
+        assert!(database
+            .register_circuit("hash", abi.clone(), vk.clone(), Some("0xdef"))
+            .await
+            .is_err());
+
+        // Jobs are only leased to the provers of their circuit.
+        let other = database
+            .register_circuit("other", abi, vk, None)
+            .await
+            .unwrap();
+        assert_eq!(circuit_id(&database, "other").await.unwrap(), other.f_id);
+        database
+            .store_witness_job(
+                BlockNumber(1),
+                BlockNumber(1),
+                b"witness".to_vec(),
+                "out".into(),
+                circuit.f_id,
+            )
+            .await
+            .unwrap();
+        assert!(
+            prover_api::get_job(&database, &retry(), &job_request("unknown"))
+                .await
+                .is_err()
+        );
+        assert!(
+            prover_api::get_job(&database, &retry(), &job_request("other"))
+                .await
+                .unwrap()
+                .is_none()
+        );
+        let job = prover_api::get_job(&database, &retry(), &job_request("hash"))
+            .await
+            .unwrap()
+            .unwrap();
+
+        // Proofs of another circuit are not submitted by the verifier.
+        assert!(prover_api::publish(
+            &database,
+            PublishProof {
+                job_id: job.job_id,
+                prover_name: "prover".into(),
+                proof: "{}".into(),
+            },
+        )
+        .await
+        .unwrap());
+        let mut verifier_generator = VerifierGenerator::new(
+            database.clone(),
+            std::time::Duration::from_millis(10),
+            BlockNumber(0),
+            BlockNumber(1),
+            1,
+            MockSettlement::new(BackendParameter::Ark, serde_json::Value::Null),
+            other,
+            BackendParameter::Ark,
+            retry(),
+        );
+        assert!(matches!(
+            verifier_generator.verify_next_proof().await,
+            Err(PipelineError::CircuitMismatch { .. })
+        ));
+    }
+}
@@ -12,10 +12,55 @@ pub struct ScalerOracle<DB: DatabaseInterface> {
 
     /// Number of idle provers running for faster up-scaling.
     idle_provers: u32,
+
+    /// Amount of traces covered by one prover job.
+    batch_size: u32,
 }
 
 impl<DB: DatabaseInterface> ScalerOracle<DB> {
-    pub fn new(db: DB, idle_provers: u32) -> Self {
-        Self { db, idle_provers }
+    pub fn new(db: DB, idle_provers: u32, batch_size: u32) -> Self {
+        Self {
+            db,
+            idle_provers,
+            batch_size,
+        }
+    }
+
+    /// Decides how many prover entities should be created depending on the amount of
+    /// pending jobs and of the jobs the witness generator is about to create.
+    pub async fn provers_required(&mut self) -> anyhow::Result<u32> {
+        let pending_jobs = self.db.pending_jobs_count().await?;
+        let pending_traces = self.db.pending_witness_traces_count().await?;
+
+        Ok(required_replicas(
+            pending_jobs,
+            pending_traces,
+            self.batch_size,
+            self.idle_provers,
+        ))
+    }
+}
+
+/// Every pending job and every complete batch of traces without a witness
+/// needs a prover, on top of the idle ones.
+fn required_replicas(
+    pending_jobs: u32,
+    pending_traces: u32,
+    batch_size: u32,
+    idle_provers: u32,
+) -> u32 {
+    pending_jobs + pending_traces / batch_size.max(1) + idle_provers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replicas() {
+        assert_eq!(required_replicas(0, 0, 1, 1), 1);
+        assert_eq!(required_replicas(3, 0, 1, 1), 4);
+        assert_eq!(required_replicas(3, 5, 1, 0), 8);
+        assert_eq!(required_replicas(0, 99, 50, 1), 2);
     }
 }
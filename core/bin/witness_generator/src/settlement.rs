@@ -0,0 +1,263 @@
+//! Settlement layers the verifier submits the proofs to.
+//!
+//! `EvmSettlement` sends them to the verifier contract, `MockSettlement` checks them in process
+//! and `DryRunSettlement` only writes the calldata of the transactions it would send, so the
+//! pipeline can run without a chain.
+
+// Built-in
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+// External
+use serde_json::Value;
+// Workspace deps
+use config::configs::verifier::{SettlementKind, VerifierConfig};
+use types::BlockNumber;
+use zokrates_common::helpers::BackendParameter;
+use zokrates_field::Bn128Field;
+use zokrates_proof_systems::{Proof, G16};
+
+/// Layer settling the proofs taken from the database.
+#[async_trait::async_trait]
+pub trait Settlement: Send + Sync + 'static {
+    /// Submits the proof of the batch starting at `block`.
+    /// Returns whether `proof` has been accepted, or an error if it couldn't be submitted.
+    async fn settle(
+        &self,
+        block: BlockNumber,
+        proof: Proof<Bn128Field, G16>,
+    ) -> Result<bool, String>;
+
+    /// Submits together the proofs of consecutive batches, the first of which starts at `first_block`.
+    /// Returns whether all of `proofs` have been accepted, or an error if they couldn't be submitted.
+    async fn settle_batch(
+        &self,
+        first_block: BlockNumber,
+        proofs: Vec<Proof<Bn128Field, G16>>,
+    ) -> Result<bool, String>;
+}
+
+#[async_trait::async_trait]
+impl<S: Settlement + ?Sized> Settlement for Box<S> {
+    async fn settle(
+        &self,
+        block: BlockNumber,
+        proof: Proof<Bn128Field, G16>,
+    ) -> Result<bool, String> {
+        (**self).settle(block, proof).await
+    }
+
+    async fn settle_batch(
+        &self,
+        first_block: BlockNumber,
+        proofs: Vec<Proof<Bn128Field, G16>>,
+    ) -> Result<bool, String> {
+        (**self).settle_batch(first_block, proofs).await
+    }
+}
+
+/// Creates the settlement layer selected by `VERIFIER_SETTLEMENT`.
+/// The mock checks the proofs against `verification_key` with `backend`.
+pub fn from_config(
+    config: &VerifierConfig,
+    verification_key: Value,
+    backend: BackendParameter,
+) -> Box<dyn Settlement> {
+    match config.settlement {
+        SettlementKind::Evm => Box::new(EvmSettlement {
+            chain_url: config.chain_url.clone(),
+            contract_address: config.contract_address.clone(),
+            batch_contract_address: config.batch_contract_address.clone(),
+            abi_path: config.abi_path.clone(),
+            account: config.account.clone(),
+        }),
+        SettlementKind::Mock => Box::new(MockSettlement::new(backend, verification_key)),
+        SettlementKind::DryRun => {
+            let output_path = config
+                .dry_run_path
+                .clone()
+                .expect("VERIFIER_DRY_RUN_PATH is required by the dry-run settlement");
+            Box::new(DryRunSettlement::new(&config.abi_path, output_path))
+        }
+    }
+}
+
+/// Submits the proofs to the verifier contract, or together to the batch verifier contract,
+/// signing the transactions with `account`.
+#[derive(Debug, Clone)]
+pub struct EvmSettlement {
+    pub chain_url: String,
+    pub contract_address: String,
+    pub batch_contract_address: Option<String>,
+    pub abi_path: String,
+    /// Private key of the account sending the transactions, in hex.
+    pub account: String,
+}
+
+#[async_trait::async_trait]
+impl Settlement for EvmSettlement {
+    async fn settle(
+        &self,
+        _block: BlockNumber,
+        proof: Proof<Bn128Field, G16>,
+    ) -> Result<bool, String> {
+        circuit::proof::call_verify(
+            proof,
+            &self.chain_url,
+            &self.contract_address,
+            &self.abi_path,
+            &self.account,
+        )
+        .await
+    }
+
+    async fn settle_batch(
+        &self,
+        _first_block: BlockNumber,
+        proofs: Vec<Proof<Bn128Field, G16>>,
+    ) -> Result<bool, String> {
+        let batch_contract_address = self
+            .batch_contract_address
+            .as_ref()
+            .ok_or("No batch verifier contract, VERIFIER_BATCH_CONTRACT_ADDRESS is not set")?;
+        circuit::proof::call_verify_batch(
+            proofs,
+            &self.chain_url,
+            batch_contract_address,
+            &self.abi_path,
+            &self.account,
+        )
+        .await
+    }
+}
+
+/// Proofs submitted together to a `MockSettlement`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MockSubmission {
+    /// First block of the first proven batch.
+    pub block: BlockNumber,
+    /// Public inputs of each proof.
+    pub inputs: Vec<Vec<String>>,
+    pub accepted: bool,
+}
+
+/// In-process chain verifying the proofs with the Rust backend, as the contract would.
+/// The clones share the submissions, so they can be inspected while the verifier runs.
+#[derive(Debug, Clone)]
+pub struct MockSettlement(Arc<MockChain>);
+
+#[derive(Debug)]
+struct MockChain {
+    backend: BackendParameter,
+    verification_key: Value,
+    submissions: Mutex<Vec<MockSubmission>>,
+}
+
+impl MockSettlement {
+    pub fn new(backend: BackendParameter, verification_key: Value) -> Self {
+        Self(Arc::new(MockChain {
+            backend,
+            verification_key,
+            submissions: Mutex::default(),
+        }))
+    }
+
+    /// Returns the proofs submitted so far, in order.
+    pub fn submissions(&self) -> Vec<MockSubmission> {
+        self.0.submissions.lock().unwrap().clone()
+    }
+
+    /// Accepts `proofs` if all of them are valid, like the batch verifier contract.
+    fn submit(
+        &self,
+        block: BlockNumber,
+        proofs: Vec<Proof<Bn128Field, G16>>,
+    ) -> Result<bool, String> {
+        let inputs = proofs.iter().map(|proof| proof.inputs.clone()).collect();
+        let mut accepted = true;
+        for proof in proofs {
+            accepted &=
+                circuit::proof::verify_proof(&self.0.backend, &self.0.verification_key, proof)?;
+        }
+        self.0.submissions.lock().unwrap().push(MockSubmission {
+            block,
+            inputs,
+            accepted,
+        });
+
+        Ok(accepted)
+    }
+}
+
+#[async_trait::async_trait]
+impl Settlement for MockSettlement {
+    async fn settle(
+        &self,
+        block: BlockNumber,
+        proof: Proof<Bn128Field, G16>,
+    ) -> Result<bool, String> {
+        self.submit(block, vec![proof])
+    }
+
+    async fn settle_batch(
+        &self,
+        first_block: BlockNumber,
+        proofs: Vec<Proof<Bn128Field, G16>>,
+    ) -> Result<bool, String> {
+        self.submit(first_block, proofs)
+    }
+}
+
+/// Writes the calldata of `verifyTx`, or `verifyTxs` for the proofs submitted together, to
+/// `<output_path>/<block>.calldata` instead of sending it. Every proof is reported as accepted.
+#[derive(Debug, Clone)]
+pub struct DryRunSettlement {
+    abi_path: String,
+    output_path: PathBuf,
+}
+
+impl DryRunSettlement {
+    pub fn new(abi_path: &str, output_path: impl Into<PathBuf>) -> Self {
+        Self {
+            abi_path: abi_path.to_owned(),
+            output_path: output_path.into(),
+        }
+    }
+
+    /// Path of the calldata written for the batch starting at `block`.
+    pub fn calldata_path(&self, block: BlockNumber) -> PathBuf {
+        self.output_path.join(format!("{}.calldata", *block))
+    }
+
+    fn write(&self, block: BlockNumber, calldata: Vec<u8>) -> Result<bool, String> {
+        fs::create_dir_all(&self.output_path)
+            .map_err(|why| format!("Could not create {:?}: {}", self.output_path, why))?;
+        let path = self.calldata_path(block);
+        fs::write(&path, format!("0x{}", hex::encode(calldata)))
+            .map_err(|why| format!("Could not write {:?}: {}", path, why))?;
+        vlog::info!("Wrote the calldata of block {} to {:?}", *block, path);
+
+        Ok(true)
+    }
+}
+
+#[async_trait::async_trait]
+impl Settlement for DryRunSettlement {
+    async fn settle(
+        &self,
+        block: BlockNumber,
+        proof: Proof<Bn128Field, G16>,
+    ) -> Result<bool, String> {
+        let calldata = circuit::proof::verify_tx_calldata(proof, &self.abi_path)?;
+        self.write(block, calldata)
+    }
+
+    async fn settle_batch(
+        &self,
+        first_block: BlockNumber,
+        proofs: Vec<Proof<Bn128Field, G16>>,
+    ) -> Result<bool, String> {
+        let calldata = circuit::proof::verify_txs_calldata(proofs, &self.abi_path)?;
+        self.write(first_block, calldata)
+    }
+}
@@ -0,0 +1,100 @@
+//! Trace ingestion served by `run_prover_server` and used by the `import-traces` command of the server.
+//!
+//! Every record is checked against the trace record type of the circuit ABI before any of
+//! them is stored. The stored records get consecutive blocks, and keep the program id,
+//! segment and step they were taken from.
+//! An import has to fill whole batches of the circuit: the circuit checks that every record of
+//! a batch continues from the state the previous one ends with, so a batch can't hold the end
+//! of an import and the start of the next one. Each import then starts on a batch boundary.
+
+// External
+use serde::{Deserialize, Serialize};
+// Workspace deps
+use circuit::batch::TraceRecordType;
+use storage::prover::records::NewTrace;
+use types::BlockNumber;
+// Local deps
+use crate::database_interface::DatabaseInterface;
+
+/// Input of the `/api/internal/traces/import` endpoint.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportTraces {
+    /// Program the traces are taken from.
+    pub program_id: String,
+    pub traces: Vec<NewTrace>,
+}
+
+/// Output of the `/api/internal/traces/import` endpoint.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportedTraces {
+    /// Block assigned to the first imported trace.
+    pub first_block: BlockNumber,
+    /// Block assigned to the last imported trace.
+    pub last_block: BlockNumber,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ImportError {
+    #[error("no traces to import")]
+    Empty,
+    #[error("trace {index} is invalid: {error}")]
+    InvalidTrace { index: usize, error: String },
+    #[error("{count} traces don't fill whole batches of {batch_size} traces")]
+    PartialBatch { count: usize, batch_size: u32 },
+    #[error("database access failed: {0}")]
+    Database(#[from] anyhow::Error),
+}
+
+/// Checks `trace` before it is stored.
+pub fn check_trace(record_type: &TraceRecordType, trace: &NewTrace) -> Result<(), String> {
+    if trace.segment < 0 || trace.step < 0 {
+        return Err(format!(
+            "negative position {}/{}",
+            trace.segment, trace.step
+        ));
+    }
+
+    record_type.check(&trace.trace)
+}
+
+/// Checks that `count` traces fill whole batches of `batch_size` traces.
+pub fn check_whole_batches(count: usize, batch_size: u32) -> Result<(), ImportError> {
+    if count % batch_size.max(1) as usize != 0 {
+        return Err(ImportError::PartialBatch { count, batch_size });
+    }
+
+    Ok(())
+}
+
+/// Stores the traces of `request` after the ones already stored, unless one of them doesn't
+/// match `record_type` or they don't fill whole batches of `batch_size` traces.
+pub async fn import_traces<DB: DatabaseInterface>(
+    database: &DB,
+    record_type: &TraceRecordType,
+    batch_size: u32,
+    request: ImportTraces,
+) -> Result<ImportedTraces, ImportError> {
+    if request.traces.is_empty() {
+        return Err(ImportError::Empty);
+    }
+    check_whole_batches(request.traces.len(), batch_size)?;
+    for (index, trace) in request.traces.iter().enumerate() {
+        check_trace(record_type, trace)
+            .map_err(|error| ImportError::InvalidTrace { index, error })?;
+    }
+
+    let (first_block, last_block) = database
+        .store_traces(&request.program_id, request.traces)
+        .await?;
+    vlog::info!(
+        "Imported the traces of {} as blocks {}..={}",
+        request.program_id,
+        *first_block,
+        *last_block
+    );
+
+    Ok(ImportedTraces {
+        first_block,
+        last_block,
+    })
+}
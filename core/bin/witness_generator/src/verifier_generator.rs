@@ -7,8 +7,15 @@ use futures::channel::mpsc;
 use tokio::time::sleep;
 // Workspace deps
 use crate::database_interface::DatabaseInterface;
+use crate::error::{handle_failure, PipelineError};
+use crate::settlement::Settlement;
 use types::BlockNumber;
+use circuit::commitment::StateCommitments;
+use config::configs::prover::Retry;
+use storage::prover::PipelineStage;
+use storage::prover::records::{StorageCircuit, StoredProof};
 use utils::panic_notify::ThreadPanicNotify;
+use zokrates_common::helpers::BackendParameter;
 use zokrates_proof_systems::{Proof,G16};
 use zokrates_field::Bn128Field;
 
@@ -16,45 +23,66 @@ use zokrates_field::Bn128Field;
 /// The essential part of this structure is `maintain` function
 /// which runs forever and adds data to the database.
 ///
-/// This will generate and store in db witnesses for blocks with indexes
-/// start_block, start_block + block_step, start_block + 2*block_step, ...
-pub struct VerifierGenerator<DB: DatabaseInterface> {
+/// This will submit to the settlement layer the proofs for blocks with indexes
+/// start_block + 1, start_block + 1 + block_step, start_block + 1 + 2*block_step, ...
+/// Proofs that expose state commitments are only submitted if they continue
+/// from the state the previous proof ends with, and proofs made with another
+/// circuit than the one of the verifier are never submitted.
+/// Every proof is checked against the verification key of the circuit before it
+/// is submitted: an invalid proof is marked as such and its batch is proven again.
+/// A proof that keeps failing is retried with a backoff and skipped once it is
+/// dead-lettered, as are the batches dead-lettered before they got a proof.
+/// Skipping a batch leaves a gap in the submitted states: it is logged and counted by the
+/// `pipeline.skipped_batches` metric, and the chain restarts from the proof after it.
+/// With an `aggregation_size` above 1, the proofs of that many consecutive batches
+/// are collected and submitted together to the batch verifier.
+pub struct VerifierGenerator<DB: DatabaseInterface, S: Settlement> {
     /// Connection to the database.
     database: DB,
     /// Routine refresh interval.
     rounds_interval: time::Duration,
 
-    start_block: BlockNumber,
+    /// Last verified block, the next proof starts right after it.
+    current_block: BlockNumber,
     block_step: BlockNumber,
+    /// Proofs submitted together.
+    aggregation_size: usize,
 
-    chain_url: String,
-    contract_address: String,
-    abi_path: String,
-    account: String,
-
+    settlement: S,
+    /// Circuit in the registry, only its proofs are submitted to `settlement`.
+    circuit: StorageCircuit,
+    /// Backend checking the proofs against the verification key of `circuit`.
+    backend: BackendParameter,
+    /// Commitments of the last verified proof.
+    last_commitments: Option<StateCommitments>,
+    /// Retry policy for the proofs that fail.
+    retry: Retry,
 }
 
-impl<DB: DatabaseInterface> VerifierGenerator<DB> {
+impl<DB: DatabaseInterface, S: Settlement> VerifierGenerator<DB, S> {
     /// Creates a new `WitnessGenerator` object.
     pub fn new(
         database: DB,
         rounds_interval: time::Duration,
         start_block: BlockNumber,
         block_step: BlockNumber,
-        chain_url: String,
-        contract_address: String,
-        abi_path: String,
-        account: String,
+        aggregation_size: u32,
+        settlement: S,
+        circuit: StorageCircuit,
+        backend: BackendParameter,
+        retry: Retry,
     ) -> Self {
         Self {
             database,
             rounds_interval,
-            start_block,
+            current_block: start_block,
             block_step,
-            chain_url,
-            contract_address,
-            abi_path,
-            account,
+            aggregation_size: aggregation_size.max(1) as usize,
+            settlement,
+            circuit,
+            backend,
+            last_commitments: None,
+            retry,
         }
     }
 
@@ -76,51 +104,419 @@ impl<DB: DatabaseInterface> VerifierGenerator<DB> {
             .expect("failed to start verifier server");
     }
 
-    async fn maintain(self) {
+    async fn maintain(mut self) {
         vlog::info!(
             "preparing verifier data routine started with start_block({}), block_step({})",
-            *self.start_block,
+            *self.current_block,
             *self.block_step
         );
 
         // Initialize counters for cache hits/misses.
 
-        let mut current_block = self.start_block;
+        self.last_commitments = self.load_commitments(self.current_block).await;
         loop {
             sleep(self.rounds_interval).await;
 
-            let mut storage = self.database.acquire_connection().await.unwrap();
-            let next_block = BlockNumber(*current_block + *self.block_step);
-            let proof_storage = self
-                .database
-                .load_proof(&mut storage,current_block+1)
-                .await
-                .unwrap();
+            if let Err(e) = self.verify_next_proof().await {
+                if let Some(delay) = self.record_failure(&e).await {
+                    sleep(delay).await;
+                }
+            }
+        }
+    }
+
+    /// Records that the proof following `current_block` failed with `error`, and skips its batch
+    /// once it is dead-lettered. Returns the delay before the proof is verified again.
+    async fn record_failure(&mut self, error: &PipelineError) -> Option<time::Duration> {
+        let proof_block = BlockNumber(*self.current_block + 1);
+        let delay = handle_failure(
+            &self.database,
+            &self.retry,
+            PipelineStage::Verification,
+            proof_block,
+            error,
+        )
+        .await;
+
+        if delay.is_none() {
+            if let Err(e) = self.skip_batch().await {
+                vlog::warn!("Failed to skip block {}: {}", *proof_block, e);
+            }
+        }
+        delay
+    }
+
+    /// Moves past the batch following `current_block` without verifying it.
+    /// The commitments of the last submitted proof are dropped, so the next proof is submitted
+    /// whatever state it starts from. The dead-lettered failure of the batch records the gap.
+    async fn skip_batch(&mut self) -> Result<(), PipelineError> {
+        let skipped_block = BlockNumber(*self.current_block + 1);
+        let next_block = BlockNumber(*self.current_block + *self.block_step);
+        self.database
+            .update_last_verified_proof_block_number(next_block)
+            .await?;
+
+        self.current_block = next_block;
+        metrics::increment_counter!("pipeline.skipped_batches");
+        match self.last_commitments.take() {
+            Some(commitments) => vlog::error!(
+                "Skipped block {}, the state {} is left behind and the chain restarts from the next proof",
+                *skipped_block,
+                commitments.post
+            ),
+            None => vlog::error!("Skipped block {}", *skipped_block),
+        }
+        Ok(())
+    }
+
+    /// Returns whether the witness or the proof of the batch starting at `block` has been dead-lettered.
+    async fn is_dead(&self, block: BlockNumber) -> Result<bool, PipelineError> {
+        self.is_dead_at(block, &[PipelineStage::Witness, PipelineStage::Proof])
+            .await
+    }
+
+    /// Returns whether the batch starting at `block` has been dead-lettered by one of `stages`.
+    async fn is_dead_at(
+        &self,
+        block: BlockNumber,
+        stages: &[PipelineStage],
+    ) -> Result<bool, PipelineError> {
+        for stage in stages.iter() {
+            let failure = self.database.load_block_failure(*stage, block).await?;
+            if failure.is_some_and(|failure| failure.f_dead) {
+                return Ok(true);
+            }
+        }
+
+        Ok(false)
+    }
+
+    /// Verifies the proof of the batch following `current_block`, along with the proofs of the
+    /// following batches up to `aggregation_size` proofs.
+    /// Returns `false` if a proof is not stored yet or has just been rejected by the local
+    /// verification, and an error if the first proof can't be verified.
+    /// Batches dead-lettered by the witness generator or the provers are skipped.
+    pub async fn verify_next_proof(&mut self) -> Result<bool, PipelineError> {
+        let first_block = BlockNumber(*self.current_block + 1);
+        let first = match self
+            .load_checked_proof(first_block, self.last_commitments.as_ref())
+            .await?
+        {
+            NextProof::Ready(proof) => proof,
+            NextProof::Rejected => return Ok(false),
+            NextProof::Missing => {
+                if self.is_dead(first_block).await? {
+                    vlog::warn!("Skipping dead-lettered block {}", *first_block);
+                    self.skip_batch().await?;
+                    return Ok(true);
+                }
+                return Ok(false);
+            }
+        };
+
+        let mut proofs = vec![first];
+        while proofs.len() < self.aggregation_size {
+            let last = proofs.last().unwrap();
+            let block = BlockNumber(*last.block + *self.block_step);
+            let last_commitments = last.commitments.clone();
+            match self.load_checked_proof(block, last_commitments.as_ref()).await {
+                Ok(NextProof::Ready(proof)) => proofs.push(proof),
+                Ok(NextProof::Missing) => {
+                    // The proofs after a dead-lettered batch are submitted once it is skipped.
+                    if self.is_dead(block).await? {
+                        break;
+                    }
+                    return Ok(false);
+                }
+                Ok(NextProof::Rejected) => return Ok(false),
+                // The proofs that can't be verified are left out, so that their failure is
+                // recorded once they come first.
+                Err(e) => {
+                    vlog::warn!("Submitting the proofs before block {}: {}", *block, e);
+                    break;
+                }
+            }
+        }
+
+        let last = proofs.last().unwrap();
+        let last_block = last.block;
+        let last_commitments = last.commitments.clone();
+        let submitted = if proofs.len() == 1 {
+            let proof = parse_proof(&proofs[0].proof.f_proof)?;
+            self.settlement.settle(first_block, proof).await
+        } else {
+            let parsed = proofs
+                .iter()
+                .map(|proof| parse_proof(&proof.proof.f_proof))
+                .collect::<Result<Vec<_>, _>>()?;
+            self.settlement.settle_batch(first_block, parsed).await
+        };
+        if !submitted.map_err(PipelineError::Submission)? {
+            return Err(PipelineError::Rejected);
+        }
+
+        if proofs.len() > 1 {
+            let proof_ids = proofs.iter().map(|proof| proof.proof.f_id).collect();
+            self.database
+                .store_aggregated_proof(first_block, last_block, proof_ids)
+                .await?;
+        }
+        let next_block = BlockNumber(*last_block - 1 + *self.block_step);
+        self.database
+            .update_last_verified_proof_block_number(next_block)
+            .await?;
+
+        // Update current block.
+        self.current_block = next_block;
+        self.last_commitments = last_commitments;
+        Ok(true)
+    }
+
+    /// Loads the proof of the batch starting at `block` and checks that it can be submitted after
+    /// a proof ending with `last_commitments`.
+    async fn load_checked_proof(
+        &self,
+        block: BlockNumber,
+        last_commitments: Option<&StateCommitments>,
+    ) -> Result<NextProof, PipelineError> {
+        let proof_storage = match self.database.load_proof(block).await? {
+            Some(proof_storage) => proof_storage,
+            None => return Ok(NextProof::Missing),
+        };
+
+        // The proofs stored before the registry was introduced have no circuit.
+        if let Some(found) = proof_storage.f_circuit_id {
+            if found != self.circuit.f_id {
+                return Err(PipelineError::CircuitMismatch {
+                    expected: self.circuit.f_id,
+                    found,
+                });
+            }
+        }
+
+        let proof = parse_proof(&proof_storage.f_proof)?;
 
-            if proof_storage.is_none() {
-                continue;
+        let commitments = StateCommitments::from_inputs(&proof.inputs);
+        if let (Some(last), Some(commitments)) = (last_commitments, &commitments) {
+            if !last.chains_to(commitments) {
+                return Err(PipelineError::BrokenChain {
+                    pre: commitments.pre.clone(),
+                    post: last.post.clone(),
+                });
             }
+        }
 
-            let proof: Proof<Bn128Field, G16> = serde_json::from_value(proof_storage.unwrap().f_proof).unwrap();
+        let valid = circuit::proof::verify_proof(
+            &self.backend,
+            &self.circuit.f_verification_key,
+            proof,
+        )
+        .map_err(PipelineError::LocalVerification)?;
+        if !valid {
+            self.reject_proof(block).await?;
+            return Ok(NextProof::Rejected);
+        }
+
+        Ok(NextProof::Ready(CheckedProof {
+            block,
+            proof: proof_storage,
+            commitments,
+        }))
+    }
 
+    /// Marks the proof of the batch starting at `block` as invalid, and returns the batch to the
+    /// provers after a backoff. The failure counts as a failed proof, so the batch is dead-lettered
+    /// once it got `retry.max_attempts` invalid proofs.
+    async fn reject_proof(&self, block: BlockNumber) -> Result<(), PipelineError> {
+        vlog::warn!("The proof of block {} is invalid, proving it again", *block);
+        metrics::increment_counter!("pipeline.failures", "stage" => PipelineStage::Proof.to_string());
 
-            let result = circuit::proof::call_verify(proof, self.chain_url.as_str(), self.contract_address.as_str(),self.abi_path.as_str(),self.account.as_str()).await;
-            if result {
-                let mut transaction = storage.start_transaction().await.unwrap();
-                self.database
-                    .update_last_verified_proof_block_number(
-                        &mut transaction,
-                        next_block,
-                    )
-                    .await.unwrap();
+        let failure = self
+            .database
+            .record_block_failure(
+                PipelineStage::Proof,
+                block,
+                "invalid proof",
+                self.retry.max_attempts,
+            )
+            .await?;
+        let retry_after = if failure.f_dead {
+            metrics::increment_counter!("pipeline.dead_letters", "stage" => PipelineStage::Proof.to_string());
+            None
+        } else {
+            Some(self.retry.backoff(failure.f_attempts as u32))
+        };
+        self.database.reject_proof(block, retry_after).await?;
 
-                transaction.commit().await.unwrap();
+        Ok(())
+    }
 
-                // Update current block.
-                current_block = next_block;
-            } else {
-                break;
+    /// Loads the state commitments of the proof of the batch ending at `current_block`.
+    /// There are none if that batch has been skipped, as the chain restarts after it.
+    async fn load_commitments(&self, current_block: BlockNumber) -> Option<StateCommitments> {
+        if *current_block < *self.block_step {
+            return None;
+        }
+        let last_block = BlockNumber(*current_block + 1 - *self.block_step);
+        let stages = [
+            PipelineStage::Witness,
+            PipelineStage::Proof,
+            PipelineStage::Verification,
+        ];
+        match self.is_dead_at(last_block, &stages).await {
+            Ok(false) => {}
+            Ok(true) => return None,
+            Err(e) => {
+                vlog::warn!(
+                    "Failed to load the failures of block {}: {}",
+                    *last_block,
+                    e
+                );
+                return None;
             }
         }
+
+        let proof_storage = match self.database.load_proof(last_block).await {
+            Ok(proof_storage) => proof_storage?,
+            Err(e) => {
+                vlog::warn!("Failed to load the proof of block {}: {}", *last_block, e);
+                return None;
+            }
+        };
+
+        // A malformed proof has been dead-lettered, so there is nothing to chain to.
+        let proof: Proof<Bn128Field, G16> = serde_json::from_value(proof_storage.f_proof).ok()?;
+        StateCommitments::from_inputs(&proof.inputs)
+    }
+}
+
+/// Outcome of loading the proof of a batch.
+enum NextProof {
+    /// The proof is not stored yet.
+    Missing,
+    /// The proof has just been rejected by the local verification.
+    Rejected,
+    Ready(CheckedProof),
+}
+
+/// Proof that can be submitted.
+struct CheckedProof {
+    /// First block of the proven batch.
+    block: BlockNumber,
+    proof: StoredProof,
+    /// Commitments the proof exposes.
+    commitments: Option<StateCommitments>,
+}
+
+fn parse_proof(proof: &serde_json::Value) -> Result<Proof<Bn128Field, G16>, PipelineError> {
+    serde_json::from_value(proof.clone()).map_err(|e| PipelineError::MalformedProof(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory_database::InMemoryDatabase;
+    use crate::settlement::MockSettlement;
+
+    /// Proof of `commitment.zok` going from the state `pre` to the state `post`.
+    fn proof(pre: &str, post: &str) -> String {
+        serde_json::json!({
+            "proof": {
+                "a": ["0x01", "0x02"],
+                "b": [["0x01", "0x02"], ["0x03", "0x04"]],
+                "c": ["0x01", "0x02"],
+            },
+            "inputs": [pre, post],
+        })
+        .to_string()
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn chain_restarts_after_a_skipped_batch() {
+        let database = InMemoryDatabase::new();
+        let circuit = database
+            .register_circuit("hash", serde_json::json!({}), serde_json::json!({}), None)
+            .await
+            .unwrap();
+        // The batch 2 doesn't start from the state the batch 1 ends with.
+        let states = [("0x01", "0x02"), ("0x05", "0x06"), ("0x06", "0x07")];
+        for (block, (pre, post)) in (1..).zip(states.iter()) {
+            database
+                .store_witness_job(
+                    BlockNumber(block),
+                    BlockNumber(block),
+                    b"witness".to_vec(),
+                    "out".into(),
+                    circuit.f_id,
+                )
+                .await
+                .unwrap();
+            let job = database
+                .get_idle_prover_job_from_job_queue("prover", circuit.f_id)
+                .await
+                .unwrap()
+                .unwrap();
+            assert!(database
+                .store_proof(job.f_id, "prover", proof(pre, post))
+                .await
+                .unwrap());
+        }
+
+        let settlement = MockSettlement::new(BackendParameter::Ark, serde_json::Value::Null);
+        let retry = Retry {
+            max_attempts: 1,
+            backoff: 0,
+            max_backoff: 0,
+        };
+        let mut verifier_generator = VerifierGenerator::new(
+            database.clone(),
+            time::Duration::from_millis(10),
+            BlockNumber(1),
+            BlockNumber(1),
+            1,
+            settlement.clone(),
+            circuit,
+            BackendParameter::Ark,
+            retry,
+        );
+        verifier_generator.last_commitments = verifier_generator
+            .load_commitments(verifier_generator.current_block)
+            .await;
+
+        // The proof with the broken chain is dead-lettered and its batch skipped.
+        let error = verifier_generator.verify_next_proof().await.unwrap_err();
+        assert!(matches!(error, PipelineError::BrokenChain { .. }));
+        assert_eq!(verifier_generator.record_failure(&error).await, None);
+        assert_eq!(*verifier_generator.current_block, 2);
+
+        assert!(verifier_generator.last_commitments.is_none());
+        assert_eq!(
+            database
+                .load_block_failure(PipelineStage::Verification, BlockNumber(2))
+                .await
+                .unwrap()
+                .map(|failure| failure.f_dead),
+            Some(true)
+        );
+
+        // The next proof isn't held to the state the last submitted one ends with: it goes on to
+        // the local verification, which the stand-in proofs don't pass.
+        let error = verifier_generator.verify_next_proof().await.unwrap_err();
+        assert!(matches!(error, PipelineError::LocalVerification(_)));
+        assert_eq!(*verifier_generator.current_block, 2);
+        assert!(settlement.submissions().is_empty());
+
+        // The chain restarts after the skipped batch after a restart as well.
+        assert!(verifier_generator
+            .load_commitments(BlockNumber(2))
+            .await
+            .is_none());
+        assert_eq!(
+            verifier_generator
+                .load_commitments(BlockNumber(1))
+                .await
+                .map(|commitments| commitments.post),
+            Some("0x02".to_string())
+        );
+    }
+}
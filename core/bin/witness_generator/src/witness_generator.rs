@@ -1,28 +1,46 @@
 use std::time::Instant;
 // Built-in
 use std::{thread, time};
-use std::collections::HashMap;
 // External
 use futures::channel::mpsc;
 use tokio::time::sleep;
 // Workspace deps
 use crate::database_interface::DatabaseInterface;
+use circuit::batch::BatchInput;
+use circuit::witness::WitnessOptions;
+use config::configs::prover::{Circuit, Retry};
+use storage::prover::PipelineStage;
 use types::BlockNumber;
 use utils::panic_notify::ThreadPanicNotify;
+// Local deps
+use crate::error::{handle_failure, PipelineError};
 
 /// The essential part of this structure is `maintain` function
 /// which runs forever and adds data to the database.
 ///
 /// This will generate and store in db witnesses for blocks with indexes
 /// start_block, start_block + block_step, start_block + 2*block_step, ...
+/// Each witness covers `block_step` consecutive traces, which must match
+/// the batch size of the circuit.
+/// A batch whose witness keeps failing is retried with a backoff and skipped
+/// once it is dead-lettered.
 pub struct WitnessGenerator<DB: DatabaseInterface> {
     /// Connection to the database.
     database: DB,
     /// Routine refresh interval.
     rounds_interval: time::Duration,
 
-    start_block: BlockNumber,
+    /// First block of the next witness.
+    current_block: BlockNumber,
     block_step: BlockNumber,
+    /// Circuit the witnesses are computed for.
+    circuit: Circuit,
+    /// Input shape of the circuit.
+    input: BatchInput,
+    /// Id of the circuit in the registry, stored with the witnesses.
+    circuit_id: i64,
+    /// Retry policy for the batches that fail.
+    retry: Retry,
 }
 
 impl<DB: DatabaseInterface> WitnessGenerator<DB> {
@@ -32,12 +50,20 @@ impl<DB: DatabaseInterface> WitnessGenerator<DB> {
         rounds_interval: time::Duration,
         start_block: BlockNumber,
         block_step: BlockNumber,
+        circuit: Circuit,
+        input: BatchInput,
+        circuit_id: i64,
+        retry: Retry,
     ) -> Self {
         Self {
             database,
             rounds_interval,
-            start_block,
+            current_block: start_block,
             block_step,
+            circuit,
+            input,
+            circuit_id,
+            retry,
         }
     }
 
@@ -59,10 +85,10 @@ impl<DB: DatabaseInterface> WitnessGenerator<DB> {
             .expect("failed to start provers server");
     }
 
-    async fn maintain(self) {
+    async fn maintain(mut self) {
         vlog::info!(
             "preparing prover data routine started with start_block({}), block_step({})",
-            *self.start_block,
+            *self.current_block,
             *self.block_step
         );
 
@@ -71,49 +97,79 @@ impl<DB: DatabaseInterface> WitnessGenerator<DB> {
         metrics::register_counter!("witness_generator.cache_access", "type" => "off_by_1");
         metrics::register_counter!("witness_generator.cache_access", "type" => "miss");
 
-        let mut current_block = self.start_block;
         loop {
             sleep(self.rounds_interval).await;
 
-            let mut storage = self.database.acquire_connection().await.unwrap();
-            let next_block = BlockNumber(*current_block + *self.block_step);
-            let trace = self
-                .database
-                .load_trace(&mut storage,current_block)
+            if let Err(e) = self.prepare_next_witness().await {
+                let current_block = self.current_block;
+                match handle_failure(
+                    &self.database,
+                    &self.retry,
+                    PipelineStage::Witness,
+                    current_block,
+                    &e,
+                )
                 .await
-                .unwrap();
-
-            if trace.is_none() {
-                continue;
+                {
+                    Some(delay) => sleep(delay).await,
+                    None => self.skip_batch().await,
+                }
             }
+        }
+    }
+
+    /// Moves past the batch starting at `current_block` without a witness.
+    async fn skip_batch(&mut self) {
+        let next_block = BlockNumber(*self.current_block + *self.block_step);
+        if let Err(e) = self
+            .database
+            .update_last_witness_block_number(next_block)
+            .await
+        {
+            // The batch is skipped again after a restart, as it stays dead-lettered.
+            vlog::warn!("Failed to skip block {}: {}", *self.current_block, e);
+        }
+        self.current_block = next_block;
+    }
 
-            let mut block_args = HashMap::new();
-            let circuit_path = std::env::var("CIRCUIT_FILE_PATH").unwrap();
-            let abi_path = std::env::var("CIRCUIT_ABI_FILE_PATH").unwrap();
-            block_args.insert("input".to_string(), circuit_path);
-            block_args.insert("abi-spec".to_string(), abi_path);
-            block_args.insert("arguments".to_string(), trace.unwrap());
-            let witness_str = circuit::witness::compute_witness(&block_args).unwrap();
+    /// Computes and stores the witness for the batch starting at `current_block` and
+    /// adds the job proving it. Returns `false` if the traces of the batch are not stored yet.
+    pub async fn prepare_next_witness(&mut self) -> Result<bool, PipelineError> {
+        let current_block = self.current_block;
+        let next_block = BlockNumber(*current_block + *self.block_step);
+        let last_block = BlockNumber(*next_block - 1);
+        let traces = self
+            .database
+            .load_traces(current_block, *self.block_step)
+            .await?;
 
-            let mut transaction = storage.start_transaction().await.unwrap();
-            self.database
-                .store_witness(
-                    &mut transaction,
-                    current_block,
-                    witness_str,
-                )
-                .await.unwrap();
-            self.database
-                .update_last_witness_block_number(
-                    &mut transaction,
-                    next_block,
-                )
-                .await.unwrap();
+        let traces = match traces {
+            Some(traces) => traces,
+            None => return Ok(false),
+        };
 
-            transaction.commit().await.unwrap();
+        let arguments = self
+            .input
+            .arguments(traces)
+            .map_err(PipelineError::Witness)?;
+        let options = WitnessOptions::new(&self.circuit.file_path)
+            .abi(&self.circuit.abi_file_path)
+            .arguments(arguments);
+        let witness =
+            circuit::witness::compute_witness(&options).map_err(PipelineError::Witness)?;
 
-            // Update current block.
-            current_block = next_block;
-        }
+        self.database
+            .store_witness_job(
+                current_block,
+                last_block,
+                witness.into_bytes(),
+                self.circuit.file_path.clone(),
+                self.circuit_id,
+            )
+            .await?;
+
+        // Update current block.
+        self.current_block = next_block;
+        Ok(true)
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,102 @@
+//! Storage of the witnesses in the blob store, see `blob_store`.
+//!
+//! A witness is written gzipped under a key derived from its circuit and first block,
+//! and `t_block_witness_cloud` only records the key and the hash of the witness.
+
+// Built-in
+use std::fmt;
+use std::sync::Arc;
+// Workspace uses
+use blob_store::BlobStore;
+use config::configs::blob_store::BlobStoreConfig;
+use storage::prover::records::StorageBlockWitnessCloud;
+use types::BlockNumber;
+
+/// Blob store holding the witnesses.
+#[derive(Clone)]
+pub struct WitnessStore {
+    store: Arc<dyn BlobStore>,
+}
+
+impl fmt::Debug for WitnessStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WitnessStore").finish()
+    }
+}
+
+impl WitnessStore {
+    pub fn new(store: Arc<dyn BlobStore>) -> Self {
+        Self { store }
+    }
+
+    /// Opens the blob store configured by `config`.
+    pub fn from_config(config: &BlobStoreConfig) -> anyhow::Result<Self> {
+        Ok(Self::new(blob_store::from_config(config)?))
+    }
+
+    /// Key of the witness of the batch starting at `first_block`.
+    pub fn key(first_block: BlockNumber, circuit_id: i64) -> String {
+        format!("witness/{}/{}", circuit_id, *first_block)
+    }
+
+    /// Writes the witness of the batch starting at `first_block`,
+    /// returns its key and the hash of its content.
+    pub async fn put(
+        &self,
+        first_block: BlockNumber,
+        circuit_id: i64,
+        witness: &[u8],
+    ) -> anyhow::Result<(String, String)> {
+        let key = Self::key(first_block, circuit_id);
+        let hash = blob_store::put_compressed(self.store.as_ref(), &key, witness).await?;
+
+        Ok((key, hash))
+    }
+
+    /// Reads the witness recorded in `record`.
+    /// The witnesses recorded before the blob store are read from the record itself.
+    pub async fn load(&self, record: StorageBlockWitnessCloud) -> anyhow::Result<Vec<u8>> {
+        match (record.f_content_hash, record.f_object_witness) {
+            (Some(hash), _) => {
+                blob_store::get_compressed(self.store.as_ref(), &record.f_object_key, &hash)
+                    .await?
+                    .ok_or_else(|| {
+                        anyhow::format_err!(
+                            "Witness of block {} is missing from the blob store",
+                            record.f_block
+                        )
+                    })
+            }
+            (None, Some(witness)) => Ok(hex::decode(witness)?),
+            (None, None) => anyhow::bail!("Witness of block {} has no content", record.f_block),
+        }
+    }
+
+    /// Reads the witness recorded in `record` as it is stored, gzipped,
+    /// returns it along with the hash of its content, see `blob_store::decompress`.
+    /// The witnesses recorded before the blob store are compressed on the fly.
+    pub async fn load_compressed(
+        &self,
+        record: StorageBlockWitnessCloud,
+    ) -> anyhow::Result<(Vec<u8>, String)> {
+        match (record.f_content_hash, record.f_object_witness) {
+            (Some(hash), _) => {
+                let compressed = self.store.get(&record.f_object_key).await?.ok_or_else(|| {
+                    anyhow::format_err!(
+                        "Witness of block {} is missing from the blob store",
+                        record.f_block
+                    )
+                })?;
+                Ok((compressed, hash))
+            }
+            (None, Some(witness)) => {
+                let witness = hex::decode(witness)?;
+                Ok((
+                    blob_store::compress(&witness)?,
+                    blob_store::content_hash(&witness),
+                ))
+            }
+            (None, None) => anyhow::bail!("Witness of block {} has no content", record.f_block),
+        }
+    }
+}
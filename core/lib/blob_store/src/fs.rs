@@ -0,0 +1,83 @@
+// Built-in
+use std::io::ErrorKind;
+use std::path::{Component, Path, PathBuf};
+// External
+use anyhow::Context;
+// Local
+use crate::BlobStore;
+
+/// Keeps the objects in files under a local directory, the keys being their relative paths.
+#[derive(Debug, Clone)]
+pub struct FsBlobStore {
+    root: PathBuf,
+}
+
+impl FsBlobStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    fn path(&self, key: &str) -> anyhow::Result<PathBuf> {
+        let relative = Path::new(key);
+        if !relative
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+        {
+            anyhow::bail!("Invalid object key {}", key);
+        }
+        Ok(self.root.join(relative))
+    }
+}
+
+#[async_trait::async_trait]
+impl BlobStore for FsBlobStore {
+    async fn put(&self, key: &str, data: Vec<u8>) -> anyhow::Result<()> {
+        let path = self.path(key)?;
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .with_context(|| format!("Could not create {}", parent.display()))?;
+        }
+        // Readers never see a partially written object.
+        let partial = path.with_extension("partial");
+        tokio::fs::write(&partial, data)
+            .await
+            .with_context(|| format!("Could not write {}", partial.display()))?;
+        tokio::fs::rename(&partial, &path)
+            .await
+            .with_context(|| format!("Could not write {}", path.display()))?;
+
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        let path = self.path(key)?;
+        match tokio::fs::read(&path).await {
+            Ok(data) => Ok(Some(data)),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e).with_context(|| format!("Could not read {}", path.display())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn objects_are_files() {
+        let dir = tempdir::TempDir::new("blob_store").unwrap();
+        let store = FsBlobStore::new(dir.path());
+
+        store.put("witness/1/5", vec![1, 2, 3]).await.unwrap();
+        assert_eq!(
+            std::fs::read(dir.path().join("witness/1/5")).unwrap(),
+            vec![1, 2, 3]
+        );
+        assert_eq!(store.get("witness/1/5").await.unwrap(), Some(vec![1, 2, 3]));
+        assert_eq!(store.get("witness/1/6").await.unwrap(), None);
+
+        assert!(store.put("../outside", vec![]).await.is_err());
+        assert!(store.get("/etc/passwd").await.is_err());
+    }
+}
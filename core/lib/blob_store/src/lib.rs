@@ -0,0 +1,145 @@
+//! Object stores for the large artifacts of the pipeline, such as the witnesses, which are kept
+//! out of the database. The database only records the key of an object and the hash of its content.
+//!
+//! `FsBlobStore` keeps the objects in a local directory, `S3BlobStore` in a bucket of an
+//! S3-compatible service and `MemoryBlobStore` in memory, for tests.
+
+// Built-in
+use std::io::{Read, Write};
+use std::sync::Arc;
+// External
+use anyhow::Context;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use sha2::{Digest, Sha256};
+// Workspace deps
+use config::configs::blob_store::{BlobStoreConfig, BlobStoreKind};
+
+pub use self::fs::FsBlobStore;
+pub use self::memory::MemoryBlobStore;
+pub use self::s3::S3BlobStore;
+
+mod fs;
+mod memory;
+mod s3;
+
+/// Store of binary objects addressed by a key.
+#[async_trait::async_trait]
+pub trait BlobStore: Send + Sync + 'static {
+    /// Writes `data` under `key`, replacing the object stored there if any.
+    async fn put(&self, key: &str, data: Vec<u8>) -> anyhow::Result<()>;
+
+    /// Reads the object stored under `key`, `None` if there is none.
+    async fn get(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>>;
+}
+
+/// Creates the store selected by `BLOB_STORE_KIND`.
+pub fn from_config(config: &BlobStoreConfig) -> anyhow::Result<Arc<dyn BlobStore>> {
+    match config.kind {
+        BlobStoreKind::Fs => {
+            let path = config
+                .path
+                .as_ref()
+                .context("BLOB_STORE_PATH is required by the fs store")?;
+            Ok(Arc::new(FsBlobStore::new(path)))
+        }
+        BlobStoreKind::S3 => {
+            let required = |value: &Option<String>, name: &str| {
+                value
+                    .clone()
+                    .with_context(|| format!("{} is required by the s3 store", name))
+            };
+            let store = S3BlobStore::new(
+                &required(&config.s3_endpoint, "BLOB_STORE_S3_ENDPOINT")?,
+                &required(&config.s3_region, "BLOB_STORE_S3_REGION")?,
+                &required(&config.s3_bucket, "BLOB_STORE_S3_BUCKET")?,
+                &required(&config.s3_access_key, "BLOB_STORE_S3_ACCESS_KEY")?,
+                &required(&config.s3_secret_key, "BLOB_STORE_S3_SECRET_KEY")?,
+            )?;
+            Ok(Arc::new(store))
+        }
+    }
+}
+
+/// Returns the SHA-256 of `data`, in hex.
+pub fn content_hash(data: &[u8]) -> String {
+    hex::encode(Sha256::digest(data))
+}
+
+/// Gzips `data`, the way `put_compressed` stores it.
+pub fn compress(data: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data)?;
+
+    Ok(encoder.finish()?)
+}
+
+/// Decompresses the content gzipped by `compress`, and checks that it has the hash `hash`.
+pub fn decompress(compressed: &[u8], hash: &str) -> anyhow::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    GzDecoder::new(compressed)
+        .read_to_end(&mut data)
+        .context("Could not decompress the content")?;
+
+    let actual = content_hash(&data);
+    if actual != hash {
+        anyhow::bail!("Content has the hash {}, expected {}", actual, hash);
+    }
+    Ok(data)
+}
+
+/// Writes `data` gzipped under `key`, returns the hash of the uncompressed content.
+pub async fn put_compressed(
+    store: &dyn BlobStore,
+    key: &str,
+    data: &[u8],
+) -> anyhow::Result<String> {
+    store.put(key, compress(data)?).await?;
+
+    Ok(content_hash(data))
+}
+
+/// Reads the object written by `put_compressed` under `key`, and checks that its content
+/// has the hash `hash`. Returns `None` if there is no object under `key`.
+pub async fn get_compressed(
+    store: &dyn BlobStore,
+    key: &str,
+    hash: &str,
+) -> anyhow::Result<Option<Vec<u8>>> {
+    match store.get(key).await? {
+        Some(compressed) => {
+            let data = decompress(&compressed, hash).with_context(|| format!("Invalid {}", key))?;
+            Ok(Some(data))
+        }
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn compressed_objects() {
+        let store = MemoryBlobStore::new();
+        let data = vec![7u8; 4096];
+
+        let hash = put_compressed(&store, "witness/1", &data).await.unwrap();
+        assert_eq!(hash, content_hash(&data));
+        assert!(store.get("witness/1").await.unwrap().unwrap().len() < data.len());
+        assert_eq!(
+            get_compressed(&store, "witness/1", &hash).await.unwrap(),
+            Some(data)
+        );
+        assert_eq!(
+            get_compressed(&store, "witness/2", &hash).await.unwrap(),
+            None
+        );
+
+        put_compressed(&store, "witness/1", &[1, 2, 3])
+            .await
+            .unwrap();
+        assert!(get_compressed(&store, "witness/1", &hash).await.is_err());
+    }
+}
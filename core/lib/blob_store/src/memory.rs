@@ -0,0 +1,42 @@
+// Built-in
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+// Local
+use crate::BlobStore;
+
+/// Keeps the objects in memory, for tests. The clones share the objects.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryBlobStore {
+    objects: Arc<Mutex<HashMap<String, Vec<u8>>>>,
+}
+
+impl MemoryBlobStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the keys of the stored objects, sorted.
+    pub fn keys(&self) -> Vec<String> {
+        let mut keys: Vec<_> = self.objects.lock().unwrap().keys().cloned().collect();
+        keys.sort();
+        keys
+    }
+
+    /// Drops the object stored under `key`, if any.
+    pub fn remove(&self, key: &str) {
+        self.objects.lock().unwrap().remove(key);
+    }
+}
+
+#[async_trait::async_trait]
+impl BlobStore for MemoryBlobStore {
+    async fn put(&self, key: &str, data: Vec<u8>) -> anyhow::Result<()> {
+        self.objects.lock().unwrap().insert(key.to_string(), data);
+
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        Ok(self.objects.lock().unwrap().get(key).cloned())
+    }
+}
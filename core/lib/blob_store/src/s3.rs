@@ -0,0 +1,49 @@
+// External
+use s3::creds::Credentials;
+use s3::error::S3Error;
+use s3::{Bucket, Region};
+// Local
+use crate::BlobStore;
+
+/// Keeps the objects in a bucket of an S3-compatible service, such as AWS S3 or MinIO.
+#[derive(Debug, Clone)]
+pub struct S3BlobStore {
+    bucket: Bucket,
+}
+
+impl S3BlobStore {
+    pub fn new(
+        endpoint: &str,
+        region: &str,
+        bucket: &str,
+        access_key: &str,
+        secret_key: &str,
+    ) -> anyhow::Result<Self> {
+        let region = Region::Custom {
+            region: region.to_string(),
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+        };
+        let credentials = Credentials::new(Some(access_key), Some(secret_key), None, None, None)?;
+        // MinIO and most S3-compatible services only serve the path-style URLs.
+        let bucket = Bucket::new(bucket, region, credentials)?.with_path_style();
+
+        Ok(Self { bucket })
+    }
+}
+
+#[async_trait::async_trait]
+impl BlobStore for S3BlobStore {
+    async fn put(&self, key: &str, data: Vec<u8>) -> anyhow::Result<()> {
+        self.bucket.put_object(key, &data).await?;
+
+        Ok(())
+    }
+
+    async fn get(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        match self.bucket.get_object(key).await {
+            Ok(response) => Ok(Some(response.bytes().to_vec())),
+            Err(S3Error::Http(404, _)) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
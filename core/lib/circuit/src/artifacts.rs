@@ -0,0 +1,71 @@
+//! Identity of a compiled circuit, as recorded in the circuit registry.
+//!
+//! A circuit is identified by the hash of its compiled program. The ABI and the
+//! verification key are registered along with it, so that the witnesses and proofs
+//! made with different artifacts can't be mixed.
+
+// Built-in deps
+use std::fs::{self, File};
+use std::io;
+use std::path::Path;
+// External imports
+use sha2::{Digest, Sha256};
+
+/// Artifacts of a compiled circuit.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CircuitArtifacts {
+    /// SHA-256 of the compiled program, in hex.
+    pub program_hash: String,
+    pub abi: serde_json::Value,
+    pub verification_key: serde_json::Value,
+}
+
+impl CircuitArtifacts {
+    /// Loads the artifacts produced by `zokrates compile` and `zokrates setup`.
+    pub fn load(
+        program_path: &Path,
+        abi_path: &Path,
+        verification_key_path: &Path,
+    ) -> Result<Self, String> {
+        Ok(Self {
+            program_hash: program_hash(program_path)?,
+            abi: read_json(abi_path)?,
+            verification_key: read_json(verification_key_path)?,
+        })
+    }
+}
+
+/// Returns the SHA-256 of the compiled program at `program_path`, in hex.
+pub fn program_hash(program_path: &Path) -> Result<String, String> {
+    let mut file = File::open(program_path)
+        .map_err(|why| format!("Could not open {}: {}", program_path.display(), why))?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher)
+        .map_err(|why| format!("Could not read {}: {}", program_path.display(), why))?;
+
+    Ok(hex::encode(hasher.finalize()))
+}
+
+fn read_json(path: &Path) -> Result<serde_json::Value, String> {
+    let file =
+        fs::read(path).map_err(|why| format!("Could not open {}: {}", path.display(), why))?;
+    serde_json::from_slice(&file).map_err(|why| format!("Invalid {}: {}", path.display(), why))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn program_hash_of_file() {
+        let dir = tempdir::TempDir::new("artifacts").unwrap();
+        let path = dir.path().join("out");
+        fs::write(&path, b"abc").unwrap();
+
+        assert_eq!(
+            program_hash(&path).unwrap(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert!(program_hash(&dir.path().join("missing")).is_err());
+    }
+}
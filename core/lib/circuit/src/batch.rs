@@ -0,0 +1,270 @@
+//! Grouping of consecutive trace records into the input of a single circuit run.
+//!
+//! `mips_vm_poseidon.zok` takes one `trace_record`, while the circuits generated for a batch size
+//! of N (see [`crate::generator`]) take `trace_record[N]` and check that the records form a
+//! continuous execution. The offline memory circuit takes the witness [`OfflineBatch`] built from
+//! the records instead.
+
+// Built-in deps
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+// Workspace imports
+use mips_vm::memory::PROOF_SIZE;
+use mips_vm::offline::OfflineBatch;
+use mips_vm::TraceRecord;
+use zokrates_ast::typed::abi::Abi;
+use zokrates_ast::typed::types::ConcreteStructType;
+use zokrates_ast::typed::ConcreteType;
+use zokrates_field::Bn128Field;
+
+/// Name of the argument of `offline_memory.zok`.
+const OFFLINE_BATCH: &str = "offline_batch";
+
+/// Shape of the input of a MIPS circuit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BatchInput {
+    /// A single `trace_record`.
+    Record,
+    /// An array of `trace_record`s of the given size.
+    Records(u32),
+    /// The `offline_batch` of `batch_size` records accessing at most `max_leaves` leaves.
+    Offline { batch_size: u32, max_leaves: u32 },
+}
+
+impl BatchInput {
+    /// Reads the input shape from the ABI of the compiled circuit at `abi_path`.
+    pub fn from_abi(abi_path: &Path) -> Result<Self, String> {
+        let inputs = read_inputs(abi_path)?;
+        match inputs.as_slice() {
+            [ConcreteType::Struct(batch)] if batch.name() == OFFLINE_BATCH => {
+                match (array_size(batch, "records"), array_size(batch, "boundary")) {
+                    (Some(batch_size), Some(max_leaves)) => Ok(Self::Offline {
+                        batch_size,
+                        max_leaves,
+                    }),
+                    _ => Err(format!(
+                        "{} is not the ABI of an offline memory circuit",
+                        abi_path.display()
+                    )),
+                }
+            }
+            [ConcreteType::Struct(_)] => Ok(Self::Record),
+            [ConcreteType::Array(array)] if matches!(*array.ty, ConcreteType::Struct(_)) => {
+                Ok(Self::Records(*array.size))
+            }
+            _ => Err(format!(
+                "{} is not the ABI of a MIPS circuit",
+                abi_path.display()
+            )),
+        }
+    }
+
+    /// Returns how many trace records the circuit takes.
+    pub fn batch_size(&self) -> u32 {
+        match self {
+            Self::Record => 1,
+            Self::Records(size) => *size,
+            Self::Offline { batch_size, .. } => *batch_size,
+        }
+    }
+
+    /// Returns the maximum number of leaves of the offline memory circuit, `None` for the others.
+    pub fn max_leaves(&self) -> Option<u32> {
+        match self {
+            Self::Offline { max_leaves, .. } => Some(*max_leaves),
+            _ => None,
+        }
+    }
+
+    /// Builds the witness arguments for the trace records of one batch.
+    pub fn arguments(&self, mut traces: Vec<serde_json::Value>) -> Result<String, String> {
+        let arguments = match self {
+            Self::Record => traces.remove(0),
+            Self::Records(_) => serde_json::Value::Array(traces),
+            Self::Offline { max_leaves, .. } => {
+                let records = traces
+                    .into_iter()
+                    .map(serde_json::from_value)
+                    .collect::<Result<Vec<TraceRecord>, _>>()
+                    .map_err(|why| format!("Invalid trace record: {}", why))?;
+                let batch = OfflineBatch::new(&records, *max_leaves as usize)
+                    .map_err(|why| why.to_string())?;
+                serde_json::to_value(&batch).map_err(|why| why.to_string())?
+            }
+        };
+
+        Ok(arguments.to_string())
+    }
+}
+
+/// Type of the trace records taken by a MIPS circuit, to check them before they are stored.
+/// `None` for the offline memory circuit, whose ABI has no trace record: the records are parsed
+/// as the [`TraceRecord`]s its witness is built from.
+#[derive(Debug, Clone)]
+pub struct TraceRecordType(Option<ConcreteType>);
+
+impl TraceRecordType {
+    /// Reads the record type from the ABI of the compiled circuit at `abi_path`.
+    pub fn from_abi(abi_path: &Path) -> Result<Self, String> {
+        let inputs = read_inputs(abi_path)?;
+        match inputs.as_slice() {
+            [ConcreteType::Struct(batch)] if batch.name() == OFFLINE_BATCH => Ok(Self(None)),
+            [record @ ConcreteType::Struct(_)] => Ok(Self(Some(record.clone()))),
+            [ConcreteType::Array(array)] if matches!(*array.ty, ConcreteType::Struct(_)) => {
+                Ok(Self(Some((*array.ty).clone())))
+            }
+            _ => Err(format!(
+                "{} is not the ABI of a MIPS circuit",
+                abi_path.display()
+            )),
+        }
+    }
+
+    /// Checks that `record` can be passed to the circuit as a trace record.
+    pub fn check(&self, record: &serde_json::Value) -> Result<(), String> {
+        let record_type = match &self.0 {
+            Some(record_type) => record_type,
+            None => {
+                let record: TraceRecord =
+                    serde_json::from_value(record.clone()).map_err(|why| why.to_string())?;
+                if record.insn_proof.len() != PROOF_SIZE || record.mem_proof.len() != PROOF_SIZE {
+                    return Err(format!("the proofs must have {} bytes", PROOF_SIZE));
+                }
+                return Ok(());
+            }
+        };
+
+        zokrates_abi::parse_strict_json::<Bn128Field>(
+            vec![record.clone()],
+            vec![record_type.clone()],
+        )
+        .map(|_| ())
+        .map_err(|why| why.to_string())
+    }
+}
+
+/// Returns the size of the array `member` of `batch`.
+fn array_size(batch: &ConcreteStructType, member: &str) -> Option<u32> {
+    batch
+        .iter()
+        .find(|m| m.id == member)
+        .and_then(|m| match &*m.ty {
+            ConcreteType::Array(array) => Some(*array.size),
+            _ => None,
+        })
+}
+
+fn read_inputs(abi_path: &Path) -> Result<Vec<ConcreteType>, String> {
+    let file = File::open(abi_path)
+        .map_err(|why| format!("Could not open {}: {}", abi_path.display(), why))?;
+    let abi: Abi = serde_json::from_reader(BufReader::new(file)).map_err(|why| why.to_string())?;
+
+    Ok(abi.signature().inputs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn arguments() {
+        let trace = json!({ "pc": "0x00400000" });
+
+        assert_eq!(
+            BatchInput::Record.arguments(vec![trace.clone()]),
+            Ok(trace.to_string())
+        );
+        assert_eq!(
+            BatchInput::Records(1).arguments(vec![trace.clone()]),
+            Ok(json!([trace]).to_string())
+        );
+        assert_eq!(
+            BatchInput::Records(2).arguments(vec![trace.clone(), trace.clone()]),
+            Ok(json!([trace, trace]).to_string())
+        );
+    }
+
+    /// The records of a store and a load of the word at 0x100, as they are stored.
+    fn offline_traces() -> Vec<serde_json::Value> {
+        use mips_vm::state::HEAP_START;
+        use mips_vm::{Executor, Memory, NoPreimages, State};
+
+        let program = [
+            0x2408_0005, // addiu $t0, $zero, 5
+            0xac08_0100, // sw    $t0, 0x100($zero)
+            0x8c09_0100, // lw    $t1, 0x100($zero)
+        ];
+        let mut memory = Memory::new();
+        for (i, insn) in program.iter().enumerate() {
+            memory.set_word(0x0040_0000 + 4 * i as u32, *insn).unwrap();
+        }
+        let state = State {
+            cycle: 0,
+            pc: 0x0040_0000,
+            next_pc: 0x0040_0004,
+            lo: 0,
+            hi: 0,
+            regs: [0; 32],
+            heap: HEAP_START,
+            exit_code: 0,
+            exited: false,
+            memory,
+        };
+        let mut executor = Executor::new(state, NoPreimages);
+        (0..program.len())
+            .map(|_| serde_json::to_value(executor.step().unwrap().unwrap()).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn offline_arguments() {
+        let traces = offline_traces();
+        let input = BatchInput::Offline {
+            batch_size: 3,
+            max_leaves: 2,
+        };
+        let arguments: serde_json::Value =
+            serde_json::from_str(&input.arguments(traces.clone()).unwrap()).unwrap();
+
+        assert_eq!(arguments["records"].as_array().unwrap().len(), 3);
+        assert_eq!(arguments["sorted"].as_array().unwrap().len(), 6);
+        assert_eq!(arguments["boundary"].as_array().unwrap().len(), 2);
+
+        // the records don't follow each other
+        let mut gap = traces.clone();
+        gap.remove(1);
+        assert!(input.arguments(gap).is_err());
+        assert!(input
+            .arguments(vec![json!({ "pc": "0x00400000" })])
+            .is_err());
+
+        let record = TraceRecordType(None);
+        assert!(record.check(&traces[0]).is_ok());
+        assert!(record.check(&json!({ "pc": "0x00400000" })).is_err());
+        let mut short = traces[0].clone();
+        short["insn_proof"].as_array_mut().unwrap().pop();
+        assert!(record.check(&short).is_err());
+    }
+
+    #[test]
+    fn check_record() {
+        use zokrates_ast::typed::types::{ConcreteStructMember, ConcreteStructType, UBitwidth};
+
+        let record = TraceRecordType(Some(ConcreteType::Struct(ConcreteStructType::new(
+            "".into(),
+            "trace_record".into(),
+            vec![],
+            vec![ConcreteStructMember::new(
+                "pc".into(),
+                ConcreteType::Uint(UBitwidth::B32),
+            )],
+        ))));
+
+        assert!(record.check(&json!({ "pc": "0x00400000" })).is_ok());
+        assert!(record.check(&json!({ "pc": 4194304 })).is_err());
+        assert!(record.check(&json!({ "cycle": "0x00000000" })).is_err());
+        assert!(record.check(&json!([{ "pc": "0x00400000" }])).is_err());
+    }
+}
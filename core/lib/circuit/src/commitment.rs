@@ -0,0 +1,56 @@
+//! Chaining of the proofs of `commitment.zok`.
+//!
+//! `commitment.zok` returns the Poseidon commitments to the state before and after the proven
+//! batch (see `mips_vm::commitment`). Return values are public, so they end up as the last two
+//! public inputs of the proof.
+
+/// State commitments exposed by a proof.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StateCommitments {
+    /// Commitment to the state the proven batch starts from.
+    pub pre: String,
+    /// Commitment to the state the proven batch ends with.
+    pub post: String,
+}
+
+impl StateCommitments {
+    /// Extracts the commitments from the public inputs of a proof of `commitment.zok`.
+    /// Returns `None` for the proofs of circuits without commitments.
+    pub fn from_inputs(inputs: &[String]) -> Option<Self> {
+        match inputs {
+            [pre, post] => Some(Self {
+                pre: pre.to_lowercase(),
+                post: post.to_lowercase(),
+            }),
+            _ => None,
+        }
+    }
+
+    /// Checks that `next` starts from the state this batch ends with.
+    pub fn chains_to(&self, next: &StateCommitments) -> bool {
+        self.post == next.pre
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn commitments(pre: &str, post: &str) -> StateCommitments {
+        StateCommitments::from_inputs(&[pre.to_string(), post.to_string()]).unwrap()
+    }
+
+    #[test]
+    fn from_inputs() {
+        assert_eq!(StateCommitments::from_inputs(&["0x01".to_string()]), None);
+        assert_eq!(commitments("0x0A", "0x0b").pre, "0x0a");
+    }
+
+    #[test]
+    fn chains_to() {
+        let first = commitments("0x01", "0x02");
+
+        assert!(first.chains_to(&commitments("0x02", "0x03")));
+        assert!(!first.chains_to(&commitments("0x01", "0x02")));
+    }
+}
@@ -0,0 +1,380 @@
+//! Differential testing of `mips_vm_poseidon.zok` against the Rust model in `mips_vm::model`.
+//!
+//! `differential.zok` runs the step function of the circuit on a `mips_states` input and returns
+//! the computed state instead of asserting it. [`fuzz`] feeds random instructions through both
+//! the compiled circuit and [`model::step`] and reports every field on which they disagree.
+
+// Built-in deps
+use std::fmt;
+use std::path::Path;
+// External imports
+use rand_0_8::rngs::StdRng;
+use rand_0_8::{Rng, SeedableRng};
+use typed_arena::Arena;
+// Workspace imports
+use mips_vm::cpu::{PREIMAGE_KEY_ADDR, PREIMAGE_MAX_SIZE, SYS_PREIMAGE};
+use mips_vm::memory::empty_proof;
+use mips_vm::model::{self, MipsStates, States};
+use mips_vm::state::HEAP_START;
+use mips_vm::{preimage_key, Memory, PreimageData};
+use zokrates_abi::{Decode, Encode};
+use zokrates_ast::ir;
+use zokrates_ast::typed::abi::Abi;
+use zokrates_common::CompileConfig;
+use zokrates_core::compile::compile;
+use zokrates_field::Bn128Field;
+use zokrates_fs_resolver::FileSystemResolver;
+
+/// A circuit compiled in memory, which is run by the interpreter on JSON arguments.
+pub struct CompiledCircuit<'ast> {
+    program: ir::Prog<'ast, Bn128Field>,
+    abi: Abi,
+}
+
+impl<'ast> CompiledCircuit<'ast> {
+    /// Compiles the circuit at `entry_point`.
+    pub fn compile(
+        entry_point: &Path,
+        stdlib: &Path,
+        arena: &'ast Arena<String>,
+    ) -> Result<Self, String> {
+        let source = std::fs::read_to_string(entry_point)
+            .map_err(|why| format!("Could not open {}: {}", entry_point.display(), why))?;
+        let stdlib = stdlib
+            .to_str()
+            .ok_or_else(|| format!("Invalid stdlib path: {}", stdlib.display()))?;
+        let resolver = FileSystemResolver::with_stdlib_root(stdlib);
+
+        let artifacts = compile::<Bn128Field, _>(
+            source,
+            entry_point.to_path_buf(),
+            Some(&resolver),
+            CompileConfig::default(),
+            arena,
+        )
+        .map_err(|e| {
+            e.0.iter()
+                .map(|e| format!("{}:{}", e.file().display(), e.value()))
+                .collect::<Vec<_>>()
+                .join("\n")
+        })?;
+        let (program, abi) = artifacts.into_inner();
+
+        Ok(Self {
+            program: program.collect(),
+            abi,
+        })
+    }
+
+    /// Returns the number of constraints of the circuit, along with the share of every source
+    /// location in it as printed by `zokrates profile`.
+    pub fn profile(&self) -> (usize, String) {
+        let heat_map = zokrates_profiler::profile(self.program.clone());
+        (heat_map.count(), heat_map.display(&self.program.module_map))
+    }
+
+    /// Writes the program and its ABI like `zokrates compile` does, and returns the number of
+    /// constraints of the program.
+    pub fn write(&self, program_path: &Path, abi_path: &Path) -> Result<usize, String> {
+        let abi = serde_json::to_vec_pretty(&self.abi).map_err(|e| e.to_string())?;
+        std::fs::write(abi_path, abi)
+            .map_err(|why| format!("Could not write {}: {}", abi_path.display(), why))?;
+
+        let file = std::fs::File::create(program_path)
+            .map_err(|why| format!("Could not create {}: {}", program_path.display(), why))?;
+        self.program
+            .clone()
+            .serialize(std::io::BufWriter::new(file))
+            .map_err(|why| format!("Could not write {}: {}", program_path.display(), why))
+    }
+
+    /// Runs the circuit on the ABI encoded `arguments` and returns its ABI encoded output.
+    /// An error means that the circuit rejects the input, e.g. because one of its asserts fails.
+    pub fn execute(&self, arguments: Vec<serde_json::Value>) -> Result<serde_json::Value, String> {
+        let signature = self.abi.signature();
+
+        let arguments = zokrates_abi::parse_strict_json::<Bn128Field>(arguments, signature.inputs)
+            .map_err(|e| e.to_string())?
+            .encode();
+
+        let witness = zokrates_interpreter::Interpreter::default()
+            .execute(
+                &arguments,
+                self.program.statements.iter(),
+                &self.program.arguments,
+                &self.program.solvers,
+            )
+            .map_err(|e| format!("Execution failed: {}", e))?;
+
+        Ok(
+            zokrates_abi::Value::decode(witness.return_values(), *signature.output)
+                .into_serde_json(),
+        )
+    }
+}
+
+/// The compiled `differential.zok`.
+pub struct StepCircuit<'ast>(CompiledCircuit<'ast>);
+
+impl<'ast> StepCircuit<'ast> {
+    /// Compiles the harness at `entry_point`, which may take several minutes.
+    pub fn compile(
+        entry_point: &Path,
+        stdlib: &Path,
+        arena: &'ast Arena<String>,
+    ) -> Result<Self, String> {
+        CompiledCircuit::compile(entry_point, stdlib, arena).map(Self)
+    }
+
+    /// Runs one step in the circuit and returns the computed state along with the `illegal` flag.
+    /// An error means that the circuit rejects the input, e.g. because one of its asserts fails.
+    pub fn run(&self, input: &MipsStates) -> Result<(States, bool), String> {
+        let values = vec![serde_json::to_value(input).map_err(|e| e.to_string())?];
+
+        let output = self.0.execute(values)?;
+        serde_json::from_value(output).map_err(|e| format!("Unexpected circuit output: {}", e))
+    }
+}
+
+/// A field on which the model and the circuit disagree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    pub field: String,
+    pub model: String,
+    pub circuit: String,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: model {}, circuit {}",
+            self.field, self.model, self.circuit
+        )
+    }
+}
+
+/// A random input on which the model and the circuit disagree.
+#[derive(Debug, Clone)]
+pub struct CaseReport {
+    pub insn: u32,
+    pub input: MipsStates,
+    pub divergences: Vec<Divergence>,
+}
+
+impl fmt::Display for CaseReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "insn {:#010x} (opcode {:#04x}, funct {:#04x}) at pc {:#010x}",
+            self.insn,
+            self.insn >> 26,
+            self.insn & 0x3F,
+            self.input.states.pc
+        )?;
+        for divergence in &self.divergences {
+            writeln!(f, "  {}", divergence)?;
+        }
+        Ok(())
+    }
+}
+
+/// Lists the fields of the model result that differ from the circuit result.
+/// If either side rejects the instruction, only that decision is compared.
+pub fn compare(model: &MipsStates, circuit: &Result<(States, bool), String>) -> Vec<Divergence> {
+    let (circuit_states, circuit_illegal) = match circuit {
+        Ok((states, illegal)) => (Some(states), *illegal),
+        Err(_) => (None, true),
+    };
+
+    let mut divergences = Vec::new();
+    let mut check = |field: String, model: String, circuit: String| {
+        if model != circuit {
+            divergences.push(Divergence {
+                field,
+                model,
+                circuit,
+            });
+        }
+    };
+
+    if model.illegal || circuit_illegal {
+        let circuit = match circuit {
+            Err(e) => format!("rejected ({})", e),
+            Ok(_) => circuit_illegal.to_string(),
+        };
+        check("illegal".into(), model.illegal.to_string(), circuit);
+        return divergences;
+    }
+
+    let (m, c) = (
+        &model.states,
+        circuit_states.expect("circuit accepted the input"),
+    );
+    check("cycle".into(), m.cycle.to_string(), c.cycle.to_string());
+    check("pc".into(), hex32(m.pc), hex32(c.pc));
+    check("nextPC".into(), hex32(m.next_pc), hex32(c.next_pc));
+    check("lo".into(), hex32(m.lo), hex32(c.lo));
+    check("hi".into(), hex32(m.hi), hex32(c.hi));
+    for (i, (a, b)) in m.regs.iter().zip(c.regs.iter()).enumerate() {
+        check(format!("regs[{}]", i), hex32(*a), hex32(*b));
+    }
+    check("heap".into(), hex32(m.heap), hex32(c.heap));
+    check(
+        "exitCode".into(),
+        m.exit_code.to_string(),
+        c.exit_code.to_string(),
+    );
+    check("exited".into(), m.exited.to_string(), c.exited.to_string());
+    check(
+        "memRoot".into(),
+        hex::encode(m.mem_root),
+        hex::encode(c.mem_root),
+    );
+
+    divergences
+}
+
+fn hex32(value: u32) -> String {
+    format!("{:#010x}", value)
+}
+
+/// Runs `iterations` random instructions through the model and the circuit and returns the
+/// cases on which they disagree.
+pub fn fuzz(circuit: &StepCircuit, iterations: usize, seed: u64) -> Vec<CaseReport> {
+    let mut rng = StdRng::seed_from_u64(seed);
+
+    (0..iterations)
+        .filter_map(|_| {
+            let (insn, input) = random_case(&mut rng);
+            let expected = model::step(input.clone());
+            let actual = circuit.run(&input);
+
+            let divergences = compare(&expected, &actual);
+            if divergences.is_empty() {
+                None
+            } else {
+                vlog::warn!("Divergence on instruction {:#010x}", insn);
+                Some(CaseReport {
+                    insn,
+                    input,
+                    divergences,
+                })
+            }
+        })
+        .collect()
+}
+
+const SYSCALLS: [u32; 18] = [
+    4090, 4045, 4120, 4246, 4003, 4004, 4055, 4020, 4001, 4005, 4238, 4162, 4166, 4222, 4218, 4263,
+    4288, 4353,
+];
+
+/// Register values that tend to hit the corner cases of comparisons and shifts.
+fn random_value(rng: &mut StdRng) -> u32 {
+    const EDGES: [u32; 6] = [0, 1, 0x7FFF_FFFF, 0x8000_0000, 0x8000_0001, 0xFFFF_FFFF];
+    if rng.gen_bool(0.25) {
+        EDGES[rng.gen_range(0..EDGES.len())]
+    } else {
+        rng.gen()
+    }
+}
+
+fn random_insn(rng: &mut StdRng) -> u32 {
+    const FUNCTS: [u32; 29] = [
+        0x00, 0x02, 0x03, 0x04, 0x06, 0x07, 0x08, 0x09, 0x0A, 0x0B, 0x0C, 0x10, 0x11, 0x12, 0x13,
+        0x18, 0x19, 0x1A, 0x1B, 0x20, 0x21, 0x22, 0x23, 0x24, 0x25, 0x26, 0x27, 0x2A, 0x2B,
+    ];
+    const OPCODES: [u32; 28] = [
+        0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0A, 0x0B, 0x0C, 0x0D, 0x0E, 0x0F,
+        0x20, 0x21, 0x22, 0x23, 0x24, 0x25, 0x26, 0x28, 0x29, 0x2A, 0x2B, 0x2E, 0x30,
+    ];
+    const SPECIAL2: [u32; 3] = [0x02, 0x20, 0x21];
+
+    let fields = rng.gen::<u32>() & 0x03FF_FFFF;
+    match rng.gen_range(0..10) {
+        // R-type
+        0..=3 => (fields & !0x3F) | FUNCTS[rng.gen_range(0..FUNCTS.len())],
+        4 => (0x1C << 26) | (fields & !0x3F) | SPECIAL2[rng.gen_range(0..SPECIAL2.len())],
+        5 => (0x38 << 26) | fields,
+        // bltz / bgez
+        6 => (0x01 << 26) | (fields & !(0x1F << 16)) | (rng.gen_range(0..2) << 16),
+        _ => (OPCODES[rng.gen_range(0..OPCODES.len())] << 26) | fields,
+    }
+}
+
+/// Builds a random, consistent `mips_states` input around a random instruction.
+fn random_case(rng: &mut StdRng) -> (u32, MipsStates) {
+    let insn = random_insn(rng);
+    let pc = 0x0040_0000 + 4 * rng.gen_range(0..0x4_0000);
+
+    let mut regs = [0u32; 32];
+    for reg in regs.iter_mut().skip(1) {
+        *reg = random_value(rng);
+    }
+    if insn >> 26 == 0 && insn & 0x3F == 0x0C {
+        regs[2] = if rng.gen_bool(0.9) {
+            SYSCALLS[rng.gen_range(0..SYSCALLS.len())]
+        } else {
+            rng.gen_range(4000..4400)
+        };
+    }
+
+    let mut memory = Memory::new();
+    memory.set_word(pc, insn).expect("pc is aligned");
+
+    let opcode = insn >> 26;
+    let mut mem_proof = empty_proof();
+    if opcode >= 0x20 {
+        let base = ((insn >> 21) & 0x1F) as usize;
+        let offset = (insn & 0xFFFF) as u16 as i16 as i32 as u32;
+        let mut addr = regs[base].wrapping_add(offset) & !3;
+        // keep the instruction out of the way of the data word
+        while base != 0 && addr >> 5 == pc >> 5 {
+            regs[base] = random_value(rng);
+            addr = regs[base].wrapping_add(offset) & !3;
+        }
+        if addr >> 5 != pc >> 5 {
+            memory.set_word(addr, rng.gen()).expect("addr is aligned");
+        }
+        mem_proof = memory.merkle_proof(addr);
+    }
+
+    // A preimage syscall gets the preimage of its key most of the time, or a wrong one.
+    let mut preimage = PreimageData::default();
+    if insn >> 26 == 0 && insn & 0x3F == 0x0C && regs[2] == SYS_PREIMAGE {
+        let data: Vec<u8> = (0..rng.gen_range(0..=PREIMAGE_MAX_SIZE))
+            .map(|_| rng.gen())
+            .collect();
+        let key = if rng.gen_bool(0.8) {
+            preimage_key(&data)
+        } else {
+            rng.gen()
+        };
+        memory.set_memory_range(PREIMAGE_KEY_ADDR, &key);
+        mem_proof = memory.merkle_proof(PREIMAGE_KEY_ADDR);
+        preimage = PreimageData::new(&mut memory, &data);
+    }
+
+    let states = States {
+        cycle: rng.gen_range(0..0x1000_0000),
+        pc,
+        next_pc: pc + 4,
+        lo: random_value(rng),
+        hi: random_value(rng),
+        regs,
+        heap: HEAP_START + 0x1000 * rng.gen_range(0..0x1000),
+        exit_code: 0,
+        exited: false,
+        mem_root: memory.root(),
+    };
+    let insn_proof = memory.merkle_proof(pc);
+
+    (
+        insn,
+        MipsStates {
+            preimage,
+            ..MipsStates::new(states, insn_proof, mem_proof)
+        },
+    )
+}
@@ -0,0 +1,481 @@
+//! Generation of the MIPS circuit family from `mips_vm_poseidon.zok`.
+//!
+//! `mips_vm_poseidon.zok` is both the single record circuit and the template of the others:
+//! [`CircuitParams::sources`] rewrites its `main` for the batch size, its `hashPair` for the
+//! hash of the memory tree and the gadgets of the disabled features. [`CircuitCache`] compiles
+//! the generated sources in-process and keeps the artifacts of every set of parameters, so that
+//! a fix to the template is made once for all the circuits.
+
+// Built-in deps
+use std::fs;
+use std::path::{Path, PathBuf};
+// External imports
+use sha2::{Digest, Sha256};
+use typed_arena::Arena;
+// Workspace imports
+pub use mips_vm::memory::MemoryHash;
+// Local imports
+use crate::differential::CompiledCircuit;
+
+/// The template of the circuits, written along with the entry point as `mips_vm_poseidon.zok`
+/// since `commitment.zok` and `state_commitment.zok` import it by this name.
+const TEMPLATE: &str = include_str!("../mips_vm_poseidon.zok");
+const TEMPLATE_FILE: &str = "mips_vm_poseidon.zok";
+const COMMITMENT: &str = include_str!("../commitment.zok");
+const COMMITMENT_FILE: &str = "commitment.zok";
+const STATE_COMMITMENT: &str = include_str!("../state_commitment.zok");
+const STATE_COMMITMENT_FILE: &str = "state_commitment.zok";
+const OFFLINE_MEMORY: &str = include_str!("../offline_memory.zok");
+const OFFLINE_MEMORY_FILE: &str = "offline_memory.zok";
+
+const POSEIDON_IMPORT: &str = "import \"hashes/poseidon/poseidon\" as poseidon;";
+const KECCAK_IMPORT: &str = "import \"hashes/keccak/256bit\" as keccak256;";
+
+const KECCAK_HASH_PAIR: &str = "def hashPair(u8[32] a, u8[32] b) -> u8[32] {
+\treturn keccak256([...a, ...b]);
+}";
+
+const NO_PREIMAGE: &str = "def doGetPreImage(mips_states states) -> (bool, u8[32])
+{
+\t// the circuit is generated without the preimage feature, the preimage syscall is illegal
+\treturn (false, states.states.memRoot);
+}";
+
+/// How the memory accesses are checked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MemoryModel {
+    /// Every access is checked with the Merkle proof of its record.
+    Merkle,
+    /// The accesses of a batch are checked together, with one Merkle proof per distinct leaf
+    /// at the boundaries of the batch, see `offline_memory.zok`.
+    Offline {
+        /// Maximum number of distinct leaves accessed by a batch.
+        max_leaves: u32,
+    },
+}
+
+/// Optional parts of the circuit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Features {
+    /// Checks the preimage syscall. Without it, the preimage syscall is illegal and the batches
+    /// don't pay for the Keccak of the preimage and the hashing of the subtree it is written to.
+    pub preimage: bool,
+    /// Returns the commitments to the state before and after the batch, see `commitment.zok`.
+    pub commitment: bool,
+}
+
+impl Default for Features {
+    fn default() -> Self {
+        Self {
+            preimage: true,
+            commitment: false,
+        }
+    }
+}
+
+/// Parameters of a MIPS circuit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CircuitParams {
+    /// Amount of consecutive trace records checked by the circuit, i.e. `CIRCUIT_BATCH_SIZE`.
+    pub batch_size: u32,
+    pub memory_hash: MemoryHash,
+    pub memory_model: MemoryModel,
+    pub features: Features,
+    /// Preimage syscalls a batch may hold with the preimage feature, each of them paying for the
+    /// check of a preimage whether the batch holds it or not.
+    pub preimage_calls: u32,
+}
+
+impl CircuitParams {
+    /// The circuit checking `batch_size` records of a Poseidon memory, with the default features.
+    pub fn new(batch_size: u32) -> Self {
+        Self {
+            batch_size,
+            memory_hash: MemoryHash::Poseidon,
+            memory_model: MemoryModel::Merkle,
+            features: Features::default(),
+            preimage_calls: 1,
+        }
+    }
+
+    /// Returns a name telling the parameters apart, e.g. `b50-poseidon-preimage`, or
+    /// `b50-poseidon-preimage2` for 2 preimage syscalls per batch.
+    pub fn name(&self) -> String {
+        let mut name = format!("b{}-{}", self.batch_size, self.memory_hash);
+        if let MemoryModel::Offline { max_leaves } = self.memory_model {
+            name.push_str(&format!("-offline{}", max_leaves));
+        }
+        if self.features.preimage {
+            name.push_str("-preimage");
+            if self.preimage_calls > 1 {
+                name.push_str(&self.preimage_calls.to_string());
+            }
+        }
+        if self.features.commitment {
+            name.push_str("-commitment");
+        }
+        name
+    }
+
+    /// Returns the source files of the circuit by file name, the entry point first.
+    pub fn sources(&self) -> Result<Vec<(&'static str, String)>, String> {
+        if self.batch_size == 0 {
+            return Err("The batch size must be at least 1".to_string());
+        }
+
+        if self.memory_hash == MemoryHash::PoseidonPallas {
+            return Err("The Pallas memory hash is the one of nova_step.zok only".to_string());
+        }
+
+        let mut template = replace_def(TEMPLATE, "main", &self.main())?;
+        if self.memory_hash == MemoryHash::Keccak {
+            if !template.contains(POSEIDON_IMPORT) {
+                return Err(format!("{} doesn't import Poseidon", TEMPLATE_FILE));
+            }
+            template = template.replacen(
+                POSEIDON_IMPORT,
+                &format!("{}\n{}", POSEIDON_IMPORT, KECCAK_IMPORT),
+                1,
+            );
+            template = replace_def(&template, "hashPair", KECCAK_HASH_PAIR)?;
+        }
+        if self.features.preimage {
+            if self.preimage_calls == 0 || self.preimage_calls > self.batch_size {
+                return Err(format!(
+                    "The preimage syscalls per batch must be between 1 and the batch size {}",
+                    self.batch_size
+                ));
+            }
+            template = set_const(&template, "PREIMAGE_CALLS", self.preimage_calls)?;
+        } else {
+            template = replace_def(&template, "doGetPreImage", NO_PREIMAGE)?;
+        }
+
+        if let MemoryModel::Offline { max_leaves } = self.memory_model {
+            if self.features.preimage || self.features.commitment {
+                return Err(
+                    "The offline memory supports neither the preimage syscall nor the commitments"
+                        .to_string(),
+                );
+            }
+            if self.memory_hash != MemoryHash::Poseidon {
+                return Err("The offline memory only supports the Poseidon memory hash".to_string());
+            }
+            let offline = set_const(OFFLINE_MEMORY, "BATCH_SIZE", self.batch_size)?;
+            let offline = set_const(&offline, "MAX_LEAVES", max_leaves)?;
+            return Ok(vec![
+                (OFFLINE_MEMORY_FILE, offline),
+                (TEMPLATE_FILE, template),
+            ]);
+        }
+        if !self.features.commitment {
+            return Ok(vec![(TEMPLATE_FILE, template)]);
+        }
+
+        Ok(vec![
+            (
+                COMMITMENT_FILE,
+                set_const(COMMITMENT, "BATCH_SIZE", self.batch_size)?,
+            ),
+            (TEMPLATE_FILE, template),
+            (STATE_COMMITMENT_FILE, STATE_COMMITMENT.to_string()),
+        ])
+    }
+
+    /// The `main` of the template, which takes a single record as `mips_vm_poseidon.zok` does
+    /// for a batch size of 1, so that the witnesses of both are the same.
+    fn main(&self) -> String {
+        if self.batch_size == 1 {
+            "def main(private trace_record input) -> bool {
+\ttrace_record[1] mut trace = [input];
+\treturn do_check(trace);
+}"
+            .to_string()
+        } else {
+            format!(
+                "def main(private trace_record[{}] input) -> bool {{
+\treturn do_check(input);
+}}",
+                self.batch_size
+            )
+        }
+    }
+}
+
+/// Replaces the top level function `name` of `source` by `def`, up to its closing brace.
+fn replace_def(source: &str, name: &str, def: &str) -> Result<String, String> {
+    let start = [format!("\ndef {}(", name), format!("\ndef {}<", name)]
+        .iter()
+        .filter_map(|pattern| source.find(pattern.as_str()))
+        .min()
+        .ok_or_else(|| format!("No function {} to replace", name))?
+        + 1;
+    let end = source[start..]
+        .find("\n}")
+        .ok_or_else(|| format!("Function {} isn't closed", name))?
+        + start
+        + 2;
+
+    Ok(format!("{}{}{}", &source[..start], def, &source[end..]))
+}
+
+/// Sets the value of the `u32` constant `name` of `source`.
+fn set_const(source: &str, name: &str, value: u32) -> Result<String, String> {
+    let declaration = format!("\nconst u32 {} = ", name);
+    let start = source
+        .find(&declaration)
+        .ok_or_else(|| format!("No constant {} to set", name))?
+        + declaration.len();
+    let end = source[start..]
+        .find(';')
+        .ok_or_else(|| format!("Constant {} isn't terminated", name))?
+        + start;
+
+    Ok(format!("{}{}{}", &source[..start], value, &source[end..]))
+}
+
+/// Artifacts of a circuit compiled by [`CircuitCache`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct CachedCircuit {
+    /// Directory holding the sources and the artifacts.
+    pub dir: PathBuf,
+    pub entry_point: PathBuf,
+    /// The compiled program, as written by `zokrates compile`.
+    pub program_path: PathBuf,
+    pub abi_path: PathBuf,
+}
+
+/// Circuits compiled from generated sources, in a directory per set of parameters and
+/// version of the sources.
+#[derive(Debug, Clone)]
+pub struct CircuitCache {
+    root: PathBuf,
+    stdlib: PathBuf,
+}
+
+impl CircuitCache {
+    /// Keeps the circuits in `root`, `stdlib` being the root of the ZoKrates standard library.
+    pub fn new(root: &Path, stdlib: &Path) -> Self {
+        Self {
+            root: root.to_path_buf(),
+            stdlib: stdlib.to_path_buf(),
+        }
+    }
+
+    /// Returns the circuit generated for `params`, which is compiled unless it is in the cache
+    /// already. Compiling takes several minutes and needs a large stack.
+    pub fn get(&self, params: &CircuitParams) -> Result<CachedCircuit, String> {
+        let sources = params.sources()?;
+        let dir = self.root.join(cache_key(params, &sources));
+        let circuit = CachedCircuit {
+            entry_point: dir.join(sources[0].0),
+            program_path: dir.join("out"),
+            abi_path: dir.join("abi.json"),
+            dir,
+        };
+        if circuit.program_path.is_file() {
+            return Ok(circuit);
+        }
+
+        fs::create_dir_all(&circuit.dir)
+            .map_err(|why| format!("Could not create {}: {}", circuit.dir.display(), why))?;
+        for (file, source) in &sources {
+            let path = circuit.dir.join(file);
+            fs::write(&path, source)
+                .map_err(|why| format!("Could not write {}: {}", path.display(), why))?;
+        }
+
+        vlog::info!("Compiling {}", circuit.entry_point.display());
+        let arena = Arena::new();
+        let compiled = CompiledCircuit::compile(&circuit.entry_point, &self.stdlib, &arena)?;
+        // the program is moved in place last, as it marks the circuit as cached
+        let program_path = circuit.program_path.with_extension("partial");
+        let constraint_count = compiled.write(&program_path, &circuit.abi_path)?;
+        fs::rename(&program_path, &circuit.program_path).map_err(|why| {
+            format!(
+                "Could not write {}: {}",
+                circuit.program_path.display(),
+                why
+            )
+        })?;
+        vlog::info!(
+            "Compiled {} with {} constraints",
+            circuit.entry_point.display(),
+            constraint_count
+        );
+
+        Ok(circuit)
+    }
+}
+
+fn cache_key(params: &CircuitParams, sources: &[(&'static str, String)]) -> String {
+    let mut hasher = Sha256::new();
+    for (file, source) in sources {
+        hasher.update(file.as_bytes());
+        hasher.update(source.as_bytes());
+    }
+    let version = hex::encode(hasher.finalize());
+
+    format!("{}-{}", params.name(), &version[..16])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The template without its `main`, which is the last function.
+    fn without_main(source: &str) -> &str {
+        &source[..source.find("\ndef main(").unwrap()]
+    }
+
+    #[test]
+    fn single_record_circuit_is_the_template() {
+        let sources = CircuitParams::new(1).sources().unwrap();
+
+        assert_eq!(sources, vec![(TEMPLATE_FILE, TEMPLATE.to_string())]);
+    }
+
+    #[test]
+    fn batch_size_only_changes_main() {
+        let sources = CircuitParams::new(50).sources().unwrap();
+        let (file, source) = &sources[0];
+
+        assert_eq!(*file, TEMPLATE_FILE);
+        assert_eq!(without_main(source), without_main(TEMPLATE));
+        assert!(source.ends_with(
+            "def main(private trace_record[50] input) -> bool {\n\treturn do_check(input);\n}\n"
+        ));
+    }
+
+    #[test]
+    fn keccak_memory_replaces_hash_pair() {
+        let params = CircuitParams {
+            memory_hash: MemoryHash::Keccak,
+            ..CircuitParams::new(1)
+        };
+        let source = &params.sources().unwrap()[0].1;
+
+        assert!(source.contains(KECCAK_IMPORT));
+        assert!(source.contains(KECCAK_HASH_PAIR));
+        assert!(!source.contains("field out = poseidon(input);"));
+        // the preimage subtree is hashed with the same function
+        assert!(source.contains("nodes[i] = hashPair(nodes[2 * i], nodes[2 * i + 1]);"));
+    }
+
+    #[test]
+    fn disabled_preimage_is_illegal() {
+        let params = CircuitParams {
+            features: Features {
+                preimage: false,
+                commitment: false,
+            },
+            ..CircuitParams::new(1)
+        };
+        let source = &params.sources().unwrap()[0].1;
+
+        assert!(source.contains(NO_PREIMAGE));
+        assert!(!source.contains("preimageHash(preimage.data, preimage.len)"));
+    }
+
+    #[test]
+    fn preimage_calls_set_the_slots() {
+        let params = CircuitParams {
+            preimage_calls: 2,
+            ..CircuitParams::new(4)
+        };
+        let source = &params.sources().unwrap()[0].1;
+
+        assert!(source.contains("\nconst u32 PREIMAGE_CALLS = 2;\n"));
+        assert_eq!(params.name(), "b4-poseidon-preimage2");
+        assert_eq!(CircuitParams::new(4).name(), "b4-poseidon-preimage");
+    }
+
+    #[test]
+    fn commitment_sets_batch_size() {
+        let params = CircuitParams {
+            features: Features {
+                preimage: true,
+                commitment: true,
+            },
+            ..CircuitParams::new(50)
+        };
+        let sources = params.sources().unwrap();
+        let files: Vec<_> = sources.iter().map(|(file, _)| *file).collect();
+
+        assert_eq!(
+            files,
+            vec![COMMITMENT_FILE, TEMPLATE_FILE, STATE_COMMITMENT_FILE]
+        );
+        assert!(sources[0].1.contains("const u32 BATCH_SIZE = 50;"));
+    }
+
+    #[test]
+    fn offline_memory_sets_sizes() {
+        let params = CircuitParams {
+            memory_model: MemoryModel::Offline { max_leaves: 64 },
+            features: Features {
+                preimage: false,
+                commitment: false,
+            },
+            ..CircuitParams::new(50)
+        };
+        let sources = params.sources().unwrap();
+
+        assert_eq!(params.name(), "b50-poseidon-offline64");
+        assert_eq!(sources[0].0, OFFLINE_MEMORY_FILE);
+        assert!(sources[0].1.contains("\nconst u32 BATCH_SIZE = 50;\n"));
+        assert!(sources[0].1.contains("\nconst u32 MAX_LEAVES = 64;\n"));
+        assert!(sources[1].1.contains(NO_PREIMAGE));
+
+        let with_preimage = CircuitParams {
+            features: Features::default(),
+            ..params
+        };
+        assert!(with_preimage.sources().is_err());
+        let keccak = CircuitParams {
+            memory_hash: MemoryHash::Keccak,
+            ..params
+        };
+        assert!(keccak.sources().is_err());
+    }
+
+    #[test]
+    fn cache_key_depends_on_parameters() {
+        let single = CircuitParams::new(1);
+        let batch = CircuitParams::new(50);
+        let keccak = CircuitParams {
+            memory_hash: MemoryHash::Keccak,
+            ..single
+        };
+        let keys: Vec<_> = [single, batch, keccak]
+            .iter()
+            .map(|params| cache_key(params, &params.sources().unwrap()))
+            .collect();
+
+        assert!(keys[0].starts_with("b1-poseidon-preimage-"));
+        assert!(keys[1].starts_with("b50-poseidon-preimage-"));
+        assert!(keys[2].starts_with("b1-keccak-preimage-"));
+        assert_eq!(keys[0], cache_key(&single, &single.sources().unwrap()));
+    }
+
+    #[test]
+    fn invalid_parameters() {
+        assert!(CircuitParams::new(0).sources().is_err());
+        for preimage_calls in [0, 5].iter() {
+            let params = CircuitParams {
+                preimage_calls: *preimage_calls,
+                ..CircuitParams::new(4)
+            };
+            assert!(params.sources().is_err());
+        }
+        assert!(replace_def(TEMPLATE, "missing", "").is_err());
+        assert!(set_const(COMMITMENT, "MISSING", 1).is_err());
+        let pallas = CircuitParams {
+            memory_hash: MemoryHash::PoseidonPallas,
+            ..CircuitParams::new(1)
+        };
+        assert!(pallas.sources().is_err());
+        assert!("sha256".parse::<MemoryHash>().is_err());
+        assert_eq!("keccak".parse::<MemoryHash>(), Ok(MemoryHash::Keccak));
+    }
+}
@@ -1,2 +1,9 @@
+pub mod artifacts;
+pub mod batch;
+pub mod commitment;
+pub mod differential;
+pub mod generator;
 pub mod witness;
-pub mod proof;
\ No newline at end of file
+pub mod proof;
+#[cfg(feature = "bellperson")]
+pub mod nova;
\ No newline at end of file
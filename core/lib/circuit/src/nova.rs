@@ -0,0 +1,265 @@
+//! Nova mode: the trace records of a whole program run are folded into one recursive SNARK with
+//! `nova_step.zok`, compiled for Pallas or Vesta, and compressed with Spartan, instead of being
+//! proven batch by batch with Groth16.
+//!
+//! The Nova state vector is the MIPS `states` struct the step circuit takes and returns, so the
+//! run starts from the state before its first record. Compiled for Pallas, the `hashPair` of the
+//! template is Poseidon over the Pallas field, so the records have to be generated in a memory
+//! hashed with `mips_vm::memory::MemoryHash::PoseidonPallas`.
+
+// Built-in
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+// External
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+// Workspace deps
+use zokrates_abi::{parse_value, Encode};
+use zokrates_ast::ir::{self, ProgEnum};
+use zokrates_ast::typed::abi::Abi;
+use zokrates_ast::typed::ConcreteType;
+use zokrates_bellperson::nova::{self, NovaField};
+
+/// Fields of a trace record holding the state before its step, i.e. the fields of `states`.
+const STATE_FIELDS: [&str; 10] = [
+    "cycle", "pc", "nextPC", "lo", "hi", "regs", "heap", "exitCode", "exited", "memRoot",
+];
+
+/// Step circuit of the Nova mode.
+#[derive(Debug, Clone)]
+pub struct NovaOptions {
+    /// `nova_step.zok` compiled with `--curve pallas`.
+    program_path: PathBuf,
+    /// ABI of the step circuit.
+    abi_path: PathBuf,
+    /// Public parameters written by `zokrates nova setup`, generated on every run if unset.
+    params_path: Option<PathBuf>,
+}
+
+impl NovaOptions {
+    pub fn new(program_path: impl Into<PathBuf>, abi_path: impl Into<PathBuf>) -> Self {
+        Self {
+            program_path: program_path.into(),
+            abi_path: abi_path.into(),
+            params_path: None,
+        }
+    }
+
+    pub fn params(mut self, params_path: Option<impl Into<PathBuf>>) -> Self {
+        self.params_path = params_path.map(Into::into);
+        self
+    }
+}
+
+/// Compressed proof of every step of a program run.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NovaProof {
+    /// Amount of folded trace records.
+    pub steps: usize,
+    /// State before the first record, the initial Nova state.
+    pub initial_state: Value,
+    /// State after the last record.
+    pub final_state: Value,
+    /// Spartan proof of the recursive SNARK.
+    pub proof: Value,
+}
+
+/// Returns the state the trace record `trace` starts from, as a `states` value.
+pub fn initial_state(trace: &Value) -> Result<Value, String> {
+    state_of(trace, |field| field.to_string())
+}
+
+/// Returns the state the trace record `trace` ends in, as a `states` value.
+pub fn final_state(trace: &Value) -> Result<Value, String> {
+    // The record names the fields after the step `newCycle`, `newPc`, `newNextPC`...
+    state_of(trace, |field| {
+        let mut chars = field.chars();
+        let first = chars.next().unwrap().to_ascii_uppercase();
+        format!("new{}{}", first, chars.as_str())
+    })
+}
+
+fn state_of(trace: &Value, name: impl Fn(&str) -> String) -> Result<Value, String> {
+    let state = STATE_FIELDS
+        .iter()
+        .map(|field| {
+            let value = trace
+                .get(name(field))
+                .ok_or_else(|| format!("Trace record without `{}`", name(field)))?;
+            Ok((field.to_string(), value.clone()))
+        })
+        .collect::<Result<Map<_, _>, String>>()?;
+
+    Ok(Value::Object(state))
+}
+
+/// Folds the trace records `traces` of a program run, in order, and compresses the result.
+pub fn prove_run(options: &NovaOptions, traces: &[Value]) -> Result<NovaProof, String> {
+    let path = options.program_path.as_path();
+    let file =
+        File::open(path).map_err(|why| format!("Could not open {}: {}", path.display(), why))?;
+
+    let mut reader = BufReader::new(file);
+
+    match ProgEnum::deserialize(&mut reader)? {
+        ProgEnum::PallasProgram(p) => prove(p, options, traces),
+        ProgEnum::VestaProgram(p) => prove(p, options, traces),
+        _ => Err("Nova is only supported for the following curves: [\"pallas\", \"vesta\"]".into()),
+    }
+}
+
+/// Checks `proof` against the step circuit of `options` and its claimed final state.
+/// The verification key is derived from the public parameters of the step circuit,
+/// never taken from the proof.
+pub fn verify_run(options: &NovaOptions, proof: &NovaProof) -> Result<bool, String> {
+    let path = options.program_path.as_path();
+    let file =
+        File::open(path).map_err(|why| format!("Could not open {}: {}", path.display(), why))?;
+
+    let mut reader = BufReader::new(file);
+
+    match ProgEnum::deserialize(&mut reader)? {
+        ProgEnum::PallasProgram(p) => verify(p, options, proof),
+        ProgEnum::VestaProgram(p) => verify(p, options, proof),
+        _ => Err("Nova is only supported for the following curves: [\"pallas\", \"vesta\"]".into()),
+    }
+}
+
+/// Returns the types of the state and of the step inputs of the step circuit.
+fn step_types(abi_path: &Path) -> Result<(ConcreteType, ConcreteType), String> {
+    let file = File::open(abi_path)
+        .map_err(|why| format!("Could not open {}: {}", abi_path.display(), why))?;
+    let abi: Abi = serde_json::from_reader(BufReader::new(file)).map_err(|why| why.to_string())?;
+
+    match &abi.signature().inputs[..] {
+        [state, step] => Ok((state.clone(), step.clone())),
+        inputs => Err(format!(
+            "The step circuit must take the state and a trace record, found {} inputs",
+            inputs.len()
+        )),
+    }
+}
+
+fn encode<T: NovaField>(value: &Value, ty: ConcreteType) -> Result<Vec<T>, String> {
+    parse_value(value.clone(), ty)
+        .map(|value| value.encode())
+        .map_err(|why| why.to_string())
+}
+
+fn prove<'ast, T: NovaField, I: Iterator<Item = ir::Statement<'ast, T>>>(
+    program: ir::ProgIterator<'ast, T, I>,
+    options: &NovaOptions,
+    traces: &[Value],
+) -> Result<NovaProof, String> {
+    let (first, last) = match traces {
+        [first, .., last] => (first, last),
+        [single] => (single, single),
+        [] => return Err("No trace records to fold".into()),
+    };
+    vlog::info!("Folding {} trace records...", traces.len());
+    let start = Instant::now();
+
+    let program = program.collect();
+    let (state_type, step_type) = step_types(&options.abi_path)?;
+    let initial_state = initial_state(first)?;
+    let final_state = final_state(last)?;
+    let init = encode::<T>(&initial_state, state_type)?;
+    let steps = traces
+        .iter()
+        .map(|trace| encode::<T>(trace, step_type.clone()))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let params = public_parameters(options, &program)?;
+
+    let instance = nova::prove(&params, &program, init, None, steps)
+        .map_err(|e| format!("Error `{}` during folding", e))?
+        .expect("at least one step is folded");
+    let steps = instance.steps;
+    let (proof, _) = nova::compress(&params, instance);
+
+    metrics::histogram!("circuit.nova_prove_run", start.elapsed());
+    vlog::info!(
+        "Folded and compressed {} steps in {:?}",
+        steps,
+        start.elapsed()
+    );
+
+    Ok(NovaProof {
+        steps,
+        initial_state,
+        final_state,
+        proof: serde_json::to_value(&proof).map_err(|why| why.to_string())?,
+    })
+}
+
+fn verify<'ast, T: NovaField, I: Iterator<Item = ir::Statement<'ast, T>>>(
+    program: ir::ProgIterator<'ast, T, I>,
+    options: &NovaOptions,
+    proof: &NovaProof,
+) -> Result<bool, String> {
+    let program = program.collect();
+    let (state_type, _) = step_types(&options.abi_path)?;
+    let init = encode::<T>(&proof.initial_state, state_type.clone())?;
+    let final_state = encode::<T>(&proof.final_state, state_type)?;
+    let snark: nova::CompressedSNARK<T> = serde_json::from_value(proof.proof.clone())
+        .map_err(|why| format!("Invalid Nova proof: {}", why))?;
+
+    let params = public_parameters(options, &program)?;
+    let verification_key = nova::verifier_key(&params).map_err(|e| e.to_string())?;
+
+    Ok(
+        match nova::verify_compressed(&snark, &verification_key, init, proof.steps) {
+            Ok(state) => state == final_state,
+            Err(_) => false,
+        },
+    )
+}
+
+/// Reads the public parameters of `options`, or generates them for `program` if unset.
+fn public_parameters<'ast, T: NovaField>(
+    options: &NovaOptions,
+    program: &'ast ir::Prog<'ast, T>,
+) -> Result<nova::PublicParams<'ast, T>, String> {
+    match &options.params_path {
+        Some(path) => {
+            let file = File::open(path)
+                .map_err(|why| format!("Could not open {}: {}", path.display(), why))?;
+            serde_cbor::from_reader(BufReader::new(file))
+                .map_err(|why| format!("Could not deserialize {}: {}", path.display(), why))
+        }
+        None => nova::generate_public_parameters(program).map_err(|e| e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn states_of_a_record() {
+        let mut trace = json!({
+            "cycle": "0x1", "pc": "0x2", "nextPC": "0x3", "lo": "0x4", "hi": "0x5",
+            "regs": ["0x6"], "heap": "0x7", "exitCode": "0x8", "exited": false,
+            "memRoot": ["0x9"],
+            "newCycle": "0x11", "newPc": "0x12", "newNextPC": "0x13", "newLo": "0x14",
+            "newHi": "0x15", "newRegs": ["0x16"], "newHeap": "0x17", "newExitCode": "0x18",
+            "newExited": true, "newMemRoot": ["0x19"],
+        });
+
+        let pre = initial_state(&trace).unwrap();
+        assert_eq!(pre["nextPC"], "0x3");
+        assert_eq!(pre["memRoot"], json!(["0x9"]));
+        assert_eq!(pre.as_object().unwrap().len(), STATE_FIELDS.len());
+
+        let post = final_state(&trace).unwrap();
+        assert_eq!(post["pc"], "0x12");
+        assert_eq!(post["nextPC"], "0x13");
+        assert_eq!(post["exited"], true);
+
+        trace.as_object_mut().unwrap().remove("newHeap");
+        assert!(final_state(&trace).is_err());
+    }
+}
@@ -0,0 +1,137 @@
+//! `verifyTx` transactions of the proofs, built offline to debug their verification on chain.
+
+// Built-in
+use std::fmt;
+// External
+use web3::ethabi::Token;
+use zokrates_field::Bn128Field;
+use zokrates_proof_systems::{Proof, G16};
+
+/// Base cost of a transaction.
+const TX_GAS: u64 = 21_000;
+/// Cost of the calldata bytes, EIP-2028.
+const ZERO_BYTE_GAS: u64 = 4;
+const NON_ZERO_BYTE_GAS: u64 = 16;
+/// Cost of the alt_bn128 precompiles, EIP-1108.
+const EC_ADD_GAS: u64 = 150;
+const EC_MUL_GAS: u64 = 6_000;
+const PAIRING_GAS: u64 = 45_000;
+const PAIRING_POINT_GAS: u64 = 34_000;
+/// Pairings of the Groth16 check e(A, B) = e(alpha, beta) e(vk_x, gamma) e(C, delta).
+const G16_PAIRINGS: u64 = 4;
+/// Rough allowance for the rest of the verifier contract: building the verification key,
+/// checking the inputs against the field modulus and the memory it uses.
+const VERIFIER_EXECUTION_GAS: u64 = 30_000;
+
+/// Estimates the gas of a `verifyTx` transaction with `calldata`, checking a Groth16 proof
+/// with `inputs` public inputs. Only the pricing rules are used, no node is queried.
+pub fn estimate_verify_gas(calldata: &[u8], inputs: usize) -> u64 {
+    let calldata_gas: u64 = calldata
+        .iter()
+        .map(|byte| {
+            if *byte == 0 {
+                ZERO_BYTE_GAS
+            } else {
+                NON_ZERO_BYTE_GAS
+            }
+        })
+        .sum();
+    // vk_x = gamma_abc[0] + sum(inputs[i] * gamma_abc[i + 1])
+    let vk_x_gas = inputs as u64 * (EC_MUL_GAS + EC_ADD_GAS) + EC_ADD_GAS;
+    let pairing_gas = PAIRING_GAS + G16_PAIRINGS * PAIRING_POINT_GAS;
+
+    TX_GAS + calldata_gas + vk_x_gas + pairing_gas + VERIFIER_EXECUTION_GAS
+}
+
+/// `verifyTx` transaction submitting a proof to the verifier contract.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerifyTxCalldata {
+    pub calldata: Vec<u8>,
+    /// Arguments decoded back from `calldata`: the proof tuple and the inputs.
+    pub arguments: Vec<Token>,
+    pub estimated_gas: u64,
+}
+
+impl VerifyTxCalldata {
+    /// Builds the transaction of `proof`, as stored in `t_proofs`, for the verifier contract
+    /// described by `<abi_path>/Verifier.abi`.
+    pub fn from_stored_proof(proof: &serde_json::Value, abi_path: &str) -> Result<Self, String> {
+        let proof: Proof<Bn128Field, G16> = serde_json::from_value(proof.clone())
+            .map_err(|why| format!("Invalid proof: {}", why))?;
+        let inputs = proof.inputs.len();
+
+        let calldata = super::verify_tx_calldata(proof, abi_path)?;
+        let arguments = super::verify_tx_function(abi_path)?
+            .decode_input(&calldata[4..])
+            .map_err(|why| format!("Could not decode verifyTx: {}", why))?;
+        let estimated_gas = estimate_verify_gas(&calldata, inputs);
+
+        Ok(Self {
+            calldata,
+            arguments,
+            estimated_gas,
+        })
+    }
+}
+
+impl fmt::Display for VerifyTxCalldata {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let arguments = self
+            .arguments
+            .iter()
+            .map(|argument| argument.to_string())
+            .collect::<Vec<_>>();
+        writeln!(f, "calldata: 0x{}", hex::encode(&self.calldata))?;
+        writeln!(f, "verifyTx({})", arguments.join(","))?;
+        write!(f, "estimated gas: {}", self.estimated_gas)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+    use web3::types::U256;
+
+    fn word(byte: u8) -> String {
+        format!("0x{}", hex::encode([byte; 32]))
+    }
+
+    #[test]
+    fn gas_of_the_calldata_bytes() {
+        let base = estimate_verify_gas(&[], 1);
+        assert_eq!(estimate_verify_gas(&[0, 0], 1), base + 8);
+        assert_eq!(estimate_verify_gas(&[0, 1], 1), base + 20);
+        assert_eq!(estimate_verify_gas(&[], 2), base + EC_MUL_GAS + EC_ADD_GAS);
+    }
+
+    #[test]
+    fn calldata_of_stored_proof() {
+        let abi_path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("../../../contract/verifier/g16/verifier")
+            .to_str()
+            .unwrap()
+            .to_owned();
+        let proof = serde_json::json!({
+            "proof": {
+                "a": [word(1), word(2)],
+                "b": [[word(3), word(4)], [word(5), word(6)]],
+                "c": [word(7), word(8)],
+            },
+            "inputs": [word(9)],
+        });
+
+        let tx = VerifyTxCalldata::from_stored_proof(&proof, &abi_path).unwrap();
+
+        // The selector, the 8 words of the proof and the input.
+        assert_eq!(tx.calldata.len(), 4 + 9 * 32);
+        assert_eq!(&tx.calldata[4..36], &[1u8; 32]);
+        assert_eq!(&tx.calldata[tx.calldata.len() - 32..], &[9u8; 32]);
+        assert_eq!(tx.arguments.len(), 2);
+        assert_eq!(
+            tx.arguments[1],
+            Token::FixedArray(vec![Token::Uint(U256::from(&[9u8; 32][..]))])
+        );
+        assert_eq!(tx.estimated_gas, estimate_verify_gas(&tx.calldata, 1));
+    }
+}
@@ -1,15 +1,17 @@
 use rand_0_8::rngs::StdRng;
 use rand_0_8::SeedableRng;
-use std::collections::HashMap;
 use std::convert::TryFrom;
 use std::fs::File;
-use std::io::{BufReader, Cursor};
-use std::path::Path;
+use std::io::BufReader;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
 use zokrates_ark::Ark;
 use zokrates_ast::ir;
-use zokrates_ast::ir::{ProgEnum, Witness};
+use zokrates_ast::ir::ProgEnum;
 use zokrates_bellman::Bellman;
-use zokrates_common::helpers::{BackendParameter, Parameters, SchemeParameter, CurveParameter};
+use zokrates_common::helpers::{BackendParameter, SchemeParameter, CurveParameter};
 use zokrates_field::{Bn128Field,Field};
 use zokrates_proof_systems::{Backend, G16, GM17, Marlin, Scheme, TaggedProof};
 use zokrates_proof_systems::rng::get_rng_from_entropy;
@@ -31,64 +33,139 @@ use web3::contract::tokens::Tokenizable;
 use web3::types::TransactionRequest;
 use web3::types::U256;
 use to_token::ToToken;
+pub mod calldata;
 pub mod to_token;
 use secp256k1::SecretKey;
+use crate::witness::Witness;
+
+
+
+/// Options of `generate_proof`.
+#[derive(Debug)]
+pub struct ProofOptions {
+    /// Compiled program.
+    program_path: PathBuf,
+    /// Witness of the program run to prove.
+    witness: Witness,
+    proving_key_path: PathBuf,
+    backend: BackendParameter,
+    scheme: SchemeParameter,
+    /// Entropy of the proof randomness, taken from the OS if not set.
+    entropy: Option<String>,
+    /// Print the generated proof.
+    verbose: bool,
+    /// Set to stop the proof before its next step.
+    abort: Option<Arc<AtomicBool>>,
+}
+
+impl ProofOptions {
+    /// Options for proving `witness` of the program at `program_path` with the Ark G16 backend.
+    pub fn new(
+        program_path: impl Into<PathBuf>,
+        witness: Witness,
+        proving_key_path: impl Into<PathBuf>,
+    ) -> Self {
+        Self {
+            program_path: program_path.into(),
+            witness,
+            proving_key_path: proving_key_path.into(),
+            backend: BackendParameter::Ark,
+            scheme: SchemeParameter::G16,
+            entropy: None,
+            verbose: false,
+            abort: None,
+        }
+    }
+
+    pub fn backend(mut self, backend: BackendParameter) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    pub fn scheme(mut self, scheme: SchemeParameter) -> Self {
+        self.scheme = scheme;
+        self
+    }
+
+    pub fn entropy(mut self, entropy: impl Into<String>) -> Self {
+        self.entropy = Some(entropy.into());
+        self
+    }
 
+    pub fn verbose(mut self, verbose: bool) -> Self {
+        self.verbose = verbose;
+        self
+    }
+
+    /// Flag stopping the proof once set. The backend can't be interrupted while it proves,
+    /// so the flag is checked before each step of `generate_proof`.
+    pub fn abort(mut self, abort: Arc<AtomicBool>) -> Self {
+        self.abort = Some(abort);
+        self
+    }
 
+    /// Returns an error if the proof has been aborted.
+    fn check_aborted(&self) -> Result<(), String> {
+        match &self.abort {
+            Some(abort) if abort.load(Ordering::SeqCst) => {
+                Err("Proof generation aborted".to_string())
+            }
+            _ => Ok(()),
+        }
+    }
+}
 
-pub fn generate_proof(args: &HashMap<String, String>) -> Result<String, String> {
-    let path = Path::new(args.get(&"input".to_string()).unwrap());
+/// Proves the witness of `options`, records the duration of the proof generation.
+pub fn generate_proof(options: &ProofOptions) -> Result<String, String> {
+    options.check_aborted()?;
+    let path = options.program_path.as_path();
     let file = File::open(path).map_err(|why| format!("Could not open {}: {}", path.display(), why))?;
 
     let mut reader = BufReader::new(file);
     let prog = ProgEnum::deserialize(&mut reader)?;
 
-    let curve_parameter = CurveParameter::try_from(prog.curve())?;
+    let curve = prog.curve();
+    // Rejects the programs compiled for a curve no backend knows about.
+    CurveParameter::try_from(curve)?;
 
-    let backend_parameter = BackendParameter::try_from(args.get(&"backend".to_string()).unwrap().as_str())?;
-    let scheme_parameter =
-        SchemeParameter::try_from(args.get(&"proving-scheme".to_string()).unwrap().as_str())?;
-
-    let parameters = Parameters(backend_parameter, curve_parameter, scheme_parameter);
-
-    match parameters {
+    match (&options.backend, &options.scheme) {
         #[cfg(feature = "bellman")]
-        Parameters(BackendParameter::Bellman, _, SchemeParameter::G16) => match prog {
-            ProgEnum::Bn128Program(p) => generate::<_, _, G16, Bellman>(p, args),
+        (BackendParameter::Bellman, SchemeParameter::G16) => match prog {
+            ProgEnum::Bn128Program(p) => generate::<_, _, G16, Bellman>(p, options),
             ProgEnum::Bls12_381Program(p) => {
-                generate::<_, _, G16, Bellman>(p, args)
+                generate::<_, _, G16, Bellman>(p, options)
             }
-            _ => unreachable!(),
+            _ => Err(format!("{:?} does not support {}", options.backend, curve)),
         },
         #[cfg(feature = "ark")]
-        Parameters(BackendParameter::Ark, _, SchemeParameter::G16) => match prog {
-            ProgEnum::Bn128Program(p) => generate::<_, _, G16, Ark>(p, args),
-            ProgEnum::Bls12_381Program(p) => generate::<_, _, G16, Ark>(p, args),
-            ProgEnum::Bls12_377Program(p) => generate::<_, _, G16, Ark>(p, args),
-            ProgEnum::Bw6_761Program(p) => generate::<_, _, G16, Ark>(p, args),
-            _ => unreachable!(),
+        (BackendParameter::Ark, SchemeParameter::G16) => match prog {
+            ProgEnum::Bn128Program(p) => generate::<_, _, G16, Ark>(p, options),
+            ProgEnum::Bls12_381Program(p) => generate::<_, _, G16, Ark>(p, options),
+            ProgEnum::Bls12_377Program(p) => generate::<_, _, G16, Ark>(p, options),
+            ProgEnum::Bw6_761Program(p) => generate::<_, _, G16, Ark>(p, options),
+            _ => Err(format!("{:?} does not support {}", options.backend, curve)),
         },
         #[cfg(feature = "ark")]
-        Parameters(BackendParameter::Ark, _, SchemeParameter::GM17) => match prog {
-            ProgEnum::Bn128Program(p) => generate::<_, _, GM17, Ark>(p, args),
-            ProgEnum::Bls12_381Program(p) => generate::<_, _, GM17, Ark>(p, args),
-            ProgEnum::Bls12_377Program(p) => generate::<_, _, GM17, Ark>(p, args),
-            ProgEnum::Bw6_761Program(p) => generate::<_, _, GM17, Ark>(p, args),
-            _ => unreachable!(),
+        (BackendParameter::Ark, SchemeParameter::GM17) => match prog {
+            ProgEnum::Bn128Program(p) => generate::<_, _, GM17, Ark>(p, options),
+            ProgEnum::Bls12_381Program(p) => generate::<_, _, GM17, Ark>(p, options),
+            ProgEnum::Bls12_377Program(p) => generate::<_, _, GM17, Ark>(p, options),
+            ProgEnum::Bw6_761Program(p) => generate::<_, _, GM17, Ark>(p, options),
+            _ => Err(format!("{:?} does not support {}", options.backend, curve)),
         },
         #[cfg(feature = "ark")]
-        Parameters(BackendParameter::Ark, _, SchemeParameter::MARLIN) => match prog {
-            ProgEnum::Bn128Program(p) => generate::<_, _, Marlin, Ark>(p, args),
+        (BackendParameter::Ark, SchemeParameter::MARLIN) => match prog {
+            ProgEnum::Bn128Program(p) => generate::<_, _, Marlin, Ark>(p, options),
             ProgEnum::Bls12_381Program(p) => {
-                generate::<_, _, Marlin, Ark>(p, args)
+                generate::<_, _, Marlin, Ark>(p, options)
             }
             ProgEnum::Bls12_377Program(p) => {
-                generate::<_, _, Marlin, Ark>(p, args)
+                generate::<_, _, Marlin, Ark>(p, options)
             }
-            ProgEnum::Bw6_761Program(p) => generate::<_, _, Marlin, Ark>(p, args),
-            _ => unreachable!(),
+            ProgEnum::Bw6_761Program(p) => generate::<_, _, Marlin, Ark>(p, options),
+            _ => Err(format!("{:?} does not support {}", options.backend, curve)),
         },
-        _ => unreachable!(),
+        (backend, scheme) => Err(format!("{:?} does not support {:?}", backend, scheme)),
     }
 }
 
@@ -100,51 +177,75 @@ fn generate<
     B: Backend<T, S>,
 >(
     program: ir::ProgIterator<'a, T, I>,
-    args: &HashMap<String, String>,
+    options: &ProofOptions,
 ) -> Result<String, String> {
     vlog::info!("Generating proof...");
 
-    let t = "true".to_string();
     // deserialize witness
-    let witness_str = args.get(&"witness".to_string()).unwrap().clone();
-    vlog::info!("witness_str:{}\n", witness_str);
-    let witness_bytes = hex::decode(witness_str).unwrap();
-    let mut buff = Cursor::new(witness_bytes);
-    let witness = Witness::read(buff).map_err(|why| format!("Could not load witness: {:?}", why))?;
+    let witness = ir::Witness::read(options.witness.as_bytes())
+        .map_err(|why| format!("Could not load witness: {:?}", why))?;
+    options.check_aborted()?;
 
-    let pk_path = Path::new(args.get(&"proving-key-path".to_string()).unwrap());
+    let pk_path = options.proving_key_path.as_path();
     let pk_file = File::open(pk_path)
         .map_err(|why| format!("Could not open {}: {}", pk_path.display(), why))?;
 
     let pk_reader = BufReader::new(pk_file);
 
-    let mut rng = args
-        .get("entropy")
-        .map(|s| s.as_str())
+    let mut rng = options
+        .entropy
+        .as_deref()
         .map(get_rng_from_entropy)
         .unwrap_or_else(StdRng::from_entropy);
 
+    let start = Instant::now();
     let proof = B::generate_proof(program, witness, pk_reader, &mut rng);
+    metrics::histogram!("circuit.generate_proof", start.elapsed());
 
-    let proof_str =
-        serde_json::to_string_pretty(&TaggedProof::<T, S>::new(proof.proof, proof.inputs)).unwrap();
+    let proof_str = serde_json::to_string_pretty(&TaggedProof::<T, S>::new(proof.proof, proof.inputs))
+        .map_err(|why| format!("Could not serialize proof: {}", why))?;
 
-    let verbose = matches!(args.get(&"verbose".to_string()).unwrap_or(&"true".to_string()), t);
-    if verbose {
+    if options.verbose {
         vlog::info!("Proof:\n{}", proof_str);
     }
 
     Ok(proof_str)
 }
 
-pub async fn call_verify<S: SolidityCompatibleScheme<Bn128Field> + ToToken<Bn128Field>>(
-    proof: Proof<Bn128Field, S>,chainUrl:&str,contractAddress:&str,abiPath:&str,account:&str) -> bool {
+/// Checks `proof` with `backend` against the verification key `vk`, as written by `zokrates setup`.
+/// Returns an error if the key is malformed or `backend` can't verify G16 proofs.
+pub fn verify_proof(
+    backend: &BackendParameter,
+    vk: &serde_json::Value,
+    proof: Proof<Bn128Field, G16>,
+) -> Result<bool, String> {
+    let vk = serde_json::from_value(vk.clone())
+        .map_err(|why| format!("Could not load verification key: {}", why))?;
+
+    match backend {
+        #[cfg(feature = "ark")]
+        BackendParameter::Ark => Ok(<Ark as Backend<Bn128Field, G16>>::verify(vk, proof)),
+        #[cfg(feature = "bellman")]
+        BackendParameter::Bellman => Ok(<Bellman as Backend<Bn128Field, G16>>::verify(vk, proof)),
+        backend => Err(format!("{:?} does not support {:?}", backend, SchemeParameter::G16)),
+    }
+}
+
+/// Name of the contract checking the proofs one by one, the ABI of which is `<abiPath>/Verifier.abi`.
+const VERIFIER_CONTRACT: &str = "Verifier";
+/// Name of the contract checking several proofs in a single call, exported with
+/// `zokrates export-verifier --batch`. Its ABI is `<abiPath>/BatchVerifier.abi`.
+const BATCH_VERIFIER_CONTRACT: &str = "BatchVerifier";
 
+/// Builds the tokens of the proof and its public inputs.
+fn proof_tokens<S: SolidityCompatibleScheme<Bn128Field> + ToToken<Bn128Field>>(
+    proof: Proof<Bn128Field, S>,
+) -> Result<(Token, Token), String> {
     let solidity_proof = S::Proof::from(proof.proof);
 
     // let modified_solidity_proof = S::modify(solidity_proof);
 
-    let proof_token = S::to_token(solidity_proof.clone());
+    let proof_token = S::to_token(solidity_proof);
 
     // let proof_token = S::to_token(modified_solidity_proof.clone());
 
@@ -153,38 +254,144 @@ pub async fn call_verify<S: SolidityCompatibleScheme<Bn128Field> + ToToken<Bn128
             .inputs
             .iter()
             .map(|s| {
-                let mut bytes = hex::decode(s.trim_start_matches("0x")).unwrap();
+                let bytes = hex::decode(s.trim_start_matches("0x"))
+                    .map_err(|why| format!("Invalid proof input {}: {}", s, why))?;
                 debug_assert_eq!(bytes.len(), 32);
-                Token::Uint(U256::from(&bytes[..]))
+                Ok(Token::Uint(U256::from(&bytes[..])))
             })
-            .collect::<Vec<_>>(),
+            .collect::<Result<Vec<_>, String>>()?,
     );
 
-    // let inputs = ethabi::encode(&[proof_token, input_token.clone()]);
+    Ok((proof_token, input_token))
+}
 
-    let key_bytes = hex::decode(account).unwrap();
+/// Builds the arguments of `verifyTx`: the proof and its public inputs.
+fn verify_tx_tokens<S: SolidityCompatibleScheme<Bn128Field> + ToToken<Bn128Field>>(
+    proof: Proof<Bn128Field, S>,
+) -> Result<Vec<Token>, String> {
+    let (proof_token, input_token) = proof_tokens(proof)?;
+    Ok(vec![proof_token, input_token])
+}
+
+/// Builds the arguments of `verifyTxs`: the proofs and their public inputs, in the same order.
+fn verify_txs_tokens<S: SolidityCompatibleScheme<Bn128Field> + ToToken<Bn128Field>>(
+    proofs: Vec<Proof<Bn128Field, S>>,
+) -> Result<Vec<Token>, String> {
+    let (proof_tokens, input_tokens): (Vec<_>, Vec<_>) = proofs
+        .into_iter()
+        .map(proof_tokens)
+        .collect::<Result<Vec<_>, String>>()?
+        .into_iter()
+        .unzip();
+    Ok(vec![Token::Array(proof_tokens), Token::Array(input_tokens)])
+}
+
+/// Reads `<abiPath>/<contract>.abi`.
+fn read_verifier_abi(abiPath: &str, contract: &str) -> Result<(String, Vec<u8>), String> {
+    let abi_path = format!("{}/{}.abi", abiPath, contract);
+    let abi = fs::read(&abi_path).map_err(|why| format!("Could not open {}: {}", abi_path, why))?;
+    Ok((abi_path, abi))
+}
+
+/// Returns `function` as described by `<abiPath>/<contract>.abi`.
+fn verifier_function(abiPath: &str, contract: &str, function: &str) -> Result<web3::ethabi::Function, String> {
+    let (abi_path, abi) = read_verifier_abi(abiPath, contract)?;
+    let abi = web3::ethabi::Contract::load(abi.as_slice())
+        .map_err(|why| format!("Invalid verifier ABI {}: {}", abi_path, why))?;
+    abi.function(function)
+        .map(|function| function.clone())
+        .map_err(|why| format!("Invalid verifier ABI {}: {}", abi_path, why))
+}
+
+/// Returns `verifyTx` as described by `<abiPath>/Verifier.abi`.
+fn verify_tx_function(abiPath: &str) -> Result<web3::ethabi::Function, String> {
+    verifier_function(abiPath, VERIFIER_CONTRACT, "verifyTx")
+}
 
-    let key = SecretKey::from_slice(key_bytes.as_slice()).unwrap();
+/// Returns `verifyTxs` as described by `<abiPath>/BatchVerifier.abi`.
+fn verify_txs_function(abiPath: &str) -> Result<web3::ethabi::Function, String> {
+    verifier_function(abiPath, BATCH_VERIFIER_CONTRACT, "verifyTxs")
+}
 
+/// Returns the calldata of the `verifyTx` transaction submitting `proof` to the verifier contract
+/// described by `<abiPath>/Verifier.abi`.
+pub fn verify_tx_calldata<S: SolidityCompatibleScheme<Bn128Field> + ToToken<Bn128Field>>(
+    proof: Proof<Bn128Field, S>,abiPath:&str) -> Result<Vec<u8>, String> {
+    let tokens = verify_tx_tokens(proof)?;
+    verify_tx_function(abiPath)?
+        .encode_input(&tokens)
+        .map_err(|why| format!("Could not encode verifyTx: {}", why))
+}
 
-    let http = web3::transports::Http::new(chainUrl).unwrap();
+/// Returns the calldata of the `verifyTxs` transaction submitting `proofs` together to the batch
+/// verifier contract described by `<abiPath>/BatchVerifier.abi`.
+pub fn verify_txs_calldata<S: SolidityCompatibleScheme<Bn128Field> + ToToken<Bn128Field>>(
+    proofs: Vec<Proof<Bn128Field, S>>,abiPath:&str) -> Result<Vec<u8>, String> {
+    let tokens = verify_txs_tokens(proofs)?;
+    verify_txs_function(abiPath)?
+        .encode_input(&tokens)
+        .map_err(|why| format!("Could not encode verifyTxs: {}", why))
+}
+
+/// Sends `verifyTx` to the verifier contract and waits for its receipt.
+/// Returns whether the contract accepted the proof, an error means that it couldn't be submitted.
+pub async fn call_verify<S: SolidityCompatibleScheme<Bn128Field> + ToToken<Bn128Field>>(
+    proof: Proof<Bn128Field, S>,chainUrl:&str,contractAddress:&str,abiPath:&str,account:&str) -> Result<bool, String> {
+    let tokens = verify_tx_tokens(proof)?;
+    send_verification(VERIFIER_CONTRACT, "verifyTx", tokens, chainUrl, contractAddress, abiPath, account).await
+}
+
+/// Sends `verifyTxs` with all of `proofs` to the batch verifier contract and waits for its receipt.
+/// Returns whether the contract accepted the proofs, which it only does if all of them are valid.
+pub async fn call_verify_batch<S: SolidityCompatibleScheme<Bn128Field> + ToToken<Bn128Field>>(
+    proofs: Vec<Proof<Bn128Field, S>>,chainUrl:&str,contractAddress:&str,abiPath:&str,account:&str) -> Result<bool, String> {
+    let tokens = verify_txs_tokens(proofs)?;
+    send_verification(BATCH_VERIFIER_CONTRACT, "verifyTxs", tokens, chainUrl, contractAddress, abiPath, account).await
+}
+
+/// Sends a transaction calling `function` of `contract` with `tokens`, signed with `account`.
+/// Returns whether it succeeded.
+async fn send_verification(
+    contract: &str,
+    function: &str,
+    tokens: Vec<Token>,
+    chainUrl:&str,contractAddress:&str,abiPath:&str,account:&str) -> Result<bool, String> {
+
+    let key_bytes = hex::decode(account.trim_start_matches("0x"))
+        .map_err(|why| format!("Invalid account key: {}", why))?;
+
+    let key = SecretKey::from_slice(key_bytes.as_slice())
+        .map_err(|why| format!("Invalid account key: {}", why))?;
+
+
+    let http = web3::transports::Http::new(chainUrl)
+        .map_err(|why| format!("Could not connect to {}: {}", chainUrl, why))?;
     let web3 = web3::Web3::new(http);
 
     let mut op = Options::default();
     op.gas = Some(3_000_000.into());
-    let abi_path = abiPath.to_owned() + "/Verifier.abi";
-    let contract = Contract::from_json(web3.eth(),contractAddress.parse().unwrap(),fs::read(abi_path).unwrap().as_slice()).unwrap();
-
-    // let inputs = contract.abi().function("verifyTx").unwrap().encode_input(&[proof_token, input_token.clone()]).unwrap();
-    let result = contract.signed_call_with_confirmations("verifyTx",vec![proof_token, input_token.clone()].as_slice(), op, 1, &key).await.unwrap();
-    if result.status.unwrap() == U64::from(1){
+    let address = contractAddress
+        .parse()
+        .map_err(|why| format!("Invalid contract address {}: {}", contractAddress, why))?;
+    let (abi_path, abi) = read_verifier_abi(abiPath, contract)?;
+    let contract = Contract::from_json(web3.eth(), address, abi.as_slice())
+        .map_err(|why| format!("Invalid verifier ABI {}: {}", abi_path, why))?;
+
+    let result = contract
+        .signed_call_with_confirmations(function,tokens.as_slice(), op, 1, &key)
+        .await
+        .map_err(|why| format!("{} failed: {}", function, why))?;
+    let status = result
+        .status
+        .ok_or_else(|| format!("No status in the receipt of {:?}", result.transaction_hash))?;
+    if status == U64::from(1){
         println!("verify proof success, tx hash is: {:?}",result.transaction_hash);
         vlog::warn!("verify proof success, tx hash is: {:?}",result.transaction_hash);
-        return true;
+        Ok(true)
     } else {
         println!("verify proof false");
         vlog::warn!("verify proof false");
-        return  false;
+        Ok(false)
     }
 
 }
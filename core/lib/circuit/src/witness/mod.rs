@@ -1,13 +1,11 @@
 // Built-in deps
-use std::collections::HashMap;
 use std::fs::File;
-use std::str::FromStr;
-use std::io::{BufReader, BufWriter, Read, stdin};
-use std::path::Path;
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
 use zokrates_abi::Encode;
 use zokrates_ast::ir;
 // Workspace imports
-use types::{BlockNumber};
 use zokrates_ast::ir::{ProgEnum};
 use zokrates_ast::typed::{ConcreteSignature, ConcreteType};
 use zokrates_ast::typed::abi::Abi;
@@ -16,49 +14,114 @@ use zokrates_field::Field;
 // External imports
 use serde_json::from_reader;
 
-pub fn compute_witness(args: &HashMap<String, String>) -> Result<String, String> {
-    let path = Path::new(args.get(&"input".to_string()).unwrap());
-    let file = File::open(path).map_err(|why| format!("Could not open {}: {}", path.display(), why))?;
+/// Witness of a program run, serialized by `ir::Witness::write`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Witness(Vec<u8>);
 
-    let mut reader = BufReader::new(file);
+impl Witness {
+    pub fn from_bytes(bytes: Vec<u8>) -> Self {
+        Self(bytes)
+    }
 
-    match ProgEnum::deserialize(&mut reader)? {
-        ProgEnum::Bls12_381Program(p) => compute(p, args),
-        ProgEnum::Bn128Program(p) => compute(p, args),
-        ProgEnum::Bls12_377Program(p) => compute(p, args),
-        ProgEnum::Bw6_761Program(p) => compute(p, args),
-        ProgEnum::PallasProgram(p) => compute(p, args),
-        ProgEnum::VestaProgram(p) => compute(p, args),
+    /// Decodes a witness stored as a hex string.
+    pub fn from_hex(witness: &str) -> Result<Self, String> {
+        let bytes = hex::decode(witness).map_err(|why| format!("Could not decode witness: {}", why))?;
+        Ok(Self(bytes))
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.0
+    }
+
+    /// Encodes the witness as a hex string, the way it is stored in the database.
+    pub fn to_hex(&self) -> String {
+        hex::encode(&self.0)
     }
 }
 
-fn compute<'a, T: Field, I: Iterator<Item=ir::Statement<'a, T>>>(
-    ir_prog: ir::ProgIterator<'a, T, I>,
-    args: &HashMap<String, String>,
-) -> Result<String, String> {
-    vlog::warn!("Computing witness...");
+/// Options of `compute_witness`.
+#[derive(Debug, Clone)]
+pub struct WitnessOptions {
+    /// Compiled program.
+    program_path: PathBuf,
+    /// ABI of the program, the arguments are parsed as JSON against it if set.
+    abi_path: Option<PathBuf>,
+    /// Arguments of the program.
+    arguments: String,
+    /// Print the outputs of the program.
+    verbose: bool,
+}
+
+impl WitnessOptions {
+    /// Options for running the compiled program at `program_path` without arguments.
+    pub fn new(program_path: impl Into<PathBuf>) -> Self {
+        Self {
+            program_path: program_path.into(),
+            abi_path: None,
+            arguments: String::new(),
+            verbose: false,
+        }
+    }
 
-    let t = "true".to_string();
-    let verbose = matches!(args.get(&"verbose".to_string()).unwrap_or(&"true".to_string()), t);
-    let is_stdin = matches!(args.get(&"stdin".to_string()).unwrap_or(&"true".to_string()),t);
-    let is_abi = matches!(args.get(&"abi".to_string()).unwrap_or(&"true".to_string()),t);
+    /// Parses the arguments as JSON values of the types declared by the ABI at `abi_path`.
+    pub fn abi(mut self, abi_path: impl Into<PathBuf>) -> Self {
+        self.abi_path = Some(abi_path.into());
+        self
+    }
 
-    if !is_stdin && is_abi {
-        return Err("ABI input as inline argument is not supported. Please use `stdin`.".into());
+    /// Sets the arguments of the program: JSON if an ABI is set,
+    /// decimal field elements separated by spaces otherwise.
+    pub fn arguments(mut self, arguments: impl Into<String>) -> Self {
+        self.arguments = arguments.into();
+        self
     }
 
-    let signature = match is_abi {
-        true => {
-            let path = Path::new(args.get(&"abi-spec".to_string()).unwrap());
-            let file = File::open(path)
-                .map_err(|why| format!("Could not open {}: {}", path.display(), why))?;
-            let mut reader = BufReader::new(file);
+    pub fn verbose(mut self, verbose: bool) -> Self {
+        self.verbose = verbose;
+        self
+    }
+}
 
-            let abi: Abi = from_reader(&mut reader).map_err(|why| why.to_string())?;
+/// Runs the program with the arguments of `options`.
+/// Records the duration of the run and the amount of constraints of the program.
+pub fn compute_witness(options: &WitnessOptions) -> Result<Witness, String> {
+    let path = options.program_path.as_path();
+    let file = File::open(path).map_err(|why| format!("Could not open {}: {}", path.display(), why))?;
 
-            abi.signature()
-        }
-        false => ConcreteSignature::new()
+    let mut reader = BufReader::new(file);
+
+    match ProgEnum::deserialize(&mut reader)? {
+        ProgEnum::Bls12_381Program(p) => compute(p, options),
+        ProgEnum::Bn128Program(p) => compute(p, options),
+        ProgEnum::Bls12_377Program(p) => compute(p, options),
+        ProgEnum::Bw6_761Program(p) => compute(p, options),
+        ProgEnum::PallasProgram(p) => compute(p, options),
+        ProgEnum::VestaProgram(p) => compute(p, options),
+    }
+}
+
+fn read_abi(path: &Path) -> Result<Abi, String> {
+    let file = File::open(path)
+        .map_err(|why| format!("Could not open {}: {}", path.display(), why))?;
+    let mut reader = BufReader::new(file);
+
+    from_reader(&mut reader).map_err(|why| why.to_string())
+}
+
+fn compute<'a, T: Field, I: Iterator<Item=ir::Statement<'a, T>>>(
+    ir_prog: ir::ProgIterator<'a, T, I>,
+    options: &WitnessOptions,
+) -> Result<Witness, String> {
+    vlog::info!("Computing witness...");
+    let start = Instant::now();
+
+    let signature = match &options.abi_path {
+        Some(abi_path) => read_abi(abi_path)?.signature(),
+        None => ConcreteSignature::new()
             .inputs(vec![ConcreteType::FieldElement; ir_prog.arguments.len()])
             .output(ConcreteType::Tuple(GTupleType::new(
                 vec![ConcreteType::FieldElement; ir_prog.return_count],
@@ -68,75 +131,67 @@ fn compute<'a, T: Field, I: Iterator<Item=ir::Statement<'a, T>>>(
     use zokrates_abi::Inputs;
 
     // get arguments
-    let arguments = match is_stdin {
-        // take inline arguments
-        false => {
-            let arguments = args.get(&"arguments".to_string()).unwrap();
-            arguments
-                .split(' ')
-                .map(|x| T::try_from_dec_str(x).map_err(|_| x.to_string()))
-                .collect::<Result<Vec<_>, _>>()
-                .map(Inputs::Raw)
-        }
-        // take stdin arguments
-        true => {
-            let mut stdin = stdin();
-            let mut input = String::new();
-
-            match is_abi {
-                true => {
-                    use zokrates_abi::parse_strict;
-
-                    input = args.get(&"arguments".to_string()).clone().unwrap().to_string();
-                    parse_strict(&input, signature.inputs)
-                        .map(Inputs::Abi)
-                        .map_err(|why| why.to_string())
-                },
-                false =>  match ir_prog.arguments.len() {
-                    0 => Ok(Inputs::Raw(vec![])),
-                    _ => match stdin.read_to_string(&mut input) {
-                        Ok(_) => {
-                            input.retain(|x| x != '\n');
-                            input
-                                .split(' ')
-                                .map(|x| T::try_from_dec_str(x).map_err(|_| x.to_string()))
-                                .collect::<Result<Vec<_>, _>>()
-                                .map(Inputs::Raw)
-                        }
-                        Err(_) => Err(String::from("???")),
-                    },
-                }
-            }
+    let arguments = match options.abi_path {
+        Some(_) => {
+            use zokrates_abi::parse_strict;
+
+            parse_strict(&options.arguments, signature.inputs)
+                .map(Inputs::Abi)
+                .map_err(|why| why.to_string())
         }
+        None => options
+            .arguments
+            .split_whitespace()
+            .map(|x| T::try_from_dec_str(x).map_err(|_| x.to_string()))
+            .collect::<Result<Vec<_>, _>>()
+            .map(Inputs::Raw),
     }
     .map_err(|e| format!("Could not parse argument: {}", e))?;
 
     let interpreter = zokrates_interpreter::Interpreter::default();
 
+    let mut constraints = 0u64;
+    let statements = ir_prog.statements.inspect(|statement| {
+        if matches!(statement, ir::Statement::Constraint(..)) {
+            constraints += 1;
+        }
+    });
     let witness = interpreter
         .execute_with_log_stream(
             &arguments.encode(),
-            ir_prog.statements,
+            statements,
             &ir_prog.arguments,
             &ir_prog.solvers,
             &mut std::io::stdout(),
         )
         .map_err(|e| format!("Execution failed: {}", e))?;
+    metrics::histogram!("circuit.compute_witness", start.elapsed());
+    metrics::histogram!("circuit.constraints", constraints as f64);
 
     use zokrates_abi::Decode;
 
-    let results_json_value: serde_json::Value =
-        zokrates_abi::Value::decode(witness.return_values(), *signature.output).into_serde_json();
-
-    if verbose {
-        println!("\nWitness: \n{}\n", results_json_value);
+    if options.verbose {
+        let results_json_value: serde_json::Value =
+            zokrates_abi::Value::decode(witness.return_values(), *signature.output).into_serde_json();
+        vlog::info!("Witness: {}", results_json_value);
     }
 
     let mut buff = BufWriter::new(Vec::new());
     witness.write(&mut buff).map_err(|e| format!("Serialize witness failed: {}", e))?;
     let bytes = buff.into_inner().map_err(|e| format!("get witness buff failed: {}", e))?;
-    let witness_str = hex::encode(bytes);
-    println!("\nwitness_str: \n{}\n", witness_str);
 
-    Ok(witness_str)
-}
\ No newline at end of file
+    Ok(Witness(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn witness_hex_round_trip() {
+        let witness = Witness::from_bytes(vec![0x00, 0x2a, 0xff]);
+        assert_eq!(witness.to_hex(), "002aff");
+        assert_eq!(Witness::from_hex("002aff").unwrap(), witness);
+        assert!(Witness::from_hex("0x2a").is_err());
+    }
+}
@@ -0,0 +1,54 @@
+use std::path::Path;
+
+use circuit::differential::CompiledCircuit;
+use mips_vm::commitment::state_commitment;
+use mips_vm::model::States;
+use rand_0_8::rngs::StdRng;
+use rand_0_8::{Rng, SeedableRng};
+use zokrates_field::Field;
+
+#[test]
+#[ignore] // compiling the circuit takes a while
+fn commitment_matches_model() {
+    // compiling and interpreting the circuit needs more stack than a test thread has
+    let builder = std::thread::Builder::new().stack_size(64 * 1024 * 1024);
+
+    builder
+        .spawn(|| {
+            let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+            let stdlib = root.join("../../../zokrates_stdlib/stdlib");
+            let arena = typed_arena::Arena::new();
+            let circuit =
+                CompiledCircuit::compile(&root.join("state_commitment.zok"), &stdlib, &arena)
+                    .unwrap();
+
+            let mut rng = StdRng::seed_from_u64(42);
+            for _ in 0..10 {
+                let mut regs = [0u32; 32];
+                rng.fill(&mut regs[1..]);
+                let states = States {
+                    cycle: rng.gen(),
+                    pc: rng.gen(),
+                    next_pc: rng.gen(),
+                    lo: rng.gen(),
+                    hi: rng.gen(),
+                    regs,
+                    heap: rng.gen(),
+                    exit_code: rng.gen(),
+                    exited: rng.gen(),
+                    mem_root: mips_vm::Memory::new().root(),
+                };
+
+                let output = circuit
+                    .execute(vec![serde_json::to_value(&states).unwrap()])
+                    .unwrap();
+                assert_eq!(
+                    output,
+                    serde_json::Value::String(state_commitment(&states).to_dec_string())
+                );
+            }
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+}
@@ -0,0 +1,75 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use circuit::differential::CompiledCircuit;
+
+/// The circuits whose constraint count is tracked, relative to the crate root.
+const CIRCUITS: [&str; 2] = ["differential.zok", "mips_vm_poseidon.zok"];
+
+/// The recorded constraint counts, relative to the crate root.
+const RECORD: &str = "constraint_counts.json";
+
+/// How many lines of the profile to report when a count grows.
+const PROFILE_LINES: usize = 30;
+
+#[test]
+#[ignore] // compiling the circuits takes several minutes
+fn constraint_counts_do_not_grow() {
+    // compiling the circuit needs more stack than a test thread has
+    let builder = std::thread::Builder::new().stack_size(64 * 1024 * 1024);
+
+    builder
+        .spawn(|| {
+            let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+            let stdlib = root.join("../../../zokrates_stdlib/stdlib");
+            let record_path = root.join(RECORD);
+            let update = std::env::var_os("UPDATE_CONSTRAINT_COUNTS").is_some();
+
+            let mut record: BTreeMap<String, usize> = std::fs::read_to_string(&record_path)
+                .map(|s| serde_json::from_str(&s).unwrap())
+                .unwrap_or_default();
+
+            let mut failures = vec![];
+            for name in CIRCUITS {
+                let arena = typed_arena::Arena::new();
+                let circuit = CompiledCircuit::compile(&root.join(name), &stdlib, &arena).unwrap();
+                let (count, profile) = circuit.profile();
+
+                match record.get(name) {
+                    _ if update => {
+                        record.insert(name.to_string(), count);
+                    }
+                    None => failures.push(format!(
+                        "{}: {} constraints, none recorded, run with UPDATE_CONSTRAINT_COUNTS=1",
+                        name, count
+                    )),
+                    Some(&recorded) if count > recorded => failures.push(format!(
+                        "{}: {} constraints, {} recorded. Top of the profile:\n{}",
+                        name,
+                        count,
+                        recorded,
+                        profile
+                            .lines()
+                            .take(PROFILE_LINES)
+                            .collect::<Vec<_>>()
+                            .join("\n")
+                    )),
+                    Some(&recorded) if count < recorded => failures.push(format!(
+                        "{}: {} constraints, down from {} recorded, run with UPDATE_CONSTRAINT_COUNTS=1 to keep the improvement",
+                        name, count, recorded
+                    )),
+                    Some(_) => {}
+                }
+            }
+
+            if update {
+                let json = serde_json::to_string_pretty(&record).unwrap();
+                std::fs::write(&record_path, json + "\n").unwrap();
+            }
+
+            assert!(failures.is_empty(), "{}", failures.join("\n"));
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+}
@@ -0,0 +1,35 @@
+use std::path::Path;
+
+use circuit::differential::{fuzz, StepCircuit};
+
+#[test]
+#[ignore] // compiling the circuit takes several minutes
+fn model_matches_circuit() {
+    // compiling and interpreting the circuit needs more stack than a test thread has
+    let builder = std::thread::Builder::new().stack_size(64 * 1024 * 1024);
+
+    builder
+        .spawn(|| {
+            let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+            let stdlib = root.join("../../../zokrates_stdlib/stdlib");
+            let arena = typed_arena::Arena::new();
+            let circuit =
+                StepCircuit::compile(&root.join("differential.zok"), &stdlib, &arena).unwrap();
+
+            let reports = fuzz(&circuit, 500, 42);
+            let report = reports
+                .iter()
+                .map(|r| r.to_string())
+                .collect::<Vec<_>>()
+                .join("\n");
+            assert!(
+                reports.is_empty(),
+                "{} of 500 instructions diverge:\n{}",
+                reports.len(),
+                report
+            );
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+}
@@ -0,0 +1,121 @@
+use std::path::Path;
+
+use circuit::batch::BatchInput;
+use circuit::generator::{CircuitCache, CircuitParams, Features, MemoryModel};
+use circuit::witness::{compute_witness, WitnessOptions};
+use mips_vm::state::HEAP_START;
+use mips_vm::{Executor, Memory, NoPreimages, State};
+use serde_json::Value;
+
+const BASE: u32 = 0x0040_0000;
+const BATCH_SIZE: u32 = 8;
+const MAX_LEAVES: u32 = 4;
+
+#[test]
+#[ignore] // compiling the circuit takes several minutes
+fn offline_memory_accepts_trace() {
+    // compiling and interpreting the circuit needs more stack than a test thread has
+    let builder = std::thread::Builder::new().stack_size(64 * 1024 * 1024);
+
+    builder
+        .spawn(|| {
+            let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+            let stdlib = root.join("../../../zokrates_stdlib/stdlib");
+            let params = CircuitParams {
+                memory_model: MemoryModel::Offline {
+                    max_leaves: MAX_LEAVES,
+                },
+                features: Features {
+                    preimage: false,
+                    commitment: false,
+                },
+                ..CircuitParams::new(BATCH_SIZE)
+            };
+            let dir = tempdir::TempDir::new("offline_memory").unwrap();
+            let circuit = CircuitCache::new(dir.path(), &stdlib).get(&params).unwrap();
+            let input = BatchInput::from_abi(&circuit.abi_path).unwrap();
+            assert_eq!(
+                input,
+                BatchInput::Offline {
+                    batch_size: BATCH_SIZE,
+                    max_leaves: MAX_LEAVES,
+                }
+            );
+
+            // a loop loading back what it stores, with a store in the delay slot of its branch
+            let program = [
+                0x2408_0002, // addiu $t0, $zero, 2
+                0xac08_0100, // sw    $t0, 0x100($zero)
+                0x8c09_0100, // lw    $t1, 0x100($zero)
+                0x2508_ffff, // addiu $t0, $t0, -1
+                0x1500_fffd, // bne   $t0, $zero, -3
+                0xac09_0104, // sw    $t1, 0x104($zero)
+            ];
+            let mut memory = Memory::new();
+            for (i, insn) in program.iter().enumerate() {
+                memory.set_word(BASE + 4 * i as u32, *insn).unwrap();
+            }
+            let state = State {
+                cycle: 0,
+                pc: BASE,
+                next_pc: BASE + 4,
+                lo: 0,
+                hi: 0,
+                regs: [0; 32],
+                heap: HEAP_START,
+                exit_code: 0,
+                exited: false,
+                memory,
+            };
+            let mut executor = Executor::new(state, NoPreimages);
+            // the traces the way the witness generator loads them
+            let traces: Vec<Value> = (0..BATCH_SIZE)
+                .map(|_| serde_json::to_value(executor.step().unwrap().unwrap()).unwrap())
+                .collect();
+
+            let witness = |arguments: &str| {
+                compute_witness(
+                    &WitnessOptions::new(&circuit.program_path)
+                        .abi(&circuit.abi_path)
+                        .arguments(arguments),
+                )
+            };
+            let arguments = input.arguments(traces).unwrap();
+            assert!(witness(&arguments).is_ok());
+
+            // swapping two accesses to the data leaf breaks the order by leaf and time
+            let batch: Value = serde_json::from_str(&arguments).unwrap();
+            let data = batch["sorted"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .position(|access| access["leaf"] == "0x00000008")
+                .unwrap();
+            let mut tampered = batch.clone();
+            tampered["sorted"]
+                .as_array_mut()
+                .unwrap()
+                .swap(data, data + 1);
+            assert!(witness(&tampered.to_string()).is_err());
+
+            // the load reads a value the store before it didn't write
+            let load = batch["sorted"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .position(|access| access["time"] == "0x00000005")
+                .unwrap();
+            let mut tampered = batch.clone();
+            tampered["sorted"][load]["before"][3] = "0x03".into();
+            tampered["sorted"][load]["after"][3] = "0x03".into();
+            assert!(witness(&tampered.to_string()).is_err());
+
+            // the record of the load doesn't match its sorted access
+            let mut tampered = batch;
+            tampered["records"][2]["mem_leaf"][3] = "0x03".into();
+            assert!(witness(&tampered.to_string()).is_err());
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+}
@@ -0,0 +1,183 @@
+use std::path::Path;
+
+use circuit::differential::CompiledCircuit;
+use circuit::generator::CircuitParams;
+use mips_vm::cpu::{PREIMAGE_DATA_ADDR, PREIMAGE_KEY_ADDR};
+use mips_vm::state::HEAP_START;
+use mips_vm::{preimage_key, DirectoryOracle, Executor, Memory, NoPreimages, State};
+use serde_json::Value;
+
+const BASE: u32 = 0x0040_0000;
+
+fn state(program: &[u32], mut memory: Memory) -> State {
+    for (i, insn) in program.iter().enumerate() {
+        memory.set_word(BASE + 4 * i as u32, *insn).unwrap();
+    }
+    State {
+        cycle: 0,
+        pc: BASE,
+        next_pc: BASE + 4,
+        lo: 0,
+        hi: 0,
+        regs: [0; 32],
+        heap: HEAP_START,
+        exit_code: 0,
+        exited: false,
+        memory,
+    }
+}
+
+/// Runs `test` on a thread with a stack large enough to compile and interpret the circuit.
+fn with_large_stack(test: impl FnOnce() + Send + 'static) {
+    std::thread::Builder::new()
+        .stack_size(64 * 1024 * 1024)
+        .spawn(test)
+        .unwrap()
+        .join()
+        .unwrap();
+}
+
+#[test]
+#[ignore] // compiling the circuit takes several minutes
+fn forged_memory_proof_is_rejected() {
+    with_large_stack(|| {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let stdlib = root.join("../../../zokrates_stdlib/stdlib");
+        let arena = typed_arena::Arena::new();
+        let circuit =
+            CompiledCircuit::compile(&root.join("mips_vm_poseidon.zok"), &stdlib, &arena).unwrap();
+
+        let program = [
+            0x3c08_1000, // lui   $t0, 0x1000
+            0x8d09_0000, // lw    $t1, 0($t0)
+            0xad09_0004, // sw    $t1, 4($t0)
+        ];
+        let mut memory = Memory::new();
+        memory.set_word(0x1000_0000, 7).unwrap();
+        let mut executor = Executor::new(state(&program, memory), NoPreimages);
+        executor.step().unwrap().unwrap();
+
+        let load = executor.step().unwrap().unwrap();
+        let store = executor.step().unwrap().unwrap();
+        for record in &[&load, &store] {
+            assert_eq!(
+                circuit.execute(vec![serde_json::to_value(record).unwrap()]),
+                Ok(Value::Bool(true))
+            );
+        }
+
+        // the leaf claims that the word holds 8, and the post states follow from it
+        let mut forged = load.clone();
+        forged.mem_proof[3] = 8;
+        forged.new_regs[9] = 8;
+        forged.new_mem_root = Memory::root_from_proof(&forged.mem_proof, 0x1000_0000);
+        assert!(circuit
+            .execute(vec![serde_json::to_value(&forged).unwrap()])
+            .is_err());
+
+        let mut forged = store.clone();
+        forged.mem_proof[3] = 8;
+        let mut written = forged.mem_proof.clone();
+        written[4..8].copy_from_slice(&7u32.to_be_bytes());
+        forged.new_mem_root = Memory::root_from_proof(&written, 0x1000_0004);
+        assert!(circuit
+            .execute(vec![serde_json::to_value(&forged).unwrap()])
+            .is_err());
+    });
+}
+
+#[test]
+#[ignore] // compiling the circuit takes several minutes
+fn preimage_is_checked_once_per_batch() {
+    with_large_stack(|| {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let stdlib = root.join("../../../zokrates_stdlib/stdlib");
+        let dir = tempdir::TempDir::new("preimage_batch").unwrap();
+        let sources = CircuitParams::new(2).sources().unwrap();
+        for (file, source) in &sources {
+            std::fs::write(dir.path().join(file), source).unwrap();
+        }
+        let arena = typed_arena::Arena::new();
+        let circuit =
+            CompiledCircuit::compile(&dir.path().join(sources[0].0), &stdlib, &arena).unwrap();
+
+        let program = [
+            0x2402_0fb4, // addiu $v0, $zero, 4020
+            0x0000_000c, // syscall
+        ];
+        let preimage = b"block header".repeat(40);
+        let key = preimage_key(&preimage);
+        std::fs::write(
+            dir.path().join(format!("0x{}", hex::encode(key))),
+            &preimage,
+        )
+        .unwrap();
+        let mut memory = Memory::new();
+        memory.set_memory_range(PREIMAGE_KEY_ADDR, &key);
+        memory.set_word(PREIMAGE_DATA_ADDR + 0x300, 0x42).unwrap();
+        let mut executor = Executor::new(state(&program, memory), DirectoryOracle::new(dir.path()));
+        let records: Vec<_> = (0..2).map(|_| executor.step().unwrap().unwrap()).collect();
+        let arguments = |records: &[mips_vm::TraceRecord]| vec![serde_json::json!(records)];
+
+        assert_eq!(circuit.execute(arguments(&records)), Ok(Value::Bool(true)));
+
+        // the preimage doesn't hash to the key
+        let mut forged = records.clone();
+        forged[1].preimage.data[0] ^= 1;
+        assert!(circuit.execute(arguments(&forged)).is_err());
+
+        // the root after the syscall isn't the one of the memory with the preimage written
+        let mut forged = records;
+        forged[1].new_mem_root[0] ^= 1;
+        assert!(circuit.execute(arguments(&forged)).is_err());
+    });
+}
+
+#[test]
+#[ignore] // compiling the circuit takes several minutes
+fn two_preimages_are_checked_in_one_batch() {
+    with_large_stack(|| {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let stdlib = root.join("../../../zokrates_stdlib/stdlib");
+        let dir = tempdir::TempDir::new("preimage_calls").unwrap();
+        let params = CircuitParams {
+            preimage_calls: 2,
+            ..CircuitParams::new(4)
+        };
+        let sources = params.sources().unwrap();
+        for (file, source) in &sources {
+            std::fs::write(dir.path().join(file), source).unwrap();
+        }
+        let arena = typed_arena::Arena::new();
+        let circuit =
+            CompiledCircuit::compile(&dir.path().join(sources[0].0), &stdlib, &arena).unwrap();
+
+        let program = [
+            0x2402_0fb4, // addiu $v0, $zero, 4020
+            0x0000_000c, // syscall
+            0x2402_0fb4, // addiu $v0, $zero, 4020
+            0x0000_000c, // syscall
+        ];
+        let preimage = b"block header".repeat(40);
+        let key = preimage_key(&preimage);
+        std::fs::write(
+            dir.path().join(format!("0x{}", hex::encode(key))),
+            &preimage,
+        )
+        .unwrap();
+        let mut memory = Memory::new();
+        memory.set_memory_range(PREIMAGE_KEY_ADDR, &key);
+        let mut executor = Executor::new(state(&program, memory), DirectoryOracle::new(dir.path()));
+        let records: Vec<_> = (0..4).map(|_| executor.step().unwrap().unwrap()).collect();
+        let arguments = |records: &[mips_vm::TraceRecord]| vec![serde_json::json!(records)];
+
+        assert_eq!(circuit.execute(arguments(&records)), Ok(Value::Bool(true)));
+
+        // each syscall is checked in its own slot
+        for call in [1, 3].iter() {
+            let mut forged = records.clone();
+            forged[*call].preimage.data[0] ^= 1;
+            assert!(circuit.execute(arguments(&forged)).is_err());
+        }
+    });
+}
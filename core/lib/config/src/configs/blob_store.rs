@@ -0,0 +1,67 @@
+// External uses
+use serde::Deserialize;
+// Local uses
+use crate::envy_load;
+
+/// Object store the witnesses are written to.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum BlobStoreKind {
+    /// Files under `path`.
+    Fs,
+    /// A bucket of an S3-compatible service, e.g. MinIO.
+    S3,
+}
+
+/// Configuration of the object store shared by the witness generator and the provers.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct BlobStoreConfig {
+    /// `fs` or `s3`.
+    pub kind: BlobStoreKind,
+    /// Directory of the `fs` store.
+    pub path: Option<String>,
+    /// Endpoint of the `s3` store, e.g. `http://127.0.0.1:9000` for a local MinIO.
+    pub s3_endpoint: Option<String>,
+    pub s3_region: Option<String>,
+    pub s3_bucket: Option<String>,
+    pub s3_access_key: Option<String>,
+    pub s3_secret_key: Option<String>,
+}
+
+impl BlobStoreConfig {
+    pub fn from_env() -> Self {
+        envy_load!("blob_store", "BLOB_STORE_")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::configs::test_utils::set_env;
+
+    #[test]
+    fn from_env() {
+        let config = r#"
+BLOB_STORE_KIND="s3"
+BLOB_STORE_S3_ENDPOINT="http://127.0.0.1:9000"
+BLOB_STORE_S3_REGION="us-east-1"
+BLOB_STORE_S3_BUCKET="witnesses"
+BLOB_STORE_S3_ACCESS_KEY="minioadmin"
+BLOB_STORE_S3_SECRET_KEY="minioadmin"
+        "#;
+        set_env(config);
+
+        assert_eq!(
+            BlobStoreConfig::from_env(),
+            BlobStoreConfig {
+                kind: BlobStoreKind::S3,
+                path: None,
+                s3_endpoint: Some("http://127.0.0.1:9000".into()),
+                s3_region: Some("us-east-1".into()),
+                s3_bucket: Some("witnesses".into()),
+                s3_access_key: Some("minioadmin".into()),
+                s3_secret_key: Some("minioadmin".into()),
+            }
+        );
+    }
+}
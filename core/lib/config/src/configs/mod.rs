@@ -9,6 +9,7 @@ pub use self::{
 };
 
 pub mod api;
+pub mod blob_store;
 pub mod chain;
 pub mod contracts;
 pub mod database;
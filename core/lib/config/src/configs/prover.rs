@@ -11,6 +11,8 @@ pub struct ProverConfig {
     pub prover: Prover,
     pub core: Core,
     pub witness_generator: WitnessGenerator,
+    pub circuit: Circuit,
+    pub retry: Retry,
 }
 
 impl ProverConfig {
@@ -19,10 +21,22 @@ impl ProverConfig {
             prover: envy_load!("prover.prover", "PROVER_PROVER_"),
             core: envy_load!("prover.core", "PROVER_CORE_"),
             witness_generator: envy_load!("prover.witness_generator", "PROVER_WITNESS_GENERATOR_"),
+            circuit: envy_load!("prover.circuit", "CIRCUIT_"),
+            retry: envy_load!("prover.retry", "PROVER_RETRY_"),
         }
     }
 }
 
+/// Proof system the prover proves the traces with.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ProverMode {
+    /// A Groth16 proof for each job of the queue, with `Circuit`.
+    Groth16,
+    /// A compressed Nova proof for each program run, folding all its trace records with `NovaCircuit`.
+    Nova,
+}
+
 /// Actual prover application settings.
 #[derive(Debug, Deserialize, Clone, PartialEq)]
 pub struct Prover {
@@ -34,6 +48,12 @@ pub struct Prover {
     pub request_timeout: u64,
     /// Flag for dying after proving cycle
     pub die_after_proof: bool,
+    /// Flag for getting jobs from the prover server API instead of the database.
+    pub use_api: bool,
+    /// Port of the Prometheus exporter of the prover, the metrics aren't exported if not set.
+    pub prometheus_port: Option<u16>,
+    /// `groth16` or `nova`.
+    pub mode: ProverMode,
 }
 
 impl Prover {
@@ -84,6 +104,67 @@ impl WitnessGenerator {
     }
 }
 
+/// Compiled circuit used by the witness generator and the prover.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct Circuit {
+    /// Amount of consecutive trace records covered by one witness and proof.
+    /// Must match the size of the `main` input of the compiled circuit.
+    pub batch_size: u32,
+    /// Path to the compiled circuit.
+    pub file_path: String,
+    /// Path to the ABI of the compiled circuit.
+    pub abi_file_path: String,
+    /// Path to the proving key of the compiled circuit.
+    pub proving_key_path: String,
+    /// Path to the verification key of the compiled circuit.
+    pub verification_key_path: String,
+    /// Maximum number of distinct leaves of a batch, set if the circuit is `offline_memory.zok`:
+    /// the witnesses are then built with the sorted memory accesses of the batches.
+    pub offline_max_leaves: Option<u32>,
+}
+
+impl Circuit {
+    pub fn from_env() -> Self {
+        envy_load!("circuit", "CIRCUIT_")
+    }
+}
+
+/// Step circuit of the Nova mode, `nova_step.zok` compiled for the Pallas curve.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct NovaCircuit {
+    /// Path to the compiled step circuit.
+    pub file_path: String,
+    /// Path to the ABI of the compiled step circuit.
+    pub abi_file_path: String,
+    /// Path to the public parameters written by `zokrates nova setup`, generated by the prover if not set.
+    pub params_path: Option<String>,
+}
+
+impl NovaCircuit {
+    pub fn from_env() -> Self {
+        envy_load!("nova_circuit", "NOVA_CIRCUIT_")
+    }
+}
+
+/// Retry policy for the blocks that fail to be witnessed, proven or verified.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+pub struct Retry {
+    /// Amount of failed attempts after which a block is moved to the dead letter queue.
+    pub max_attempts: u32,
+    /// Delay before the first retry in ms, doubled after every failed attempt.
+    pub backoff: u64,
+    /// Upper bound of the delay between retries in ms.
+    pub max_backoff: u64,
+}
+
+impl Retry {
+    /// Returns the delay before retrying a block that failed `attempts` times.
+    pub fn backoff(&self, attempts: u32) -> Duration {
+        let factor = 1u64.checked_shl(attempts.saturating_sub(1)).unwrap_or(u64::MAX);
+        Duration::from_millis(self.backoff.saturating_mul(factor).min(self.max_backoff))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -96,6 +177,9 @@ mod tests {
                 cycle_wait: 500,
                 request_timeout: 10,
                 die_after_proof: false,
+                use_api: true,
+                prometheus_port: Some(3313),
+                mode: ProverMode::Groth16,
             },
             core: Core {
                 gone_timeout: 60000,
@@ -105,6 +189,19 @@ mod tests {
                 prepare_data_interval: 500,
                 witness_generators: 2,
             },
+            circuit: Circuit {
+                batch_size: 50,
+                file_path: "/usr/src/circuit/out".into(),
+                abi_file_path: "/usr/src/circuit/abi.json".into(),
+                proving_key_path: "/usr/src/circuit/proving.key".into(),
+                verification_key_path: "/usr/src/circuit/verification.key".into(),
+                offline_max_leaves: Some(64),
+            },
+            retry: Retry {
+                max_attempts: 5,
+                backoff: 1000,
+                max_backoff: 60000,
+            },
         }
     }
 
@@ -115,10 +212,22 @@ PROVER_PROVER_HEARTBEAT_INTERVAL="1000"
 PROVER_PROVER_CYCLE_WAIT="500"
 PROVER_PROVER_REQUEST_TIMEOUT="10"
 PROVER_PROVER_DIE_AFTER_PROOF=false
+PROVER_PROVER_USE_API=true
+PROVER_PROVER_PROMETHEUS_PORT="3313"
+PROVER_PROVER_MODE="groth16"
 PROVER_CORE_GONE_TIMEOUT="60000"
 PROVER_CORE_IDLE_PROVERS="1"
 PROVER_WITNESS_GENERATOR_PREPARE_DATA_INTERVAL="500"
 PROVER_WITNESS_GENERATOR_WITNESS_GENERATORS="2"
+CIRCUIT_BATCH_SIZE="50"
+CIRCUIT_FILE_PATH="/usr/src/circuit/out"
+CIRCUIT_ABI_FILE_PATH="/usr/src/circuit/abi.json"
+CIRCUIT_PROVING_KEY_PATH="/usr/src/circuit/proving.key"
+CIRCUIT_VERIFICATION_KEY_PATH="/usr/src/circuit/verification.key"
+CIRCUIT_OFFLINE_MAX_LEAVES="64"
+PROVER_RETRY_MAX_ATTEMPTS="5"
+PROVER_RETRY_BACKOFF="1000"
+PROVER_RETRY_MAX_BACKOFF="60000"
         "#;
         set_env(config);
 
@@ -126,6 +235,24 @@ PROVER_WITNESS_GENERATOR_WITNESS_GENERATORS="2"
         assert_eq!(actual, expected_config());
     }
 
+    #[test]
+    fn nova_circuit_from_env() {
+        let config = r#"
+NOVA_CIRCUIT_FILE_PATH="/usr/src/circuit/nova/out"
+NOVA_CIRCUIT_ABI_FILE_PATH="/usr/src/circuit/nova/abi.json"
+        "#;
+        set_env(config);
+
+        assert_eq!(
+            NovaCircuit::from_env(),
+            NovaCircuit {
+                file_path: "/usr/src/circuit/nova/out".into(),
+                abi_file_path: "/usr/src/circuit/nova/abi.json".into(),
+                params_path: None,
+            }
+        );
+    }
+
     /// Checks the correctness of the config helper methods.
     #[test]
     fn methods() {
@@ -153,5 +280,9 @@ PROVER_WITNESS_GENERATOR_WITNESS_GENERATORS="2"
             config.witness_generator.prepare_data_interval(),
             Duration::from_millis(config.witness_generator.prepare_data_interval)
         );
+
+        assert_eq!(config.retry.backoff(1), Duration::from_millis(1000));
+        assert_eq!(config.retry.backoff(3), Duration::from_millis(4000));
+        assert_eq!(config.retry.backoff(100), Duration::from_millis(60000));
     }
 }
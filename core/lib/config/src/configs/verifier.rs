@@ -5,13 +5,36 @@ use serde::Deserialize;
 // Local uses
 use crate::envy_load;
 
+/// Settlement layer the proofs are submitted to.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum SettlementKind {
+    /// The verifier contract at `contract_address`.
+    Evm,
+    /// An in-process chain verifying the proofs with `backend`.
+    Mock,
+    /// Writes the calldata of the transactions to `dry_run_path` instead of sending them.
+    DryRun,
+}
+
 /// Configuration for the prover application and part of the server that interact with it.
 #[derive(Debug, Deserialize, Clone, PartialEq)]
 pub struct VerifierConfig {
     pub chain_url: String,
     pub contract_address: String,
+    /// Batch verifier contract, exported with `zokrates export-verifier --batch`.
+    pub batch_contract_address: Option<String>,
+    /// Private key of the account sending the transactions, in hex.
     pub account: String,
     pub abi_path: String,
+    /// Backend checking the proofs before they are submitted, `ark` or `bellman`.
+    pub backend: String,
+    /// `evm`, `mock` or `dry-run`.
+    pub settlement: SettlementKind,
+    /// Directory the `dry-run` settlement writes the calldata to.
+    pub dry_run_path: Option<String>,
+    /// Proofs submitted together to the batch verifier contract, they are submitted one by one if unset.
+    pub aggregation_size: Option<u32>,
 }
 
 impl VerifierConfig {
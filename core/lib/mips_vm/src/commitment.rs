@@ -0,0 +1,108 @@
+//! Poseidon commitment to a MIPS state, the counterpart of `state_commitment.zok`.
+//!
+//! `commitment.zok` exposes the commitments to the state before and after the proven batch as
+//! public outputs, which is what allows consecutive proofs to be chained.
+
+// External imports
+use num_bigint::BigUint;
+use zokrates_field::Bn128Field;
+// Local imports
+use crate::model::States;
+use crate::poseidon::{field_from_bytes, field_to_bytes, poseidon2};
+
+/// Concatenates `(value, bit width)` parts into a big-endian 256-bit word, the way the
+/// circuit builds the `bool[256]` argument of `pack256`.
+fn pack(parts: &[(u32, usize)]) -> Bn128Field {
+    let mut value = BigUint::from(0u32);
+    let mut width = 0;
+    for &(part, bits) in parts {
+        value = (value << bits) | BigUint::from(part);
+        width += bits;
+    }
+    assert!(width <= 256, "packed value doesn't fit into 256 bits");
+
+    let bytes = value.to_bytes_be();
+    let mut word = [0u8; 32];
+    word[32 - bytes.len()..].copy_from_slice(&bytes);
+    field_from_bytes(&word)
+}
+
+fn pack_regs(regs: &[u32]) -> Bn128Field {
+    let parts: Vec<_> = regs.iter().map(|&reg| (reg, 32)).collect();
+    pack(&parts)
+}
+
+/// Computes the commitment to `states`.
+pub fn state_commitment(states: &States) -> Bn128Field {
+    let header = pack(&[
+        (states.cycle, 32),
+        (states.pc, 32),
+        (states.next_pc, 32),
+        (states.lo, 32),
+        (states.hi, 32),
+        (states.heap, 32),
+        (u32::from(states.exit_code), 8),
+        (u32::from(states.exited), 1),
+    ]);
+
+    let mut acc = poseidon2(header, pack_regs(&states.regs[0..7]));
+    for regs in states.regs[7..].chunks(7) {
+        acc = poseidon2(acc, pack_regs(regs));
+    }
+    poseidon2(acc, field_from_bytes(&states.mem_root))
+}
+
+/// Formats a commitment the way it appears in the public inputs of a proof.
+pub fn to_proof_input(commitment: Bn128Field) -> String {
+    format!("0x{}", hex::encode(field_to_bytes(commitment)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::Memory;
+
+    fn states() -> States {
+        States {
+            cycle: 7,
+            pc: 0x0040_0000,
+            next_pc: 0x0040_0004,
+            lo: 1,
+            hi: 2,
+            regs: [3; 32],
+            heap: 0x2000_0000,
+            exit_code: 0,
+            exited: false,
+            mem_root: Memory::new().root(),
+        }
+    }
+
+    #[test]
+    fn pack_is_big_endian() {
+        assert_eq!(
+            pack(&[(1, 32), (2, 32)]),
+            Bn128Field::from((1u64 << 32) | 2)
+        );
+        assert_eq!(pack(&[(1, 8), (1, 1)]), Bn128Field::from(3u32));
+    }
+
+    #[test]
+    fn commitment_covers_every_field() {
+        let base = state_commitment(&states());
+
+        let mut changed = states();
+        changed.regs[31] = 4;
+        assert_ne!(state_commitment(&changed), base);
+
+        let mut changed = states();
+        changed.exited = true;
+        assert_ne!(state_commitment(&changed), base);
+
+        let mut changed = states();
+        changed.mem_root[31] ^= 1;
+        assert_ne!(state_commitment(&changed), base);
+
+        assert_eq!(state_commitment(&states()), base);
+        assert_eq!(to_proof_input(base).len(), 66);
+    }
+}
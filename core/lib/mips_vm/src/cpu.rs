@@ -0,0 +1,777 @@
+//! MIPS32 interpreter producing one trace record per executed instruction.
+//!
+//! Instruction semantics follow the Cannon `mipsevm`; the syscall set is the one
+//! `handleSyscall` in `mips_vm_poseidon.zok` knows how to check.
+
+// Local imports
+use crate::memory::{empty_proof, Memory};
+use crate::oracle::{preimage_key, PreimageOracle};
+use crate::state::State;
+use crate::trace::{PreimageData, StateSnapshot, TraceRecord};
+use crate::VmError;
+
+pub const SYS_EXIT: u32 = 4001;
+pub const SYS_READ: u32 = 4003;
+pub const SYS_WRITE: u32 = 4004;
+pub const SYS_OPEN: u32 = 4005;
+pub const SYS_PREIMAGE: u32 = 4020;
+pub const SYS_BRK: u32 = 4045;
+pub const SYS_FCNTL: u32 = 4055;
+pub const SYS_MMAP: u32 = 4090;
+pub const SYS_CLONE: u32 = 4120;
+pub const SYS_FUTEX: u32 = 4238;
+pub const SYS_EXIT_GROUP: u32 = 4246;
+pub const SYS_OPENAT: u32 = 4288;
+
+/// Syscalls which only return 0: the program has a single thread, no file besides the standard
+/// streams and gets no signal, so they have nothing to do. The thread id returned by gettid is 0.
+pub const NOOP_SYSCALLS: [u32; 27] = [
+    4006, // close
+    4024, // getuid
+    4047, // getgid
+    4054, // ioctl
+    4085, // readlink
+    4091, // munmap
+    4104, // setitimer
+    4122, // uname
+    4140, // _llseek
+    4162, // sched_yield
+    4166, // nanosleep
+    4194, // rt_sigaction
+    4195, // rt_sigprocmask
+    4206, // sigaltstack
+    4217, // mincore
+    4218, // madvise
+    4222, // gettid
+    4240, // sched_getaffinity
+    4248, // epoll_create
+    4249, // epoll_ctl
+    4257, // timer_create
+    4258, // timer_settime
+    4261, // timer_delete
+    4266, // tgkill
+    4313, // epoll_pwait
+    4326, // epoll_create1
+    4338, // prlimit64
+];
+
+/// Syscalls which fail with ENOSYS: they would have to write their results to memory (a stat,
+/// the time, random bytes, ...), which the circuit doesn't support.
+pub const NOSYS_SYSCALLS: [u32; 7] = [
+    4200, // pread64
+    4213, // stat64
+    4215, // fstat64
+    4263, // clock_gettime
+    4298, // readlinkat
+    4328, // pipe2
+    4353, // getrandom
+];
+
+const FUTEX_WAIT: u32 = 0;
+const FUTEX_WAKE: u32 = 1;
+/// Masks out the private and clock flags of a futex operation.
+const FUTEX_CMD_MASK: u32 = 0x7F;
+
+const FD_STDIN: u32 = 0;
+const FD_STDOUT: u32 = 1;
+const FD_STDERR: u32 = 2;
+const MIPS_EBADF: u32 = 0x9;
+const MIPS_EAGAIN: u32 = 0xb;
+const MIPS_EINVAL: u32 = 0x16;
+const MIPS_ENOSYS: u32 = 0x59;
+
+/// Address the guest writes the key of the requested preimage to.
+pub const PREIMAGE_KEY_ADDR: u32 = 0x3000_1000;
+/// Address the length of the preimage is written to, followed by the preimage itself.
+pub const PREIMAGE_DATA_ADDR: u32 = 0x3100_0000;
+/// Largest preimage the circuit can check, so that its hash takes at most 4 Keccak blocks.
+pub const PREIMAGE_MAX_SIZE: usize = 540;
+/// Depth of the subtree at [`PREIMAGE_DATA_ADDR`] whose leaves the circuit rewrites,
+/// large enough to hold the length and the largest preimage.
+pub const PREIMAGE_SUBTREE_DEPTH: usize = 5;
+
+const PAGE_ADDR_MASK: u32 = 0xFFF;
+const PAGE_SIZE: u32 = 0x1000;
+const BRK_START: u32 = 0x4000_0000;
+
+/// Sign-extends the lowest `bits` bits of `value`.
+pub(crate) fn sign_extend(value: u32, bits: u32) -> u32 {
+    let shift = 32 - bits;
+    (((value << shift) as i32) >> shift) as u32
+}
+
+pub struct Executor<O> {
+    state: State,
+    oracle: O,
+    /// Whether to collect Merkle proofs for the current step.
+    tracing: bool,
+    mem_proof: Option<Vec<u8>>,
+    /// Preimage loaded by the current step.
+    preimage: Option<PreimageData>,
+    stdout: Vec<u8>,
+    stderr: Vec<u8>,
+}
+
+impl<O: PreimageOracle> Executor<O> {
+    pub fn new(state: State, oracle: O) -> Self {
+        Self {
+            state,
+            oracle,
+            tracing: false,
+            mem_proof: None,
+            preimage: None,
+            stdout: Vec::new(),
+            stderr: Vec::new(),
+        }
+    }
+
+    pub fn state(&self) -> &State {
+        &self.state
+    }
+
+    pub fn into_state(self) -> State {
+        self.state
+    }
+
+    /// Everything the program has written to stdout so far.
+    pub fn stdout(&self) -> &[u8] {
+        &self.stdout
+    }
+
+    /// Everything the program has written to stderr so far.
+    pub fn stderr(&self) -> &[u8] {
+        &self.stderr
+    }
+
+    /// Executes one instruction and returns its trace record,
+    /// or `None` if the program has already exited.
+    pub fn step(&mut self) -> Result<Option<TraceRecord>, VmError> {
+        if self.state.exited {
+            return Ok(None);
+        }
+
+        let pre = StateSnapshot::of(&mut self.state);
+        let insn_proof = self.state.memory.merkle_proof(self.state.pc);
+
+        self.tracing = true;
+        self.mem_proof = None;
+        self.preimage = None;
+        let res = self.mips_step();
+        self.tracing = false;
+        res?;
+
+        let mem_proof = self.mem_proof.take().unwrap_or_else(empty_proof);
+        let preimage = self.preimage.take().unwrap_or_default();
+        let post = StateSnapshot::of(&mut self.state);
+
+        Ok(Some(TraceRecord::new(
+            &pre, &post, insn_proof, mem_proof, preimage,
+        )))
+    }
+
+    /// Executes up to `steps` instructions without producing trace records.
+    /// Returns the number of instructions actually executed.
+    pub fn fast_forward(&mut self, steps: u64) -> Result<u64, VmError> {
+        for executed in 0..steps {
+            if self.state.exited {
+                return Ok(executed);
+            }
+            self.mips_step()?;
+        }
+
+        Ok(steps)
+    }
+
+    /// Records the proof of the word at `addr` before the step modifies it.
+    fn track_mem_access(&mut self, addr: u32) {
+        if self.tracing && self.mem_proof.is_none() {
+            self.mem_proof = Some(self.state.memory.merkle_proof(addr));
+        }
+    }
+
+    fn mips_step(&mut self) -> Result<(), VmError> {
+        self.state.cycle = self.state.cycle.wrapping_add(1);
+
+        let insn = self.state.memory.get_word(self.state.pc)?;
+        let opcode = insn >> 26;
+
+        // j / jal
+        if opcode == 2 || opcode == 3 {
+            let link = if opcode == 3 { 31 } else { 0 };
+            let target = (self.state.next_pc & 0xF000_0000) | ((insn & 0x03FF_FFFF) << 2);
+            return self.handle_jump(link, target);
+        }
+
+        // R-type or I-type (stores rt)
+        let mut rs = self.state.regs[((insn >> 21) & 0x1F) as usize];
+        let rt_reg = (insn >> 16) & 0x1F;
+        let mut rd_reg = rt_reg;
+        let rt = if opcode == 0 || opcode == 0x1C {
+            // R-type (stores rd)
+            rd_reg = (insn >> 11) & 0x1F;
+            self.state.regs[rt_reg as usize]
+        } else if opcode < 0x20 {
+            if opcode == 0xC || opcode == 0xD || opcode == 0xE {
+                // andi, ori and xori take a zero-extended immediate
+                insn & 0xFFFF
+            } else {
+                sign_extend(insn & 0xFFFF, 16)
+            }
+        } else if opcode >= 0x28 || opcode == 0x22 || opcode == 0x26 {
+            // stores and lwl/lwr need the actual rt value
+            self.state.regs[rt_reg as usize]
+        } else {
+            0
+        };
+
+        if (4..8).contains(&opcode) || opcode == 1 {
+            return self.handle_branch(opcode, insn, rt_reg, rs);
+        }
+
+        let mut store_addr = None;
+        let mut mem = 0;
+        if opcode >= 0x20 {
+            rs = rs.wrapping_add(sign_extend(insn & 0xFFFF, 16));
+            let addr = rs & 0xFFFF_FFFC;
+            self.track_mem_access(addr);
+            mem = self.state.memory.get_word(addr)?;
+            if opcode >= 0x28 && opcode != 0x30 {
+                store_addr = Some(addr);
+                rd_reg = 0;
+            }
+        }
+
+        let val = execute(insn, rs, rt, mem).ok_or(VmError::InvalidInstruction {
+            pc: self.state.pc,
+            insn,
+        })?;
+
+        let fun = insn & 0x3F;
+        if opcode == 0 && (8..0x1C).contains(&fun) {
+            match fun {
+                // jr / jalr
+                0x08 | 0x09 => {
+                    let link = if fun == 9 { rd_reg } else { 0 };
+                    return self.handle_jump(link, rs);
+                }
+                // movz / movn
+                0x0A => return self.handle_rd(rd_reg, rs, rt == 0),
+                0x0B => return self.handle_rd(rd_reg, rs, rt != 0),
+                0x0C => return self.handle_syscall(),
+                0x10..=0x1B => return self.handle_hi_lo(fun, rs, rt, rd_reg),
+                _ => {}
+            }
+        }
+
+        // sc always succeeds, since there's a single thread
+        if opcode == 0x38 && rt_reg != 0 {
+            self.state.regs[rt_reg as usize] = 1;
+        }
+
+        if let Some(addr) = store_addr {
+            self.state.memory.set_word(addr, val)?;
+        }
+
+        self.handle_rd(rd_reg, val, true)
+    }
+
+    fn check_delay_slot(&self) -> Result<(), VmError> {
+        if self.state.next_pc != self.state.pc.wrapping_add(4) {
+            return Err(VmError::JumpInDelaySlot(self.state.pc));
+        }
+        Ok(())
+    }
+
+    fn handle_branch(
+        &mut self,
+        opcode: u32,
+        insn: u32,
+        rt_reg: u32,
+        rs: u32,
+    ) -> Result<(), VmError> {
+        self.check_delay_slot()?;
+
+        let should_branch = match opcode {
+            // beq / bne
+            4 => rs == self.state.regs[rt_reg as usize],
+            5 => rs != self.state.regs[rt_reg as usize],
+            // blez / bgtz
+            6 => (rs as i32) <= 0,
+            7 => (rs as i32) > 0,
+            // bltz / bgez
+            1 => match (insn >> 16) & 0x1F {
+                0 => (rs as i32) < 0,
+                1 => (rs as i32) >= 0,
+                _ => false,
+            },
+            _ => false,
+        };
+
+        let prev_pc = self.state.pc;
+        self.state.pc = self.state.next_pc;
+        self.state.next_pc = if should_branch {
+            prev_pc
+                .wrapping_add(4)
+                .wrapping_add(sign_extend(insn & 0xFFFF, 16) << 2)
+        } else {
+            self.state.next_pc.wrapping_add(4)
+        };
+
+        Ok(())
+    }
+
+    fn handle_jump(&mut self, link: u32, dest: u32) -> Result<(), VmError> {
+        self.check_delay_slot()?;
+
+        let prev_pc = self.state.pc;
+        self.state.pc = self.state.next_pc;
+        self.state.next_pc = dest;
+        if link != 0 {
+            self.state.regs[link as usize] = prev_pc.wrapping_add(8);
+        }
+
+        Ok(())
+    }
+
+    fn handle_rd(&mut self, rd: u32, val: u32, cond: bool) -> Result<(), VmError> {
+        if rd != 0 && cond {
+            self.state.regs[rd as usize] = val;
+        }
+        self.state.pc = self.state.next_pc;
+        self.state.next_pc = self.state.next_pc.wrapping_add(4);
+
+        Ok(())
+    }
+
+    fn handle_hi_lo(&mut self, fun: u32, rs: u32, rt: u32, rd: u32) -> Result<(), VmError> {
+        let mut val = 0;
+        match fun {
+            // mfhi / mthi / mflo / mtlo
+            0x10 => val = self.state.hi,
+            0x11 => self.state.hi = rs,
+            0x12 => val = self.state.lo,
+            0x13 => self.state.lo = rs,
+            // mult
+            0x18 => {
+                let acc = (rs as i32 as i64).wrapping_mul(rt as i32 as i64) as u64;
+                self.state.hi = (acc >> 32) as u32;
+                self.state.lo = acc as u32;
+            }
+            // multu
+            0x19 => {
+                let acc = (rs as u64) * (rt as u64);
+                self.state.hi = (acc >> 32) as u32;
+                self.state.lo = acc as u32;
+            }
+            // div
+            0x1A => {
+                if rt == 0 {
+                    return Err(VmError::DivisionByZero(self.state.pc));
+                }
+                self.state.hi = (rs as i32).wrapping_rem(rt as i32) as u32;
+                self.state.lo = (rs as i32).wrapping_div(rt as i32) as u32;
+            }
+            // divu
+            0x1B => {
+                if rt == 0 {
+                    return Err(VmError::DivisionByZero(self.state.pc));
+                }
+                self.state.hi = rs % rt;
+                self.state.lo = rs / rt;
+            }
+            _ => {}
+        }
+
+        self.handle_rd(rd, val, true)
+    }
+
+    fn handle_syscall(&mut self) -> Result<(), VmError> {
+        let regs = &self.state.regs;
+        let (sys_num, a0, a1, a2) = (regs[2], regs[4], regs[5], regs[6]);
+
+        let (v0, v1) = match syscall(sys_num, a0, a1, a2, &mut self.state.heap)? {
+            Syscall::Return(v0, v1) => {
+                if sys_num == SYS_WRITE && (a0 == FD_STDOUT || a0 == FD_STDERR) {
+                    let data = self.state.memory.read_bytes(a1, a2);
+                    if a0 == FD_STDOUT {
+                        self.stdout.extend_from_slice(&data);
+                    } else {
+                        self.stderr.extend_from_slice(&data);
+                    }
+                }
+                (v0, v1)
+            }
+            Syscall::Exit(code) => {
+                self.state.exited = true;
+                self.state.exit_code = code;
+                return Ok(());
+            }
+            Syscall::Preimage => {
+                self.load_preimage()?;
+                (0, 0)
+            }
+        };
+
+        self.state.regs[2] = v0;
+        self.state.regs[7] = v1;
+        self.state.pc = self.state.next_pc;
+        self.state.next_pc = self.state.next_pc.wrapping_add(4);
+
+        Ok(())
+    }
+
+    /// Copies the preimage of the key at [`PREIMAGE_KEY_ADDR`] to [`PREIMAGE_DATA_ADDR`].
+    /// The trace record carries the key in `mem_proof` and the preimage with the subtree it is
+    /// written to in `preimage`, which is what `doGetPreImage` checks.
+    fn load_preimage(&mut self) -> Result<(), VmError> {
+        self.track_mem_access(PREIMAGE_KEY_ADDR);
+        let mut key = [0u8; 32];
+        key.copy_from_slice(&self.state.memory.read_bytes(PREIMAGE_KEY_ADDR, 32));
+
+        let value = self.oracle.get_preimage(&key)?;
+        if preimage_key(&value) != key {
+            return Err(VmError::InvalidPreimage(hex::encode(key)));
+        }
+        if value.len() > PREIMAGE_MAX_SIZE {
+            return Err(VmError::PreimageTooLarge {
+                key: hex::encode(key),
+                len: value.len(),
+                max: PREIMAGE_MAX_SIZE,
+            });
+        }
+        if self.tracing {
+            self.preimage = Some(PreimageData::new(&mut self.state.memory, &value));
+        }
+
+        let memory: &mut Memory = &mut self.state.memory;
+        memory.set_word(PREIMAGE_DATA_ADDR, value.len() as u32)?;
+        memory.set_memory_range(PREIMAGE_DATA_ADDR + 4, &value);
+
+        Ok(())
+    }
+}
+
+/// Outcome of a syscall, see [`syscall`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Syscall {
+    /// Return `v0` and `v1` to the program.
+    Return(u32, u32),
+    /// Stop the program with the given exit code.
+    Exit(u8),
+    /// Load the preimage requested by the program and return `0, 0`.
+    Preimage,
+}
+
+/// Computes the effect of syscall `sys_num` with arguments `a0`-`a2` on the registers and the heap.
+/// Side effects on memory and on the output streams are left to the caller.
+pub(crate) fn syscall(
+    sys_num: u32,
+    a0: u32,
+    a1: u32,
+    a2: u32,
+    heap: &mut u32,
+) -> Result<Syscall, VmError> {
+    let (v0, v1) = match sys_num {
+        SYS_MMAP => {
+            let mut size = a1;
+            if size & PAGE_ADDR_MASK != 0 {
+                // round up to the page size
+                size = size.wrapping_add(PAGE_SIZE) & !PAGE_ADDR_MASK;
+            }
+            if a0 == 0 {
+                let addr = *heap;
+                *heap = heap.wrapping_add(size);
+                (addr, 0)
+            } else {
+                (a0, 0)
+            }
+        }
+        SYS_BRK => (BRK_START, 0),
+        // clone isn't really supported, it just pretends to return the thread id
+        SYS_CLONE => (1, 0),
+        SYS_EXIT_GROUP | SYS_EXIT => return Ok(Syscall::Exit(a0 as u8)),
+        SYS_READ => match a0 {
+            // reading from stdin always yields EOF
+            FD_STDIN => (0, 0),
+            _ => (0xFFFF_FFFF, MIPS_EBADF),
+        },
+        SYS_WRITE => match a0 {
+            FD_STDOUT | FD_STDERR => (a2, 0),
+            _ => (0xFFFF_FFFF, MIPS_EBADF),
+        },
+        SYS_FCNTL => {
+            // only F_GETFL is supported
+            if a1 == 3 {
+                match a0 {
+                    FD_STDIN => (0, 0),
+                    FD_STDOUT | FD_STDERR => (1, 0),
+                    _ => (0xFFFF_FFFF, MIPS_EBADF),
+                }
+            } else {
+                (0xFFFF_FFFF, MIPS_EINVAL)
+            }
+        }
+        SYS_PREIMAGE => return Ok(Syscall::Preimage),
+        // there is no file to open
+        SYS_OPEN | SYS_OPENAT => (0xFFFF_FFFF, MIPS_EBADF),
+        SYS_FUTEX => match a1 & FUTEX_CMD_MASK {
+            // waiting would block the only thread, fail as if the value had changed
+            FUTEX_WAIT => (0xFFFF_FFFF, MIPS_EAGAIN),
+            // there is no waiter to wake
+            FUTEX_WAKE => (0, 0),
+            _ => (0xFFFF_FFFF, MIPS_EINVAL),
+        },
+        _ if NOOP_SYSCALLS.contains(&sys_num) => (0, 0),
+        _ if NOSYS_SYSCALLS.contains(&sys_num) => (0xFFFF_FFFF, MIPS_ENOSYS),
+        _ => return Err(VmError::UnsupportedSyscall(sys_num)),
+    };
+
+    Ok(Syscall::Return(v0, v1))
+}
+
+/// Computes the result of an ALU, load or store instruction. For loads and stores `rs` is the
+/// effective address and `mem` the word at it, stores return the new value of that word.
+/// Returns `None` for instructions that aren't supported.
+pub(crate) fn execute(insn: u32, rs: u32, rt: u32, mem: u32) -> Option<u32> {
+    let opcode = insn >> 26;
+    let mut fun = insn & 0x3F;
+    let shamt = (insn >> 6) & 0x1F;
+
+    if opcode == 0 || (8..0xF).contains(&opcode) {
+        // map the immediate arithmetic onto the corresponding R-type functions
+        fun = match opcode {
+            0x8 => 0x20,
+            0x9 => 0x21,
+            0xA => 0x2A,
+            0xB => 0x2B,
+            0xC => 0x24,
+            0xD => 0x25,
+            0xE => 0x26,
+            _ => fun,
+        };
+        match fun {
+            0x00 => Some(rt << shamt),
+            0x02 => Some(rt >> shamt),
+            0x03 => Some(((rt as i32) >> shamt) as u32),
+            0x04 => Some(rt << (rs & 0x1F)),
+            0x06 => Some(rt >> (rs & 0x1F)),
+            0x07 => Some(((rt as i32) >> (rs & 0x1F)) as u32),
+            // handled by the caller
+            0x08..=0x0C | 0x0F | 0x10..=0x13 | 0x18..=0x1B => Some(rs),
+            0x20 | 0x21 => Some(rs.wrapping_add(rt)),
+            0x22 | 0x23 => Some(rs.wrapping_sub(rt)),
+            0x24 => Some(rs & rt),
+            0x25 => Some(rs | rt),
+            0x26 => Some(rs ^ rt),
+            0x27 => Some(!(rs | rt)),
+            0x2A => Some(((rs as i32) < (rt as i32)) as u32),
+            0x2B => Some((rs < rt) as u32),
+            _ => None,
+        }
+    } else if opcode == 0xF {
+        // lui
+        Some(rt << 16)
+    } else if opcode == 0x1C {
+        match fun {
+            // mul
+            0x02 => Some((rs as i32).wrapping_mul(rt as i32) as u32),
+            // clz / clo
+            0x20 => Some(rs.leading_zeros()),
+            0x21 => Some(rs.leading_ones()),
+            _ => None,
+        }
+    } else {
+        let byte_shift = 24 - (rs & 3) * 8;
+        let half_shift = 16 - (rs & 2) * 8;
+        match opcode {
+            // lb / lh
+            0x20 => Some(sign_extend((mem >> byte_shift) & 0xFF, 8)),
+            0x21 => Some(sign_extend((mem >> half_shift) & 0xFFFF, 16)),
+            // lwl
+            0x22 => {
+                let mask = 0xFFFF_FFFFu32 << ((rs & 3) * 8);
+                Some((rt & !mask) | (mem << ((rs & 3) * 8)))
+            }
+            // lw / lbu / lhu
+            0x23 => Some(mem),
+            0x24 => Some((mem >> byte_shift) & 0xFF),
+            0x25 => Some((mem >> half_shift) & 0xFFFF),
+            // lwr
+            0x26 => {
+                let mask = 0xFFFF_FFFFu32 >> byte_shift;
+                Some((rt & !mask) | (mem >> byte_shift))
+            }
+            // sb / sh
+            0x28 => Some((mem & !(0xFF << byte_shift)) | ((rt & 0xFF) << byte_shift)),
+            0x29 => Some((mem & !(0xFFFF << half_shift)) | ((rt & 0xFFFF) << half_shift)),
+            // swl
+            0x2A => {
+                let mask = 0xFFFF_FFFFu32 >> ((rs & 3) * 8);
+                Some((mem & !mask) | (rt >> ((rs & 3) * 8)))
+            }
+            // sw
+            0x2B => Some(rt),
+            // swr
+            0x2E => {
+                let mask = 0xFFFF_FFFFu32 << byte_shift;
+                Some((mem & !mask) | (rt << byte_shift))
+            }
+            // ll / sc
+            0x30 => Some(mem),
+            0x38 => Some(rt),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::oracle::NoPreimages;
+    use crate::state::HEAP_START;
+
+    const BASE: u32 = 0x0040_0000;
+
+    fn executor(program: &[u32]) -> Executor<NoPreimages> {
+        let mut memory = Memory::new();
+        for (i, insn) in program.iter().enumerate() {
+            memory.set_word(BASE + 4 * i as u32, *insn).unwrap();
+        }
+        let state = State {
+            cycle: 0,
+            pc: BASE,
+            next_pc: BASE + 4,
+            lo: 0,
+            hi: 0,
+            regs: [0; 32],
+            heap: HEAP_START,
+            exit_code: 0,
+            exited: false,
+            memory,
+        };
+        Executor::new(state, NoPreimages)
+    }
+
+    #[test]
+    fn arithmetic_and_memory() {
+        let mut executor = executor(&[
+            0x2408_0005, // addiu $t0, $zero, 5
+            0x2409_fffd, // addiu $t1, $zero, -3
+            0x0109_5021, // addu  $t2, $t0, $t1
+            0x3c0b_1000, // lui   $t3, 0x1000
+            0xad6a_0004, // sw    $t2, 4($t3)
+            0x916c_0007, // lbu   $t4, 7($t3)
+            0x0109_001a, // div   $t0, $t1
+            0x0000_6812, // mflo  $t5
+        ]);
+
+        let mut records = Vec::new();
+        while let Some(record) = executor.step().unwrap() {
+            records.push(record);
+            if records.len() == 8 {
+                break;
+            }
+        }
+
+        let regs = executor.state().regs;
+        assert_eq!(regs[10], 2);
+        assert_eq!(regs[12], 2);
+        assert_eq!(regs[13], (-1i32) as u32);
+        assert_eq!(executor.state.memory.get_word(0x1000_0004).unwrap(), 2);
+
+        // every record continues where the previous one stopped
+        for pair in records.windows(2) {
+            assert_eq!(pair[0].new_pc, pair[1].pc);
+            assert_eq!(pair[0].new_regs, pair[1].regs);
+            assert_eq!(pair[0].new_mem_root, pair[1].mem_root);
+        }
+        // the store proves the old word and changes the root
+        let store = &records[4];
+        assert_eq!(
+            Memory::root_from_proof(&store.mem_proof, 0x1000_0004),
+            store.mem_root
+        );
+        assert_ne!(store.mem_root, store.new_mem_root);
+        assert_eq!(
+            Memory::root_from_proof(&store.insn_proof, store.pc),
+            store.mem_root
+        );
+    }
+
+    #[test]
+    fn branch_delay_slot() {
+        let mut executor = executor(&[
+            0x1000_0002, // b     +2
+            0x2408_0001, // addiu $t0, $zero, 1
+            0x2409_0001, // addiu $t1, $zero, 1
+            0x240a_0001, // addiu $t2, $zero, 1
+        ]);
+        executor.fast_forward(3).unwrap();
+
+        let regs = executor.state().regs;
+        assert_eq!((regs[8], regs[9], regs[10]), (1, 0, 1));
+    }
+
+    #[test]
+    fn exit_group() {
+        let mut executor = executor(&[
+            0x2402_1096, // addiu $v0, $zero, 4246
+            0x2404_0007, // addiu $a0, $zero, 7
+            0x0000_000c, // syscall
+        ]);
+
+        assert_eq!(executor.fast_forward(10).unwrap(), 3);
+        assert!(executor.state().exited);
+        assert_eq!(executor.state().exit_code, 7);
+        assert!(executor.step().unwrap().is_none());
+    }
+
+    #[test]
+    fn unsupported_syscall() {
+        let mut executor = executor(&[
+            0x2402_1387, // addiu $v0, $zero, 4999
+            0x0000_000c, // syscall
+        ]);
+
+        assert!(matches!(
+            executor.fast_forward(2),
+            Err(VmError::UnsupportedSyscall(4999))
+        ));
+    }
+
+    #[test]
+    fn cannon_syscalls() {
+        let mut heap = 0x2000_0000;
+        let mut call = |sys_num, a0, a1| syscall(sys_num, a0, a1, 0, &mut heap).unwrap();
+
+        assert_eq!(call(SYS_EXIT, 3, 0), Syscall::Exit(3));
+        assert_eq!(
+            call(SYS_OPEN, 0, 0),
+            Syscall::Return(0xFFFF_FFFF, MIPS_EBADF)
+        );
+        assert_eq!(
+            call(SYS_OPENAT, 0xFFFF_FF9C, 0x1000),
+            Syscall::Return(0xFFFF_FFFF, MIPS_EBADF)
+        );
+        // FUTEX_WAIT_PRIVATE and FUTEX_WAKE_PRIVATE
+        assert_eq!(
+            call(SYS_FUTEX, 0x1000, 128),
+            Syscall::Return(0xFFFF_FFFF, MIPS_EAGAIN)
+        );
+        assert_eq!(call(SYS_FUTEX, 0x1000, 129), Syscall::Return(0, 0));
+        assert_eq!(
+            call(SYS_FUTEX, 0x1000, 9),
+            Syscall::Return(0xFFFF_FFFF, MIPS_EINVAL)
+        );
+        for &sys_num in NOOP_SYSCALLS.iter() {
+            assert_eq!(call(sys_num, 1, 2), Syscall::Return(0, 0));
+        }
+        for &sys_num in NOSYS_SYSCALLS.iter() {
+            assert_eq!(
+                call(sys_num, 1, 2),
+                Syscall::Return(0xFFFF_FFFF, MIPS_ENOSYS)
+            );
+        }
+        assert_eq!(heap, 0x2000_0000);
+    }
+}
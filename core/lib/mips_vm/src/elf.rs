@@ -0,0 +1,55 @@
+//! Loader for statically linked, big-endian MIPS32 ELF executables.
+
+// External imports
+use elf::abi::{EM_MIPS, PT_LOAD};
+use elf::endian::AnyEndian;
+use elf::file::Class;
+use elf::ElfBytes;
+// Local imports
+use crate::memory::Memory;
+use crate::VmError;
+
+/// Loads every `PT_LOAD` segment of `data` into `memory` and returns the entry point.
+pub fn load_elf(data: &[u8], memory: &mut Memory) -> Result<u32, VmError> {
+    let file = ElfBytes::<AnyEndian>::minimal_parse(data)
+        .map_err(|e| VmError::InvalidElf(e.to_string()))?;
+
+    if file.ehdr.class != Class::ELF32 {
+        return Err(VmError::InvalidElf("expected a 32-bit executable".into()));
+    }
+    if file.ehdr.e_machine != EM_MIPS {
+        return Err(VmError::InvalidElf(format!(
+            "expected a MIPS executable, got machine {}",
+            file.ehdr.e_machine
+        )));
+    }
+
+    let segments = file
+        .segments()
+        .ok_or_else(|| VmError::InvalidElf("no program headers".into()))?;
+
+    for segment in segments.iter().filter(|s| s.p_type == PT_LOAD) {
+        if segment.p_filesz > segment.p_memsz {
+            return Err(VmError::InvalidElf(format!(
+                "segment at {:#010x} has filesz {} > memsz {}",
+                segment.p_vaddr, segment.p_filesz, segment.p_memsz
+            )));
+        }
+        if segment.p_vaddr + segment.p_memsz > 1 << 32 {
+            return Err(VmError::InvalidElf(format!(
+                "segment at {:#010x} does not fit into the address space",
+                segment.p_vaddr
+            )));
+        }
+
+        let content = file
+            .segment_data(&segment)
+            .map_err(|e| VmError::InvalidElf(e.to_string()))?;
+        memory.set_memory_range(segment.p_vaddr as u32, content);
+
+        // `.bss` and friends only need to be zeroed explicitly if they overlap non-zero data,
+        // which can't happen in a freshly created memory.
+    }
+
+    Ok(file.ehdr.e_entry as u32)
+}
@@ -0,0 +1,56 @@
+//! Native MIPS32 executor producing the execution traces checked by the MIPS circuit.
+//!
+//! The executor loads a big-endian MIPS ELF (e.g. minigeth built for Cannon), runs it
+//! instruction by instruction and emits a [`TraceRecord`] per step. The records carry the
+//! Merkle proofs for the instruction and data words, hashed with Poseidon unless the memory is
+//! created with another [`MemoryHash`](memory::MemoryHash), so they can be passed to
+//! `mips_vm_poseidon.zok` as is.
+
+pub mod commitment;
+pub mod cpu;
+pub mod elf;
+pub mod memory;
+pub mod model;
+pub mod offline;
+pub mod oracle;
+pub mod poseidon;
+pub mod state;
+pub mod trace;
+
+pub use cpu::Executor;
+pub use memory::Memory;
+pub use oracle::{preimage_key, DirectoryOracle, NoPreimages, PreimageOracle};
+pub use state::State;
+pub use trace::{PreimageData, TraceRecord};
+
+#[derive(Debug, thiserror::Error)]
+pub enum VmError {
+    #[error("invalid ELF file: {0}")]
+    InvalidElf(String),
+    #[error("unaligned memory access at {0:#010x}")]
+    UnalignedAccess(u32),
+    #[error("Merkle proof for {0:#010x} doesn't match the memory root")]
+    InvalidProof(u32),
+    #[error("invalid instruction {insn:#010x} at {pc:#010x}")]
+    InvalidInstruction { pc: u32, insn: u32 },
+    #[error("jump or branch in a delay slot at {0:#010x}")]
+    JumpInDelaySlot(u32),
+    #[error("division by zero at {0:#010x}")]
+    DivisionByZero(u32),
+    #[error("syscall {0} is not supported by the circuit")]
+    UnsupportedSyscall(u32),
+    #[error("no preimage for key 0x{0}")]
+    MissingPreimage(String),
+    #[error("the preimage for key 0x{0} doesn't hash to the key")]
+    InvalidPreimage(String),
+    #[error("the preimage for key 0x{key} has {len} bytes, the circuit takes at most {max}")]
+    PreimageTooLarge { key: String, len: usize, max: usize },
+    #[error("the circuit rejects the step at cycle {0}")]
+    IllegalStep(u32),
+    #[error("the offline memory circuit can't check the preimage syscall at cycle {0}")]
+    PreimageInOfflineBatch(u32),
+    #[error("the batch accesses {leaves} memory leaves, the circuit takes at most {max}")]
+    TooManyLeaves { leaves: usize, max: usize },
+    #[error("the memory doesn't match the roots of the trace records")]
+    MemoryMismatch,
+}
@@ -0,0 +1,119 @@
+// Built-in deps
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+// External imports
+use anyhow::{ensure, Context};
+use structopt::StructOpt;
+// Workspace imports
+use mips_vm::memory::MemoryHash;
+use mips_vm::offline::OfflineBatch;
+use mips_vm::{DirectoryOracle, Executor, NoPreimages, PreimageOracle, State};
+
+#[derive(StructOpt)]
+#[structopt(
+    name = "mips_vm",
+    about = "Generates MIPS execution traces for the zkm circuit"
+)]
+struct Opt {
+    /// Big-endian MIPS ELF executable to run
+    #[structopt(long, parse(from_os_str))]
+    elf: PathBuf,
+    /// Number of instructions to execute before recording the trace
+    #[structopt(long, default_value = "0")]
+    skip: u64,
+    /// Maximum number of trace records to produce
+    #[structopt(long, default_value = "1000")]
+    steps: u64,
+    /// File to write the trace records to, one JSON record per line
+    #[structopt(long, parse(from_os_str))]
+    output: PathBuf,
+    /// Hash of the memory Merkle tree, which has to be the one of the circuit: poseidon or keccak,
+    /// or poseidon-pallas for the Nova step circuit
+    #[structopt(long, default_value = "poseidon")]
+    memory_hash: MemoryHash,
+    /// Directory with the preimages requested by the program, one file per key
+    #[structopt(long, parse(from_os_str))]
+    preimages: Option<PathBuf>,
+    /// Write the arguments of the offline memory circuit for batches of this many records
+    /// instead of the trace records, one JSON line per batch
+    #[structopt(long)]
+    offline_batch: Option<usize>,
+    /// Maximum number of distinct memory leaves accessed by an offline batch
+    #[structopt(long, default_value = "64")]
+    max_leaves: usize,
+}
+
+fn main() -> anyhow::Result<()> {
+    let _vlog_guard = vlog::init();
+    let opt = Opt::from_args();
+    ensure!(
+        opt.offline_batch.is_none() || opt.memory_hash == MemoryHash::Poseidon,
+        "The offline memory circuit only supports the Poseidon memory hash"
+    );
+
+    let elf =
+        std::fs::read(&opt.elf).with_context(|| format!("Could not read {}", opt.elf.display()))?;
+    let state = State::from_elf(&elf, opt.memory_hash)?;
+
+    match &opt.preimages {
+        Some(dir) => run(Executor::new(state, DirectoryOracle::new(dir)), &opt),
+        None => run(Executor::new(state, NoPreimages), &opt),
+    }
+}
+
+fn run<O: PreimageOracle>(mut executor: Executor<O>, opt: &Opt) -> anyhow::Result<()> {
+    let skipped = executor.fast_forward(opt.skip)?;
+    vlog::info!("Skipped {} instructions", skipped);
+
+    let file = File::create(&opt.output)
+        .with_context(|| format!("Could not create {}", opt.output.display()))?;
+    let mut writer = BufWriter::new(file);
+
+    let mut recorded = 0;
+    let mut batch = Vec::new();
+    while recorded < opt.steps {
+        let record = match executor.step()? {
+            Some(record) => record,
+            None => break,
+        };
+        recorded += 1;
+
+        let batch_size = match opt.offline_batch {
+            Some(batch_size) => batch_size,
+            None => {
+                serde_json::to_writer(&mut writer, &record)?;
+                writer.write_all(b"\n")?;
+                continue;
+            }
+        };
+        batch.push(record);
+        if batch.len() == batch_size {
+            let offline = OfflineBatch::new(&batch, opt.max_leaves)?;
+            serde_json::to_writer(&mut writer, &offline)?;
+            writer.write_all(b"\n")?;
+            batch.clear();
+        }
+    }
+    writer.flush()?;
+    if !batch.is_empty() {
+        vlog::warn!(
+            "The last {} records don't fill an offline batch and were left out",
+            batch.len()
+        );
+    }
+
+    let state = executor.state();
+    vlog::info!(
+        "Recorded {} steps, pc: {:#010x}, exited: {}, exit code: {}",
+        recorded,
+        state.pc,
+        state.exited,
+        state.exit_code
+    );
+    if !executor.stdout().is_empty() {
+        vlog::info!("stdout: {}", String::from_utf8_lossy(executor.stdout()));
+    }
+
+    Ok(())
+}
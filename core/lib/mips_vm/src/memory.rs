@@ -0,0 +1,414 @@
+//! Byte-addressable MIPS memory backed by a sparse Merkle tree, hashed with Poseidon unless
+//! stated otherwise.
+//!
+//! The layout mirrors `checkMemAccess` in `mips_vm_poseidon.zok`: memory is split into
+//! 32-byte leaves (`addr >> 5` is the leaf index), the leaf bytes are used as the level 0
+//! node as is, and 27 levels of `hashPair` lead to the root. A proof is the leaf followed
+//! by the 27 sibling nodes, 896 bytes in total. [`MemoryHash`] tells which `hashPair` the
+//! tree uses: the one of the template compiled for BN254 or for Pallas, or the Keccak-256 one
+//! the circuit generator swaps in.
+
+// Built-in deps
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::str::FromStr;
+// Local imports
+use crate::poseidon;
+use crate::VmError;
+
+pub type Hash = [u8; 32];
+
+/// Size of a tree leaf in bytes.
+pub const LEAF_SIZE: u32 = 32;
+/// Number of hashing levels between a leaf and the root.
+pub const TREE_DEPTH: usize = 27;
+/// Size of `insn_proof` / `mem_proof` in a trace record.
+pub const PROOF_SIZE: usize = (TREE_DEPTH + 1) * 32;
+
+/// Hash function of the memory Merkle tree, i.e. `hashPair` of the circuit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MemoryHash {
+    /// Poseidon over the leaves packed into field elements, the `hashPair` of the template.
+    Poseidon,
+    /// Keccak-256 of the concatenated nodes, like the memory tree of Cannon.
+    Keccak,
+    /// Poseidon over the Pallas field, the `hashPair` of the template compiled for Pallas, i.e.
+    /// of `nova_step.zok`.
+    PoseidonPallas,
+}
+
+impl Default for MemoryHash {
+    fn default() -> Self {
+        Self::Poseidon
+    }
+}
+
+impl fmt::Display for MemoryHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Poseidon => write!(f, "poseidon"),
+            Self::Keccak => write!(f, "keccak"),
+            Self::PoseidonPallas => write!(f, "poseidon-pallas"),
+        }
+    }
+}
+
+impl FromStr for MemoryHash {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "poseidon" => Ok(Self::Poseidon),
+            "keccak" => Ok(Self::Keccak),
+            "poseidon-pallas" => Ok(Self::PoseidonPallas),
+            _ => Err(format!(
+                "Unknown memory hash {}, expected poseidon, keccak or poseidon-pallas",
+                s
+            )),
+        }
+    }
+}
+
+impl MemoryHash {
+    /// Counterpart of `hashPair` from the circuit generated for this hash.
+    pub fn hash_pair(self, a: &Hash, b: &Hash) -> Hash {
+        match self {
+            Self::Poseidon => poseidon::hash_pair(a, b),
+            Self::Keccak => tiny_keccak::keccak256(&[&a[..], &b[..]].concat()),
+            Self::PoseidonPallas => poseidon::hash_pair_pallas(a, b),
+        }
+    }
+
+    /// Recomputes the root from a subtree proof the same way `rootFromSubtree` does.
+    pub fn root_from_subtree(
+        self,
+        leaves: &[u8],
+        siblings: &[u8],
+        addr: u32,
+        depth: usize,
+    ) -> Hash {
+        let mut nodes: Vec<Hash> = leaves
+            .chunks(32)
+            .map(|chunk| {
+                let mut leaf = [0u8; 32];
+                leaf.copy_from_slice(chunk);
+                leaf
+            })
+            .collect();
+        while nodes.len() > 1 {
+            nodes = nodes
+                .chunks(2)
+                .map(|pair| self.hash_pair(&pair[0], &pair[1]))
+                .collect();
+        }
+
+        let mut node = nodes[0];
+        let mut path = (addr >> 5) >> depth;
+        for sibling in siblings.chunks(32) {
+            let mut sibling_node = [0u8; 32];
+            sibling_node.copy_from_slice(sibling);
+            node = if path & 1 == 0 {
+                self.hash_pair(&node, &sibling_node)
+            } else {
+                self.hash_pair(&sibling_node, &node)
+            };
+            path >>= 1;
+        }
+
+        node
+    }
+
+    /// Recomputes the root from a proof the same way `checkMemAccess` does.
+    pub fn root_from_proof(self, proof: &[u8], addr: u32) -> Hash {
+        let mut node = [0u8; 32];
+        node.copy_from_slice(&proof[0..32]);
+
+        let mut path = addr >> 5;
+        for level in 1..=TREE_DEPTH {
+            let mut sibling = [0u8; 32];
+            sibling.copy_from_slice(&proof[level * 32..(level + 1) * 32]);
+            node = if path & 1 == 0 {
+                self.hash_pair(&node, &sibling)
+            } else {
+                self.hash_pair(&sibling, &node)
+            };
+            path >>= 1;
+        }
+
+        node
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Memory {
+    hash: MemoryHash,
+    /// Non-zero leaves, indexed by `addr >> 5`.
+    leaves: HashMap<u32, Hash>,
+    /// Cached inner nodes, indexed by `(level, index)` with `level` in `1..=TREE_DEPTH`.
+    nodes: HashMap<(usize, u32), Hash>,
+    /// Leaves changed since the inner nodes were last recomputed.
+    dirty: BTreeSet<u32>,
+    /// Root of an empty subtree at each level.
+    zero_hashes: Vec<Hash>,
+}
+
+impl Default for Memory {
+    fn default() -> Self {
+        Self::with_hash(MemoryHash::default())
+    }
+}
+
+impl Memory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// An empty memory whose tree is hashed with `hash`.
+    pub fn with_hash(hash: MemoryHash) -> Self {
+        let mut zero_hashes = vec![[0u8; 32]];
+        for level in 1..=TREE_DEPTH {
+            let child = zero_hashes[level - 1];
+            zero_hashes.push(hash.hash_pair(&child, &child));
+        }
+
+        Self {
+            hash,
+            leaves: HashMap::new(),
+            nodes: HashMap::new(),
+            dirty: BTreeSet::new(),
+            zero_hashes,
+        }
+    }
+
+    pub fn hash(&self) -> MemoryHash {
+        self.hash
+    }
+
+    /// Loads an aligned big-endian word.
+    pub fn get_word(&self, addr: u32) -> Result<u32, VmError> {
+        if addr & 3 != 0 {
+            return Err(VmError::UnalignedAccess(addr));
+        }
+        let leaf = self.leaf(addr >> 5);
+        let offset = (addr & (LEAF_SIZE - 1)) as usize;
+
+        let mut word = [0u8; 4];
+        word.copy_from_slice(&leaf[offset..offset + 4]);
+        Ok(u32::from_be_bytes(word))
+    }
+
+    /// Stores an aligned big-endian word.
+    pub fn set_word(&mut self, addr: u32, value: u32) -> Result<(), VmError> {
+        if addr & 3 != 0 {
+            return Err(VmError::UnalignedAccess(addr));
+        }
+        let offset = (addr & (LEAF_SIZE - 1)) as usize;
+        let mut leaf = self.leaf(addr >> 5);
+        leaf[offset..offset + 4].copy_from_slice(&value.to_be_bytes());
+        self.set_leaf(addr >> 5, leaf);
+
+        Ok(())
+    }
+
+    /// Reads `len` bytes starting at an arbitrary address.
+    pub fn read_bytes(&self, addr: u32, len: u32) -> Vec<u8> {
+        (0..len)
+            .map(|i| {
+                let byte_addr = addr.wrapping_add(i);
+                self.leaf(byte_addr >> 5)[(byte_addr & (LEAF_SIZE - 1)) as usize]
+            })
+            .collect()
+    }
+
+    /// Writes `data` starting at an arbitrary address.
+    pub fn set_memory_range(&mut self, addr: u32, data: &[u8]) {
+        for (i, chunk) in data.iter().enumerate() {
+            let byte_addr = addr.wrapping_add(i as u32);
+            let index = byte_addr >> 5;
+            let mut leaf = self.leaf(index);
+            leaf[(byte_addr & (LEAF_SIZE - 1)) as usize] = *chunk;
+            self.set_leaf(index, leaf);
+        }
+    }
+
+    /// Returns the current root of the memory tree.
+    pub fn root(&mut self) -> Hash {
+        self.flush();
+        self.node(TREE_DEPTH, 0)
+    }
+
+    /// Returns the proof for the leaf containing `addr`, in the `insn_proof` / `mem_proof` layout.
+    pub fn merkle_proof(&mut self, addr: u32) -> Vec<u8> {
+        self.flush();
+
+        let mut index = addr >> 5;
+        let mut proof = Vec::with_capacity(PROOF_SIZE);
+        proof.extend_from_slice(&self.leaf(index));
+        for level in 0..TREE_DEPTH {
+            proof.extend_from_slice(&self.node(level, index ^ 1));
+            index >>= 1;
+        }
+
+        proof
+    }
+
+    /// Returns the leaves of the subtree of `1 << depth` leaves containing `addr`, and the
+    /// siblings of the subtree on the path to the root.
+    pub fn subtree_proof(&mut self, addr: u32, depth: usize) -> (Vec<u8>, Vec<u8>) {
+        self.flush();
+
+        let mut index = (addr >> 5) >> depth;
+        let first = index << depth;
+        let mut leaves = Vec::with_capacity((LEAF_SIZE as usize) << depth);
+        for leaf in first..first + (1 << depth) {
+            leaves.extend_from_slice(&self.leaf(leaf));
+        }
+        let mut siblings = Vec::with_capacity((TREE_DEPTH - depth) * 32);
+        for level in depth..TREE_DEPTH {
+            siblings.extend_from_slice(&self.node(level, index ^ 1));
+            index >>= 1;
+        }
+
+        (leaves, siblings)
+    }
+
+    /// Recomputes the root of a Poseidon tree from a subtree proof, see
+    /// [`MemoryHash::root_from_subtree`].
+    pub fn root_from_subtree(leaves: &[u8], siblings: &[u8], addr: u32, depth: usize) -> Hash {
+        MemoryHash::Poseidon.root_from_subtree(leaves, siblings, addr, depth)
+    }
+
+    /// Recomputes the root of a Poseidon tree from a proof, see [`MemoryHash::root_from_proof`].
+    pub fn root_from_proof(proof: &[u8], addr: u32) -> Hash {
+        MemoryHash::Poseidon.root_from_proof(proof, addr)
+    }
+
+    pub(crate) fn leaf(&self, index: u32) -> Hash {
+        self.leaves.get(&index).copied().unwrap_or([0u8; 32])
+    }
+
+    pub(crate) fn set_leaf(&mut self, index: u32, leaf: Hash) {
+        self.leaves.insert(index, leaf);
+        self.dirty.insert(index);
+    }
+
+    fn node(&self, level: usize, index: u32) -> Hash {
+        if level == 0 {
+            return self.leaf(index);
+        }
+        self.nodes
+            .get(&(level, index))
+            .copied()
+            .unwrap_or(self.zero_hashes[level])
+    }
+
+    /// Rehashes every inner node above the dirty leaves, one level at a time.
+    fn flush(&mut self) {
+        if self.dirty.is_empty() {
+            return;
+        }
+
+        let mut indexes = std::mem::take(&mut self.dirty);
+        for level in 1..=TREE_DEPTH {
+            indexes = indexes.into_iter().map(|index| index >> 1).collect();
+            for &index in &indexes {
+                let left = self.node(level - 1, index << 1);
+                let right = self.node(level - 1, (index << 1) | 1);
+                self.nodes
+                    .insert((level, index), self.hash.hash_pair(&left, &right));
+            }
+        }
+    }
+}
+
+/// An all-zero proof, used for `mem_proof` when the instruction doesn't touch memory.
+pub fn empty_proof() -> Vec<u8> {
+    vec![0u8; PROOF_SIZE]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn proof_matches_root() {
+        let mut memory = Memory::new();
+        memory.set_word(0x0040_0000, 0x2408_0001).unwrap();
+        memory.set_word(0x7fff_d004, 0x42).unwrap();
+
+        for &addr in &[0x0040_0000, 0x0040_0004, 0x7fff_d004, 0x1000_0000] {
+            let proof = memory.merkle_proof(addr);
+            assert_eq!(proof.len(), PROOF_SIZE);
+            assert_eq!(Memory::root_from_proof(&proof, addr), memory.root());
+        }
+        assert_eq!(memory.get_word(0x7fff_d004).unwrap(), 0x42);
+    }
+
+    #[test]
+    fn subtree_proof_matches_root() {
+        let mut memory = Memory::new();
+        memory.set_word(0x3100_0004, 0x42).unwrap();
+        memory.set_word(0x3100_03fc, 0x43).unwrap();
+        memory.set_word(0x3100_0400, 0x44).unwrap();
+
+        let (leaves, siblings) = memory.subtree_proof(0x3100_0000, 5);
+        assert_eq!(leaves.len(), 1024);
+        assert_eq!(siblings.len(), 22 * 32);
+        assert_eq!(
+            Memory::root_from_subtree(&leaves, &siblings, 0x3100_0000, 5),
+            memory.root()
+        );
+    }
+
+    /// The Keccak tree is the one of Cannon: a node is the Keccak-256 of its children.
+    #[test]
+    fn keccak_proof_matches_root() {
+        let mut memory = Memory::with_hash(MemoryHash::Keccak);
+        memory.set_word(0x0040_0000, 0x2408_0001).unwrap();
+        memory.set_word(0x3100_0004, 0x42).unwrap();
+
+        let zero = [0u8; 32];
+        assert_eq!(
+            MemoryHash::Keccak.hash_pair(&zero, &zero),
+            tiny_keccak::keccak256(&[0u8; 64])
+        );
+        let root = memory.root();
+        assert_ne!(root, Memory::new().root());
+        for &addr in &[0x0040_0000, 0x3100_0004, 0x1000_0000] {
+            let proof = memory.merkle_proof(addr);
+            assert_eq!(MemoryHash::Keccak.root_from_proof(&proof, addr), root);
+            assert_ne!(Memory::root_from_proof(&proof, addr), root);
+        }
+        let (leaves, siblings) = memory.subtree_proof(0x3100_0000, 5);
+        assert_eq!(
+            MemoryHash::Keccak.root_from_subtree(&leaves, &siblings, 0x3100_0000, 5),
+            root
+        );
+    }
+
+    #[test]
+    fn pallas_proof_matches_root() {
+        let mut memory = Memory::with_hash(MemoryHash::PoseidonPallas);
+        memory.set_word(0x0040_0000, 0x2408_0001).unwrap();
+
+        let root = memory.root();
+        let proof = memory.merkle_proof(0x0040_0000);
+        assert_eq!(
+            MemoryHash::PoseidonPallas.root_from_proof(&proof, 0x0040_0000),
+            root
+        );
+        assert_ne!(Memory::root_from_proof(&proof, 0x0040_0000), root);
+        assert_eq!(
+            "poseidon-pallas".parse::<MemoryHash>(),
+            Ok(MemoryHash::PoseidonPallas)
+        );
+    }
+
+    #[test]
+    fn empty_root() {
+        let mut empty = Memory::new();
+        let mut touched = Memory::new();
+        touched.set_word(0x100, 0).unwrap();
+        assert_eq!(empty.root(), touched.root());
+    }
+}
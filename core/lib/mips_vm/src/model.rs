@@ -0,0 +1,604 @@
+//! Reference model of the step function of `mips_vm_poseidon.zok`.
+//!
+//! [`step`] works on the same `states`/`mips_states` layout as the circuit: the instruction and
+//! the data word are read from the Merkle proofs, stores are applied to the `mem_proof` leaf and
+//! the new memory root is recomputed from it, exactly like `check_instruction` does, and a
+//! preimage syscall is checked against the `preimage` of the input like `doGetPreImage`. The
+//! instruction semantics however are those of [`Executor`](crate::Executor), i.e. of a real
+//! MIPS32 CPU, so running the same input through both exposes bugs in the circuit.
+
+// External imports
+use serde::{Deserialize, Serialize};
+// Local imports
+use crate::cpu::{
+    execute, sign_extend, syscall, Syscall, PREIMAGE_DATA_ADDR, PREIMAGE_KEY_ADDR,
+    PREIMAGE_MAX_SIZE, PREIMAGE_SUBTREE_DEPTH,
+};
+use crate::memory::{Hash, MemoryHash, PROOF_SIZE};
+use crate::oracle::preimage_key;
+use crate::trace::{abi, PreimageData, TraceRecord};
+use crate::VmError;
+
+/// Counterpart of the `states` struct of the circuit.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct States {
+    #[serde(with = "abi::u32_value")]
+    pub cycle: u32,
+    #[serde(with = "abi::u32_value")]
+    pub pc: u32,
+    #[serde(rename = "nextPC", with = "abi::u32_value")]
+    pub next_pc: u32,
+    #[serde(with = "abi::u32_value")]
+    pub lo: u32,
+    #[serde(with = "abi::u32_value")]
+    pub hi: u32,
+    #[serde(with = "abi::u32_array")]
+    pub regs: [u32; 32],
+    #[serde(with = "abi::u32_value")]
+    pub heap: u32,
+    #[serde(rename = "exitCode", with = "abi::u8_value")]
+    pub exit_code: u8,
+    pub exited: bool,
+    #[serde(rename = "memRoot", with = "abi::hash")]
+    pub mem_root: Hash,
+}
+
+/// Counterpart of the `mips_states` struct of the circuit.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MipsStates {
+    pub states: States,
+
+    #[serde(with = "abi::bytes")]
+    pub insn_proof: Vec<u8>,
+    #[serde(with = "abi::bytes")]
+    pub mem_proof: Vec<u8>,
+    pub preimage: PreimageData,
+
+    pub read: bool,
+    #[serde(with = "abi::u32_value")]
+    pub read_addr: u32,
+    pub write: bool,
+    #[serde(with = "abi::u32_value")]
+    pub write_addr: u32,
+    #[serde(rename = "updateRd")]
+    pub update_rd: bool,
+    #[serde(with = "abi::u32_value")]
+    pub rd: u32,
+    #[serde(rename = "rdVal", with = "abi::u32_value")]
+    pub rd_val: u32,
+    pub illegal: bool,
+
+    /// Hash of the memory tree the proofs are checked against, which the circuit doesn't take
+    /// as an input but is generated for.
+    #[serde(skip)]
+    pub memory_hash: MemoryHash,
+}
+
+impl MipsStates {
+    /// Creates the input of a step, with all the flags cleared and no preimage.
+    pub fn new(states: States, insn_proof: Vec<u8>, mem_proof: Vec<u8>) -> Self {
+        Self {
+            states,
+            insn_proof,
+            mem_proof,
+            preimage: PreimageData::default(),
+            read: false,
+            read_addr: 0,
+            write: false,
+            write_addr: 0,
+            update_rd: false,
+            rd: 0,
+            rd_val: 0,
+            illegal: false,
+            memory_hash: MemoryHash::Poseidon,
+        }
+    }
+
+    /// Checks the proofs against a memory tree hashed with `hash`, as the circuit generated
+    /// with that memory hash does.
+    pub fn with_memory_hash(mut self, hash: MemoryHash) -> Self {
+        self.memory_hash = hash;
+        self
+    }
+}
+
+impl From<&TraceRecord> for MipsStates {
+    fn from(record: &TraceRecord) -> Self {
+        Self {
+            preimage: record.preimage.clone(),
+            ..Self::new(
+                record.pre_states(),
+                record.insn_proof.clone(),
+                record.mem_proof.clone(),
+            )
+        }
+    }
+}
+
+impl TraceRecord {
+    /// State before the step, as `do_check` builds it from the record.
+    pub fn pre_states(&self) -> States {
+        States {
+            cycle: self.cycle,
+            pc: self.pc,
+            next_pc: self.next_pc,
+            lo: self.lo,
+            hi: self.hi,
+            regs: self.regs,
+            heap: self.heap,
+            exit_code: self.exit_code,
+            exited: self.exited,
+            mem_root: self.mem_root,
+        }
+    }
+
+    /// State after the step, i.e. the `result` the circuit compares against.
+    pub fn post_states(&self) -> States {
+        States {
+            cycle: self.new_cycle,
+            pc: self.new_pc,
+            next_pc: self.new_next_pc,
+            lo: self.new_lo,
+            hi: self.new_hi,
+            regs: self.new_regs,
+            heap: self.new_heap,
+            exit_code: self.new_exit_code,
+            exited: self.new_exited,
+            mem_root: self.new_mem_root,
+        }
+    }
+}
+
+/// Executes one instruction, the way `check_instruction` does before comparing the result with
+/// the trace.
+///
+/// Anything the circuit would reject (unsupported instructions and syscalls, invalid proofs
+/// and preimages, division by zero, ...) sets `illegal` instead of returning an error.
+pub fn step(mut states: MipsStates) -> MipsStates {
+    let (root, mem_proof) = (states.states.mem_root, states.mem_proof.clone());
+    let hash = states.memory_hash;
+    if execute_step(&mut states).is_err() {
+        states.illegal = true;
+        return states;
+    }
+
+    states.states.cycle = states.states.cycle.wrapping_add(1);
+    if states.update_rd {
+        states.states.regs[states.rd as usize] = states.rd_val;
+    }
+
+    let mem_addr = if states.write {
+        states.write_addr
+    } else {
+        states.read_addr
+    };
+    // the leaf accessed by loads and stores has to be the one of the memory before the step
+    if (states.read || states.write) && hash.root_from_proof(&mem_proof, mem_addr) != root {
+        states.illegal = true;
+        return states;
+    }
+    states.states.mem_root = if states.read || states.write {
+        hash.root_from_proof(&states.mem_proof, mem_addr)
+    } else {
+        states.states.mem_root
+    };
+
+    states
+}
+
+fn word_from_proof(proof: &[u8], addr: u32) -> Result<u32, VmError> {
+    if proof.len() != PROOF_SIZE {
+        return Err(VmError::InvalidProof(addr));
+    }
+    if addr & 3 != 0 {
+        return Err(VmError::UnalignedAccess(addr));
+    }
+    let offset = (addr & 31) as usize;
+
+    let mut word = [0u8; 4];
+    word.copy_from_slice(&proof[offset..offset + 4]);
+    Ok(u32::from_be_bytes(word))
+}
+
+fn execute_step(s: &mut MipsStates) -> Result<(), VmError> {
+    let pc = s.states.pc;
+    let insn = word_from_proof(&s.insn_proof, pc)?;
+    if s.memory_hash.root_from_proof(&s.insn_proof, pc) != s.states.mem_root {
+        return Err(VmError::InvalidProof(pc));
+    }
+    let opcode = insn >> 26;
+
+    // j / jal
+    if opcode == 2 || opcode == 3 {
+        let link = if opcode == 3 { 31 } else { 0 };
+        let target = (s.states.next_pc & 0xF000_0000) | ((insn & 0x03FF_FFFF) << 2);
+        return handle_jump(s, link, target);
+    }
+
+    let mut rs = s.states.regs[((insn >> 21) & 0x1F) as usize];
+    let rt_reg = (insn >> 16) & 0x1F;
+    let mut rd_reg = rt_reg;
+    let rt = if opcode == 0 || opcode == 0x1C {
+        rd_reg = (insn >> 11) & 0x1F;
+        s.states.regs[rt_reg as usize]
+    } else if opcode < 0x20 {
+        if opcode == 0xC || opcode == 0xD || opcode == 0xE {
+            insn & 0xFFFF
+        } else {
+            sign_extend(insn & 0xFFFF, 16)
+        }
+    } else if opcode >= 0x28 || opcode == 0x22 || opcode == 0x26 {
+        s.states.regs[rt_reg as usize]
+    } else {
+        0
+    };
+
+    if (4..8).contains(&opcode) || opcode == 1 {
+        return handle_branch(s, opcode, insn, rt_reg, rs);
+    }
+
+    let mut store_addr = None;
+    let mut mem = 0;
+    if opcode >= 0x20 {
+        rs = rs.wrapping_add(sign_extend(insn & 0xFFFF, 16));
+        let addr = rs & 0xFFFF_FFFC;
+        mem = word_from_proof(&s.mem_proof, addr)?;
+        s.read = true;
+        s.read_addr = addr;
+        if opcode >= 0x28 && opcode != 0x30 {
+            store_addr = Some(addr);
+            rd_reg = 0;
+        }
+    }
+
+    let val = execute(insn, rs, rt, mem).ok_or(VmError::InvalidInstruction { pc, insn })?;
+
+    let fun = insn & 0x3F;
+    if opcode == 0 && (8..0x1C).contains(&fun) {
+        match fun {
+            0x08 | 0x09 => {
+                let link = if fun == 9 { rd_reg } else { 0 };
+                return handle_jump(s, link, rs);
+            }
+            0x0A => return handle_rd(s, rd_reg, rs, rt == 0),
+            0x0B => return handle_rd(s, rd_reg, rs, rt != 0),
+            0x0C => return handle_syscall(s),
+            0x10..=0x1B => return handle_hi_lo(s, fun, rs, rt, rd_reg),
+            _ => {}
+        }
+    }
+
+    if opcode == 0x38 && rt_reg != 0 {
+        s.states.regs[rt_reg as usize] = 1;
+    }
+
+    if let Some(addr) = store_addr {
+        let offset = (addr & 31) as usize;
+        s.mem_proof[offset..offset + 4].copy_from_slice(&val.to_be_bytes());
+        s.write = true;
+        s.write_addr = addr;
+    }
+
+    handle_rd(s, rd_reg, val, true)
+}
+
+fn check_delay_slot(s: &MipsStates) -> Result<(), VmError> {
+    if s.states.next_pc != s.states.pc.wrapping_add(4) {
+        return Err(VmError::JumpInDelaySlot(s.states.pc));
+    }
+    Ok(())
+}
+
+fn handle_branch(
+    s: &mut MipsStates,
+    opcode: u32,
+    insn: u32,
+    rt_reg: u32,
+    rs: u32,
+) -> Result<(), VmError> {
+    check_delay_slot(s)?;
+
+    let should_branch = match opcode {
+        4 => rs == s.states.regs[rt_reg as usize],
+        5 => rs != s.states.regs[rt_reg as usize],
+        6 => (rs as i32) <= 0,
+        7 => (rs as i32) > 0,
+        1 => match (insn >> 16) & 0x1F {
+            0 => (rs as i32) < 0,
+            1 => (rs as i32) >= 0,
+            _ => false,
+        },
+        _ => false,
+    };
+
+    let prev_pc = s.states.pc;
+    s.states.pc = s.states.next_pc;
+    s.states.next_pc = if should_branch {
+        prev_pc
+            .wrapping_add(4)
+            .wrapping_add(sign_extend(insn & 0xFFFF, 16) << 2)
+    } else {
+        s.states.next_pc.wrapping_add(4)
+    };
+
+    Ok(())
+}
+
+fn handle_jump(s: &mut MipsStates, link: u32, dest: u32) -> Result<(), VmError> {
+    check_delay_slot(s)?;
+
+    let prev_pc = s.states.pc;
+    s.states.pc = s.states.next_pc;
+    s.states.next_pc = dest;
+    if link != 0 {
+        s.states.regs[link as usize] = prev_pc.wrapping_add(8);
+    }
+
+    Ok(())
+}
+
+fn handle_rd(s: &mut MipsStates, rd: u32, val: u32, cond: bool) -> Result<(), VmError> {
+    s.states.pc = s.states.next_pc;
+    s.states.next_pc = s.states.next_pc.wrapping_add(4);
+    s.update_rd = rd != 0 && cond;
+    s.rd = rd;
+    s.rd_val = val;
+
+    Ok(())
+}
+
+fn handle_hi_lo(s: &mut MipsStates, fun: u32, rs: u32, rt: u32, rd: u32) -> Result<(), VmError> {
+    let mut val = 0;
+    match fun {
+        0x10 => val = s.states.hi,
+        0x11 => s.states.hi = rs,
+        0x12 => val = s.states.lo,
+        0x13 => s.states.lo = rs,
+        0x18 => {
+            let acc = (rs as i32 as i64).wrapping_mul(rt as i32 as i64) as u64;
+            s.states.hi = (acc >> 32) as u32;
+            s.states.lo = acc as u32;
+        }
+        0x19 => {
+            let acc = (rs as u64) * (rt as u64);
+            s.states.hi = (acc >> 32) as u32;
+            s.states.lo = acc as u32;
+        }
+        0x1A => {
+            if rt == 0 {
+                return Err(VmError::DivisionByZero(s.states.pc));
+            }
+            s.states.hi = (rs as i32).wrapping_rem(rt as i32) as u32;
+            s.states.lo = (rs as i32).wrapping_div(rt as i32) as u32;
+        }
+        0x1B => {
+            if rt == 0 {
+                return Err(VmError::DivisionByZero(s.states.pc));
+            }
+            s.states.hi = rs % rt;
+            s.states.lo = rs / rt;
+        }
+        _ => {}
+    }
+
+    handle_rd(s, rd, val, true)
+}
+
+fn handle_syscall(s: &mut MipsStates) -> Result<(), VmError> {
+    let regs = &s.states.regs;
+    let (sys_num, a0, a1, a2) = (regs[2], regs[4], regs[5], regs[6]);
+
+    let (v0, v1) = match syscall(sys_num, a0, a1, a2, &mut s.states.heap)? {
+        Syscall::Return(v0, v1) => (v0, v1),
+        Syscall::Exit(code) => {
+            s.states.exited = true;
+            s.states.exit_code = code;
+            return Ok(());
+        }
+        Syscall::Preimage => {
+            load_preimage(s)?;
+            (0, 0)
+        }
+    };
+
+    s.states.regs[2] = v0;
+    s.states.regs[7] = v1;
+    s.states.pc = s.states.next_pc;
+    s.states.next_pc = s.states.next_pc.wrapping_add(4);
+
+    Ok(())
+}
+
+/// Checks the preimage of the key at `PREIMAGE_KEY_ADDR`, proven by `mem_proof`, and writes it
+/// with its length to the subtree at `PREIMAGE_DATA_ADDR`, like `doGetPreImage`.
+fn load_preimage(s: &mut MipsStates) -> Result<(), VmError> {
+    let (root, hash) = (s.states.mem_root, s.memory_hash);
+    if s.mem_proof.len() != PROOF_SIZE
+        || hash.root_from_proof(&s.mem_proof, PREIMAGE_KEY_ADDR) != root
+    {
+        return Err(VmError::InvalidProof(PREIMAGE_KEY_ADDR));
+    }
+    let key = &s.mem_proof[0..32];
+
+    let preimage = &s.preimage;
+    let sizes = PreimageData::default();
+    if preimage.data.len() != sizes.data.len()
+        || preimage.leaves.len() != sizes.leaves.len()
+        || preimage.proof.len() != sizes.proof.len()
+    {
+        return Err(VmError::InvalidProof(PREIMAGE_DATA_ADDR));
+    }
+    let len = preimage.len as usize;
+    if len > PREIMAGE_MAX_SIZE {
+        return Err(VmError::PreimageTooLarge {
+            key: hex::encode(key),
+            len,
+            max: PREIMAGE_MAX_SIZE,
+        });
+    }
+    if preimage_key(&preimage.data[..len]) != key {
+        return Err(VmError::InvalidPreimage(hex::encode(key)));
+    }
+    let subtree_root = |leaves: &[u8]| {
+        hash.root_from_subtree(
+            leaves,
+            &preimage.proof,
+            PREIMAGE_DATA_ADDR,
+            PREIMAGE_SUBTREE_DEPTH,
+        )
+    };
+    if subtree_root(&preimage.leaves) != root {
+        return Err(VmError::InvalidProof(PREIMAGE_DATA_ADDR));
+    }
+
+    let mut leaves = preimage.leaves.clone();
+    leaves[..4].copy_from_slice(&preimage.len.to_be_bytes());
+    leaves[4..4 + len].copy_from_slice(&preimage.data[..len]);
+    s.states.mem_root = subtree_root(&leaves);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::Memory;
+    use crate::oracle::{NoPreimages, PreimageOracle};
+    use crate::state::{State, HEAP_START};
+    use crate::Executor;
+
+    fn state(program: &[u32], mut memory: Memory) -> State {
+        for (i, insn) in program.iter().enumerate() {
+            memory.set_word(0x0040_0000 + 4 * i as u32, *insn).unwrap();
+        }
+        State {
+            cycle: 0,
+            pc: 0x0040_0000,
+            next_pc: 0x0040_0004,
+            lo: 0,
+            hi: 0,
+            regs: [0; 32],
+            heap: HEAP_START,
+            exit_code: 0,
+            exited: false,
+            memory,
+        }
+    }
+
+    /// Serves the same preimage for every key.
+    struct OnePreimage(Vec<u8>);
+
+    impl PreimageOracle for OnePreimage {
+        fn get_preimage(&self, _key: &[u8; 32]) -> Result<Vec<u8>, VmError> {
+            Ok(self.0.clone())
+        }
+    }
+
+    /// The model has to agree with the executor on every record the executor produces.
+    #[test]
+    fn matches_executor() {
+        let program = [
+            0x3c08_1000, // lui   $t0, 0x1000
+            0x2409_00ff, // addiu $t1, $zero, 0xff
+            0xa109_0002, // sb    $t1, 2($t0)
+            0x8d0a_0000, // lw    $t2, 0($t0)
+            0x0c10_0008, // jal   0x00400020
+            0x012a_0018, // mult  $t1, $t2
+            0x0000_0000, // nop
+            0x0000_0000, // nop
+            0x2402_1096, // addiu $v0, $zero, 4246
+            0x0000_000c, // syscall
+        ];
+
+        let mut executor = Executor::new(state(&program, Memory::new()), NoPreimages);
+        let mut steps = 0;
+        while let Some(record) = executor.step().unwrap() {
+            let res = step(MipsStates::from(&record));
+            assert!(!res.illegal, "step {} is illegal", steps);
+            assert_eq!(res.states, record.post_states(), "step {}", steps);
+            steps += 1;
+        }
+        assert_eq!(steps, 8);
+    }
+
+    /// The records of a Keccak memory are checked against the Keccak tree only.
+    #[test]
+    fn keccak_memory() {
+        let program = [
+            0x3c08_1000, // lui   $t0, 0x1000
+            0x2409_00ff, // addiu $t1, $zero, 0xff
+            0xad09_0004, // sw    $t1, 4($t0)
+            0x8d0a_0004, // lw    $t2, 4($t0)
+        ];
+        let memory = Memory::with_hash(MemoryHash::Keccak);
+
+        let mut executor = Executor::new(state(&program, memory), NoPreimages);
+        for _ in 0..program.len() {
+            let record = executor.step().unwrap().unwrap();
+            let states = MipsStates::from(&record);
+            assert!(step(states.clone()).illegal);
+
+            let res = step(states.with_memory_hash(MemoryHash::Keccak));
+            assert!(!res.illegal);
+            assert_eq!(res.states, record.post_states());
+        }
+    }
+
+    /// A load or a store has to prove the leaf it accesses against the root before the step.
+    #[test]
+    fn forged_memory_proof() {
+        let program = [
+            0x3c08_1000, // lui   $t0, 0x1000
+            0x8d09_0000, // lw    $t1, 0($t0)
+            0xad09_0004, // sw    $t1, 4($t0)
+        ];
+        let mut memory = Memory::new();
+        memory.set_word(0x1000_0000, 7).unwrap();
+
+        let mut executor = Executor::new(state(&program, memory), NoPreimages);
+        executor.step().unwrap().unwrap();
+        for _ in 0..2 {
+            let record = executor.step().unwrap().unwrap();
+            assert!(!step(MipsStates::from(&record)).illegal);
+
+            // the leaf claims that the word holds 8
+            let mut forged = MipsStates::from(&record);
+            forged.mem_proof[3] = 8;
+            assert!(step(forged).illegal);
+        }
+    }
+
+    /// The preimage is only accepted if it hashes to the key and is written where it belongs.
+    #[test]
+    fn preimage_syscall() {
+        let program = [
+            0x2402_0fb4, // addiu $v0, $zero, 4020
+            0x0000_000c, // syscall
+        ];
+        let preimage = b"block header".repeat(40);
+        let mut memory = Memory::new();
+        memory.set_memory_range(PREIMAGE_KEY_ADDR, &preimage_key(&preimage));
+        memory.set_word(PREIMAGE_DATA_ADDR + 0x300, 0x42).unwrap();
+
+        let mut executor = Executor::new(state(&program, memory), OnePreimage(preimage));
+        executor.step().unwrap().unwrap();
+        let record = executor.step().unwrap().unwrap();
+        assert_eq!(record.preimage.len, 480);
+
+        let res = step(MipsStates::from(&record));
+        assert!(!res.illegal);
+        assert_eq!(res.states, record.post_states());
+
+        let mut forged = MipsStates::from(&record);
+        forged.preimage.data[0] ^= 1;
+        assert!(step(forged).illegal);
+
+        let mut forged = MipsStates::from(&record);
+        forged.preimage.leaves[0x300] ^= 1;
+        assert!(step(forged).illegal);
+
+        let mut forged = MipsStates::from(&record);
+        forged.preimage.len = 600;
+        assert!(step(forged).illegal);
+    }
+}
@@ -0,0 +1,379 @@
+//! Witness of `offline_memory.zok`, the batch circuit checking the memory offline.
+//!
+//! Instead of a Merkle proof per access, the circuit takes the leaves every step reads, logs two
+//! accesses per step (the fetch of the instruction at time `2 * i` and the data access at time
+//! `2 * i + 1`) and checks them against the same accesses sorted by leaf and time. The memory root
+//! is only updated once per batch, with a Merkle proof per distinct leaf: the [`BoundaryLeaf`]s.
+//!
+//! The witness is built from the records alone, so that it can be computed from stored traces:
+//! their proofs reveal the part of the memory tree the batch touches.
+
+// Built-in deps
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+// External imports
+use serde::{Deserialize, Serialize};
+// Local imports
+use crate::cpu::SYS_PREIMAGE;
+use crate::memory::{Hash, LEAF_SIZE, PROOF_SIZE, TREE_DEPTH};
+use crate::model::{self, MipsStates, States};
+use crate::poseidon::hash_pair;
+use crate::trace::{abi, TraceRecord};
+use crate::VmError;
+
+/// Counterpart of the `offline_record` struct of the circuit.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OfflineRecord {
+    pub pre: States,
+    /// The leaf holding the instruction.
+    #[serde(with = "abi::hash")]
+    pub insn_leaf: Hash,
+    /// The leaf of the data access before the step, all zeros if there is none.
+    #[serde(with = "abi::hash")]
+    pub mem_leaf: Hash,
+    pub post: States,
+}
+
+/// Counterpart of the `memory_access` struct of the circuit.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MemoryAccess {
+    /// `addr >> 5`
+    #[serde(with = "abi::u32_value")]
+    pub leaf: u32,
+    #[serde(with = "abi::u32_value")]
+    pub time: u32,
+    #[serde(with = "abi::hash")]
+    pub before: Hash,
+    #[serde(with = "abi::hash")]
+    pub after: Hash,
+}
+
+/// Counterpart of the `boundary_leaf` struct of the circuit: a leaf accessed by the batch, with
+/// its siblings in the root once the leaves before it are updated.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BoundaryLeaf {
+    pub used: bool,
+    #[serde(with = "abi::u32_value")]
+    pub leaf: u32,
+    /// The value before the batch.
+    #[serde(with = "abi::hash")]
+    pub initial: Hash,
+    /// The value after the batch.
+    #[serde(with = "abi::hash")]
+    pub updated: Hash,
+    #[serde(with = "abi::bytes")]
+    pub siblings: Vec<u8>,
+}
+
+impl BoundaryLeaf {
+    fn unused() -> Self {
+        Self {
+            used: false,
+            leaf: 0,
+            initial: [0; 32],
+            updated: [0; 32],
+            siblings: vec![0; TREE_DEPTH * 32],
+        }
+    }
+}
+
+/// Counterpart of the `offline_batch` struct of the circuit, the argument of `offline_memory.zok`
+/// for a batch of consecutive records.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OfflineBatch {
+    pub records: Vec<OfflineRecord>,
+    /// The accesses of the steps, sorted by leaf and time.
+    pub sorted: Vec<MemoryAccess>,
+    /// The leaves accessed by the batch in increasing order, padded to the maximum number of
+    /// leaves with unused entries.
+    pub boundary: Vec<BoundaryLeaf>,
+}
+
+impl OfflineBatch {
+    /// Builds the witness of `records`, which have to follow each other.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `records` is empty.
+    pub fn new(records: &[TraceRecord], max_leaves: usize) -> Result<Self, VmError> {
+        let last = records.last().expect("empty batch");
+
+        // the tree during the batch, and the nodes of the tree before the batch
+        let mut tree = PartialTree::default();
+        let mut initial = PartialTree::default();
+        let mut offline_records = Vec::with_capacity(records.len());
+        let mut sorted = Vec::with_capacity(2 * records.len());
+        for (i, record) in records.iter().enumerate() {
+            let step = model::step(MipsStates::from(record));
+            if step.illegal
+                || step.states != record.post_states()
+                || record.mem_proof.len() != PROOF_SIZE
+            {
+                return Err(VmError::IllegalStep(record.cycle));
+            }
+            if is_preimage_syscall(record) {
+                return Err(VmError::PreimageInOfflineBatch(record.cycle));
+            }
+            if tree.root().is_some_and(|root| root != record.mem_root) {
+                return Err(VmError::MemoryMismatch);
+            }
+            tree.add_proof(&mut initial, &record.insn_proof, record.pc >> 5);
+            if step.read || step.write {
+                tree.add_proof(&mut initial, &record.mem_proof, step.read_addr >> 5);
+            }
+            if step.write {
+                tree.set_leaf(step.read_addr >> 5, leaf_of(&step.mem_proof));
+            }
+
+            let insn_leaf = leaf_of(&record.insn_proof);
+            let mem_leaf = leaf_of(&record.mem_proof);
+            let fetch = MemoryAccess {
+                leaf: record.pc >> 5,
+                time: 2 * i as u32,
+                before: insn_leaf,
+                after: insn_leaf,
+            };
+            let data = if step.read || step.write {
+                MemoryAccess {
+                    leaf: step.read_addr >> 5,
+                    time: 2 * i as u32 + 1,
+                    before: mem_leaf,
+                    after: leaf_of(&step.mem_proof),
+                }
+            } else {
+                MemoryAccess {
+                    time: 2 * i as u32 + 1,
+                    ..fetch.clone()
+                }
+            };
+            sorted.push(fetch);
+            sorted.push(data);
+
+            offline_records.push(OfflineRecord {
+                pre: record.pre_states(),
+                insn_leaf,
+                mem_leaf,
+                post: record.post_states(),
+            });
+        }
+        sorted.sort_by_key(|access| (access.leaf, access.time));
+
+        let mut boundary: Vec<BoundaryLeaf> = Vec::new();
+        for access in &sorted {
+            match boundary.last_mut() {
+                Some(leaf) if leaf.leaf == access.leaf => leaf.updated = access.after,
+                _ => boundary.push(BoundaryLeaf {
+                    used: true,
+                    leaf: access.leaf,
+                    initial: access.before,
+                    updated: access.after,
+                    siblings: vec![],
+                }),
+            }
+        }
+        if boundary.len() > max_leaves {
+            return Err(VmError::TooManyLeaves {
+                leaves: boundary.len(),
+                max: max_leaves,
+            });
+        }
+
+        // the leaves are updated in order, so the siblings of a leaf are taken in the tree with
+        // the leaves before it updated
+        let mut tree = initial;
+        for leaf in &mut boundary {
+            leaf.siblings = tree.siblings(leaf.leaf);
+            tree.set_leaf(leaf.leaf, leaf.updated);
+        }
+        if tree.root() != Some(last.new_mem_root) {
+            return Err(VmError::MemoryMismatch);
+        }
+
+        boundary.resize(max_leaves, BoundaryLeaf::unused());
+        Ok(Self {
+            records: offline_records,
+            sorted,
+            boundary,
+        })
+    }
+}
+
+/// The nodes of the memory tree known from the proofs of a batch, indexed by `(level, index)` with
+/// the leaves at level 0.
+#[derive(Debug, Clone, Default)]
+struct PartialTree {
+    nodes: HashMap<(usize, u32), Hash>,
+}
+
+impl PartialTree {
+    /// Adds the nodes of `proof`, the proof of `leaf` against the current root. The nodes that
+    /// weren't known haven't changed since the start of the batch, as the batch only changed the
+    /// paths of the leaves it wrote, so they are added to `initial` as well.
+    fn add_proof(&mut self, initial: &mut PartialTree, proof: &[u8], leaf: u32) {
+        let mut learn = |key: (usize, u32), node: Hash| {
+            if let Entry::Vacant(e) = self.nodes.entry(key) {
+                e.insert(node);
+                initial.nodes.insert(key, node);
+            }
+        };
+
+        let mut node = leaf_of(proof);
+        let mut index = leaf;
+        learn((0, index), node);
+        for level in 0..TREE_DEPTH {
+            let sibling = leaf_of(&proof[(level + 1) * 32..]);
+            learn((level, index ^ 1), sibling);
+            node = parent(node, sibling, index);
+            index >>= 1;
+            learn((level + 1, index), node);
+        }
+    }
+
+    /// Sets `leaf` to `value`, the siblings of `leaf` have to be known.
+    fn set_leaf(&mut self, leaf: u32, value: Hash) {
+        let mut node = value;
+        let mut index = leaf;
+        self.nodes.insert((0, index), node);
+        for level in 0..TREE_DEPTH {
+            node = parent(node, self.nodes[&(level, index ^ 1)], index);
+            index >>= 1;
+            self.nodes.insert((level + 1, index), node);
+        }
+    }
+
+    /// The siblings of `leaf`, which have to be known, in the layout of a proof without its leaf.
+    fn siblings(&self, leaf: u32) -> Vec<u8> {
+        (0..TREE_DEPTH)
+            .flat_map(|level| self.nodes[&(level, (leaf >> level) ^ 1)].to_vec())
+            .collect()
+    }
+
+    fn root(&self) -> Option<Hash> {
+        self.nodes.get(&(TREE_DEPTH, 0)).copied()
+    }
+}
+
+/// The parent of `node`, the node at `index` of its level, and of its `sibling`.
+fn parent(node: Hash, sibling: Hash, index: u32) -> Hash {
+    if index & 1 == 0 {
+        hash_pair(&node, &sibling)
+    } else {
+        hash_pair(&sibling, &node)
+    }
+}
+
+fn leaf_of(proof: &[u8]) -> Hash {
+    let mut leaf = [0u8; 32];
+    leaf.copy_from_slice(&proof[0..32]);
+    leaf
+}
+
+fn is_preimage_syscall(record: &TraceRecord) -> bool {
+    let offset = (record.pc & (LEAF_SIZE - 1)) as usize;
+    let mut insn = [0u8; 4];
+    insn.copy_from_slice(&record.insn_proof[offset..offset + 4]);
+
+    u32::from_be_bytes(insn) & 0xFC00_003F == 0xC && record.regs[2] == SYS_PREIMAGE
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::Memory;
+    use crate::oracle::NoPreimages;
+    use crate::state::{State, HEAP_START};
+    use crate::Executor;
+
+    const BASE: u32 = 0x0040_0000;
+
+    /// Runs a program storing to and loading from the leaf at 0x100.
+    fn run() -> Vec<TraceRecord> {
+        let program = [
+            0x2408_0005, // addiu $t0, $zero, 5
+            0xac08_0100, // sw    $t0, 0x100($zero)
+            0x8c09_0100, // lw    $t1, 0x100($zero)
+            0xac09_0104, // sw    $t1, 0x104($zero)
+        ];
+        let mut memory = Memory::new();
+        for (i, insn) in program.iter().enumerate() {
+            memory.set_word(BASE + 4 * i as u32, *insn).unwrap();
+        }
+        let state = State {
+            cycle: 0,
+            pc: BASE,
+            next_pc: BASE + 4,
+            lo: 0,
+            hi: 0,
+            regs: [0; 32],
+            heap: HEAP_START,
+            exit_code: 0,
+            exited: false,
+            memory,
+        };
+
+        let mut executor = Executor::new(state, NoPreimages);
+        (0..program.len())
+            .map(|_| executor.step().unwrap().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn witness_is_consistent() {
+        let records = run();
+        let batch = OfflineBatch::new(&records, 4).unwrap();
+        let last = records.last().unwrap();
+
+        assert_eq!(batch.records.len(), 4);
+        assert_eq!(batch.sorted.len(), 8);
+        assert_eq!(batch.records[3].post, last.post_states());
+
+        // 5 accesses to the code and one per step to the data, which is written by the stores
+        let data: Vec<_> = batch.sorted.iter().filter(|a| a.leaf == 8).collect();
+        assert_eq!(
+            data.iter().map(|a| a.time).collect::<Vec<_>>(),
+            vec![3, 5, 7]
+        );
+        assert_eq!(data[0].before, [0; 32]);
+        assert_eq!(data[2].after[0..8], [0, 0, 0, 5, 0, 0, 0, 5]);
+        for pair in batch.sorted.windows(2) {
+            assert!((pair[0].leaf, pair[0].time) < (pair[1].leaf, pair[1].time));
+            if pair[0].leaf == pair[1].leaf {
+                assert_eq!(pair[0].after, pair[1].before);
+            }
+        }
+
+        // the boundary leaves update the root before the batch to the root after it
+        assert_eq!(batch.boundary.len(), 4);
+        assert_eq!(batch.boundary[0].leaf, 8);
+        assert_eq!(batch.boundary[1].leaf, BASE >> 5);
+        assert!(!batch.boundary[2].used && !batch.boundary[3].used);
+        let mut root = records[0].mem_root;
+        for leaf in batch.boundary.iter().filter(|leaf| leaf.used) {
+            let addr = leaf.leaf << 5;
+            assert_eq!(
+                Memory::root_from_proof(&[&leaf.initial[..], &leaf.siblings].concat(), addr),
+                root
+            );
+            root = Memory::root_from_proof(&[&leaf.updated[..], &leaf.siblings].concat(), addr);
+        }
+        assert_eq!(root, last.new_mem_root);
+    }
+
+    #[test]
+    fn too_many_leaves() {
+        assert!(matches!(
+            OfflineBatch::new(&run(), 1),
+            Err(VmError::TooManyLeaves { leaves: 2, max: 1 })
+        ));
+    }
+
+    #[test]
+    fn records_have_to_follow_each_other() {
+        let mut records = run();
+        records.remove(1);
+        assert!(matches!(
+            OfflineBatch::new(&records, 4),
+            Err(VmError::MemoryMismatch)
+        ));
+    }
+}
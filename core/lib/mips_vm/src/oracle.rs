@@ -0,0 +1,46 @@
+//! Sources of preimages for the `4020` syscall.
+
+// Built-in deps
+use std::path::PathBuf;
+// Local imports
+use crate::VmError;
+
+/// Returns the key of `preimage`, i.e. its Keccak-256, which the circuit checks it against.
+pub fn preimage_key(preimage: &[u8]) -> [u8; 32] {
+    tiny_keccak::keccak256(preimage)
+}
+
+pub trait PreimageOracle {
+    /// Returns the preimage of the 32-byte `key`.
+    fn get_preimage(&self, key: &[u8; 32]) -> Result<Vec<u8>, VmError>;
+}
+
+/// Serves preimages from a directory holding one file per key, named `0x<hex key>`,
+/// which is the layout minigeth dumps them in.
+#[derive(Debug, Clone)]
+pub struct DirectoryOracle {
+    dir: PathBuf,
+}
+
+impl DirectoryOracle {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+}
+
+impl PreimageOracle for DirectoryOracle {
+    fn get_preimage(&self, key: &[u8; 32]) -> Result<Vec<u8>, VmError> {
+        let path = self.dir.join(format!("0x{}", hex::encode(key)));
+        std::fs::read(&path).map_err(|_| VmError::MissingPreimage(hex::encode(key)))
+    }
+}
+
+/// Oracle for programs that never request a preimage.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoPreimages;
+
+impl PreimageOracle for NoPreimages {
+    fn get_preimage(&self, key: &[u8; 32]) -> Result<Vec<u8>, VmError> {
+        Err(VmError::MissingPreimage(hex::encode(key)))
+    }
+}
@@ -0,0 +1,152 @@
+//! Poseidon hash used by the memory Merkle tree of the MIPS circuit.
+//!
+//! This is a port of `hashes/poseidon/poseidon` from the ZoKrates stdlib specialised
+//! for two inputs, so that roots computed here match `hashPair` in `mips_vm_poseidon.zok`.
+//! The stdlib uses the same constants whatever the curve, so the circuit compiled for Pallas,
+//! the step circuit of the Nova mode, hashes with the same permutation over the Pallas field,
+//! see [`hash_pair_pallas`].
+
+// Built-in deps
+use std::convert::TryFrom;
+// External imports
+use num_bigint::BigUint;
+use once_cell::sync::Lazy;
+use zokrates_field::{Bn128Field, Field, PallasField};
+// Local imports
+use self::constants::{POSEIDON_C, POSEIDON_M};
+
+mod constants;
+
+/// Width of the permutation (`N + 1` for `N = 2` inputs).
+const T: usize = 3;
+const FULL_ROUNDS: usize = 8;
+const PARTIAL_ROUNDS: usize = 57;
+
+struct PoseidonParams<F> {
+    c: Vec<F>,
+    m: [[F; T]; T],
+}
+
+impl<F: Field> PoseidonParams<F> {
+    fn parse() -> Self {
+        let parse = |s: &str| F::try_from_dec_str(s).expect("invalid poseidon constant");
+
+        let c = POSEIDON_C.iter().map(|s| parse(s)).collect();
+        let mut m = [[F::from(0u32); T]; T];
+        for (i, row) in POSEIDON_M.iter().enumerate() {
+            for (j, value) in row.iter().enumerate() {
+                m[i][j] = parse(value);
+            }
+        }
+
+        Self { c, m }
+    }
+}
+
+static PARAMS: Lazy<PoseidonParams<Bn128Field>> = Lazy::new(PoseidonParams::parse);
+static PALLAS_PARAMS: Lazy<PoseidonParams<PallasField>> = Lazy::new(PoseidonParams::parse);
+
+fn pow5<F: Field>(x: F) -> F {
+    let x2 = x * x;
+    x2 * x2 * x
+}
+
+fn permute<F: Field>(params: &PoseidonParams<F>, a: F, b: F) -> F {
+    let mut state = [F::from(0u32), a, b];
+
+    for r in 0..FULL_ROUNDS + PARTIAL_ROUNDS {
+        for (i, s) in state.iter_mut().enumerate() {
+            *s = *s + params.c[r * T + i];
+        }
+
+        let full_round = !(FULL_ROUNDS / 2..FULL_ROUNDS / 2 + PARTIAL_ROUNDS).contains(&r);
+        state[0] = pow5(state[0]);
+        if full_round {
+            for s in state.iter_mut().skip(1) {
+                *s = pow5(*s);
+            }
+        }
+
+        let mut mixed = [F::from(0u32); T];
+        for (i, out) in mixed.iter_mut().enumerate() {
+            for (j, s) in state.iter().enumerate() {
+                *out = *out + *s * params.m[i][j];
+            }
+        }
+        state = mixed;
+    }
+
+    state[0]
+}
+
+/// Poseidon hash of two field elements.
+pub fn poseidon2(a: Bn128Field, b: Bn128Field) -> Bn128Field {
+    permute(&*PARAMS, a, b)
+}
+
+/// Interprets 32 big-endian bytes as a field element, the way `pack256` does
+/// (i.e. the value is reduced modulo the field order).
+pub fn field_from_bytes<F: Field>(bytes: &[u8; 32]) -> F {
+    let modulus = F::max_value().to_biguint() + 1u32;
+    let value = BigUint::from_bytes_be(bytes) % modulus;
+    F::try_from(value).expect("value is reduced modulo the field order")
+}
+
+/// Encodes a field element as 32 big-endian bytes, the way `unpack256` does.
+pub fn field_to_bytes<F: Field>(value: F) -> [u8; 32] {
+    let bytes = value.to_biguint().to_bytes_be();
+    let mut res = [0u8; 32];
+    res[32 - bytes.len()..].copy_from_slice(&bytes);
+    res
+}
+
+/// Counterpart of `hashPair` from the circuit.
+pub fn hash_pair(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
+    field_to_bytes(poseidon2(field_from_bytes(a), field_from_bytes(b)))
+}
+
+/// Counterpart of `hashPair` from the circuit compiled for Pallas.
+pub fn hash_pair_pallas(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
+    let hash = permute(&*PALLAS_PARAMS, field_from_bytes(a), field_from_bytes(b));
+    field_to_bytes(hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test vector from `zokrates_stdlib/tests/tests/hashes/poseidon/poseidon_2.json`.
+    #[test]
+    fn stdlib_vector() {
+        let hash = poseidon2(Bn128Field::from(1u32), Bn128Field::from(2u32));
+        assert_eq!(
+            hash.to_dec_string(),
+            "7853200120776062878684798364095072458815029376092732009249414926327459813530"
+        );
+    }
+
+    #[test]
+    fn empty_pair() {
+        assert_eq!(
+            hex::encode(hash_pair(&[0u8; 32], &[0u8; 32])),
+            "2098f5fb9e239eab3ceac3f27b81e481dc3124d55ffed523a839ee8446b64864"
+        );
+    }
+
+    /// The Pallas hash reduces its inputs modulo the Pallas order, which is larger than the
+    /// BN254 one.
+    #[test]
+    fn pallas_pair() {
+        let zero = [0u8; 32];
+        let order = (Bn128Field::max_value().to_biguint() + 1u32).to_bytes_be();
+        let mut bn128_order = [0u8; 32];
+        bn128_order[32 - order.len()..].copy_from_slice(&order);
+
+        assert_eq!(hash_pair(&bn128_order, &zero), hash_pair(&zero, &zero));
+        assert_ne!(hash_pair_pallas(&zero, &zero), hash_pair(&zero, &zero));
+        assert_ne!(
+            hash_pair_pallas(&bn128_order, &zero),
+            hash_pair_pallas(&zero, &zero)
+        );
+    }
+}
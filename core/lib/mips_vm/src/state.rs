@@ -0,0 +1,56 @@
+// Local imports
+use crate::elf::load_elf;
+use crate::memory::{Memory, MemoryHash};
+use crate::VmError;
+
+/// Initial value of the heap pointer handed out by `mmap`.
+pub const HEAP_START: u32 = 0x2000_0000;
+/// Initial stack pointer set up by [`State::patch_stack`].
+pub const STACK_POINTER: u32 = 0x7fff_d000;
+
+/// Architectural state of the MIPS machine, i.e. everything a trace record commits to.
+#[derive(Debug, Clone)]
+pub struct State {
+    pub cycle: u32,
+    pub pc: u32,
+    pub next_pc: u32,
+    pub lo: u32,
+    pub hi: u32,
+    pub regs: [u32; 32],
+    pub heap: u32,
+    pub exit_code: u8,
+    pub exited: bool,
+    pub memory: Memory,
+}
+
+impl State {
+    /// Creates the initial state of `elf` in a memory hashed with `hash`: segments are loaded,
+    /// execution starts at the entry point and the stack is set up with empty `argv`/`envp`.
+    pub fn from_elf(elf: &[u8], hash: MemoryHash) -> Result<Self, VmError> {
+        let mut memory = Memory::with_hash(hash);
+        let entry = load_elf(elf, &mut memory)?;
+
+        let mut state = Self {
+            cycle: 0,
+            pc: entry,
+            next_pc: entry.wrapping_add(4),
+            lo: 0,
+            hi: 0,
+            regs: [0; 32],
+            heap: HEAP_START,
+            exit_code: 0,
+            exited: false,
+            memory,
+        };
+        state.patch_stack();
+
+        Ok(state)
+    }
+
+    /// Sets up the stack the way the Go runtime expects it on entry: `argc = 0` followed by
+    /// the `argv`, `envp` and auxv terminators. Untouched memory already reads as zero, so
+    /// only the stack pointer has to be set.
+    pub fn patch_stack(&mut self) {
+        self.regs[29] = STACK_POINTER;
+    }
+}
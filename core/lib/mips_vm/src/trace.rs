@@ -0,0 +1,294 @@
+//! Trace records in the JSON layout consumed by `mips_vm_poseidon.zok`.
+//!
+//! Field names follow the `trace_record` struct of the circuit and integers are encoded the
+//! way the ZoKrates ABI expects them: `u8`/`u32` values are hex strings and `u8` arrays are
+//! arrays of hex strings.
+
+// External imports
+use serde::{Deserialize, Serialize};
+// Local imports
+use crate::cpu::{PREIMAGE_DATA_ADDR, PREIMAGE_MAX_SIZE, PREIMAGE_SUBTREE_DEPTH};
+use crate::memory::{Hash, Memory, LEAF_SIZE, TREE_DEPTH};
+use crate::state::State;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TraceRecord {
+    #[serde(with = "abi::u32_value")]
+    pub cycle: u32,
+    #[serde(with = "abi::u32_value")]
+    pub pc: u32,
+    #[serde(rename = "nextPC", with = "abi::u32_value")]
+    pub next_pc: u32,
+    #[serde(with = "abi::u32_value")]
+    pub lo: u32,
+    #[serde(with = "abi::u32_value")]
+    pub hi: u32,
+    #[serde(with = "abi::u32_array")]
+    pub regs: [u32; 32],
+    #[serde(with = "abi::u32_value")]
+    pub heap: u32,
+    #[serde(rename = "exitCode", with = "abi::u8_value")]
+    pub exit_code: u8,
+    pub exited: bool,
+    #[serde(rename = "memRoot", with = "abi::hash")]
+    pub mem_root: Hash,
+
+    #[serde(with = "abi::bytes")]
+    pub insn_proof: Vec<u8>,
+    #[serde(with = "abi::bytes")]
+    pub mem_proof: Vec<u8>,
+    pub preimage: PreimageData,
+
+    #[serde(rename = "newCycle", with = "abi::u32_value")]
+    pub new_cycle: u32,
+    #[serde(rename = "newPc", with = "abi::u32_value")]
+    pub new_pc: u32,
+    #[serde(rename = "newNextPC", with = "abi::u32_value")]
+    pub new_next_pc: u32,
+    #[serde(rename = "newLo", with = "abi::u32_value")]
+    pub new_lo: u32,
+    #[serde(rename = "newHi", with = "abi::u32_value")]
+    pub new_hi: u32,
+    #[serde(rename = "newRegs", with = "abi::u32_array")]
+    pub new_regs: [u32; 32],
+    #[serde(rename = "newHeap", with = "abi::u32_value")]
+    pub new_heap: u32,
+    #[serde(rename = "newExitCode", with = "abi::u8_value")]
+    pub new_exit_code: u8,
+    #[serde(rename = "newExited")]
+    pub new_exited: bool,
+    #[serde(rename = "newMemRoot", with = "abi::hash")]
+    pub new_mem_root: Hash,
+}
+
+impl TraceRecord {
+    /// Builds a record from the states before and after a step.
+    pub(crate) fn new(
+        pre: &StateSnapshot,
+        post: &StateSnapshot,
+        insn_proof: Vec<u8>,
+        mem_proof: Vec<u8>,
+        preimage: PreimageData,
+    ) -> Self {
+        Self {
+            cycle: pre.cycle,
+            pc: pre.pc,
+            next_pc: pre.next_pc,
+            lo: pre.lo,
+            hi: pre.hi,
+            regs: pre.regs,
+            heap: pre.heap,
+            exit_code: pre.exit_code,
+            exited: pre.exited,
+            mem_root: pre.mem_root,
+            insn_proof,
+            mem_proof,
+            preimage,
+            new_cycle: post.cycle,
+            new_pc: post.pc,
+            new_next_pc: post.next_pc,
+            new_lo: post.lo,
+            new_hi: post.hi,
+            new_regs: post.regs,
+            new_heap: post.heap,
+            new_exit_code: post.exit_code,
+            new_exited: post.exited,
+            new_mem_root: post.mem_root,
+        }
+    }
+}
+
+/// Counterpart of the `preimage_data` struct of the circuit: the preimage loaded by a preimage
+/// syscall and the subtree of the memory it is written to. All zeros in the other records.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PreimageData {
+    #[serde(with = "abi::u32_value")]
+    pub len: u32,
+    /// The preimage, padded with zeros to `PREIMAGE_MAX_SIZE` bytes.
+    #[serde(with = "abi::bytes")]
+    pub data: Vec<u8>,
+    /// The leaves of the subtree at `PREIMAGE_DATA_ADDR` before the preimage is written.
+    #[serde(with = "abi::bytes")]
+    pub leaves: Vec<u8>,
+    /// The siblings of the subtree on the path to the root.
+    #[serde(with = "abi::bytes")]
+    pub proof: Vec<u8>,
+}
+
+impl Default for PreimageData {
+    fn default() -> Self {
+        Self {
+            len: 0,
+            data: vec![0; PREIMAGE_MAX_SIZE],
+            leaves: vec![0; (LEAF_SIZE as usize) << PREIMAGE_SUBTREE_DEPTH],
+            proof: vec![0; (TREE_DEPTH - PREIMAGE_SUBTREE_DEPTH) * 32],
+        }
+    }
+}
+
+impl PreimageData {
+    /// Records `preimage` along with the subtree of `memory` it is about to be written to.
+    /// `preimage` must not be longer than `PREIMAGE_MAX_SIZE`.
+    pub fn new(memory: &mut Memory, preimage: &[u8]) -> Self {
+        let (leaves, proof) = memory.subtree_proof(PREIMAGE_DATA_ADDR, PREIMAGE_SUBTREE_DEPTH);
+        let mut data = preimage.to_vec();
+        data.resize(PREIMAGE_MAX_SIZE, 0);
+
+        Self {
+            len: preimage.len() as u32,
+            data,
+            leaves,
+            proof,
+        }
+    }
+}
+
+/// The non-memory part of a [`State`] together with its memory root.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct StateSnapshot {
+    pub cycle: u32,
+    pub pc: u32,
+    pub next_pc: u32,
+    pub lo: u32,
+    pub hi: u32,
+    pub regs: [u32; 32],
+    pub heap: u32,
+    pub exit_code: u8,
+    pub exited: bool,
+    pub mem_root: Hash,
+}
+
+impl StateSnapshot {
+    pub fn of(state: &mut State) -> Self {
+        Self {
+            cycle: state.cycle,
+            pc: state.pc,
+            next_pc: state.next_pc,
+            lo: state.lo,
+            hi: state.hi,
+            regs: state.regs,
+            heap: state.heap,
+            exit_code: state.exit_code,
+            exited: state.exited,
+            mem_root: state.memory.root(),
+        }
+    }
+}
+
+/// Serde helpers for the ZoKrates ABI encoding of integers.
+pub mod abi {
+    // Built-in deps
+    use std::convert::TryInto;
+    // External imports
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    fn parse(value: &str) -> Result<u32, String> {
+        match value.strip_prefix("0x") {
+            Some(hex) => u32::from_str_radix(hex, 16),
+            None => value.parse(),
+        }
+        .map_err(|e| format!("invalid integer `{}`: {}", value, e))
+    }
+
+    fn parse_u8(value: &str) -> Result<u8, String> {
+        parse(value)?
+            .try_into()
+            .map_err(|_| format!("`{}` does not fit into u8", value))
+    }
+
+    fn parse_vec<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        Vec::<String>::deserialize(deserializer)?
+            .iter()
+            .map(|s| parse_u8(s).map_err(D::Error::custom))
+            .collect()
+    }
+
+    pub mod u32_value {
+        use super::*;
+
+        pub fn serialize<S: Serializer>(value: &u32, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_str(&format!("{:#010x}", value))
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u32, D::Error> {
+            parse(&String::deserialize(deserializer)?).map_err(D::Error::custom)
+        }
+    }
+
+    pub mod u8_value {
+        use super::*;
+
+        pub fn serialize<S: Serializer>(value: &u8, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_str(&format!("{:#04x}", value))
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u8, D::Error> {
+            parse_u8(&String::deserialize(deserializer)?).map_err(D::Error::custom)
+        }
+    }
+
+    pub mod u32_array {
+        use super::*;
+        use serde::ser::SerializeSeq;
+
+        pub fn serialize<S: Serializer>(
+            values: &[u32; 32],
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            let mut seq = serializer.serialize_seq(Some(values.len()))?;
+            for value in values {
+                seq.serialize_element(&format!("{:#010x}", value))?;
+            }
+            seq.end()
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<[u32; 32], D::Error> {
+            let values = Vec::<String>::deserialize(deserializer)?
+                .iter()
+                .map(|s| parse(s).map_err(D::Error::custom))
+                .collect::<Result<Vec<_>, _>>()?;
+            let len = values.len();
+            values
+                .try_into()
+                .map_err(|_| D::Error::invalid_length(len, &"32 registers"))
+        }
+    }
+
+    pub mod bytes {
+        use super::*;
+
+        pub fn serialize<S: Serializer>(values: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_seq(values.iter().map(|value| format!("{:#04x}", value)))
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Vec<u8>, D::Error> {
+            parse_vec(deserializer)
+        }
+    }
+
+    pub mod hash {
+        use super::*;
+
+        pub fn serialize<S: Serializer>(
+            values: &[u8; 32],
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            super::bytes::serialize(values, serializer)
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<[u8; 32], D::Error> {
+            let values = parse_vec(deserializer)?;
+            let len = values.len();
+            values
+                .try_into()
+                .map_err(|_| D::Error::invalid_length(len, &"32 bytes"))
+        }
+    }
+}
@@ -0,0 +1,30 @@
+//! Exporter serving the metrics of the process on `/metrics` in the Prometheus text format.
+//!
+//! Once it runs, everything recorded with the `metrics` macros is exported, i.e. the `sql`
+//! histograms of the storage, the durations of the witnesses and proofs of the circuit and
+//! the lag and failures of the pipeline.
+
+// Built-in deps
+use std::net::SocketAddr;
+// External uses
+use metrics_exporter_prometheus::PrometheusBuilder;
+use tokio::task::JoinHandle;
+
+/// Installs the Prometheus recorder and spawns the exporter listening on `port`.
+/// Must be called from a Tokio runtime, at most once per process.
+pub fn run_prometheus_exporter(port: u16) -> JoinHandle<()> {
+    let addr = SocketAddr::from(([0, 0, 0, 0], port));
+    let (recorder, exporter) = PrometheusBuilder::new()
+        .listen_address(addr)
+        .build_with_exporter()
+        .expect("Failed to build the Prometheus exporter");
+    metrics::set_boxed_recorder(Box::new(recorder))
+        .expect("Failed to install the Prometheus recorder");
+    vlog::info!("Exporting the metrics on {}/metrics", addr);
+
+    tokio::spawn(async move {
+        if let Err(e) = exporter.await {
+            vlog::error!("Prometheus exporter stopped: {}", e);
+        }
+    })
+}
@@ -0,0 +1,74 @@
+-- Tables of the prover pipeline. `IF NOT EXISTS` lets the databases created
+-- before the migrations were introduced adopt them without losing data.
+
+CREATE TABLE IF NOT EXISTS f_traces
+(
+    f_id           bigserial PRIMARY KEY,
+    f_trace        jsonb                    NOT NULL,
+    f_created_at   TIMESTAMP with time zone NOT NULL DEFAULT now()
+);
+
+CREATE TABLE IF NOT EXISTS t_block_witness_cloud
+(
+    f_id             bigserial PRIMARY KEY,
+    f_block          BIGINT NOT NULL,
+    f_version        BIGINT NOT NULL,
+    f_object_key     text   NOT NULL,
+    f_object_witness text   NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS t_prover_job_queue_cloud
+(
+    f_id           bigserial PRIMARY KEY,
+    f_job_status   INTEGER                  NOT NULL,
+    f_job_priority INTEGER                  NOT NULL,
+    f_job_type     TEXT                     NOT NULL,
+    f_created_at   timestamp with time zone NOT NULL DEFAULT now(),
+    f_version      BIGINT                   NOT NULL,
+    f_updated_by   TEXT                     NOT NULL,
+    f_updated_at   timestamp with time zone NOT NULL,
+    f_first_block  BIGINT                   NOT NULL,
+    f_last_block   BIGINT                   NOT NULL,
+    f_object_key   TEXT                     NOT NULL,
+    f_object_job   TEXT                     NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS t_block_failures
+(
+    f_id           bigserial PRIMARY KEY,
+    f_stage        TEXT                     NOT NULL,
+    f_block        BIGINT                   NOT NULL,
+    f_attempts     INTEGER                  NOT NULL,
+    f_last_error   TEXT                     NOT NULL,
+    f_dead         BOOLEAN                  NOT NULL,
+    f_updated_at   timestamp with time zone NOT NULL,
+    UNIQUE (f_stage, f_block)
+);
+
+CREATE TABLE IF NOT EXISTS t_proofs
+(
+    f_id           bigserial PRIMARY KEY,
+    f_block_number BIGINT                   NOT NULL,
+    f_proof        jsonb                    NOT NULL,
+    f_created_at   TIMESTAMP with time zone NOT NULL DEFAULT now()
+);
+
+-- Progress of the pipeline stages, a single row each.
+
+CREATE TABLE IF NOT EXISTS t_witness_block_number
+(
+    f_id           bigserial PRIMARY KEY,
+    f_block        BIGINT                   NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS t_proof_block_number
+(
+    f_id           bigserial PRIMARY KEY,
+    f_block        BIGINT                   NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS t_verified_proof_block_number
+(
+    f_id           bigserial PRIMARY KEY,
+    f_block        BIGINT                   NOT NULL
+);
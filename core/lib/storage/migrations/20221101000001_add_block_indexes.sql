@@ -0,0 +1,7 @@
+-- Witnesses and proofs are looked up by the first block of their batch.
+CREATE INDEX IF NOT EXISTS t_block_witness_cloud_f_block_idx ON t_block_witness_cloud (f_block);
+CREATE INDEX IF NOT EXISTS t_proofs_f_block_number_idx ON t_proofs (f_block_number);
+
+-- Provers take the idle jobs by priority.
+CREATE INDEX IF NOT EXISTS t_prover_job_queue_cloud_f_job_status_idx
+    ON t_prover_job_queue_cloud (f_job_status, f_job_priority DESC, f_id);
@@ -0,0 +1,38 @@
+-- Traces get explicit positions instead of relying on their serial ids:
+-- `f_block` orders them in the pipeline, while `f_program_id`, `f_segment`
+-- and `f_step` locate them in the run of the program they were taken from.
+ALTER TABLE f_traces ADD COLUMN f_block BIGINT;
+ALTER TABLE f_traces ADD COLUMN f_program_id TEXT;
+ALTER TABLE f_traces ADD COLUMN f_segment BIGINT;
+ALTER TABLE f_traces ADD COLUMN f_step BIGINT;
+
+-- The traces inserted by hand keep their position, as steps of a single run.
+UPDATE f_traces
+SET f_block = f_id, f_program_id = 'legacy', f_segment = 0, f_step = f_id - 1;
+
+ALTER TABLE f_traces
+    ALTER COLUMN f_block SET NOT NULL,
+    ALTER COLUMN f_program_id SET NOT NULL,
+    ALTER COLUMN f_segment SET NOT NULL,
+    ALTER COLUMN f_step SET NOT NULL;
+
+CREATE UNIQUE INDEX f_traces_f_block_idx ON f_traces (f_block);
+CREATE UNIQUE INDEX f_traces_f_program_id_idx ON f_traces (f_program_id, f_segment, f_step);
+
+-- Records inserted without a position, e.g. by the `mipsevm` of cannon-mips, are appended
+-- as steps of the legacy run. Concurrent inserts of this kind fail on the unique block.
+CREATE FUNCTION f_traces_append_legacy() RETURNS trigger AS $$
+BEGIN
+    IF NEW.f_block IS NULL THEN
+        NEW.f_block := (SELECT COALESCE(MAX(f_block), 0) + 1 FROM f_traces);
+        NEW.f_program_id := 'legacy';
+        NEW.f_segment := 0;
+        NEW.f_step := NEW.f_block - 1;
+    END IF;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER f_traces_append_legacy
+    BEFORE INSERT ON f_traces
+    FOR EACH ROW EXECUTE PROCEDURE f_traces_append_legacy();
@@ -0,0 +1,20 @@
+-- Registry of the circuits the pipeline runs with. A circuit is identified by the
+-- SHA-256 of its compiled program, and registered along with its ABI, its verification
+-- key and the verifier contract its proofs are submitted to.
+
+CREATE TABLE IF NOT EXISTS t_circuits
+(
+    f_id                 bigserial PRIMARY KEY,
+    f_program_hash       TEXT                     NOT NULL UNIQUE,
+    f_abi                jsonb                    NOT NULL,
+    f_verification_key   jsonb                    NOT NULL,
+    f_verifier_address   TEXT,
+    f_created_at         TIMESTAMP with time zone NOT NULL DEFAULT now()
+);
+
+-- The circuit every witness, job and proof was made with.
+-- The rows stored before the registry was introduced have none.
+
+ALTER TABLE t_block_witness_cloud ADD COLUMN IF NOT EXISTS f_circuit_id BIGINT REFERENCES t_circuits (f_id);
+ALTER TABLE t_prover_job_queue_cloud ADD COLUMN IF NOT EXISTS f_circuit_id BIGINT REFERENCES t_circuits (f_id);
+ALTER TABLE t_proofs ADD COLUMN IF NOT EXISTS f_circuit_id BIGINT REFERENCES t_circuits (f_id);
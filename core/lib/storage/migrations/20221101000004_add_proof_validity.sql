@@ -0,0 +1,4 @@
+-- Proofs rejected by the local verification of the verifier generator. They are kept
+-- for inspection, and the job of their batch is returned to the provers.
+
+ALTER TABLE t_proofs ADD COLUMN IF NOT EXISTS f_invalid BOOLEAN NOT NULL DEFAULT false;
@@ -0,0 +1,13 @@
+-- Proofs the verifier generator submitted together to the batch verifier contract,
+-- recorded once the contract accepted them.
+
+CREATE TABLE IF NOT EXISTS t_aggregated_proofs
+(
+    f_id           bigserial PRIMARY KEY,
+    f_first_block  BIGINT                   NOT NULL,
+    f_last_block   BIGINT                   NOT NULL,
+    f_proof_ids    BIGINT[]                 NOT NULL,
+    f_created_at   TIMESTAMP with time zone NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS t_aggregated_proofs_f_first_block_idx ON t_aggregated_proofs (f_first_block);
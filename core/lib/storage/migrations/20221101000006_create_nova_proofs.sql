@@ -0,0 +1,15 @@
+-- Compressed Nova proofs folding every trace record of a program run,
+-- made by the provers running in the Nova mode.
+
+CREATE TABLE IF NOT EXISTS t_nova_proofs
+(
+    f_id               bigserial PRIMARY KEY,
+    f_program_id       TEXT                     NOT NULL,
+    f_first_block      BIGINT                   NOT NULL,
+    f_last_block       BIGINT                   NOT NULL,
+    f_steps            BIGINT                   NOT NULL,
+    f_proof            jsonb                    NOT NULL,
+    f_created_at       TIMESTAMP with time zone NOT NULL DEFAULT now()
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS t_nova_proofs_f_program_id_idx ON t_nova_proofs (f_program_id);
@@ -0,0 +1,6 @@
+-- The witnesses are written compressed to the blob store, under f_object_key,
+-- and the table only keeps the SHA-256 of their content.
+-- The rows stored before keep their witness in hex in f_object_witness, with no hash.
+
+ALTER TABLE t_block_witness_cloud ADD COLUMN IF NOT EXISTS f_content_hash TEXT;
+ALTER TABLE t_block_witness_cloud ALTER COLUMN f_object_witness DROP NOT NULL;
@@ -0,0 +1,15 @@
+-- Leases of the program runs proven in the Nova mode, like the Groth16 job queue:
+-- a run is proven by one prover at a time, retried after a backoff when its prover
+-- fails it, and skipped once it failed too many times.
+-- f_status takes the numbers of ProverJobStatus. f_updated_at is the last heartbeat of
+-- the prover of a run in progress, and the time an idle run can be leased again.
+
+CREATE TABLE IF NOT EXISTS t_nova_runs
+(
+    f_program_id   TEXT PRIMARY KEY,
+    f_status       INTEGER                  NOT NULL,
+    f_updated_by   TEXT                     NOT NULL,
+    f_updated_at   timestamp with time zone NOT NULL,
+    f_attempts     INTEGER                  NOT NULL DEFAULT 0,
+    f_last_error   TEXT
+);
@@ -0,0 +1,15 @@
+-- The jobs queued before the registry was introduced have no circuit, so no prover can
+-- tell whether its circuit is the one of their witness, and they aren't leased anymore.
+-- The unfinished ones are dead-lettered like the jobs that failed too many times, so that
+-- the verifier generator skips their batch instead of waiting for its proof.
+
+INSERT INTO t_block_failures (f_stage, f_block, f_attempts, f_last_error, f_dead, f_updated_at)
+SELECT 'PROOF', f_first_block, 0, 'Job queued without a circuit', TRUE, now()
+FROM t_prover_job_queue_cloud
+WHERE f_circuit_id IS NULL AND f_job_status IN (0, 1)
+ON CONFLICT (f_stage, f_block) DO UPDATE
+SET f_dead = TRUE, f_last_error = EXCLUDED.f_last_error, f_updated_at = EXCLUDED.f_updated_at;
+
+UPDATE t_prover_job_queue_cloud
+SET f_job_status = 3, f_updated_by = 'server_fail_job', f_updated_at = now(), f_version = f_version + 1
+WHERE f_circuit_id IS NULL AND f_job_status IN (0, 1);
@@ -134,6 +134,12 @@ impl<'a> StorageProcessor<'a> {
         }
     }
 
+    /// Applies the migrations from `core/lib/storage/migrations` that haven't been applied yet.
+    pub async fn run_migrations(&mut self) -> QueryResult<()> {
+        sqlx::migrate!("./migrations").run(self.conn()).await?;
+        Ok(())
+    }
+
     /// Gains access to the `Chain` schemas.
     pub fn chain(&mut self) -> chain::ChainIntermediator<'_, 'a> {
         chain::ChainIntermediator(self)
@@ -1,12 +1,12 @@
 // Built-in deps
-use std::time::Instant;
+use std::time::{Duration, Instant};
 // External imports
 use anyhow::format_err;
 // Workspace imports
 use types::BlockNumber;
 // Local imports
 use crate::{QueryResult, StorageProcessor};
-use crate::prover::records::{StorageBlockWitnessCloud, StoredProof, StorageTrace, StorageWitnessBlockNumber, StorageProofBlockNumber};
+use crate::prover::records::{NewTrace, StorageBlockFailure, StorageBlockWitnessCloud, StorageCircuit, StorageNovaRun, StoredAggregatedProof, StoredNovaProof, StoredProof, StorageTrace, StorageWitnessBlockNumber, StorageProofBlockNumber, StorageProverJobQueueCloud};
 
 pub mod records;
 
@@ -15,6 +15,8 @@ pub enum ProverJobStatus {
     Idle = 0,
     InProgress = 1,
     Done = 2,
+    /// The job failed too many times and won't be given to provers anymore.
+    Failed = 3,
 }
 
 impl ProverJobStatus {
@@ -23,6 +25,7 @@ impl ProverJobStatus {
             ProverJobStatus::Idle => 0,
             ProverJobStatus::InProgress => 1,
             ProverJobStatus::Done => 2,
+            ProverJobStatus::Failed => 3,
         }
     }
 
@@ -31,6 +34,7 @@ impl ProverJobStatus {
             0 => Self::Idle,
             1 => Self::InProgress,
             2 => Self::Done,
+            3 => Self::Failed,
             _ => anyhow::bail!("Incorrect ProverJobStatus number: {}", num),
         })
     }
@@ -39,14 +43,30 @@ impl ProverJobStatus {
 #[derive(Debug, Clone)]
 pub enum ProverJobType {
     SingleProof,
-    AggregatedProof,
 }
 
 impl ToString for ProverJobType {
     fn to_string(&self) -> String {
         match self {
             ProverJobType::SingleProof => String::from("SINGLE_PROOF"),
-            ProverJobType::AggregatedProof => String::from("AGGREGATED_PROOF"),
+        }
+    }
+}
+
+/// Stage of the pipeline a block failed in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PipelineStage {
+    Witness,
+    Proof,
+    Verification,
+}
+
+impl ToString for PipelineStage {
+    fn to_string(&self) -> String {
+        match self {
+            PipelineStage::Witness => String::from("WITNESS"),
+            PipelineStage::Proof => String::from("PROOF"),
+            PipelineStage::Verification => String::from("VERIFICATION"),
         }
     }
 }
@@ -189,7 +209,7 @@ impl<'a, 'c> ProverSchema<'a, 'c> {
 
         let trace = sqlx::query_as!(
             StorageTrace,
-            "SELECT * FROM f_traces WHERE f_id = $1",
+            "SELECT * FROM f_traces WHERE f_block = $1",
             i64::from(*block),
         )
             .fetch_optional(self.0.conn())
@@ -205,23 +225,435 @@ impl<'a, 'c> ProverSchema<'a, 'c> {
         return Ok(None);
     }
 
-    /// Stores witness for a block
+    /// Loads `count` consecutive mips traces starting from `first_block`.
+    /// Returns `None` until all of them are available.
+    pub async fn load_traces(
+        &mut self,
+        first_block: BlockNumber,
+        count: u32,
+    ) -> QueryResult<Option<Vec<serde_json::Value>>> {
+        let start = Instant::now();
+
+        let traces = sqlx::query_as!(
+            StorageTrace,
+            "SELECT * FROM f_traces WHERE f_block >= $1 AND f_block < $2 ORDER BY f_block",
+            i64::from(*first_block),
+            i64::from(*first_block) + i64::from(count),
+        )
+            .fetch_all(self.0.conn())
+            .await?;
+
+        metrics::histogram!("sql", start.elapsed(), "prover" => "load_traces");
+
+        if traces.len() < count as usize {
+            return Ok(None);
+        }
+        Ok(Some(traces.into_iter().map(|t| t.f_trace).collect()))
+    }
+
+    /// Appends the trace records of the program `program_id` after the stored ones.
+    /// Returns the blocks assigned to the first and the last of them.
+    pub async fn store_traces(
+        &mut self,
+        program_id: &str,
+        traces: Vec<NewTrace>,
+    ) -> QueryResult<(BlockNumber, BlockNumber)> {
+        let start = Instant::now();
+        if traces.is_empty() {
+            return Err(format_err!("No traces to store"));
+        }
+        let mut transaction = self.0.start_transaction().await?;
+
+        // Keeps the blocks of concurrent imports from interleaving.
+        sqlx::query!("LOCK TABLE f_traces IN EXCLUSIVE MODE")
+            .execute(transaction.conn())
+            .await?;
+        let last_block = sqlx::query!(
+            r#"SELECT COALESCE(MAX(f_block), 0) as "block!" FROM f_traces"#,
+        )
+            .fetch_one(transaction.conn())
+            .await?
+            .block;
+
+        let first_block = last_block + 1;
+        let count = traces.len() as i64;
+        for (block, trace) in (first_block..).zip(traces) {
+            sqlx::query!(
+                r#"
+                INSERT INTO f_traces (f_block, f_program_id, f_segment, f_step, f_trace)
+                VALUES ($1, $2, $3, $4, $5)
+                "#,
+                block,
+                program_id,
+                trace.segment,
+                trace.step,
+                trace.trace,
+            )
+                .execute(transaction.conn())
+                .await?;
+        }
+
+        transaction.commit().await?;
+        metrics::histogram!("sql", start.elapsed(), "prover" => "store_traces");
+
+        Ok((
+            BlockNumber(first_block as u32),
+            BlockNumber((first_block + count - 1) as u32),
+        ))
+    }
+
+    /// Adds a job proving the traces from `first_block` to `last_block` inclusive
+    /// with the witness stored under `object_key`, made with the circuit `circuit_id`.
+    pub async fn add_prover_job_to_job_queue(
+        &mut self,
+        first_block: BlockNumber,
+        last_block: BlockNumber,
+        object_key: String,
+        object_job: String,
+        circuit_id: i64,
+    ) -> QueryResult<()> {
+        let start = Instant::now();
+
+        sqlx::query!(
+            r#"
+            INSERT INTO t_prover_job_queue_cloud (f_job_status, f_job_priority, f_job_type, f_version, f_updated_by, f_updated_at, f_first_block, f_last_block, f_object_key, f_object_job, f_circuit_id)
+            VALUES ($1, 0, $2, 0, 'witness_generator', now(), $3, $4, $5, $6, $7)
+            "#,
+            ProverJobStatus::Idle.to_number(),
+            ProverJobType::SingleProof.to_string(),
+            i64::from(*first_block),
+            i64::from(*last_block),
+            object_key,
+            object_job,
+            circuit_id,
+        )
+            .execute(self.0.conn())
+            .await?;
+
+        metrics::histogram!("sql", start.elapsed(), "prover" => "add_prover_job_to_job_queue");
+        Ok(())
+    }
+
+    /// Leases the idle job with the highest priority to `prover_name`, which proves with the circuit `circuit_id`.
+    /// Jobs leased by other provers are skipped, so concurrent provers never get the same job.
+    /// Jobs of other circuits are skipped too, the ones stored before the registry was introduced
+    /// have been dead-lettered as their circuit is unknown.
+    pub async fn get_idle_prover_job_from_job_queue(
+        &mut self,
+        prover_name: &str,
+        circuit_id: i64,
+    ) -> QueryResult<Option<StorageProverJobQueueCloud>> {
+        let start = Instant::now();
+        let mut transaction = self.0.start_transaction().await?;
+
+        let job = sqlx::query_as!(
+            StorageProverJobQueueCloud,
+            r#"
+            SELECT * FROM t_prover_job_queue_cloud
+            WHERE f_job_status = $1 AND f_updated_at <= now()
+                AND f_circuit_id = $2
+            ORDER BY f_job_priority DESC, f_id ASC
+            LIMIT 1
+            FOR UPDATE SKIP LOCKED
+            "#,
+            ProverJobStatus::Idle.to_number(),
+            circuit_id,
+        )
+            .fetch_optional(transaction.conn())
+            .await?;
+
+        let job = match job {
+            Some(job) => sqlx::query_as!(
+                StorageProverJobQueueCloud,
+                r#"
+                UPDATE t_prover_job_queue_cloud
+                SET f_job_status = $1, f_updated_by = $2, f_updated_at = now(), f_version = f_version + 1
+                WHERE f_id = $3
+                RETURNING *
+                "#,
+                ProverJobStatus::InProgress.to_number(),
+                prover_name,
+                job.f_id,
+            )
+                .fetch_optional(transaction.conn())
+                .await?,
+            None => None,
+        };
+
+        transaction.commit().await?;
+        metrics::histogram!("sql", start.elapsed(), "prover" => "get_idle_prover_job_from_job_queue");
+        Ok(job)
+    }
+
+    /// Returns the job `job_id` of the queue.
+    pub async fn load_prover_job(
+        &mut self,
+        job_id: i64,
+    ) -> QueryResult<Option<StorageProverJobQueueCloud>> {
+        let start = Instant::now();
+
+        let job = sqlx::query_as!(
+            StorageProverJobQueueCloud,
+            "SELECT * FROM t_prover_job_queue_cloud WHERE f_id = $1",
+            job_id,
+        )
+            .fetch_optional(self.0.conn())
+            .await?;
+
+        metrics::histogram!("sql", start.elapsed(), "prover" => "load_prover_job");
+        Ok(job)
+    }
+
+    /// Extends the lease of `prover_name` on the job.
+    /// Returns `false` if the job has been given to another prover in the meantime.
+    pub async fn record_prover_is_working(
+        &mut self,
+        job_id: i64,
+        prover_name: &str,
+    ) -> QueryResult<bool> {
+        let start = Instant::now();
+
+        let updated_rows = sqlx::query!(
+            r#"
+            UPDATE t_prover_job_queue_cloud
+            SET f_updated_at = now()
+            WHERE f_id = $1 AND f_updated_by = $2 AND f_job_status = $3
+            "#,
+            job_id,
+            prover_name,
+            ProverJobStatus::InProgress.to_number(),
+        )
+            .execute(self.0.conn())
+            .await?
+            .rows_affected();
+
+        metrics::histogram!("sql", start.elapsed(), "prover" => "record_prover_is_working");
+        Ok(updated_rows == 1)
+    }
+
+    /// Returns the job leased by `prover_name` to the queue, e.g. because the prover failed.
+    /// The job can't be leased again before `delay` passes.
+    /// Returns `false` if the job isn't leased by `prover_name`.
+    pub async fn release_prover_job(
+        &mut self,
+        job_id: i64,
+        prover_name: &str,
+        delay: Duration,
+    ) -> QueryResult<bool> {
+        let start = Instant::now();
+
+        let updated_rows = sqlx::query!(
+            r#"
+            UPDATE t_prover_job_queue_cloud
+            SET f_job_status = $1, f_updated_by = 'server_release_job', f_updated_at = now() + make_interval(secs => $5)
+            WHERE f_id = $2 AND f_updated_by = $3 AND f_job_status = $4
+            "#,
+            ProverJobStatus::Idle.to_number(),
+            job_id,
+            prover_name,
+            ProverJobStatus::InProgress.to_number(),
+            delay.as_secs_f64(),
+        )
+            .execute(self.0.conn())
+            .await?
+            .rows_affected();
+
+        metrics::histogram!("sql", start.elapsed(), "prover" => "release_prover_job");
+        Ok(updated_rows == 1)
+    }
+
+    /// Moves the job leased by `prover_name` to the dead letter queue.
+    /// Returns `false` if the job isn't leased by `prover_name`.
+    pub async fn fail_prover_job(&mut self, job_id: i64, prover_name: &str) -> QueryResult<bool> {
+        let start = Instant::now();
+
+        let updated_rows = sqlx::query!(
+            r#"
+            UPDATE t_prover_job_queue_cloud
+            SET f_job_status = $1, f_updated_by = 'server_fail_job', f_updated_at = now()
+            WHERE f_id = $2 AND f_updated_by = $3 AND f_job_status = $4
+            "#,
+            ProverJobStatus::Failed.to_number(),
+            job_id,
+            prover_name,
+            ProverJobStatus::InProgress.to_number(),
+        )
+            .execute(self.0.conn())
+            .await?
+            .rows_affected();
+
+        metrics::histogram!("sql", start.elapsed(), "prover" => "fail_prover_job");
+        Ok(updated_rows == 1)
+    }
+
+    /// Counts a failed attempt of `stage` on the batch starting at `block`.
+    /// The batch is dead-lettered once `max_attempts` attempts have failed.
+    pub async fn record_block_failure(
+        &mut self,
+        stage: PipelineStage,
+        block: BlockNumber,
+        error: &str,
+        max_attempts: u32,
+    ) -> QueryResult<StorageBlockFailure> {
+        let start = Instant::now();
+
+        let failure = sqlx::query_as!(
+            StorageBlockFailure,
+            r#"
+            INSERT INTO t_block_failures (f_stage, f_block, f_attempts, f_last_error, f_dead, f_updated_at)
+            VALUES ($1, $2, 1, $3, $4 <= 1, now())
+            ON CONFLICT (f_stage, f_block) DO UPDATE
+            SET f_attempts = t_block_failures.f_attempts + 1,
+                f_last_error = $3,
+                f_dead = t_block_failures.f_attempts + 1 >= $4,
+                f_updated_at = now()
+            RETURNING *
+            "#,
+            stage.to_string(),
+            i64::from(*block),
+            error,
+            max_attempts as i32,
+        )
+            .fetch_one(self.0.conn())
+            .await?;
+
+        metrics::histogram!("sql", start.elapsed(), "prover" => "record_block_failure");
+        Ok(failure)
+    }
+
+    /// Loads the failures of `stage` on the batch starting at `block`.
+    pub async fn load_block_failure(
+        &mut self,
+        stage: PipelineStage,
+        block: BlockNumber,
+    ) -> QueryResult<Option<StorageBlockFailure>> {
+        let start = Instant::now();
+
+        let failure = sqlx::query_as!(
+            StorageBlockFailure,
+            "SELECT * FROM t_block_failures WHERE f_stage = $1 AND f_block = $2",
+            stage.to_string(),
+            i64::from(*block),
+        )
+            .fetch_optional(self.0.conn())
+            .await?;
+
+        metrics::histogram!("sql", start.elapsed(), "prover" => "load_block_failure");
+        Ok(failure)
+    }
+
+    /// Returns to the queue the jobs whose provers didn't report for `gone_timeout`.
+    pub async fn mark_stale_jobs_as_idle(&mut self, gone_timeout: Duration) -> QueryResult<u64> {
+        let start = Instant::now();
+
+        let updated_rows = sqlx::query!(
+            r#"
+            UPDATE t_prover_job_queue_cloud
+            SET f_job_status = $1, f_updated_by = 'server_clean_idle', f_updated_at = now()
+            WHERE f_job_status = $2 AND f_updated_at < now() - make_interval(secs => $3)
+            "#,
+            ProverJobStatus::Idle.to_number(),
+            ProverJobStatus::InProgress.to_number(),
+            gone_timeout.as_secs_f64(),
+        )
+            .execute(self.0.conn())
+            .await?
+            .rows_affected();
+
+        metrics::histogram!("sql", start.elapsed(), "prover" => "mark_stale_jobs_as_idle");
+        Ok(updated_rows)
+    }
+
+    /// Returns the amount of prover jobs that are neither done nor dead-lettered.
+    pub async fn pending_jobs_count(&mut self) -> QueryResult<u32> {
+        let start = Instant::now();
+
+        let count = sqlx::query!(
+            r#"SELECT COUNT(*) as "count!" FROM t_prover_job_queue_cloud WHERE f_job_status IN ($1, $2)"#,
+            ProverJobStatus::Idle.to_number(),
+            ProverJobStatus::InProgress.to_number(),
+        )
+            .fetch_one(self.0.conn())
+            .await?
+            .count;
+
+        metrics::histogram!("sql", start.elapsed(), "prover" => "pending_jobs_count");
+        Ok(count as u32)
+    }
+
+    /// Returns the amount of traces covered by the prover jobs that are neither done nor dead-lettered.
+    pub async fn pending_proof_blocks_count(&mut self) -> QueryResult<u32> {
+        let start = Instant::now();
+
+        let count = sqlx::query!(
+            r#"
+            SELECT COALESCE(SUM(f_last_block - f_first_block + 1), 0)::BIGINT as "count!" FROM t_prover_job_queue_cloud
+            WHERE f_job_status IN ($1, $2)
+            "#,
+            ProverJobStatus::Idle.to_number(),
+            ProverJobStatus::InProgress.to_number(),
+        )
+            .fetch_one(self.0.conn())
+            .await?
+            .count;
+
+        metrics::histogram!("sql", start.elapsed(), "prover" => "pending_proof_blocks_count");
+        Ok(count as u32)
+    }
+
+    /// Returns the block of the last stored trace, 0 if there is none.
+    pub async fn load_last_trace_block_number(&mut self) -> QueryResult<i64> {
+        let start = Instant::now();
+
+        let block = sqlx::query!(
+            r#"SELECT COALESCE(MAX(f_block), 0) as "block!" FROM f_traces"#,
+        )
+            .fetch_one(self.0.conn())
+            .await?
+            .block;
+
+        metrics::histogram!("sql", start.elapsed(), "prover" => "load_last_trace_block_number");
+        Ok(block)
+    }
+
+    /// Returns the amount of stored traces the witness generator hasn't reached yet.
+    pub async fn pending_witness_traces_count(&mut self) -> QueryResult<u32> {
+        let start = Instant::now();
+
+        let count = sqlx::query!(
+            r#"
+            SELECT COUNT(*) as "count!" FROM f_traces
+            WHERE f_block >= COALESCE((SELECT MAX(f_block) FROM t_witness_block_number), 1)
+            "#,
+        )
+            .fetch_one(self.0.conn())
+            .await?
+            .count;
+
+        metrics::histogram!("sql", start.elapsed(), "prover" => "pending_witness_traces_count");
+        Ok(count as u32)
+    }
+
+    /// Records the witness for a block, computed with the circuit `circuit_id`.
+    /// The witness itself is stored in the blob store under `object_key`.
     pub async fn store_witness(
         &mut self,
         block: BlockNumber,
-        witness_str: String,
+        object_key: &str,
+        content_hash: &str,
+        circuit_id: i64,
     ) -> QueryResult<()> {
         let start = Instant::now();
-        let key = format!("{}", block);
 
         sqlx::query!(
             r#"
-            INSERT INTO t_block_witness_cloud (f_block, f_version, f_object_key, f_object_witness)
-            VALUES($1, 0, $2, $3)
+            INSERT INTO t_block_witness_cloud (f_block, f_version, f_object_key, f_content_hash, f_circuit_id)
+            VALUES($1, 0, $2, $3, $4)
             "#,
             i64::from(*block),
-            key,
-            witness_str
+            object_key,
+            content_hash,
+            circuit_id,
         )
             .execute(self.0.conn())
             .await?;
@@ -230,11 +662,11 @@ impl<'a, 'c> ProverSchema<'a, 'c> {
         Ok(())
     }
 
-    /// Gets stored witness for a block.
+    /// Gets the stored witness record for a block.
     pub async fn get_witness(
         &mut self,
         block_number: BlockNumber,
-    ) -> QueryResult<Option<String>> {
+    ) -> QueryResult<Option<StorageBlockWitnessCloud>> {
         let start = Instant::now();
         let block_witness = sqlx::query_as!(
             StorageBlockWitnessCloud,
@@ -245,49 +677,49 @@ impl<'a, 'c> ProverSchema<'a, 'c> {
             .await?;
 
         metrics::histogram!("sql", start.elapsed(), "prover" => "get_witness");
-        if let Some(w) = block_witness {
-            let witness_string: String = w.f_object_witness;
-
-            return Ok(Some(witness_string));
-        }
-        return Ok(None);
+        Ok(block_witness)
     }
 
-    /// Stores the proof for a block.
+    /// Stores the proof of the job leased by `prover_name` and marks the job as done.
+    /// The proof is recorded under the first block and with the circuit of its job.
+    /// Returns `false` if the job isn't in progress under the lease of `prover_name`.
     pub async fn store_proof(
         &mut self,
-        job_id: i32,
-        block_number: BlockNumber,
+        job_id: i64,
+        prover_name: &str,
         proof: serde_json::Value,
-    ) -> QueryResult<()> {
+    ) -> QueryResult<bool> {
         let start = Instant::now();
         let mut transaction = self.0.start_transaction().await?;
 
-//         let updated_rows = sqlx::query!(
-//     r#"
-// UPDATE t_prover_job_queue_cloud
-//             SET f_updated_at = now(), f_job_status = $1, f_updated_by = 'server_finish_job'
-//             WHERE f_id = $2 AND f_job_type = $3
-//     "#,
-//             ProverJobStatus::Done.to_number(),
-//             job_id as i64,
-//             ProverJobType::SingleProof.to_string()
-// )
-//             .execute(transaction.conn())
-//             .await?
-//             .rows_affected();
-//
-//         if updated_rows != 1 {
-//             return Err(format_err!("Missing job for stored proof"));
-//         }
+        let job = sqlx::query!(
+    r#"
+UPDATE t_prover_job_queue_cloud
+            SET f_updated_at = now(), f_job_status = $1, f_updated_by = 'server_finish_job'
+            WHERE f_id = $2 AND f_job_type = $3 AND f_updated_by = $4 AND f_job_status = $5
+            RETURNING f_first_block, f_circuit_id
+    "#,
+            ProverJobStatus::Done.to_number(),
+            job_id,
+            ProverJobType::SingleProof.to_string(),
+            prover_name,
+            ProverJobStatus::InProgress.to_number()
+)
+            .fetch_optional(transaction.conn())
+            .await?;
+        let job = match job {
+            Some(job) => job,
+            None => return Ok(false),
+        };
 
         sqlx::query!(
     r#"
-INSERT INTO t_proofs (f_block_number, f_proof)
-            VALUES ($1, $2)
+INSERT INTO t_proofs (f_block_number, f_proof, f_circuit_id)
+            VALUES ($1, $2, $3)
     "#,
-            i64::from(*block_number),
-            proof
+            job.f_first_block,
+            proof,
+            job.f_circuit_id
 )
             .execute(transaction.conn())
             .await?;
@@ -295,10 +727,10 @@ INSERT INTO t_proofs (f_block_number, f_proof)
         transaction.commit().await?;
         metrics::histogram!("sql", start.elapsed(), "prover" => "store_proof");
 
-        Ok(())
+        Ok(true)
     }
 
-    /// Gets the stored proof for a block.
+    /// Gets the stored proof for a block, the invalid ones are ignored.
     pub async fn load_proof(
         &mut self,
         block_number: BlockNumber,
@@ -307,7 +739,7 @@ INSERT INTO t_proofs (f_block_number, f_proof)
 
         let proof = sqlx::query_as!(
         StoredProof,
-            "SELECT * FROM t_proofs WHERE f_block_number = $1",
+            "SELECT * FROM t_proofs WHERE f_block_number = $1 AND NOT f_invalid ORDER BY f_id DESC LIMIT 1",
             i64::from(*block_number),
 )
             .fetch_optional(self.0.conn())
@@ -316,4 +748,357 @@ INSERT INTO t_proofs (f_block_number, f_proof)
         metrics::histogram!("sql", start.elapsed(), "prover" => "load_proof");
         Ok(proof)
     }
-}
\ No newline at end of file
+
+    /// Records the proofs with the ids `proof_ids`, of the batches from `first_block` to `last_block`,
+    /// as submitted together.
+    pub async fn store_aggregated_proof(
+        &mut self,
+        first_block: BlockNumber,
+        last_block: BlockNumber,
+        proof_ids: &[i64],
+    ) -> QueryResult<StoredAggregatedProof> {
+        let start = Instant::now();
+
+        let aggregated_proof = sqlx::query_as!(
+        StoredAggregatedProof,
+    r#"
+INSERT INTO t_aggregated_proofs (f_first_block, f_last_block, f_proof_ids)
+            VALUES ($1, $2, $3)
+            RETURNING *
+    "#,
+            i64::from(*first_block),
+            i64::from(*last_block),
+            proof_ids,
+)
+            .fetch_one(self.0.conn())
+            .await?;
+
+        metrics::histogram!("sql", start.elapsed(), "prover" => "store_aggregated_proof");
+        Ok(aggregated_proof)
+    }
+
+    /// Leases to `prover_name` the first run that has exited and has no Nova proof yet.
+    /// The runs failed too many times, leased to a prover or waiting for their backoff are
+    /// skipped, unless their prover didn't report for `gone_timeout`.
+    pub async fn lease_next_unproven_run(
+        &mut self,
+        prover_name: &str,
+        gone_timeout: Duration,
+    ) -> QueryResult<Option<StorageNovaRun>> {
+        let start = Instant::now();
+        let mut transaction = self.0.start_transaction().await?;
+
+        let program_id = sqlx::query!(
+            r#"
+            SELECT t.f_program_id FROM f_traces t
+            LEFT JOIN t_nova_runs r ON r.f_program_id = t.f_program_id
+            WHERE (t.f_trace->>'newExited')::boolean
+                AND NOT EXISTS (SELECT 1 FROM t_nova_proofs p WHERE p.f_program_id = t.f_program_id)
+                AND (r.f_program_id IS NULL
+                    OR (r.f_status = $1 AND r.f_updated_at <= now())
+                    OR (r.f_status = $2 AND r.f_updated_at < now() - make_interval(secs => $3)))
+            ORDER BY t.f_block
+            LIMIT 1
+            FOR UPDATE OF t SKIP LOCKED
+            "#,
+            ProverJobStatus::Idle.to_number(),
+            ProverJobStatus::InProgress.to_number(),
+            gone_timeout.as_secs_f64(),
+        )
+            .fetch_optional(transaction.conn())
+            .await?
+            .map(|row| row.f_program_id);
+
+        let run = match program_id {
+            Some(program_id) => Some(sqlx::query_as!(
+                StorageNovaRun,
+                r#"
+                INSERT INTO t_nova_runs (f_program_id, f_status, f_updated_by, f_updated_at)
+                VALUES ($1, $2, $3, now())
+                ON CONFLICT (f_program_id) DO UPDATE
+                SET f_status = $2, f_updated_by = $3, f_updated_at = now()
+                RETURNING *
+                "#,
+                program_id,
+                ProverJobStatus::InProgress.to_number(),
+                prover_name,
+            )
+                .fetch_one(transaction.conn())
+                .await?),
+            None => None,
+        };
+
+        transaction.commit().await?;
+        metrics::histogram!("sql", start.elapsed(), "prover" => "lease_next_unproven_run");
+        Ok(run)
+    }
+
+    /// Extends the lease of `prover_name` on the run of `program_id`.
+    /// Returns `false` if the run has been given to another prover in the meantime.
+    pub async fn record_nova_run_is_working(
+        &mut self,
+        program_id: &str,
+        prover_name: &str,
+    ) -> QueryResult<bool> {
+        let start = Instant::now();
+
+        let updated_rows = sqlx::query!(
+            r#"
+            UPDATE t_nova_runs
+            SET f_updated_at = now()
+            WHERE f_program_id = $1 AND f_updated_by = $2 AND f_status = $3
+            "#,
+            program_id,
+            prover_name,
+            ProverJobStatus::InProgress.to_number(),
+        )
+            .execute(self.0.conn())
+            .await?
+            .rows_affected();
+
+        metrics::histogram!("sql", start.elapsed(), "prover" => "record_nova_run_is_working");
+        Ok(updated_rows == 1)
+    }
+
+    /// Counts a failed attempt of `prover_name` at the run of `program_id` and returns the run
+    /// to the queue, to be leased again after `retry_after`. The run is skipped for good once
+    /// `max_attempts` attempts have failed. Returns `None` if `prover_name` lost the lease.
+    pub async fn fail_nova_run(
+        &mut self,
+        program_id: &str,
+        prover_name: &str,
+        error: &str,
+        max_attempts: u32,
+        retry_after: Duration,
+    ) -> QueryResult<Option<StorageNovaRun>> {
+        let start = Instant::now();
+
+        let run = sqlx::query_as!(
+            StorageNovaRun,
+            r#"
+            UPDATE t_nova_runs
+            SET f_attempts = f_attempts + 1,
+                f_last_error = $3,
+                f_status = CASE WHEN f_attempts + 1 >= $4 THEN $5 ELSE $6 END,
+                f_updated_by = 'server_release_run',
+                f_updated_at = now() + make_interval(secs => $7)
+            WHERE f_program_id = $1 AND f_updated_by = $2 AND f_status = $8
+            RETURNING *
+            "#,
+            program_id,
+            prover_name,
+            error,
+            max_attempts as i32,
+            ProverJobStatus::Failed.to_number(),
+            ProverJobStatus::Idle.to_number(),
+            retry_after.as_secs_f64(),
+            ProverJobStatus::InProgress.to_number(),
+        )
+            .fetch_optional(self.0.conn())
+            .await?;
+
+        metrics::histogram!("sql", start.elapsed(), "prover" => "fail_nova_run");
+        Ok(run)
+    }
+
+    /// Loads the trace records of the program `program_id`, in the order of its run.
+    pub async fn load_program_traces(
+        &mut self,
+        program_id: &str,
+    ) -> QueryResult<Vec<StorageTrace>> {
+        let start = Instant::now();
+
+        let traces = sqlx::query_as!(
+            StorageTrace,
+            "SELECT * FROM f_traces WHERE f_program_id = $1 ORDER BY f_segment, f_step",
+            program_id,
+        )
+            .fetch_all(self.0.conn())
+            .await?;
+
+        metrics::histogram!("sql", start.elapsed(), "prover" => "load_program_traces");
+        Ok(traces)
+    }
+
+    /// Stores the Nova proof of the run of `program_id`, covering the traces from `first_block` to `last_block`.
+    /// Returns `false` if the run has been proven already, e.g. by another prover.
+    pub async fn store_nova_proof(
+        &mut self,
+        program_id: &str,
+        first_block: BlockNumber,
+        last_block: BlockNumber,
+        steps: i64,
+        proof: serde_json::Value,
+    ) -> QueryResult<bool> {
+        let start = Instant::now();
+
+        let stored_rows = sqlx::query!(
+            r#"
+            INSERT INTO t_nova_proofs (f_program_id, f_first_block, f_last_block, f_steps, f_proof)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT (f_program_id) DO NOTHING
+            "#,
+            program_id,
+            i64::from(*first_block),
+            i64::from(*last_block),
+            steps,
+            proof,
+        )
+            .execute(self.0.conn())
+            .await?
+            .rows_affected();
+
+        metrics::histogram!("sql", start.elapsed(), "prover" => "store_nova_proof");
+        Ok(stored_rows > 0)
+    }
+
+    /// Loads the Nova proof of the run of `program_id`.
+    pub async fn load_nova_proof(
+        &mut self,
+        program_id: &str,
+    ) -> QueryResult<Option<StoredNovaProof>> {
+        let start = Instant::now();
+
+        let proof = sqlx::query_as!(
+            StoredNovaProof,
+            "SELECT * FROM t_nova_proofs WHERE f_program_id = $1",
+            program_id,
+        )
+            .fetch_optional(self.0.conn())
+            .await?;
+
+        metrics::histogram!("sql", start.elapsed(), "prover" => "load_nova_proof");
+        Ok(proof)
+    }
+
+    /// Registers the circuit compiled to the program with the hash `program_hash`, unless it is registered already.
+    /// Fails if the circuit is registered with another ABI, verification key or verifier address.
+    /// A circuit registered without a verifier address gets `verifier_address`.
+    pub async fn register_circuit(
+        &mut self,
+        program_hash: &str,
+        abi: serde_json::Value,
+        verification_key: serde_json::Value,
+        verifier_address: Option<&str>,
+    ) -> QueryResult<StorageCircuit> {
+        let start = Instant::now();
+        let mut transaction = self.0.start_transaction().await?;
+
+        sqlx::query!(
+            r#"
+            INSERT INTO t_circuits (f_program_hash, f_abi, f_verification_key, f_verifier_address)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (f_program_hash) DO NOTHING
+            "#,
+            program_hash,
+            abi,
+            verification_key,
+            verifier_address,
+        )
+            .execute(transaction.conn())
+            .await?;
+
+        let mut circuit = sqlx::query_as!(
+            StorageCircuit,
+            "SELECT * FROM t_circuits WHERE f_program_hash = $1 FOR UPDATE",
+            program_hash,
+        )
+            .fetch_one(transaction.conn())
+            .await?;
+
+        if circuit.f_abi != abi {
+            return Err(format_err!("Circuit {} is registered with another ABI", program_hash));
+        }
+        if circuit.f_verification_key != verification_key {
+            return Err(format_err!("Circuit {} is registered with another verification key", program_hash));
+        }
+        match (&circuit.f_verifier_address, verifier_address) {
+            (Some(registered), Some(address)) if !registered.eq_ignore_ascii_case(address) => {
+                return Err(format_err!(
+                    "Circuit {} is registered with the verifier {}, not {}",
+                    program_hash, registered, address
+                ));
+            }
+            (None, Some(address)) => {
+                sqlx::query!(
+                    "UPDATE t_circuits SET f_verifier_address = $1 WHERE f_id = $2",
+                    address,
+                    circuit.f_id,
+                )
+                    .execute(transaction.conn())
+                    .await?;
+                circuit.f_verifier_address = Some(address.to_string());
+            }
+            _ => {}
+        }
+
+        transaction.commit().await?;
+        metrics::histogram!("sql", start.elapsed(), "prover" => "register_circuit");
+
+        Ok(circuit)
+    }
+
+    /// Gets a registered circuit by the hash of its program.
+    pub async fn load_circuit_by_hash(
+        &mut self,
+        program_hash: &str,
+    ) -> QueryResult<Option<StorageCircuit>> {
+        let start = Instant::now();
+
+        let circuit = sqlx::query_as!(
+            StorageCircuit,
+            "SELECT * FROM t_circuits WHERE f_program_hash = $1",
+            program_hash,
+        )
+            .fetch_optional(self.0.conn())
+            .await?;
+
+        metrics::histogram!("sql", start.elapsed(), "prover" => "load_circuit_by_hash");
+        Ok(circuit)
+    }
+
+    /// Marks the proof for a block as invalid and returns the job of the block to the queue,
+    /// to be proven again after `retry_after`. The job is dead-lettered if `retry_after` is `None`.
+    /// Returns `false` if there is no valid proof for the block.
+    pub async fn reject_proof(
+        &mut self,
+        block_number: BlockNumber,
+        retry_after: Option<Duration>,
+    ) -> QueryResult<bool> {
+        let start = Instant::now();
+        let mut transaction = self.0.start_transaction().await?;
+
+        let rejected_rows = sqlx::query!(
+            r#"
+            UPDATE t_proofs SET f_invalid = true
+            WHERE f_block_number = $1 AND NOT f_invalid
+            "#,
+            i64::from(*block_number),
+        )
+            .execute(transaction.conn())
+            .await?
+            .rows_affected();
+
+        let status = match retry_after {
+            Some(_) => ProverJobStatus::Idle,
+            None => ProverJobStatus::Failed,
+        };
+        sqlx::query!(
+            r#"
+            UPDATE t_prover_job_queue_cloud
+            SET f_job_status = $1, f_updated_by = 'server_reject_proof', f_updated_at = now() + make_interval(secs => $2)
+            WHERE f_first_block = $3 AND f_job_status = $4
+            "#,
+            status.to_number(),
+            retry_after.unwrap_or_default().as_secs_f64(),
+            i64::from(*block_number),
+            ProverJobStatus::Done.to_number(),
+        )
+            .execute(transaction.conn())
+            .await?;
+
+        transaction.commit().await?;
+        metrics::histogram!("sql", start.elapsed(), "prover" => "reject_proof");
+        Ok(rejected_rows > 0)
+    }
+}
@@ -23,12 +23,30 @@ pub struct NewProof {
     pub proof: serde_json::Value,
 }
 
-#[derive(Debug, FromRow)]
+/// Proofs submitted together to the batch verifier contract.
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize, UtilsMacro)]
 pub struct StoredAggregatedProof {
-    pub first_block: i64,
-    pub last_block: i64,
-    pub proof: serde_json::Value,
-    pub created_at: DateTime<Utc>,
+    pub f_id: i64,
+    pub f_first_block: i64,
+    /// First block of the last proven batch.
+    pub f_last_block: i64,
+    /// Ids of the proofs in `t_proofs`, in block order.
+    pub f_proof_ids: Vec<i64>,
+    pub f_created_at: DateTime<Utc>,
+}
+
+/// Compressed Nova proof of a whole program run.
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize, UtilsMacro)]
+pub struct StoredNovaProof {
+    pub f_id: i64,
+    pub f_program_id: String,
+    /// Blocks of the first and the last trace records of the run.
+    pub f_first_block: i64,
+    pub f_last_block: i64,
+    pub f_steps: i64,
+    /// `circuit::nova::NovaProof`, with the initial and the final states.
+    pub f_proof: serde_json::Value,
+    pub f_created_at: DateTime<Utc>,
 }
 
 // Every time before a prover worker starts generating the proof, a prover run is recorded for monitoring purposes
@@ -72,7 +90,28 @@ pub struct StorageBlockWitnessCloud {
     pub f_block: i64,
     pub f_version: i64,
     pub f_object_key: String,
-    pub f_object_witness: String,
+    /// Witness in hex, only set for the rows stored before the blob store.
+    pub f_object_witness: Option<String>,
+    pub f_circuit_id: Option<i64>,
+    /// Hash of the witness stored in the blob store under `f_object_key`.
+    pub f_content_hash: Option<String>,
+}
+
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize, UtilsMacro)]
+pub struct StorageProverJobQueueCloud {
+    pub f_id: i64,
+    pub f_job_status: i32,
+    pub f_job_priority: i32,
+    pub f_job_type: String,
+    pub f_created_at: DateTime<Utc>,
+    pub f_version: i64,
+    pub f_updated_by: String,
+    pub f_updated_at: DateTime<Utc>,
+    pub f_first_block: i64,
+    pub f_last_block: i64,
+    pub f_object_key: String,
+    pub f_object_job: String,
+    pub f_circuit_id: Option<i64>,
 }
 
 #[derive(Debug, FromRow, Serialize, Deserialize, UtilsMacro)]
@@ -81,7 +120,9 @@ pub struct StoredProof {
     pub f_block_number: i64,
     pub f_proof: serde_json::Value,
     pub f_created_at: DateTime<Utc>,
-
+    pub f_circuit_id: Option<i64>,
+    /// Set once the proof failed the local verification.
+    pub f_invalid: bool,
 }
 
 #[derive(Debug, Clone, FromRow, Serialize, Deserialize, UtilsMacro)]
@@ -89,6 +130,20 @@ pub struct StorageTrace {
     pub f_id: i64,
     pub f_trace: serde_json::Value,
     pub f_created_at: DateTime<Utc>,
+    pub f_block: i64,
+    pub f_program_id: String,
+    pub f_segment: i64,
+    pub f_step: i64,
+}
+
+/// Trace record to be appended to `f_traces`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewTrace {
+    /// Segment of the program run the record belongs to.
+    pub segment: i64,
+    /// Step of the record within its segment.
+    pub step: i64,
+    pub trace: serde_json::Value,
 }
 
 #[derive(Debug, Clone, FromRow, Serialize, Deserialize, UtilsMacro)]
@@ -101,4 +156,40 @@ pub struct StorageWitnessBlockNumber {
 pub struct StorageProofBlockNumber {
     pub f_id: i64,
     pub f_block: i64,
-}
\ No newline at end of file
+}
+
+/// Lease of a program run proven in the Nova mode, see `t_nova_runs`.
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize, UtilsMacro)]
+pub struct StorageNovaRun {
+    pub f_program_id: String,
+    pub f_status: i32,
+    pub f_updated_by: String,
+    pub f_updated_at: DateTime<Utc>,
+    /// Failed attempts at proving the run.
+    pub f_attempts: i32,
+    pub f_last_error: Option<String>,
+}
+
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize, UtilsMacro)]
+pub struct StorageBlockFailure {
+    pub f_id: i64,
+    pub f_stage: String,
+    pub f_block: i64,
+    pub f_attempts: i32,
+    pub f_last_error: String,
+    pub f_dead: bool,
+    pub f_updated_at: DateTime<Utc>,
+}
+
+/// Circuit registered in `t_circuits`.
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize, UtilsMacro)]
+pub struct StorageCircuit {
+    pub f_id: i64,
+    /// SHA-256 of the compiled program, in hex.
+    pub f_program_hash: String,
+    pub f_abi: serde_json::Value,
+    pub f_verification_key: serde_json::Value,
+    /// Verifier contract the proofs of the circuit are submitted to.
+    pub f_verifier_address: Option<String>,
+    pub f_created_at: DateTime<Utc>,
+}
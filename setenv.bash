@@ -3,21 +3,45 @@ export DATABASE_POOL_SIZE=10
 export API_PROVER_PORT=8088
 export API_PROVER_URL=http://127.0.0.1:8088
 export API_PROVER_SECRET_AUTH=sample
+export API_PROMETHEUS_PORT=3312 # metrics of the server, on http://127.0.0.1:3312/metrics
 export PROVER_PROVER_HEARTBEAT_INTERVAL=1000
 export PROVER_PROVER_CYCLE_WAIT=500
 export PROVER_PROVER_REQUEST_TIMEOUT=10
 export PROVER_PROVER_DIE_AFTER_PROOF=false
+export PROVER_PROVER_USE_API=false # true to get jobs through API_PROVER_URL instead of DATABASE_URL
+export PROVER_PROVER_PROMETHEUS_PORT=3313 # metrics of the prover, unset to disable them
+export PROVER_PROVER_MODE=groth16 # groth16 proves the jobs of the queue, nova folds whole program runs
 export PROVER_CORE_GONE_TIMEOUT=60000
 export PROVER_CORE_IDLE_PROVERS=1
+export PROVER_RETRY_MAX_ATTEMPTS=5 # failed attempts before a block is dead-lettered
+export PROVER_RETRY_BACKOFF=1000
+export PROVER_RETRY_MAX_BACKOFF=60000
 export PROVER_WITNESS_GENERATOR_PREPARE_DATA_INTERVAL=500
 export PROVER_WITNESS_GENERATOR_WITNESS_GENERATORS=2
+export CIRCUIT_BATCH_SIZE=1 # trace records per proof: 1 for mips_vm_poseidon.zok, or the batch size of a generated circuit
 export CIRCUIT_FILE_PATH=${PWD}/core/lib/circuit/out # generated by zokrates compile -i mips_vm_poseidon.zok
 export CIRCUIT_ABI_FILE_PATH=${PWD}/core/lib/circuit/abi.json # generated by zokrates compile -i mips_vm_poseidon.zok
+# export NOVA_CIRCUIT_FILE_PATH=${PWD}/core/lib/circuit/nova/out # nova mode: zokrates compile -i nova_step.zok --curve pallas
+# export NOVA_CIRCUIT_ABI_FILE_PATH=${PWD}/core/lib/circuit/nova/abi.json
+# export NOVA_CIRCUIT_PARAMS_PATH=${PWD}/core/lib/circuit/nova/nova.params # zokrates nova setup, generated by the prover if unset
+export BLOB_STORE_KIND=fs # where the witnesses are kept: fs or s3
+export BLOB_STORE_PATH=${PWD}/witnesses # fs store: directory of the witnesses
+# export BLOB_STORE_S3_ENDPOINT=http://127.0.0.1:9000 # s3 store: AWS S3 or a MinIO server
+# export BLOB_STORE_S3_REGION=us-east-1
+# export BLOB_STORE_S3_BUCKET=witnesses
+# export BLOB_STORE_S3_ACCESS_KEY=minioadmin
+# export BLOB_STORE_S3_SECRET_KEY=minioadmin
 export RUST_LOG=warn
 export VERIFIER_CHAIN_URL=https://eth-goerli.g.alchemy.com/v2/aLS5R8CYWcswzRyfKtGDDQD_noFqseN5 # chain url where the verifier contract deployed, Note: please use your own secret key here
 export VERIFIER_CONTRACT_ADDRESS=0xacd47ec395668320770e7183b9ee817f4ff8774e # verifier contract address
-export VERIFIER_ACCOUNT=b75dc70f894ef8bbd8cbb6d9f70c146b87f53cdb959f0ab6ac272a8b33e767f2 # your goerli account private key
+export VERIFIER_ACCOUNT=PRIVATE_KEY # your goerli account private key
 export VERIFIER_ABI_PATH=${PWD}/contract/verifier/g16/verifier
+export VERIFIER_BACKEND=ark # checks the proofs before submitting them: ark or bellman
+export VERIFIER_SETTLEMENT=evm # where the proofs are submitted: evm, mock or dry-run
+export VERIFIER_DRY_RUN_PATH=${PWD}/calldata # calldata written by the dry-run settlement
+export VERIFIER_AGGREGATION_SIZE=1 # proofs submitted together, above 1 they go to the batch verifier
+# export VERIFIER_BATCH_CONTRACT_ADDRESS= # batch verifier contract, required with an aggregation size above 1
 export CHAIN_ETH_NETWORK=rinkeby
 export CIRCUIT_PROVING_KEY_PATH=${PWD}/core/lib/circuit/proving.key # generated by: zokrates compile -i mips_vm_poseidon.zok
+export CIRCUIT_VERIFICATION_KEY_PATH=${PWD}/core/lib/circuit/verification.key # generated by: zokrates setup
 
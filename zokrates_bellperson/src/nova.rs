@@ -61,7 +61,7 @@ type G2<T> = <<T as Cycle>::Other as Cycle>::Point;
 type C1<'ast, T> = NovaComputation<'ast, T>;
 type C2<T> = TrivialTestCircuit<<<T as Cycle>::Point as Group>::Base>;
 
-type PublicParams<'ast, T> = GPublicParams<G1<T>, G2<T>, C1<'ast, T>, C2<T>>;
+pub type PublicParams<'ast, T> = GPublicParams<G1<T>, G2<T>, C1<'ast, T>, C2<T>>;
 pub type RecursiveSNARK<'ast, T> = GRecursiveSNARK<G1<T>, G2<T>, C1<'ast, T>, C2<T>>;
 
 #[derive(Debug)]
@@ -142,18 +142,29 @@ pub fn compress<'ast, T: NovaField>(
     )
 }
 
+/// Returns the key `compress` verifies the proofs of `public_parameters` with.
+pub fn verifier_key<'ast, T: NovaField>(
+    public_parameters: &PublicParams<'ast, T>,
+) -> Result<VerifierKey<'ast, T>, Error> {
+    let (_, vk) = CompressedSNARK::<'ast, T>::setup(public_parameters)?;
+
+    Ok(vk)
+}
+
+/// Verifies a compressed proof, returns the final state of the primary circuit.
 pub fn verify_compressed<'ast, T: NovaField>(
     proof: &CompressedSNARK<'ast, T>,
     vk: &VerifierKey<'ast, T>,
     arguments: Vec<T>,
     step_count: usize,
-) -> bool {
+) -> Result<Vec<T>, Error> {
     let z0_primary: Vec<_> = arguments.into_iter().map(|a| a.into_bellperson()).collect();
     let z0_secondary = vec![<<T as Cycle>::Point as Group>::Base::ONE];
 
     proof
         .verify(vk, step_count, z0_primary, z0_secondary)
-        .is_ok()
+        .map_err(Error::Internal)
+        .map(|(primary, _)| primary.into_iter().map(T::from_bellperson).collect())
 }
 
 pub fn prove<'ast, T: NovaField>(
@@ -31,6 +31,12 @@ pub fn subcommand() -> App<'static, 'static> {
                 .required(false)
                 .default_value(cli_constants::VERIFICATION_CONTRACT_DEFAULT_PATH),
         )
+        .arg(
+            Arg::with_name("batch")
+                .long("batch")
+                .help("Exports a verifier checking several proofs in a single call (g16 only)")
+                .required(false),
+        )
 }
 
 pub fn exec(sub_matches: &ArgMatches) -> Result<(), String> {
@@ -58,6 +64,15 @@ pub fn exec(sub_matches: &ArgMatches) -> Result<(), String> {
     let curve_parameter = CurveParameter::try_from(vk_curve)?;
     let scheme_parameter = SchemeParameter::try_from(vk_scheme)?;
 
+    if sub_matches.is_present("batch") {
+        return match (curve_parameter, scheme_parameter) {
+            (CurveParameter::Bn128, SchemeParameter::G16) => {
+                cli_export_batch_verifier::<Bn128Field, G16>(sub_matches, vk)
+            }
+            (curve_parameter, scheme_parameter) => Err(format!("Could not export batch verifier with given parameters (curve: {}, scheme: {}): not supported", curve_parameter, scheme_parameter))
+        };
+    }
+
     match (curve_parameter, scheme_parameter) {
         (CurveParameter::Bn128, SchemeParameter::G16) => {
             cli_export_verifier::<Bn128Field, G16>(sub_matches, vk)
@@ -82,6 +97,21 @@ fn cli_export_verifier<T: SolidityCompatibleField, S: SolidityCompatibleScheme<T
 
     let verifier = S::export_solidity_verifier(vk);
 
+    write_verifier(sub_matches, verifier)
+}
+
+fn cli_export_batch_verifier<T: SolidityCompatibleField, S: SolidityCompatibleBatchScheme<T>>(
+    sub_matches: &ArgMatches,
+    vk: serde_json::Value,
+) -> Result<(), String> {
+    let vk = serde_json::from_value(vk).map_err(|why| format!("{}", why))?;
+
+    let verifier = S::export_solidity_batch_verifier(vk);
+
+    write_verifier(sub_matches, verifier)
+}
+
+fn write_verifier(sub_matches: &ArgMatches, verifier: String) -> Result<(), String> {
     //write output file
     let output_path = Path::new(sub_matches.value_of("output").unwrap());
     let output_file = File::create(output_path)
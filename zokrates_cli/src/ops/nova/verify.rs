@@ -115,7 +115,7 @@ fn cli_nova_verify<'ast, T: NovaField>(
         serde_json::from_reader(BufReader::new(File::open(instance_path).unwrap())).unwrap();
     let steps = instance.steps;
 
-    if nova::verify_compressed(&proof, &vk, init, steps) {
+    if nova::verify_compressed(&proof, &vk, init, steps).is_ok() {
         println!("Compressed proof succesfully verified");
     } else {
         eprintln!("Compressed proof verification failed");
@@ -204,6 +204,7 @@ mod integration {
         let witness_path = tmp_base.join(program_name).join("witness");
         let inline_witness_path = tmp_base.join(program_name).join("inline_witness");
         let proof_path = tmp_base.join(program_name).join("proof.json");
+        let second_proof_path = tmp_base.join(program_name).join("second_proof.json");
         let universal_setup_path = global_path.join("universal_setup.dat");
         let verification_key_path = tmp_base
             .join(program_name)
@@ -218,6 +219,10 @@ mod integration {
             .join(program_name)
             .join("verifier")
             .with_extension("sol");
+        let batch_verification_contract_path = tmp_base
+            .join(program_name)
+            .join("batch_verifier")
+            .with_extension("sol");
 
         // create a tmp folder to store artifacts
         fs::create_dir(test_case_path).unwrap();
@@ -446,6 +451,57 @@ mod integration {
                                 &contract_str,
                                 proof,
                             );
+
+                            // BATCH VERIFIER
+                            // A second proof of the same witness, the proofs being randomized
+                            assert_cli::Assert::main_binary()
+                                .with_args(&[
+                                    "generate-proof",
+                                    "-i",
+                                    flattened_path.to_str().unwrap(),
+                                    "-w",
+                                    witness_path.to_str().unwrap(),
+                                    "-p",
+                                    proving_key_path.to_str().unwrap(),
+                                    "--proving-scheme",
+                                    scheme,
+                                    "--backend",
+                                    backend,
+                                    "-j",
+                                    second_proof_path.to_str().unwrap(),
+                                ])
+                                .succeeds()
+                                .unwrap();
+
+                            assert_cli::Assert::main_binary()
+                                .with_args(&[
+                                    "export-verifier",
+                                    "--batch",
+                                    "-i",
+                                    verification_key_path.to_str().unwrap(),
+                                    "-o",
+                                    batch_verification_contract_path.to_str().unwrap(),
+                                ])
+                                .succeeds()
+                                .unwrap();
+
+                            let batch_contract_str =
+                                std::fs::read_to_string(&batch_verification_contract_path).unwrap();
+                            let proofs: Vec<Proof<Bn128Field, G16>> =
+                                [&proof_path, &second_proof_path]
+                                    .iter()
+                                    .map(|path| {
+                                        serde_json::from_reader(File::open(path).unwrap()).unwrap()
+                                    })
+                                    .collect();
+
+                            test_solidity_batch_verifier(
+                                program_name,
+                                backend,
+                                &solidity_test_path,
+                                &batch_contract_str,
+                                proofs,
+                            );
                         }
                         "gm17" => {
                             // Get the proof
@@ -587,6 +643,134 @@ mod integration {
         write!(file, "{}", test_content).unwrap();
     }
 
+    /// Writes a test calling `verifyTxs` of the batch verifier with `proofs`, which it accepts,
+    /// and with the last of them modified, which it rejects along with the whole batch.
+    fn test_solidity_batch_verifier(
+        program_name: &str,
+        backend: &str,
+        solidity_test_path: &Path,
+        contract_str: &str,
+        proofs: Vec<Proof<Bn128Field, G16>>,
+    ) {
+        let encode = |proofs: Vec<<G16 as SolidityCompatibleScheme<Bn128Field>>::Proof>,
+                      inputs: &[Vec<String>]| {
+            let proof_tokens = proofs
+                .into_iter()
+                .map(<G16 as ToToken<Bn128Field>>::to_token)
+                .collect();
+            let input_tokens = inputs
+                .iter()
+                .map(|inputs| {
+                    Token::FixedArray(
+                        inputs
+                            .iter()
+                            .map(|s| {
+                                let bytes = hex::decode(s.trim_start_matches("0x")).unwrap();
+                                debug_assert_eq!(bytes.len(), 32);
+                                Token::Uint(U256::from(&bytes[..]))
+                            })
+                            .collect(),
+                    )
+                })
+                .collect();
+
+            // the contract takes no inputs if the program has no public input
+            if inputs[0].is_empty() {
+                ethabi::encode(&[Token::Array(proof_tokens)])
+            } else {
+                ethabi::encode(&[Token::Array(proof_tokens), Token::Array(input_tokens)])
+            }
+        };
+
+        let inputs: Vec<_> = proofs.iter().map(|proof| proof.inputs.clone()).collect();
+        let mut solidity_proofs: Vec<_> = proofs
+            .into_iter()
+            .map(|proof| <G16 as SolidityCompatibleScheme<Bn128Field>>::Proof::from(proof.proof))
+            .collect();
+        let valid_batch = encode(solidity_proofs.clone(), &inputs);
+
+        let last = solidity_proofs.pop().unwrap();
+        solidity_proofs.push(<G16 as ToToken<Bn128Field>>::modify(last));
+        let invalid_batch = encode(solidity_proofs, &inputs);
+
+        let verifier_name = format!("BatchVerifier_{}_g16_{}", program_name, backend);
+
+        let verifier_path = solidity_test_path
+            .join("src")
+            .join(&verifier_name)
+            .with_extension("sol");
+        let mut file = File::create(verifier_path).unwrap();
+        write!(file, "{}", contract_str).unwrap();
+
+        let test_path = solidity_test_path
+            .join("test")
+            .join(format!(
+                "BatchVerifier_{}_g16_{}_Test",
+                program_name, backend
+            ))
+            .with_extension("t.sol");
+        let mut file = File::create(test_path).unwrap();
+        let test_content = format!(
+            r#"
+    
+        pragma solidity ^0.8.17;
+
+        import "forge-std/Test.sol";
+        import "../src/{}.sol";
+        
+        contract BatchVerifierTest is Test {{
+            BatchVerifier public verifier;
+        
+            constructor() {{
+                verifier = new BatchVerifier();
+            }}
+        
+            function call(bytes memory arguments) internal returns (bool, bytes memory) {{
+                return address(verifier).call(abi.encodePacked(verifier.verifyTxs.selector, arguments));
+            }}
+        
+            function testValidBatch() public {{
+                uint8[{}] memory b = [{}];
+                bytes memory arguments = new bytes(b.length);
+                for(uint i; i < b.length; i++) {{
+                    arguments[i] = bytes1(b[i]);
+                }}
+                (bool success, bytes memory returnData) = call(arguments);
+                assertEq(success, true);
+                bool res = abi.decode(returnData, (bool));
+                assertEq(res, true);
+            }}
+        
+            function testBatchWithInvalidProof() public {{
+                uint8[{}] memory b = [{}];
+                bytes memory arguments = new bytes(b.length);
+                for(uint i; i < b.length; i++) {{
+                    arguments[i] = bytes1(b[i]);
+                }}
+                (bool success, ) = call(arguments);
+                assertEq(success, false);
+            }}
+        }}
+    
+    "#,
+            verifier_name,
+            valid_batch.len(),
+            valid_batch
+                .iter()
+                .map(|v| format!("{:#04X?}", v))
+                .collect::<Vec<_>>()
+                .join(", "),
+            invalid_batch.len(),
+            invalid_batch
+                .iter()
+                .map(|v| format!("{:#04X?}", v))
+                .collect::<Vec<_>>()
+                .join(", "),
+        );
+
+        write!(file, "{}", test_content).unwrap();
+    }
+
     fn test_compile_and_smtlib2(
         program_name: &str,
         program_path: &Path,
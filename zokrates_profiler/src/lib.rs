@@ -14,6 +14,11 @@ pub struct HeatMap {
 }
 
 impl HeatMap {
+    /// Returns the total number of constraints
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
     pub fn display(&self, module_map: &ModuleMap) -> String {
         let count = self.count;
 
@@ -1,6 +1,9 @@
 use crate::scheme::{NonUniversalScheme, Scheme};
 use crate::solidity::solidity_pairing_lib;
-use crate::{G1Affine, G2Affine, MpcScheme, SolidityCompatibleField, SolidityCompatibleScheme};
+use crate::{
+    G1Affine, G2Affine, MpcScheme, SolidityCompatibleBatchScheme, SolidityCompatibleField,
+    SolidityCompatibleScheme,
+};
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 use zokrates_field::Field;
@@ -38,96 +41,134 @@ impl<T: SolidityCompatibleField> SolidityCompatibleScheme<T> for G16 {
     type Proof = Self::ProofPoints;
 
     fn export_solidity_verifier(vk: <G16 as Scheme<T>>::VerificationKey) -> String {
-        let (mut template_text, solidity_pairing_lib_sans_bn256g2) =
-            (String::from(CONTRACT_TEMPLATE), solidity_pairing_lib(false));
-
-        let vk_regex = Regex::new(r#"(<%vk_[^i%]*%>)"#).unwrap();
-        let vk_gamma_abc_len_regex = Regex::new(r#"(<%vk_gamma_abc_length%>)"#).unwrap();
-        let vk_gamma_abc_repeat_regex = Regex::new(r#"(<%vk_gamma_abc_pts%>)"#).unwrap();
-        let vk_input_len_regex = Regex::new(r#"(<%vk_input_length%>)"#).unwrap();
-        let input_loop = Regex::new(r#"(<%input_loop%>)"#).unwrap();
-        let input_argument = Regex::new(r#"(<%input_argument%>)"#).unwrap();
-
-        template_text = vk_regex
-            .replace(template_text.as_str(), vk.alpha.to_string().as_str())
-            .into_owned();
-
-        template_text = vk_regex
-            .replace(template_text.as_str(), vk.beta.to_string().as_str())
-            .into_owned();
-
-        template_text = vk_regex
-            .replace(template_text.as_str(), vk.gamma.to_string().as_str())
-            .into_owned();
-
-        template_text = vk_regex
-            .replace(template_text.as_str(), vk.delta.to_string().as_str())
-            .into_owned();
-
-        let gamma_abc_count: usize = vk.gamma_abc.len();
-        template_text = vk_gamma_abc_len_regex
-            .replace(
-                template_text.as_str(),
-                format!("{}", gamma_abc_count).as_str(),
-            )
-            .into_owned();
-
-        template_text = vk_input_len_regex
-            .replace(
-                template_text.as_str(),
-                format!("{}", gamma_abc_count - 1).as_str(),
-            )
-            .into_owned();
+        let input_count = vk.gamma_abc.len() - 1;
 
         // feed input values only if there are any
-        template_text = if gamma_abc_count > 1 {
-            input_loop.replace(
-                template_text.as_str(),
-                r#"
+        let input_loop = if input_count > 0 {
+            r#"
         for(uint i = 0; i < input.length; i++){
             inputValues[i] = input[i];
-        }"#,
-            )
+        }"#
         } else {
-            input_loop.replace(template_text.as_str(), "")
-        }
-        .to_string();
+            ""
+        };
 
         // take input values as argument only if there are any
-        template_text = if gamma_abc_count > 1 {
-            input_argument.replace(
-                template_text.as_str(),
-                format!(", uint[{}] memory input", gamma_abc_count - 1).as_str(),
-            )
+        let input_argument = if input_count > 0 {
+            format!(", uint[{}] memory input", input_count)
         } else {
-            input_argument.replace(template_text.as_str(), "")
-        }
-        .to_string();
+            String::new()
+        };
 
-        let mut gamma_abc_repeat_text = String::new();
-        for (i, g1) in vk.gamma_abc.iter().enumerate() {
-            gamma_abc_repeat_text.push_str(
-                format!(
-                    "vk.gamma_abc[{}] = Pairing.G1Point({});",
-                    i,
-                    g1.to_string().as_str()
-                )
-                .as_str(),
-            );
-            if i < gamma_abc_count - 1 {
-                gamma_abc_repeat_text.push_str("\n        ");
+        render_verifier(CONTRACT_TEMPLATE, vk, input_loop, &input_argument)
+    }
+}
+
+impl<T: SolidityCompatibleField> SolidityCompatibleBatchScheme<T> for G16 {
+    fn export_solidity_batch_verifier(vk: <G16 as Scheme<T>>::VerificationKey) -> String {
+        let input_count = vk.gamma_abc.len() - 1;
+
+        let input_loop = if input_count > 0 {
+            r#"
+        require(input.length == proofs.length);
+        for(uint i = 0; i < proofs.length; i++){
+            for(uint j = 0; j < input[i].length; j++){
+                inputValues[i][j] = input[i][j];
             }
-        }
+        }"#
+        } else {
+            ""
+        };
 
-        template_text = vk_gamma_abc_repeat_regex
-            .replace(template_text.as_str(), gamma_abc_repeat_text.as_str())
-            .into_owned();
+        let input_argument = if input_count > 0 {
+            format!(", uint[{}][] memory input", input_count)
+        } else {
+            String::new()
+        };
 
-        let re = Regex::new(r"(?P<v>0[xX][0-9a-fA-F]{64})").unwrap();
-        template_text = re.replace_all(&template_text, "uint256($v)").to_string();
+        render_verifier(BATCH_CONTRACT_TEMPLATE, vk, input_loop, &input_argument)
+    }
+}
 
-        format!("{}{}", solidity_pairing_lib_sans_bn256g2, template_text)
+/// Fills `template` with `vk` and the handling of the inputs, and prepends the pairing library.
+fn render_verifier(
+    template: &str,
+    vk: VerificationKey<G1Affine, G2Affine>,
+    input_loop_text: &str,
+    input_argument_text: &str,
+) -> String {
+    let (mut template_text, solidity_pairing_lib_sans_bn256g2) =
+        (String::from(template), solidity_pairing_lib(false));
+
+    let vk_regex = Regex::new(r#"(<%vk_[^i%]*%>)"#).unwrap();
+    let vk_gamma_abc_len_regex = Regex::new(r#"(<%vk_gamma_abc_length%>)"#).unwrap();
+    let vk_gamma_abc_repeat_regex = Regex::new(r#"(<%vk_gamma_abc_pts%>)"#).unwrap();
+    let vk_input_len_regex = Regex::new(r#"(<%vk_input_length%>)"#).unwrap();
+    let input_loop = Regex::new(r#"(<%input_loop%>)"#).unwrap();
+    let input_argument = Regex::new(r#"(<%input_argument%>)"#).unwrap();
+
+    template_text = vk_regex
+        .replace(template_text.as_str(), vk.alpha.to_string().as_str())
+        .into_owned();
+
+    template_text = vk_regex
+        .replace(template_text.as_str(), vk.beta.to_string().as_str())
+        .into_owned();
+
+    template_text = vk_regex
+        .replace(template_text.as_str(), vk.gamma.to_string().as_str())
+        .into_owned();
+
+    template_text = vk_regex
+        .replace(template_text.as_str(), vk.delta.to_string().as_str())
+        .into_owned();
+
+    let gamma_abc_count: usize = vk.gamma_abc.len();
+    template_text = vk_gamma_abc_len_regex
+        .replace(
+            template_text.as_str(),
+            format!("{}", gamma_abc_count).as_str(),
+        )
+        .into_owned();
+
+    template_text = vk_input_len_regex
+        .replace(
+            template_text.as_str(),
+            format!("{}", gamma_abc_count - 1).as_str(),
+        )
+        .into_owned();
+
+    template_text = input_loop
+        .replace(template_text.as_str(), input_loop_text)
+        .to_string();
+
+    template_text = input_argument
+        .replace(template_text.as_str(), input_argument_text)
+        .to_string();
+
+    let mut gamma_abc_repeat_text = String::new();
+    for (i, g1) in vk.gamma_abc.iter().enumerate() {
+        gamma_abc_repeat_text.push_str(
+            format!(
+                "vk.gamma_abc[{}] = Pairing.G1Point({});",
+                i,
+                g1.to_string().as_str()
+            )
+            .as_str(),
+        );
+        if i < gamma_abc_count - 1 {
+            gamma_abc_repeat_text.push_str("\n        ");
+        }
     }
+
+    template_text = vk_gamma_abc_repeat_regex
+        .replace(template_text.as_str(), gamma_abc_repeat_text.as_str())
+        .into_owned();
+
+    let re = Regex::new(r"(?P<v>0[xX][0-9a-fA-F]{64})").unwrap();
+    template_text = re.replace_all(&template_text, "uint256($v)").to_string();
+
+    format!("{}{}", solidity_pairing_lib_sans_bn256g2, template_text)
 }
 
 const CONTRACT_TEMPLATE: &str = r#"
@@ -184,3 +225,80 @@ contract Verifier {
     }
 }
 "#;
+
+/// Checks k proofs with a single pairing of k + 3 points instead of 4k: the Groth16 equations of
+/// the proofs are combined with random coefficients r_i derived from all of them, r_0 being 1,
+/// into prod e(r_i a_i, b_i) = e(sum r_i alpha, beta) e(sum r_i vk_x_i, gamma) e(sum r_i c_i, delta).
+const BATCH_CONTRACT_TEMPLATE: &str = r#"
+contract BatchVerifier {
+    using Pairing for *;
+    struct VerifyingKey {
+        Pairing.G1Point alpha;
+        Pairing.G2Point beta;
+        Pairing.G2Point gamma;
+        Pairing.G2Point delta;
+        Pairing.G1Point[] gamma_abc;
+    }
+    struct Proof {
+        Pairing.G1Point a;
+        Pairing.G2Point b;
+        Pairing.G1Point c;
+    }
+    function verifyingKey() pure internal returns (VerifyingKey memory vk) {
+        vk.alpha = Pairing.G1Point(<%vk_alpha%>);
+        vk.beta = Pairing.G2Point(<%vk_beta%>);
+        vk.gamma = Pairing.G2Point(<%vk_gamma%>);
+        vk.delta = Pairing.G2Point(<%vk_delta%>);
+        vk.gamma_abc = new Pairing.G1Point[](<%vk_gamma_abc_length%>);
+        <%vk_gamma_abc_pts%>
+    }
+    function verifyBatch(uint[][] memory input, Proof[] memory proofs) internal view returns (uint) {
+        uint256 snark_scalar_field = 21888242871839275222246405745257275088548364400416034343698204186575808495617;
+        VerifyingKey memory vk = verifyingKey();
+        require(proofs.length > 0 && input.length == proofs.length);
+        // The coefficients can't be chosen by the sender, as they depend on all the proofs
+        uint256 seed = uint256(keccak256(abi.encode(input, proofs)));
+        Pairing.G1Point[] memory p1 = new Pairing.G1Point[](proofs.length + 3);
+        Pairing.G2Point[] memory p2 = new Pairing.G2Point[](proofs.length + 3);
+        Pairing.G1Point memory vk_x = Pairing.G1Point(0, 0);
+        Pairing.G1Point memory c = Pairing.G1Point(0, 0);
+        uint256 r_sum = 0;
+        for (uint i = 0; i < proofs.length; i++) {
+            require(input[i].length + 1 == vk.gamma_abc.length);
+            uint256 r = i == 0 ? 1 : uint256(keccak256(abi.encode(seed, i))) % snark_scalar_field;
+            // Compute the linear combination vk_x of the proof
+            Pairing.G1Point memory proof_vk_x = Pairing.G1Point(0, 0);
+            for (uint j = 0; j < input[i].length; j++) {
+                require(input[i][j] < snark_scalar_field);
+                proof_vk_x = Pairing.addition(proof_vk_x, Pairing.scalar_mul(vk.gamma_abc[j + 1], input[i][j]));
+            }
+            proof_vk_x = Pairing.addition(proof_vk_x, vk.gamma_abc[0]);
+            vk_x = Pairing.addition(vk_x, Pairing.scalar_mul(proof_vk_x, r));
+            c = Pairing.addition(c, Pairing.scalar_mul(proofs[i].c, r));
+            r_sum = addmod(r_sum, r, snark_scalar_field);
+            p1[i] = Pairing.scalar_mul(proofs[i].a, r);
+            p2[i] = proofs[i].b;
+        }
+        p1[proofs.length] = Pairing.negate(vk_x);
+        p2[proofs.length] = vk.gamma;
+        p1[proofs.length + 1] = Pairing.negate(c);
+        p2[proofs.length + 1] = vk.delta;
+        p1[proofs.length + 2] = Pairing.negate(Pairing.scalar_mul(vk.alpha, r_sum));
+        p2[proofs.length + 2] = vk.beta;
+        if(!Pairing.pairing(p1, p2)) return 1;
+        return 0;
+    }
+    function verifyTxs(
+            Proof[] memory proofs<%input_argument%>
+        ) public view returns (bool r) {
+        uint[][] memory inputValues = new uint[][](proofs.length);
+        for(uint i = 0; i < proofs.length; i++){
+            inputValues[i] = new uint[](<%vk_input_length%>);
+        }
+        <%input_loop%>
+        // Reverts, so that a transaction sending an invalid proof fails
+        require(verifyBatch(inputValues, proofs) == 0, "V_F");
+        return true;
+    }
+}
+"#;
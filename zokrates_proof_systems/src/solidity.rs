@@ -10,6 +10,13 @@ pub trait SolidityCompatibleScheme<T: SolidityCompatibleField>: Scheme<T> {
     fn export_solidity_verifier(vk: Self::VerificationKey) -> String;
 }
 
+/// Schemes whose proofs can be checked together in a single contract call.
+pub trait SolidityCompatibleBatchScheme<T: SolidityCompatibleField>:
+    SolidityCompatibleScheme<T>
+{
+    fn export_solidity_batch_verifier(vk: Self::VerificationKey) -> String;
+}
+
 pub const SOLIDITY_G2_ADDITION_LIB: &str = r#"// SPDX-License-Identifier: LGPL-3.0-only
 // This file is LGPL3 Licensed
 pragma solidity ^0.8.0;